@@ -1,6 +1,9 @@
 use clap::{self, crate_version};
 use jni::signature::{JavaType, Primitive};
-use safe_bindgen::{Bindgen, LangC, LangCSharp, LangJava};
+use safe_bindgen::{
+    Bindgen, LangC, LangCSharp, LangCpp, LangCython, LangDart, LangGo, LangJava, LangJavaPanama,
+    LangKotlin, LangLua, LangPython, LangRuby, LangSwift, LangTypeScript,
+};
 use std::collections::HashMap;
 use unwrap::unwrap;
 
@@ -24,15 +27,53 @@ fn main() {
                 .takes_value(true)
                 .required(true)
                 .help("target language")
-                .possible_values(&["csharp", "java", "c"]),
+                .possible_values(&[
+                    "csharp",
+                    "java",
+                    "java-panama",
+                    "kotlin",
+                    "c",
+                    "python",
+                    "ruby",
+                    "swift",
+                    "dart",
+                    "go",
+                    "typescript",
+                    "cpp",
+                    "lua",
+                    "cython",
+                ]),
         )
         .arg(
             clap::Arg::with_name("LIB")
                 .long("--lib")
                 .takes_value(true)
-                .required(true)
+                .required_unless("MEMBER")
                 .help("name of the native library to link"),
         )
+        .arg(
+            clap::Arg::with_name("DEPENDENCY")
+                .long("--dependency")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "generate the types used from a dependency crate, given as NAME or \
+                     NAME=PATH to its root source file (located with cargo metadata otherwise)",
+                ),
+        )
+        .arg(
+            clap::Arg::with_name("MEMBER")
+                .long("--member")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["FILE", "LIB"])
+                .help(
+                    "generate bindings for a workspace crate, given as NAME=PATH to its root \
+                     source file, into a subdirectory of the output directory",
+                ),
+        )
         .arg(
             clap::Arg::with_name("OUTPUT")
                 .index(1)
@@ -42,97 +83,145 @@ fn main() {
 
     let mut bindgen = Bindgen::new().expect("cargo manifest could not be read");
     let lang = unwrap!(matches.value_of("LANG"));
-    let lib = unwrap!(matches.value_of("LIB"));
+    let lib = matches.value_of("LIB").unwrap_or_default();
 
     if let Some(file) = matches.value_of("FILE") {
         bindgen.source_file(&file);
     }
 
+    for dependency in matches.values_of("DEPENDENCY").into_iter().flatten() {
+        match dependency.find('=') {
+            Some(index) => bindgen.dependency(&dependency[..index], &dependency[index + 1..]),
+            None => bindgen.dependency_from_cargo(dependency),
+        };
+    }
+
+    for member in matches.values_of("MEMBER").into_iter().flatten() {
+        match member.find('=') {
+            Some(index) => bindgen.workspace_member(&member[..index], &member[index + 1..]),
+            None => panic!("workspace members must be given as NAME=PATH"),
+        };
+    }
+    let workspace = matches.is_present("MEMBER");
+
     let output_dir = if let Some(output) = matches.value_of("OUTPUT") {
         output.to_string()
     } else {
         format!("bind-gen/{}", lang)
     };
 
-    match lang {
-        "c" => {
-            let mut lang = LangC::new();
-            lang.set_lib_name(lib);
-            bindgen.run_build(&mut lang, &output_dir)
-        }
-        "csharp" => {
-            let mut lang = LangCSharp::new();
-            lang.set_lib_name(lib);
-            bindgen.run_build(&mut lang, &output_dir)
-        }
-        "java" => {
-            let mut type_map = HashMap::new();
-            type_map.insert(
-                "XorNameArray",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "SignSecretKey",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "SignPublicKey",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "SymSecretKey",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "SymNonce",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "AsymPublicKey",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "AsymSecretKey",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert(
-                "AsymNonce",
-                JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
-            );
-            type_map.insert("CipherOptHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("EncryptPubKeyHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("EncryptSecKeyHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("MDataEntriesHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert(
-                "MDataEntryActionsHandle",
-                JavaType::Primitive(Primitive::Long),
-            );
-            type_map.insert(
-                "MDataPermissionsHandle",
-                JavaType::Primitive(Primitive::Long),
-            );
-            type_map.insert(
-                "SelfEncryptorReaderHandle",
-                JavaType::Primitive(Primitive::Long),
-            );
-            type_map.insert(
-                "SelfEncryptorWriterHandle",
-                JavaType::Primitive(Primitive::Long),
-            );
-            type_map.insert("SEReaderHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("SEWriterHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("SignPubKeyHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("SignSecKeyHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("FileContextHandle", JavaType::Primitive(Primitive::Long));
-            type_map.insert("App", JavaType::Primitive(Primitive::Long));
-            type_map.insert("Authenticator", JavaType::Primitive(Primitive::Long));
+    // Builds the bindings of the crate, or of each workspace member, with the languages created
+    // by `$new_lang` for the name of the native library.
+    macro_rules! build {
+        (|$lib:ident| $new_lang:expr) => {{
+            let new_lang = |$lib: &str| $new_lang;
+            if workspace {
+                bindgen.run_workspace_build(new_lang, &output_dir)
+            } else {
+                bindgen.run_build(&mut new_lang(lib), &output_dir)
+            }
+        }};
+        // Java languages are given the type map and namespaces named after the library too.
+        (java $lang:ident) => {{
+            let type_map = java_type_map();
+            build!(|lib| {
+                let mut java = $lang::new(type_map.clone());
+                java.set_namespace(format!("net.maidsafe.{}", lib));
+                java.set_model_namespace(format!("net.maidsafe.{}", lib));
+                java.set_lib_name(lib);
+                java
+            })
+        }};
+        ($lang:ident) => {
+            build!(|lib| {
+                let mut lang = $lang::new();
+                lang.set_lib_name(lib);
+                lang
+            })
+        };
+    }
 
-            let mut java = LangJava::new(type_map);
-            java.set_namespace(format!("net.maidsafe.{}", lib));
-            java.set_model_namespace(format!("net.maidsafe.{}", lib));
-            java.set_lib_name(lib);
-            bindgen.run_build(&mut java, &output_dir);
-        }
+    match lang {
+        "c" => build!(LangC),
+        "csharp" => build!(LangCSharp),
+        "python" => build!(LangPython),
+        "ruby" => build!(LangRuby),
+        "lua" => build!(LangLua),
+        "cython" => build!(LangCython),
+        "swift" => build!(LangSwift),
+        "dart" => build!(LangDart),
+        "go" => build!(LangGo),
+        "typescript" => build!(LangTypeScript),
+        "cpp" => build!(LangCpp),
+        "java" => build!(java LangJava),
+        "java-panama" => build!(java LangJavaPanama),
+        "kotlin" => build!(java LangKotlin),
         _ => unreachable!(),
     }
 }
+
+/// Maps the handles and key types of the SAFE libraries to their Java types.
+fn java_type_map() -> HashMap<&'static str, JavaType> {
+    let mut type_map = HashMap::new();
+    type_map.insert(
+        "XorNameArray",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "SignSecretKey",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "SignPublicKey",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "SymSecretKey",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "SymNonce",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "AsymPublicKey",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "AsymSecretKey",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert(
+        "AsymNonce",
+        JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+    );
+    type_map.insert("CipherOptHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("EncryptPubKeyHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("EncryptSecKeyHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("MDataEntriesHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert(
+        "MDataEntryActionsHandle",
+        JavaType::Primitive(Primitive::Long),
+    );
+    type_map.insert(
+        "MDataPermissionsHandle",
+        JavaType::Primitive(Primitive::Long),
+    );
+    type_map.insert(
+        "SelfEncryptorReaderHandle",
+        JavaType::Primitive(Primitive::Long),
+    );
+    type_map.insert(
+        "SelfEncryptorWriterHandle",
+        JavaType::Primitive(Primitive::Long),
+    );
+    type_map.insert("SEReaderHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("SEWriterHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("SignPubKeyHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("SignSecKeyHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("FileContextHandle", JavaType::Primitive(Primitive::Long));
+    type_map.insert("App", JavaType::Primitive(Primitive::Long));
+    type_map.insert("Authenticator", JavaType::Primitive(Primitive::Long));
+
+    type_map
+}
@@ -49,6 +49,27 @@ pub trait Lang {
         outputs: &mut Outputs,
     ) -> Result<(), Error>;
 
+    /// Convert a Rust union into a target language union.
+    fn parse_union(
+        &mut self,
+        _item: &syn::ItemUnion,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Convert an exported Rust static (`#[no_mangle] pub static NAME: Type = value;`) into a
+    /// target language declaration giving access to it.
+    fn parse_static(
+        &mut self,
+        _item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Convert a Rust function declaration into a target language function declaration.
     fn parse_fn(
         &mut self,
@@ -59,6 +80,17 @@ pub trait Lang {
 
     /// Add extra and custom code after the code generation part is done.
     fn finalise_output(&mut self, _outputs: &mut Outputs) -> Result<(), Error>;
+
+    /// How other bindings refer to the types generated by these ones, e.g. a header to include
+    /// or a namespace to import. Bindings which can't be referred to generate the types of the
+    /// units they use themselves.
+    fn unit_reference(&self) -> Option<String> {
+        None
+    }
+
+    /// Declare that the types of the crate `package` are generated by the bindings referred to
+    /// by `reference` (see `unit_reference`), so they're referred to instead of generated.
+    fn add_provided_unit(&mut self, _package: &str, _reference: &str) {}
 }
 
 /// Append or create new output file
@@ -235,31 +267,83 @@ where
     (check_passed, retrieved_str)
 }
 
-/// Check the attribute is #[repr(C)].
-pub fn check_repr_c(attr: &syn::Attribute) -> bool {
-    match unwrap!(attr.parse_meta()) {
-        syn::Meta::List(ref word)
-            if attr
-                .to_owned()
-                .path
-                .into_token_stream()
-                .to_string()
-                .as_str()
-                == "repr" =>
-        {
-            match word.nested.first() {
-                Some(word) => {
-                    match word.into_value() {
-                        // Return true only if attribute is #[repr(C)].
-                        syn::NestedMeta::Meta(ref item) if item.name() == "C" => true,
-                        _ => false,
-                    }
+/// Layout modifiers of a type, collected from its `#[repr(...)]` attributes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Repr {
+    /// `#[repr(C)]`
+    pub c: bool,
+    /// `#[repr(transparent)]`
+    pub transparent: bool,
+    /// Maximum field alignment: `1` for `#[repr(packed)]` and `N` for `#[repr(packed(N))]`.
+    pub packed: Option<u64>,
+    /// Minimum alignment from `#[repr(align(N))]`.
+    pub align: Option<u64>,
+    /// Primitive integer representation of an enum (e.g. `u8` for `#[repr(u8)]`).
+    pub int: Option<String>,
+}
+
+/// Returns the items nested in a `#[repr(...)]` attribute.
+fn repr_items(attr: &syn::Attribute) -> Vec<syn::NestedMeta> {
+    if attr.path.clone().into_token_stream().to_string() != "repr" {
+        return Vec::new();
+    }
+
+    match attr.parse_meta() {
+        Ok(syn::Meta::List(list)) => list.nested.into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Extracts `N` from a `name(N)` repr item.
+fn repr_int_arg(list: &syn::MetaList) -> Option<u64> {
+    match list.nested.first().map(|item| item.into_value()) {
+        Some(syn::NestedMeta::Literal(syn::Lit::Int(ref val))) => Some(val.value()),
+        _ => None,
+    }
+}
+
+/// Collect the layout modifiers from all `#[repr(...)]` attributes.
+pub fn parse_repr(attrs: &[syn::Attribute]) -> Repr {
+    let mut repr = Repr::default();
+
+    for item in attrs.iter().flat_map(repr_items) {
+        match item {
+            syn::NestedMeta::Meta(syn::Meta::Word(ref word)) => match word.to_string().as_str() {
+                "C" => repr.c = true,
+                "transparent" => repr.transparent = true,
+                "packed" => repr.packed = Some(1),
+                int @ "u8"
+                | int @ "u16"
+                | int @ "u32"
+                | int @ "u64"
+                | int @ "usize"
+                | int @ "i8"
+                | int @ "i16"
+                | int @ "i32"
+                | int @ "i64"
+                | int @ "isize" => repr.int = Some(int.to_string()),
+                _ => (),
+            },
+            syn::NestedMeta::Meta(syn::Meta::List(ref list)) => {
+                match list.ident.to_string().as_str() {
+                    "packed" => repr.packed = repr_int_arg(list),
+                    "align" => repr.align = repr_int_arg(list),
+                    _ => (),
                 }
-                _ => false,
             }
+            _ => (),
         }
-        _ => false,
     }
+
+    repr
+}
+
+/// Check the attribute is #[repr(C)], possibly along with other modifiers (e.g. `#[repr(C, packed)]`).
+pub fn check_repr_c(attr: &syn::Attribute) -> bool {
+    repr_items(attr).iter().any(|item| match *item {
+        syn::NestedMeta::Meta(syn::Meta::Word(ref word)) => word == "C",
+        _ => false,
+    })
 }
 
 /// If the attribute is a docstring, indent it the required amount and return it.
@@ -302,11 +386,189 @@ pub fn extract_int_literal(lit: &syn::ExprLit) -> Option<i64> {
     }
 }
 
+/// Returns whether any of the enum variants carries data.
+pub fn has_data_variants(item: &syn::ItemEnum) -> bool {
+    item.variants
+        .iter()
+        .any(|variant| variant.fields != syn::Fields::Unit)
+}
+
+/// Returns the fields of a struct or an enum variant, naming the fields of tuple-like ones
+/// `field_0`, `field_1`, etc.
+pub fn named_fields(fields: &syn::Fields) -> Vec<syn::Field> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let mut field = field.clone();
+            if field.ident.is_none() {
+                field.ident = Some(syn::Ident::new(
+                    &format!("field_{}", idx),
+                    proc_macro2::Span::call_site(),
+                ));
+            }
+            field
+        })
+        .collect()
+}
+
+/// Returns the field wrapped by a `#[repr(transparent)]` struct, skipping any `PhantomData`
+/// markers.
+pub fn transparent_field(fields: &syn::Fields) -> Option<&syn::Field> {
+    fields.iter().find(|field| match field.ty {
+        syn::Type::Path(ref path) => path
+            .path
+            .segments
+            .last()
+            .is_none_or(|segment| segment.value().ident != "PhantomData"),
+        _ => true,
+    })
+}
+
+/// Returns the function pointer type wrapped in `Option<extern "C" fn(...)>`, which Rust
+/// guarantees to have the same representation as a nullable function pointer.
+pub fn nullable_fn_ptr(ty: &syn::Type) -> Option<&syn::TypeBareFn> {
+    let path = match *ty {
+        syn::Type::Path(ref path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    match segments.join("::").as_str() {
+        "Option" | "option::Option" | "std::option::Option" | "core::option::Option" => (),
+        _ => return None,
+    }
+
+    match unwrap!(path.segments.last()).value().arguments {
+        syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            match args.args[0] {
+                syn::GenericArgument::Type(syn::Type::BareFn(ref bare_fn)) => Some(bare_fn),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Safe Rust pointer types which are represented as plain pointers across FFI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SafePtrKind {
+    /// `&T`
+    Ref,
+    /// `&mut T`
+    RefMut,
+    /// `NonNull<T>`
+    NonNull,
+    /// `Box<T>`
+    Box,
+}
+
+/// A reference, `NonNull<T>` or `Box<T>` pointing to `elem`. These are never null unless
+/// they're wrapped in an `Option`.
+#[derive(Debug, Clone)]
+pub struct SafePtr<'a> {
+    pub kind: SafePtrKind,
+    pub elem: &'a syn::Type,
+    pub nullable: bool,
+}
+
+impl<'a> SafePtr<'a> {
+    /// Whether the pointee can be modified through the pointer.
+    pub fn is_mutable(&self) -> bool {
+        self.kind != SafePtrKind::Ref
+    }
+
+    /// Returns the equivalent raw pointer type (`*const T` or `*mut T`).
+    pub fn to_raw(&self) -> syn::TypePtr {
+        syn::TypePtr {
+            star_token: Default::default(),
+            const_token: if self.is_mutable() {
+                None
+            } else {
+                Some(Default::default())
+            },
+            mutability: if self.is_mutable() {
+                Some(Default::default())
+            } else {
+                None
+            },
+            elem: Box::new(self.elem.clone()),
+        }
+    }
+}
+
+/// Recognises references, `NonNull<T>` and `Box<T>`, optionally wrapped in an `Option` which
+/// makes them nullable. Pointers to dynamically sized types (`&str`, `&[T]`, `Box<dyn T>`) are
+/// not FFI-safe and aren't recognised.
+pub fn safe_ptr(ty: &syn::Type) -> Option<SafePtr<'_>> {
+    let (kind, elem) = match *ty {
+        syn::Type::Reference(ref reference) => {
+            let kind = if reference.mutability.is_some() {
+                SafePtrKind::RefMut
+            } else {
+                SafePtrKind::Ref
+            };
+            (kind, &*reference.elem)
+        }
+        syn::Type::Path(ref path) if path.qself.is_none() => {
+            let segment = unwrap!(path.path.segments.last()).into_value();
+            let arg = match segment.arguments {
+                syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+                    match args.args[0] {
+                        syn::GenericArgument::Type(ref arg) => arg,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            };
+
+            match segment.ident.to_string().as_str() {
+                "NonNull" => (SafePtrKind::NonNull, arg),
+                "Box" => (SafePtrKind::Box, arg),
+                "Option" => {
+                    return safe_ptr(arg)
+                        .filter(|ptr| !ptr.nullable)
+                        .map(|ptr| SafePtr {
+                            nullable: true,
+                            ..ptr
+                        });
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let unsized_elem = match *elem {
+        syn::Type::Slice(..) | syn::Type::TraitObject(..) => true,
+        syn::Type::Path(ref path) => path.path.is_ident("str"),
+        _ => false,
+    };
+    if unsized_elem {
+        return None;
+    }
+
+    Some(SafePtr {
+        kind,
+        elem,
+        nullable: false,
+    })
+}
+
 /// Extracts the enum variant value/discriminant, if it exists.
 pub fn extract_enum_variant_value(variant: &syn::Variant) -> Option<i64> {
     if let Some(ref expr) = variant.discriminant {
-        if let syn::Expr::Lit(ref lit) = expr.1 {
-            return extract_int_literal(lit);
+        match expr.1 {
+            syn::Expr::Lit(ref lit) => return extract_int_literal(lit),
+            syn::Expr::Unary(syn::ExprUnary {
+                op: syn::UnOp::Neg(_),
+                ref expr,
+                ..
+            }) => {
+                if let syn::Expr::Lit(ref lit) = **expr {
+                    return extract_int_literal(lit).map(|value| -value);
+                }
+            }
+            _ => (),
         }
     }
 
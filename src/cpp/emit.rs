@@ -0,0 +1,679 @@
+//! Utilities for emiting fragments of the target language code.
+use super::Context;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// C++ keywords which can be used as identifiers in Rust, along with the names of the local
+/// variables of the wrapper functions.
+const KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "compl",
+    "const_cast",
+    "constexpr",
+    "decltype",
+    "default",
+    "delete",
+    "double",
+    "dynamic_cast",
+    "explicit",
+    "export",
+    "float",
+    "friend",
+    "goto",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "short",
+    "signed",
+    "sizeof",
+    "static_assert",
+    "static_cast",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "xor",
+    "xor_eq",
+    "callbacks",
+    "error",
+    "future",
+    "on_error",
+    "on_success",
+    "promise",
+];
+
+/// Standard headers along with the names which require them.
+const INCLUDES: &[(&str, &[&str])] = &[
+    ("cstdint", &["std::int", "std::uint"]),
+    ("exception", &["std::make_exception_ptr"]),
+    ("functional", &["std::function"]),
+    ("future", &["std::future", "std::promise"]),
+    ("memory", &["std::make_shared", "std::unique_ptr"]),
+    ("optional", &["std::optional"]),
+    ("stdexcept", &["std::runtime_error"]),
+    ("string", &["std::string"]),
+    ("tuple", &["std::tuple"]),
+    ("type_traits", &["std::add_pointer_t", "std::remove_cv_t"]),
+    ("utility", &["std::exchange", "std::move", "std::swap"]),
+    ("vector", &["std::vector"]),
+];
+
+/// Emits the includes of the standard headers used by the code.
+pub fn emit_includes(writer: &mut IndentedWriter, code: &str) {
+    for (header, names) in INCLUDES {
+        if names.iter().any(|name| code.contains(name)) {
+            emitln!(writer, "#include <{}>", header);
+        }
+    }
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "///");
+        } else {
+            emitln!(writer, "/// {}", line);
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emit_docs(writer, docs);
+    match (&item.value, &item.ty) {
+        (ConstValue::Array(..), Type::Array(ref elem, _)) => emitln!(
+            writer,
+            "inline constexpr {} {}[] = {};",
+            c_type(elem),
+            name,
+            const_value(&item.value)
+        ),
+        (value, ty) => {
+            let ty = match *value {
+                ConstValue::String(..) => "const char*".to_string(),
+                ConstValue::Char(..) => "char32_t".to_string(),
+                ConstValue::Array(..) => "auto".to_string(),
+                _ => c_type(ty),
+            };
+            emitln!(
+                writer,
+                "inline constexpr {} {} = {};",
+                ty,
+                name,
+                const_value(value)
+            );
+        }
+    }
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    // The size of C enums is that of an `int`.
+    let ty = item.ty.as_ref().map_or_else(|| "int".to_string(), c_type);
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "enum class {} : {} {{", name, ty);
+    writer.indent();
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emit_docs(writer, &variant.docs);
+        emitln!(writer, "{} = {},", variant.name, value);
+        next = value + 1;
+    }
+
+    writer.unindent();
+    emitln!(writer, "}};");
+}
+
+/// Emits the move-only class owning the opaque type `name`, released by `free`.
+pub fn emit_handle(writer: &mut IndentedWriter, name: &str, free: &str) {
+    emitln!(
+        writer,
+        r#"
+/// Owning handle of a native `{0}`, released by `{1}`.
+class {0} {{
+public:
+    /// Takes the ownership of the handle.
+    explicit {0}(::{0}* handle) noexcept : handle_(handle) {{}}
+
+    {0}({0}&& other) noexcept : handle_(std::exchange(other.handle_, nullptr)) {{}}
+
+    {0}& operator=({0}&& other) noexcept {{
+        std::swap(handle_, other.handle_);
+        return *this;
+    }}
+
+    {0}(const {0}&) = delete;
+    {0}& operator=(const {0}&) = delete;
+
+    ~{0}() {{
+        if (handle_ != nullptr) {{
+            ::{1}(handle_);
+        }}
+    }}
+
+    /// The handle, still owned by this object.
+    ::{0}* get() const noexcept {{ return handle_; }}
+
+    /// Gives up the ownership of the handle.
+    ::{0}* release() noexcept {{ return std::exchange(handle_, nullptr); }}
+
+private:
+    ::{0}* handle_;
+}};"#,
+        name,
+        free
+    );
+}
+
+pub fn emit_error(writer: &mut IndentedWriter) {
+    emitln!(
+        writer,
+        r#"
+class FfiError : public std::runtime_error {{
+public:
+    explicit FfiError(const ::FfiResult& result)
+        : std::runtime_error(result.description != nullptr ? result.description : ""),
+          code_(result.error_code) {{}}
+
+    /// The error code of the result.
+    decltype(::FfiResult::error_code) code() const noexcept {{ return code_; }}
+
+private:
+    decltype(::FfiResult::error_code) code_;
+}};"#
+    );
+}
+
+/// Emits the helpers of the wrapper functions used by `code`.
+pub fn emit_detail(writer: &mut IndentedWriter, code: &str, futures: bool) {
+    let mut helpers = Vec::new();
+
+    if code.contains("detail::to_string(") {
+        helpers.push(
+            r#"inline std::string to_string(const char* value) {
+    return value == nullptr ? std::string() : std::string(value);
+}"#,
+        );
+    }
+    if code.contains("detail::to_vector(") {
+        helpers.push(
+            r#"template <typename T>
+std::vector<std::remove_cv_t<T>> to_vector(T* data, std::uintptr_t len) {
+    if (data == nullptr) {
+        return {};
+    }
+    return std::vector<std::remove_cv_t<T>>(data, data + len);
+}"#,
+        );
+    }
+    if code.contains("detail::to_optional(") {
+        helpers.push(
+            r#"template <typename T>
+std::optional<std::remove_cv_t<T>> to_optional(T* value) {
+    if (value == nullptr) {
+        return std::nullopt;
+    }
+    return *value;
+}"#,
+        );
+    }
+    if futures {
+        helpers.push(
+            r#"template <typename... Values>
+struct Callbacks {
+    std::function<void(Values...)> on_success;
+    std::function<void(const FfiError&)> on_error;
+};"#,
+        );
+    }
+
+    if !helpers.is_empty() {
+        emitln!(writer, "\nnamespace detail {{\n");
+        emitln!(writer, "{}", helpers.join("\n\n"));
+        emitln!(writer, "\n}}  // namespace detail");
+    }
+}
+
+pub fn emit_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let (templates, params, args) = convert_params(context, fun, None);
+    let call = format!("::{}({})", name, args.join(", "));
+
+    let (ty, value) = match fun.output {
+        Type::Unit => ("void".to_string(), call),
+        Type::String => (
+            "std::string".to_string(),
+            format!("detail::to_string({})", call),
+        ),
+        Type::User(ref name) if context.is_enum(name) => {
+            (name.clone(), format!("static_cast<{}>({})", name, call))
+        }
+        Type::Pointer(ref ty) => match **ty {
+            Type::User(ref name) if context.is_handle(name) => {
+                (name.clone(), format!("{}({})", name, call))
+            }
+            // The constness of the pointer is only known to the C header.
+            _ => ("auto".to_string(), call),
+        },
+        ref ty => (c_type(ty), call),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emit_template(writer, &templates);
+    emitln!(
+        writer,
+        "inline {} {}({}) {{",
+        ty,
+        escape_keyword(name, KEYWORDS),
+        params.join(", ")
+    );
+    if ty == "void" {
+        emitln!(writer, "    {};", value);
+    } else {
+        emitln!(writer, "    return {};", value);
+    }
+    emitln!(writer, "}}");
+}
+
+/// Emits the overload taking the functions called with the results or the error, and the
+/// overload returning a future.
+pub fn emit_async_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+    callback: &Function,
+) {
+    let user_data = "new Callbacks{std::move(on_success), std::move(on_error)}";
+    let (templates, params, args) = convert_params(context, fun, Some(user_data));
+    let values = callback_values(context, callback);
+    let types: Vec<_> = values.iter().map(|(_, ty, _)| ty.as_str()).collect();
+
+    let closure_params: Vec<_> = flatten_params(callback)
+        .into_iter()
+        .map(|name| format!("auto {}", name))
+        .collect();
+    let converted: Vec<_> = values.iter().map(|(_, _, value)| value.as_str()).collect();
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emit_template(writer, &templates);
+    let mut callback_params = params.clone();
+    callback_params.push(format!(
+        "std::function<void({})> on_success",
+        types.join(", ")
+    ));
+    callback_params.push("std::function<void(const FfiError&)> on_error".to_string());
+    emitln!(
+        writer,
+        "inline void {}({}) {{",
+        escape_keyword(name, KEYWORDS),
+        callback_params.join(", ")
+    );
+    writer.indent();
+    emitln!(
+        writer,
+        "using Callbacks = detail::Callbacks<{}>;",
+        types.join(", ")
+    );
+    emitln!(writer, "::{}(", name);
+    writer.indent();
+    for arg in &args {
+        if arg == "callback" {
+            emitln!(writer, "[]({}) {{", closure_params.join(", "));
+            writer.indent();
+            emitln!(
+                writer,
+                "std::unique_ptr<Callbacks> callbacks(static_cast<Callbacks*>({}));",
+                param_name(&callback.inputs[0].0, 0, KEYWORDS)
+            );
+            let result = param_name(&callback.inputs[1].0, 1, KEYWORDS);
+            emitln!(
+                writer,
+                "if ({0} != nullptr && {0}->error_code != 0) {{",
+                result
+            );
+            emitln!(writer, "    callbacks->on_error(FfiError(*{}));", result);
+            emitln!(writer, "}} else {{");
+            emitln!(
+                writer,
+                "    callbacks->on_success({});",
+                converted.join(", ")
+            );
+            emitln!(writer, "}}");
+            writer.unindent();
+            emitln!(writer, "}});");
+        } else {
+            emitln!(writer, "{},", arg);
+        }
+    }
+    writer.unindent();
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    // The overload returning a future.
+    let future = match types.len() {
+        0 => "void".to_string(),
+        1 => types[0].to_string(),
+        _ => format!("std::tuple<{}>", types.join(", ")),
+    };
+    let (success_params, set_value): (Vec<_>, String) = {
+        let params = values
+            .iter()
+            .map(|(name, ty, _)| format!("{} {}", ty, name))
+            .collect();
+        let moved: Vec<_> = values
+            .iter()
+            .map(|(name, _, _)| format!("std::move({})", name))
+            .collect();
+        let value = match moved.len() {
+            0 => String::new(),
+            1 => moved[0].clone(),
+            _ => format!("std::make_tuple({})", moved.join(", ")),
+        };
+        (params, value)
+    };
+    let forwarded: Vec<_> = params
+        .iter()
+        .map(|param| unwrap_name(param).to_string())
+        .collect();
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emit_template(writer, &templates);
+    emitln!(
+        writer,
+        "inline std::future<{}> {}({}) {{",
+        future,
+        escape_keyword(name, KEYWORDS),
+        params.join(", ")
+    );
+    writer.indent();
+    emitln!(
+        writer,
+        "auto promise = std::make_shared<std::promise<{}>>();",
+        future
+    );
+    emitln!(writer, "auto future = promise->get_future();");
+    emitln!(writer, "{}(", escape_keyword(name, KEYWORDS));
+    writer.indent();
+    for arg in &forwarded {
+        emitln!(writer, "{},", arg);
+    }
+    emitln!(
+        writer,
+        "[promise]({}) {{ promise->set_value({}); }},",
+        success_params.join(", "),
+        set_value
+    );
+    emitln!(
+        writer,
+        "[promise](const FfiError& error) {{ promise->set_exception(std::make_exception_ptr(error)); }});"
+    );
+    writer.unindent();
+    emitln!(writer, "return future;");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+fn emit_template(writer: &mut IndentedWriter, templates: &[String]) {
+    if !templates.is_empty() {
+        emitln!(writer, "template <{}>", templates.join(", "));
+    }
+}
+
+/// The template parameters, parameters and arguments of the wrapper of the function. For the
+/// functions with asynchronous overloads, `user_data` is passed as the user data and the
+/// callback is left as `callback`.
+fn convert_params(
+    context: &Context,
+    fun: &Function,
+    user_data: Option<&str>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut templates = Vec::new();
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        if let Some(user_data) = user_data {
+            if is_user_data(name, ty) {
+                args.push(user_data.to_string());
+                continue;
+            }
+            if extract_callback(ty).is_some() {
+                args.push("callback".to_string());
+                continue;
+            }
+        }
+
+        let param = param_name(name, index, KEYWORDS);
+        match *ty {
+            Type::String => {
+                params.push(format!("const std::string& {}", param));
+                args.push(format!("{}.c_str()", param));
+            }
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                params.push(format!("const std::vector<{}>& {}", c_type(elem), param));
+                args.push(format!("{}.data()", param));
+                args.push(format!("{}.size()", param));
+            }
+            Type::User(ref name) if context.is_enum(name) => {
+                params.push(format!("{} {}", name, param));
+                args.push(format!("static_cast<::{}>({})", name, param));
+            }
+            Type::Pointer(ref ty) if is_handle(context, ty) => {
+                if let Type::User(ref name) = **ty {
+                    params.push(format!("const {}& {}", name, param));
+                    args.push(format!("{}.get()", param));
+                }
+            }
+            // `*const T` and `&T` are declared as `T const*` by the C header.
+            Type::Pointer(ref ty) if fun.const_pointers.contains(name) => {
+                params.push(format!("{} const* {}", c_type(ty), param));
+                args.push(param);
+            }
+            // Function pointers are taken as they are, with any type converting to them, as
+            // their constness is only known to the C header.
+            Type::Function(..) => {
+                let template = format!("{}Fn", name.to_pascal_case());
+                params.push(format!("{} {}", template, param));
+                templates.push(format!("typename {}", template));
+                args.push(param);
+            }
+            ref ty => {
+                params.push(format!("{} {}", c_type(ty), param));
+                args.push(param);
+            }
+        }
+    }
+
+    (templates, params, args)
+}
+
+/// The names, types and converted values of the results passed to the callback.
+fn callback_values(context: &Context, callback: &Function) -> Vec<(String, String, String)> {
+    let mut values = Vec::new();
+
+    for (index, (name, ty)) in callback.inputs.iter().enumerate().skip(2) {
+        let param = param_name(name, index, KEYWORDS);
+        let (ty, value) = match *ty {
+            Type::String => (
+                "std::string".to_string(),
+                format!("detail::to_string({})", param),
+            ),
+            Type::Array(ref elem, ArraySize::Dynamic) => (
+                format!("std::vector<{}>", c_type(elem)),
+                format!("detail::to_vector({}, {})", param, len_name(name, index)),
+            ),
+            Type::User(ref name) if context.is_enum(name) => {
+                (name.clone(), format!("static_cast<{}>({})", name, param))
+            }
+            Type::Pointer(ref ty) => match **ty {
+                Type::User(ref name) if context.is_handle(name) => {
+                    (name.clone(), format!("{}({})", name, param))
+                }
+                Type::User(ref name) if context.is_struct(name) => (
+                    format!("std::optional<::{}>", name),
+                    format!("detail::to_optional({})", param),
+                ),
+                ref ty => (format!("{}*", c_type(ty)), param.clone()),
+            },
+            ref ty => (c_type(ty), param.clone()),
+        };
+        values.push((param, ty, value));
+    }
+
+    values
+}
+
+/// The names of the parameters of the function, with dynamic arrays passed as pointer and
+/// length.
+fn flatten_params(fun: &Function) -> Vec<String> {
+    let mut params = Vec::with_capacity(fun.inputs.len());
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        params.push(param_name(name, index, KEYWORDS));
+        if ty.is_dynamic_array() {
+            params.push(len_name(name, index));
+        }
+    }
+    params
+}
+
+fn is_handle(context: &Context, ty: &Type) -> bool {
+    match *ty {
+        Type::User(ref name) => context.is_handle(name),
+        _ => false,
+    }
+}
+
+/// The C type of the Rust type, as declared by the C header.
+pub fn c_type(ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "void".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Char | Type::CChar => "char".to_string(),
+        Type::F32 => "float".to_string(),
+        Type::F64 => "double".to_string(),
+        Type::I8 => "std::int8_t".to_string(),
+        Type::I16 => "std::int16_t".to_string(),
+        Type::I32 => "std::int32_t".to_string(),
+        Type::I64 => "std::int64_t".to_string(),
+        Type::ISize => "std::intptr_t".to_string(),
+        Type::U8 => "std::uint8_t".to_string(),
+        Type::U16 => "std::uint16_t".to_string(),
+        Type::U32 => "std::uint32_t".to_string(),
+        Type::U64 => "std::uint64_t".to_string(),
+        Type::USize => "std::uintptr_t".to_string(),
+        Type::String => "const char*".to_string(),
+        // Arrays are passed to functions as pointers.
+        Type::Pointer(ref ty) | Type::Array(ref ty, _) => format!("{}*", c_type(ty)),
+        Type::Function(ref fun) => {
+            let params: Vec<_> = fun.inputs.iter().map(|(_, ty)| c_type(ty)).collect();
+            format!(
+                "std::add_pointer_t<{}({})>",
+                c_type(&fun.output),
+                params.join(", ")
+            )
+        }
+        Type::User(ref name) => format!("::{}", name),
+    }
+}
+
+/// The C++ literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => format!("U'{}'", escape_char(value, '\'')),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => {
+            let chars: String = value.chars().map(|ch| escape_char(ch, '"')).collect();
+            format!("\"{}\"", chars)
+        }
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("{{{}}}", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+fn escape_char(ch: char, quote: char) -> String {
+    match ch {
+        '\\' => "\\\\".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        ch if ch == quote => format!("\\{}", ch),
+        ch => ch.to_string(),
+    }
+}
+
+/// The name declared by the parameter declaration.
+fn unwrap_name(param: &str) -> &str {
+    param.rsplit(' ').next().unwrap_or(param)
+}
+
+fn len_name(name: &str, index: usize) -> String {
+    if name.is_empty() {
+        format!("arg{}_len", index)
+    } else {
+        format!("{}_len", name)
+    }
+}
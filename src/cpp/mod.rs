@@ -0,0 +1,461 @@
+//! Functions for converting Rust definitions into a header-only C++17 library, wrapping the C
+//! header with `enum class` enums, RAII classes owning the opaque handles and functions taking
+//! and returning standard library types.
+//!
+//! Functions taking a single callback, which receives the user data and a `FfiResult`, get an
+//! overload taking `std::function`s called with the results or the error, and another one
+//! returning a `std::future`, which throws a `FfiError` on failure. The values passed to the
+//! callbacks are copied, so they may be used once the callback has returned.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, Lang, Outputs};
+use crate::intermediate::*;
+use crate::lang_c::LangC;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 4;
+
+pub struct LangCpp {
+    c: LangC,
+    lib_name: String,
+    /// Functions releasing the opaque types owned by RAII classes, by type name.
+    handles: HashMap<String, String>,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    /// Names of the structs and unions, which are used as the C types.
+    structs: Vec<String>,
+    functions: Vec<Snippet<Function>>,
+    aliases: HashMap<String, Type>,
+    /// Names of the types declared in the C header.
+    declared: HashSet<String>,
+}
+
+pub struct Context {
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs and unions.
+    structs: HashSet<String>,
+    /// Functions releasing the opaque types owned by RAII classes, by type name.
+    handles: HashMap<String, String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_struct(&self, name: &str) -> bool {
+        self.structs.contains(name)
+    }
+
+    pub fn is_handle(&self, name: &str) -> bool {
+        self.handles.contains_key(name)
+    }
+}
+
+impl LangCpp {
+    pub fn new() -> Self {
+        let mut lang = LangCpp {
+            c: LangC::new(),
+            lib_name: String::new(),
+            handles: Default::default(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            aliases: Default::default(),
+            declared: Default::default(),
+        };
+        lang.c.set_split_headers_enabled(false);
+        lang.set_lib_name("backend");
+        lang
+    }
+
+    /// Set the name of the native library, which is also the name of the C++ namespace and of
+    /// the C header it includes.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+        self.c.set_lib_name(self.lib_name.clone());
+        self.c
+            .set_amalgamated_header(format!("{}.h", self.lib_name));
+    }
+
+    /// Declare that the pointers to the opaque type `name` returned by the native library are
+    /// owned by the caller, and released by the function `free`. The type is then wrapped by a
+    /// move-only class calling `free` from its destructor, which the functions take and return
+    /// instead of the pointers.
+    pub fn add_handle<N: Into<String>, F: Into<String>>(&mut self, name: N, free: F) {
+        let _ = self.handles.insert(name.into(), free.into());
+    }
+
+    /// Add custom code to the C header, e.g. declarations of additional types.
+    pub fn add_custom_code(&mut self, code: &str) {
+        self.c.add_custom_code(code);
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared in the C header
+    /// `header` of its own bindings, which is included instead.
+    pub fn add_provided_package<P: AsRef<str>, H: Into<String>>(&mut self, package: P, header: H) {
+        self.c.add_provided_package(package, header);
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.consts {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Names of the user types used by the functions, in alphabetical order.
+    fn used_types(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        for snippet in &self.functions {
+            let inputs = snippet.item.inputs.iter().map(|(_, ty)| ty);
+            for ty in inputs.chain(Some(&snippet.item.output)) {
+                visit_type(ty, &mut |ty| {
+                    if let Type::User(ref name) = *ty {
+                        let _ = names.insert(name.clone());
+                    }
+                });
+            }
+        }
+        names
+    }
+
+    fn emit_header(&self, context: &Context) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.consts {
+                emit_const(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        if !self.structs.is_empty() {
+            emitln!(writer, "");
+            for name in &self.structs {
+                emitln!(writer, "using {0} = ::{0};", name);
+            }
+        }
+
+        let mut handles: Vec<_> = context.handles.iter().collect();
+        handles.sort();
+        for (name, free) in handles {
+            emit_handle(&mut writer, name, free);
+        }
+
+        let futures = self
+            .functions
+            .iter()
+            .any(|snippet| async_callback(&snippet.item, |name| context.is_struct(name)).is_some());
+        if futures {
+            emit_error(&mut writer);
+        }
+
+        let mut functions = IndentedWriter::new(INDENT_WIDTH);
+        for snippet in &self.functions {
+            let fun = &snippet.item;
+            match async_callback(fun, |name| context.is_struct(name)) {
+                Some(callback) => emit_async_function(
+                    &mut functions,
+                    context,
+                    &snippet.name,
+                    &snippet.docs,
+                    fun,
+                    callback,
+                ),
+                None => emit_function(&mut functions, context, &snippet.name, &snippet.docs, fun),
+            }
+        }
+        let functions = functions.into_inner();
+        emit_detail(&mut writer, &functions, futures);
+        unwrap!(writer.write_str(&functions));
+
+        let body = writer.into_inner();
+        let guard = format!("{}_HPP", self.lib_name.to_uppercase());
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "#ifndef {}", guard);
+        emitln!(writer, "#define {}\n", guard);
+        emit_includes(&mut writer, &body);
+        emitln!(writer, "\n#include \"{}.h\"\n", self.lib_name);
+        emitln!(
+            writer,
+            "/// Bindings of the native library `{}`.",
+            self.lib_name
+        );
+        emitln!(writer, "namespace {} {{", self.lib_name);
+        unwrap!(writer.write_str(&body));
+        emitln!(writer, "\n}}  // namespace {}\n", self.lib_name);
+        emitln!(writer, "#endif  // {}", guard);
+
+        writer.into_inner()
+    }
+}
+
+impl Default for LangCpp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangCpp {
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_const(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        if let ConstValue::Struct(..) = item.value {
+            println!(
+                "constants of struct types not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_ty(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        if !item.generics.params.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(ty) = transform_type(&item.ty) {
+            let _ = self.declared.insert(name.clone());
+            self.aliases.insert(name, ty);
+        }
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_enum(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+
+        // Data-carrying enums are used as the C type.
+        if common::has_data_variants(item) {
+            self.structs.push(name);
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_struct(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                if let Some(ty) = transform_type(&field.ty) {
+                    self.aliases.insert(name, ty);
+                }
+            }
+            return Ok(());
+        }
+
+        self.structs.push(name);
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_union(item, module, outputs)?;
+
+        if common::parse_attr(&item.attrs[..], common::check_repr_c, |_| None).0 {
+            let name = item.ident.to_string();
+            let _ = self.declared.insert(name.clone());
+            self.structs.push(name);
+        }
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        // Statics are used through the C header.
+        self.c.parse_static(item, module, outputs)
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_fn(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self.structs.iter().cloned().collect(),
+            // Only the handles released by the functions of this crate are wrapped.
+            handles: self
+                .handles
+                .iter()
+                .filter(|(_, free)| self.functions.iter().any(|snippet| snippet.name == **free))
+                .map(|(name, free)| (name.clone(), free.clone()))
+                .collect(),
+        };
+
+        // The opaque types are only known to the C header through these declarations.
+        for name in self.used_types() {
+            if !self.declared.contains(&name) {
+                self.c
+                    .add_custom_code(&format!("typedef struct {0} {0};\n", name));
+            }
+        }
+        self.c.finalise_output(outputs)?;
+
+        // The handles are released by their destructors instead.
+        let handles = &context.handles;
+        self.functions
+            .retain(|snippet| !handles.values().any(|free| *free == snippet.name));
+
+        let header = self.emit_header(&context);
+        outputs.insert(format!("{}.hpp", self.lib_name), header);
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.declared.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    /// The header of the unit is included from the library generated next to this one.
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, format!("../{0}/{0}.h", reference));
+    }
+}
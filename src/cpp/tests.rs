@@ -0,0 +1,558 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangCpp::default(), {
+        /// Maximum number of layers.
+        pub const MAX_LAYERS: u16 = 8;
+        pub const SHADER_PATH: &'static str = "C:\\shaders\\\"default\"";
+        pub const CLEAR_COLOR: [f32; 3] = [0.5, 0.25, 1.0];
+
+        /// Blending applied to a layer.
+        #[repr(i8)]
+        pub enum Blend {
+            /// Subtracts the layer.
+            Subtract = -1,
+            Normal,
+            Additive = 3,
+        }
+
+        #[repr(C)]
+        pub enum Filter {
+            Nearest,
+            Linear,
+        }
+
+        #[repr(C)]
+        pub struct Layer {
+            opacity: f32,
+            blend: Blend,
+        }
+
+        #[repr(C)]
+        pub union Pixel {
+            rgba: u32,
+            luminance: f32,
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.hpp");
+    let expected = indoc!(
+        r#"#ifndef BACKEND_HPP
+           #define BACKEND_HPP
+
+           #include <cstdint>
+
+           #include "backend.h"
+
+           /// Bindings of the native library `backend`.
+           namespace backend {
+
+           /// Maximum number of layers.
+           inline constexpr std::uint16_t MAX_LAYERS = 8;
+           inline constexpr const char* SHADER_PATH = "C:\\shaders\\\"default\"";
+           inline constexpr float CLEAR_COLOR[] = {0.5, 0.25, 1.0};
+
+           /// Blending applied to a layer.
+           enum class Blend : std::int8_t {
+               /// Subtracts the layer.
+               Subtract = -1,
+               Normal = 0,
+               Additive = 3,
+           };
+
+           enum class Filter : int {
+               Nearest = 0,
+               Linear = 1,
+           };
+
+           using Layer = ::Layer;
+           using Pixel = ::Pixel;
+
+           }  // namespace backend
+
+           #endif  // BACKEND_HPP
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangCpp::default(), {
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn record_id(record: *const Record) -> u64 {}
+
+        #[no_mangle]
+        pub extern "C" fn record_copy(from: &Record, to: &mut Record) {}
+
+        /// Writes the data
+        /// and the numbers.
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            numbers: *const i32,
+            numbers_len: usize,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn default_mode() -> Mode {}
+
+        #[no_mangle]
+        pub extern "C" fn visit(records: *const Record, visitor: extern "C" fn(id: u64) -> bool) {}
+    });
+
+    let actual = fetch(&outputs, "backend.hpp");
+    let expected = indoc!(
+        r#"#ifndef BACKEND_HPP
+           #define BACKEND_HPP
+
+           #include <cstdint>
+           #include <string>
+           #include <vector>
+
+           #include "backend.h"
+
+           /// Bindings of the native library `backend`.
+           namespace backend {
+
+           enum class Mode : int {
+               Read = 0,
+               Write = 1,
+           };
+
+           using Record = ::Record;
+
+           namespace detail {
+
+           inline std::string to_string(const char* value) {
+               return value == nullptr ? std::string() : std::string(value);
+           }
+
+           }  // namespace detail
+
+           inline std::uint64_t record_id(::Record const* record) {
+               return ::record_id(record);
+           }
+
+           inline void record_copy(::Record const* from, ::Record* to) {
+               ::record_copy(from, to);
+           }
+
+           /// Writes the data
+           /// and the numbers.
+           inline void write(const std::string& name, const std::vector<std::uint8_t>& data, const std::vector<std::int32_t>& numbers) {
+               ::write(name.c_str(), data.data(), data.size(), numbers.data(), numbers.size());
+           }
+
+           inline std::string mode_name(Mode mode) {
+               return detail::to_string(::mode_name(static_cast<::Mode>(mode)));
+           }
+
+           inline Mode default_mode() {
+               return static_cast<Mode>(::default_mode());
+           }
+
+           template <typename VisitorFn>
+           inline void visit(::Record const* records, VisitorFn visitor) {
+               ::visit(records, visitor);
+           }
+
+           }  // namespace backend
+
+           #endif  // BACKEND_HPP
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn handles() {
+    let mut lang = LangCpp::default();
+    lang.add_handle("App", "app_free");
+    lang.add_handle("Session", "session_free");
+
+    let outputs = compile!(lang, {
+        #[no_mangle]
+        pub extern "C" fn app_new(name: *const c_char) -> *mut App {}
+
+        #[no_mangle]
+        pub extern "C" fn app_name(app: *const App) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+
+        #[no_mangle]
+        pub extern "C" fn app_config(app: *const App) -> *const Config {}
+    });
+
+    let actual = fetch(&outputs, "backend.hpp");
+    let expected = indoc!(
+        r#"#ifndef BACKEND_HPP
+           #define BACKEND_HPP
+
+           #include <string>
+           #include <utility>
+
+           #include "backend.h"
+
+           /// Bindings of the native library `backend`.
+           namespace backend {
+
+           /// Owning handle of a native `App`, released by `app_free`.
+           class App {
+           public:
+               /// Takes the ownership of the handle.
+               explicit App(::App* handle) noexcept : handle_(handle) {}
+
+               App(App&& other) noexcept : handle_(std::exchange(other.handle_, nullptr)) {}
+
+               App& operator=(App&& other) noexcept {
+                   std::swap(handle_, other.handle_);
+                   return *this;
+               }
+
+               App(const App&) = delete;
+               App& operator=(const App&) = delete;
+
+               ~App() {
+                   if (handle_ != nullptr) {
+                       ::app_free(handle_);
+                   }
+               }
+
+               /// The handle, still owned by this object.
+               ::App* get() const noexcept { return handle_; }
+
+               /// Gives up the ownership of the handle.
+               ::App* release() noexcept { return std::exchange(handle_, nullptr); }
+
+           private:
+               ::App* handle_;
+           };
+
+           namespace detail {
+
+           inline std::string to_string(const char* value) {
+               return value == nullptr ? std::string() : std::string(value);
+           }
+
+           }  // namespace detail
+
+           inline App app_new(const std::string& name) {
+               return App(::app_new(name.c_str()));
+           }
+
+           inline std::string app_name(const App& app) {
+               return detail::to_string(::app_name(app.get()));
+           }
+
+           inline auto app_config(const App& app) {
+               return ::app_config(app.get());
+           }
+
+           }  // namespace backend
+
+           #endif  // BACKEND_HPP
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_callbacks() {
+    let mut lang = LangCpp::default();
+    lang.add_handle("App", "app_free");
+
+    let outputs = compile!(lang, {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+
+        #[no_mangle]
+        pub extern "C" fn app_connect(
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, app: *mut App),
+        ) {
+        }
+
+        /// Fetches the entry.
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn first_record(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                record: *const Record,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.hpp");
+    let expected = indoc!(
+        r#"#ifndef BACKEND_HPP
+           #define BACKEND_HPP
+
+           #include <cstdint>
+           #include <exception>
+           #include <functional>
+           #include <future>
+           #include <memory>
+           #include <optional>
+           #include <stdexcept>
+           #include <string>
+           #include <tuple>
+           #include <type_traits>
+           #include <utility>
+           #include <vector>
+
+           #include "backend.h"
+
+           /// Bindings of the native library `backend`.
+           namespace backend {
+
+           using FfiResult = ::FfiResult;
+           using Record = ::Record;
+
+           /// Owning handle of a native `App`, released by `app_free`.
+           class App {
+           public:
+               /// Takes the ownership of the handle.
+               explicit App(::App* handle) noexcept : handle_(handle) {}
+
+               App(App&& other) noexcept : handle_(std::exchange(other.handle_, nullptr)) {}
+
+               App& operator=(App&& other) noexcept {
+                   std::swap(handle_, other.handle_);
+                   return *this;
+               }
+
+               App(const App&) = delete;
+               App& operator=(const App&) = delete;
+
+               ~App() {
+                   if (handle_ != nullptr) {
+                       ::app_free(handle_);
+                   }
+               }
+
+               /// The handle, still owned by this object.
+               ::App* get() const noexcept { return handle_; }
+
+               /// Gives up the ownership of the handle.
+               ::App* release() noexcept { return std::exchange(handle_, nullptr); }
+
+           private:
+               ::App* handle_;
+           };
+
+           class FfiError : public std::runtime_error {
+           public:
+               explicit FfiError(const ::FfiResult& result)
+                   : std::runtime_error(result.description != nullptr ? result.description : ""),
+                     code_(result.error_code) {}
+
+               /// The error code of the result.
+               decltype(::FfiResult::error_code) code() const noexcept { return code_; }
+
+           private:
+               decltype(::FfiResult::error_code) code_;
+           };
+
+           namespace detail {
+
+           inline std::string to_string(const char* value) {
+               return value == nullptr ? std::string() : std::string(value);
+           }
+
+           template <typename T>
+           std::vector<std::remove_cv_t<T>> to_vector(T* data, std::uintptr_t len) {
+               if (data == nullptr) {
+                   return {};
+               }
+               return std::vector<std::remove_cv_t<T>>(data, data + len);
+           }
+
+           template <typename T>
+           std::optional<std::remove_cv_t<T>> to_optional(T* value) {
+               if (value == nullptr) {
+                   return std::nullopt;
+               }
+               return *value;
+           }
+
+           template <typename... Values>
+           struct Callbacks {
+               std::function<void(Values...)> on_success;
+               std::function<void(const FfiError&)> on_error;
+           };
+
+           }  // namespace detail
+
+           inline void app_connect(std::function<void(App)> on_success, std::function<void(const FfiError&)> on_error) {
+               using Callbacks = detail::Callbacks<App>;
+               ::app_connect(
+                   new Callbacks{std::move(on_success), std::move(on_error)},
+                   [](auto user_data, auto result, auto app) {
+                       std::unique_ptr<Callbacks> callbacks(static_cast<Callbacks*>(user_data));
+                       if (result != nullptr && result->error_code != 0) {
+                           callbacks->on_error(FfiError(*result));
+                       } else {
+                           callbacks->on_success(App(app));
+                       }
+                   });
+           }
+
+           inline std::future<App> app_connect() {
+               auto promise = std::make_shared<std::promise<App>>();
+               auto future = promise->get_future();
+               app_connect(
+                   [promise](App app) { promise->set_value(std::move(app)); },
+                   [promise](const FfiError& error) { promise->set_exception(std::make_exception_ptr(error)); });
+               return future;
+           }
+
+           /// Fetches the entry.
+           inline void fetch(const App& app, const std::string& key, std::function<void(std::string, std::vector<std::uint8_t>)> on_success, std::function<void(const FfiError&)> on_error) {
+               using Callbacks = detail::Callbacks<std::string, std::vector<std::uint8_t>>;
+               ::fetch(
+                   app.get(),
+                   key.c_str(),
+                   new Callbacks{std::move(on_success), std::move(on_error)},
+                   [](auto user_data, auto result, auto name, auto data, auto data_len) {
+                       std::unique_ptr<Callbacks> callbacks(static_cast<Callbacks*>(user_data));
+                       if (result != nullptr && result->error_code != 0) {
+                           callbacks->on_error(FfiError(*result));
+                       } else {
+                           callbacks->on_success(detail::to_string(name), detail::to_vector(data, data_len));
+                       }
+                   });
+           }
+
+           /// Fetches the entry.
+           inline std::future<std::tuple<std::string, std::vector<std::uint8_t>>> fetch(const App& app, const std::string& key) {
+               auto promise = std::make_shared<std::promise<std::tuple<std::string, std::vector<std::uint8_t>>>>();
+               auto future = promise->get_future();
+               fetch(
+                   app,
+                   key,
+                   [promise](std::string name, std::vector<std::uint8_t> data) { promise->set_value(std::make_tuple(std::move(name), std::move(data))); },
+                   [promise](const FfiError& error) { promise->set_exception(std::make_exception_ptr(error)); });
+               return future;
+           }
+
+           inline void first_record(const App& app, std::function<void(std::optional<::Record>)> on_success, std::function<void(const FfiError&)> on_error) {
+               using Callbacks = detail::Callbacks<std::optional<::Record>>;
+               ::first_record(
+                   app.get(),
+                   new Callbacks{std::move(on_success), std::move(on_error)},
+                   [](auto user_data, auto result, auto record) {
+                       std::unique_ptr<Callbacks> callbacks(static_cast<Callbacks*>(user_data));
+                       if (result != nullptr && result->error_code != 0) {
+                           callbacks->on_error(FfiError(*result));
+                       } else {
+                           callbacks->on_success(detail::to_optional(record));
+                       }
+                   });
+           }
+
+           inline std::future<std::optional<::Record>> first_record(const App& app) {
+               auto promise = std::make_shared<std::promise<std::optional<::Record>>>();
+               auto future = promise->get_future();
+               first_record(
+                   app,
+                   [promise](std::optional<::Record> record) { promise->set_value(std::move(record)); },
+                   [promise](const FfiError& error) { promise->set_exception(std::make_exception_ptr(error)); });
+               return future;
+           }
+
+           inline void flush(const App& app, std::function<void()> on_success, std::function<void(const FfiError&)> on_error) {
+               using Callbacks = detail::Callbacks<>;
+               ::flush(
+                   app.get(),
+                   new Callbacks{std::move(on_success), std::move(on_error)},
+                   [](auto user_data, auto result) {
+                       std::unique_ptr<Callbacks> callbacks(static_cast<Callbacks*>(user_data));
+                       if (result != nullptr && result->error_code != 0) {
+                           callbacks->on_error(FfiError(*result));
+                       } else {
+                           callbacks->on_success();
+                       }
+                   });
+           }
+
+           inline std::future<void> flush(const App& app) {
+               auto promise = std::make_shared<std::promise<void>>();
+               auto future = promise->get_future();
+               flush(
+                   app,
+                   [promise]() { promise->set_value(); },
+                   [promise](const FfiError& error) { promise->set_exception(std::make_exception_ptr(error)); });
+               return future;
+           }
+
+           }  // namespace backend
+
+           #endif  // BACKEND_HPP
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
@@ -1,6 +1,6 @@
 //! Utilities for emiting fragments of the target language code.
-use super::intermediate::*;
 use super::Context;
+use crate::intermediate::*;
 use crate::output::IndentedWriter;
 use inflector::Inflector;
 use std::fmt::Write;
@@ -9,17 +9,6 @@ use unwrap::unwrap;
 const LEN_TYPE: &str = "UIntPtr";
 const LEN_ZERO: &str = "UIntPtr.Zero";
 
-macro_rules! emit {
-    ($writer:expr, $($arg:tt)*) => {
-        unwrap!(write!($writer, $($arg)*))
-    }
-}
-macro_rules! emitln {
-    ($writer:expr, $($arg:tt)*) => {
-        unwrap!(writeln!($writer, $($arg)*))
-    }
-}
-
 pub fn emit_wrapper_function_decl(
     writer: &mut IndentedWriter,
     context: &Context,
@@ -58,6 +47,8 @@ pub fn emit_wrapper_function(
     emitln!(writer, "\n{{");
     writer.indent();
 
+    emit_null_checks(writer, fun);
+
     // Convert wrapper structs to native structs.
     for &(ref name, ref ty) in &fun.inputs {
         if context.is_native_type(ty) {
@@ -134,6 +125,34 @@ pub fn emit_wrapper_function(
     emitln!(writer, "}}\n");
 }
 
+/// Throws `ArgumentNullException` for null arguments which are passed to non-null pointers
+/// (references, `NonNull` and `Box`). Only reference types in C# can be null.
+fn emit_null_checks(writer: &mut IndentedWriter, fun: &Function) {
+    for (name, ty) in &fun.inputs {
+        if !fun.non_null.contains(name) {
+            continue;
+        }
+
+        let nullable = match *ty {
+            Type::String | Type::Array(..) => true,
+            Type::Pointer(ref ty) => matches!(**ty, Type::Array(..)),
+            _ => false,
+        };
+
+        if nullable {
+            emitln!(writer, "if ({} == null)\n{{", name.to_camel_case());
+            writer.indent();
+            emitln!(
+                writer,
+                "throw new ArgumentNullException(nameof({}));",
+                name.to_camel_case()
+            );
+            writer.unindent();
+            emitln!(writer, "}}\n");
+        }
+    }
+}
+
 pub fn emit_function_extern_decl(
     writer: &mut IndentedWriter,
     context: &Context,
@@ -225,9 +244,166 @@ pub fn emit_const(writer: &mut IndentedWriter, context: &Context, name: &str, it
     emitln!(writer, ";");
 }
 
+pub fn emit_static_library_handle(writer: &mut IndentedWriter, context: &Context) {
+    emitln!(
+        writer,
+        "private static readonly IntPtr NativeLibraryHandle = \
+         NativeLibrary.Load(DllName, typeof({}).Assembly, null);\n",
+        context.functions_section.class
+    );
+}
+
+/// Emits a property reading (and writing, for mutable statics) the exported static through
+/// its address. `enum_ty` is the underlying type of the static if it's an enum.
+pub fn emit_static(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    item: &Static,
+    enum_ty: Option<&Type>,
+) {
+    let address = format!("NativeLibrary.GetExport(NativeLibraryHandle, \"{}\")", name);
+    let type_name = static_type_name(context, &item.ty);
+    let (read, write) = match enum_ty {
+        Some(int) => {
+            let value = format!("({})value", static_type_name(context, int));
+            let (read, write) = unwrap!(static_accessors(int, "", &address, &value));
+            (format!("({}){}", type_name, read), write)
+        }
+        None => unwrap!(static_accessors(&item.ty, &type_name, &address, "value")),
+    };
+
+    emit!(
+        writer,
+        "public static {} {}",
+        type_name,
+        name.to_pascal_case()
+    );
+
+    match write {
+        Some(ref write) if item.mutable => {
+            emitln!(writer, "");
+            emitln!(writer, "{{");
+            writer.indent();
+            emitln!(writer, "get => {};", read);
+            emitln!(writer, "set => {};", write);
+            writer.unindent();
+            emitln!(writer, "}}\n");
+        }
+        _ => emitln!(writer, " => {};\n", read),
+    }
+}
+
+fn static_type_name(context: &Context, ty: &Type) -> String {
+    let mut writer = IndentedWriter::new(0);
+    emit_type(&mut writer, context, ty, Mode::Const);
+    writer.into_inner()
+}
+
+/// Returns the expressions reading the static at `address` and writing `value` into it, or
+/// `None` if the type can't be marshalled this way.
+pub fn static_accessors(
+    ty: &Type,
+    type_name: &str,
+    address: &str,
+    value: &str,
+) -> Option<(String, Option<String>)> {
+    let (read, write) = match *ty {
+        Type::Bool => (
+            format!("Marshal.ReadByte({}) != 0", address),
+            format!("Marshal.WriteByte({}, (byte)({} ? 1 : 0))", address, value),
+        ),
+        Type::U8 => (
+            format!("Marshal.ReadByte({})", address),
+            format!("Marshal.WriteByte({}, {})", address, value),
+        ),
+        Type::I8 => (
+            format!("(sbyte)Marshal.ReadByte({})", address),
+            format!("Marshal.WriteByte({}, (byte){})", address, value),
+        ),
+        Type::I16 => (
+            format!("Marshal.ReadInt16({})", address),
+            format!("Marshal.WriteInt16({}, {})", address, value),
+        ),
+        Type::U16 => (
+            format!("(ushort)Marshal.ReadInt16({})", address),
+            format!("Marshal.WriteInt16({}, (short){})", address, value),
+        ),
+        Type::I32 => (
+            format!("Marshal.ReadInt32({})", address),
+            format!("Marshal.WriteInt32({}, {})", address, value),
+        ),
+        Type::U32 => (
+            format!("(uint)Marshal.ReadInt32({})", address),
+            format!("Marshal.WriteInt32({}, (int){})", address, value),
+        ),
+        Type::I64 => (
+            format!("Marshal.ReadInt64({})", address),
+            format!("Marshal.WriteInt64({}, {})", address, value),
+        ),
+        Type::U64 => (
+            format!("(ulong)Marshal.ReadInt64({})", address),
+            format!("Marshal.WriteInt64({}, (long){})", address, value),
+        ),
+        Type::ISize | Type::Pointer(_) => (
+            format!("Marshal.ReadIntPtr({})", address),
+            format!("Marshal.WriteIntPtr({}, {})", address, value),
+        ),
+        Type::USize => (
+            format!(
+                "new UIntPtr((ulong)Marshal.ReadIntPtr({}).ToInt64())",
+                address
+            ),
+            format!(
+                "Marshal.WriteIntPtr({}, new IntPtr((long){}.ToUInt64()))",
+                address, value
+            ),
+        ),
+        Type::F32 => (
+            format!(
+                "BitConverter.Int32BitsToSingle(Marshal.ReadInt32({}))",
+                address
+            ),
+            format!(
+                "Marshal.WriteInt32({}, BitConverter.SingleToInt32Bits({}))",
+                address, value
+            ),
+        ),
+        Type::F64 => (
+            format!(
+                "BitConverter.Int64BitsToDouble(Marshal.ReadInt64({}))",
+                address
+            ),
+            format!(
+                "Marshal.WriteInt64({}, BitConverter.DoubleToInt64Bits({}))",
+                address, value
+            ),
+        ),
+        // The string is owned by the native library, so it can only be read
+        Type::String => {
+            return Some((
+                format!("Marshal.PtrToStringUTF8(Marshal.ReadIntPtr({}))", address),
+                None,
+            ));
+        }
+        Type::User(_) => (
+            format!("Marshal.PtrToStructure<{}>({})", type_name, address),
+            format!("Marshal.StructureToPtr({}, {}, false)", value, address),
+        ),
+        _ => return None,
+    };
+
+    Some((read, Some(write)))
+}
+
 pub fn emit_enum(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Enum) {
     emitln!(writer, "[PublicAPI]");
-    emitln!(writer, "public enum {}\n{{", name);
+    emit!(writer, "public enum {}", name);
+    if let Some(ref ty) = item.ty {
+        emit!(writer, " : ");
+        emit_type(writer, context, ty, Mode::Const);
+    }
+    emitln!(writer, "\n{{");
     writer.indent();
 
     for variant in &item.variants {
@@ -251,11 +427,15 @@ pub fn emit_normal_struct(
     item: &Struct,
 ) {
     emitln!(writer, "[PublicAPI]");
+    emit_struct_layout(writer, item);
     emitln!(writer, "public struct {}\n{{", name);
     writer.indent();
 
     for field in &item.fields {
         emit_docs(writer, context, &field.docs);
+        if item.union {
+            emitln!(writer, "[FieldOffset(0)]");
+        }
         emit_struct_field(writer, context, field, StructMode::Normal);
     }
 
@@ -269,6 +449,7 @@ pub fn emit_native_struct(
     name: &str,
     item: &Struct,
 ) {
+    emit_struct_layout(writer, item);
     emitln!(writer, "internal struct {}Native\n{{", name);
     writer.indent();
 
@@ -304,6 +485,20 @@ pub fn emit_native_struct(
     emitln!(writer, "}}\n");
 }
 
+// Structs are laid out sequentially by default, so the attribute is only needed for packing
+// and unions.
+fn emit_struct_layout(writer: &mut IndentedWriter, item: &Struct) {
+    if item.union {
+        emitln!(writer, "[StructLayout(LayoutKind.Explicit)]");
+    } else if let Some(pack) = item.pack {
+        emitln!(
+            writer,
+            "[StructLayout(LayoutKind.Sequential, Pack = {})]",
+            pack
+        );
+    }
+}
+
 pub fn emit_wrapper_struct(
     writer: &mut IndentedWriter,
     context: &Context,
@@ -778,7 +973,9 @@ fn emit_type(writer: &mut IndentedWriter, context: &Context, ty: &Type, mode: Mo
             _ => emit!(writer, "IntPtr"),
         },
         Type::Array(ref ty, ref size) => emit_array(writer, context, ty, size, mode),
-        Type::Function(..) => unimplemented!(),
+        // Function pointers, other than the callbacks completing tasks, are passed as they are,
+        // `IntPtr.Zero` being the null pointer.
+        Type::Function(..) => emit!(writer, "IntPtr"),
         Type::User(ref name) => {
             if context.is_native_name(name)
                 && (mode == Mode::Callback
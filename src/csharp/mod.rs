@@ -1,17 +1,15 @@
-#[macro_use]
 mod emit;
-mod intermediate;
 #[cfg(test)]
 mod tests;
 
 use self::emit::*;
-use self::intermediate::*;
 use crate::common::{self, FilterMode, Lang, Outputs};
+use crate::intermediate::*;
 use crate::output::IndentedWriter;
 use crate::{Error, Level};
 use inflector::Inflector;
 use std::collections::btree_map::Entry;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Write};
 use std::mem;
 use unwrap::unwrap;
@@ -31,7 +29,14 @@ pub struct LangCSharp {
     enums: Vec<Snippet<Enum>>,
     structs: Vec<Snippet<Struct>>,
     functions: Vec<Snippet<Function>>,
+    statics: Vec<Snippet<Static>>,
     aliases: HashMap<String, Type>,
+    /// Namespaces of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates.
+    provided_types: HashSet<String>,
+    /// Namespaces of the provided types which are referred to.
+    provided_namespaces: BTreeSet<String>,
 }
 
 pub struct Context {
@@ -110,7 +115,11 @@ impl LangCSharp {
             enums: Vec::new(),
             structs: Vec::new(),
             functions: Vec::new(),
+            statics: Vec::new(),
             aliases: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+            provided_namespaces: Default::default(),
         }
     }
 
@@ -188,6 +197,19 @@ impl LangCSharp {
         let _ = self.context.opaque_types.insert(name.into());
     }
 
+    /// Declare that the types of the dependency crate `package` are generated into the
+    /// namespace `namespace` by its own bindings. They aren't generated again, but imported
+    /// with a `using` directive instead.
+    pub fn add_provided_package<P, N>(&mut self, package: P, namespace: N)
+    where
+        P: Into<String>,
+        N: Into<String>,
+    {
+        let _ = self
+            .provided_packages
+            .insert(package.into(), namespace.into());
+    }
+
     /// Add constant definition.
     pub fn add_const<T: Display>(&mut self, ty: &str, name: &str, value: T) {
         self.custom_consts.push(format!(
@@ -270,6 +292,186 @@ impl LangCSharp {
         }
     }
 
+    /// Convert a `#[repr(transparent)]` struct into a struct wrapping a single `Value` field,
+    /// so that it stays distinct from the wrapped type.
+    fn parse_transparent_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        docs: String,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        let field = match common::transparent_field(&item.fields) {
+            Some(field) => field,
+            None => {
+                println!(
+                    "zero-sized transparent structs not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+        let ty = transform_type(&field.ty).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+
+        let item = Struct {
+            fields: vec![StructField {
+                docs: String::new(),
+                name: "value".to_string(),
+                ty,
+                has_cap: false,
+            }],
+            pack: None,
+            union: false,
+        };
+        self.structs.push(Snippet { docs, name, item });
+        self.resolve_native_types();
+
+        Ok(())
+    }
+
+    /// Convert an enum with data-carrying variants into a tagged union, following the layout
+    /// defined by RFC 2195. The variant bodies overlap, so their fields must be blittable.
+    fn parse_tagged_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        docs: String,
+        repr_c: bool,
+        tag_ty: Option<Type>,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        let tag = format!("{}Tag", name);
+        let tag_field = StructField {
+            docs: String::new(),
+            name: "tag".to_string(),
+            ty: Type::User(tag.clone()),
+            has_cap: false,
+        };
+
+        let mut variants = Vec::new();
+        let mut bodies = Vec::new();
+        let mut union_fields = Vec::new();
+        if !repr_c {
+            union_fields.push(tag_field.clone());
+        }
+
+        for variant in &item.variants {
+            let (_, variant_docs) =
+                common::parse_attr(&variant.attrs[..], |_| true, retrieve_docstring);
+            variants.push(EnumVariant {
+                docs: variant_docs,
+                name: variant.ident.to_string(),
+                value: common::extract_enum_variant_value(variant),
+            });
+
+            if variant.fields == syn::Fields::Unit {
+                continue;
+            }
+
+            let fields = common::named_fields(&variant.fields);
+            let mut body = transform_struct(&fields).ok_or_else(|| Error {
+                level: Level::Error,
+                span: None, //NONE FOR NOW
+                message: format!("bindgen cannot handle enum {}", name),
+            })?;
+            if body.fields.iter().any(|field| !field.ty.is_blittable()) {
+                println!(
+                    "tagged unions with non-blittable fields not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+            if !repr_c {
+                body.fields.insert(0, tag_field.clone());
+            }
+
+            let body_name = format!("{}{}Body", name, variant.ident);
+            union_fields.push(StructField {
+                docs: String::new(),
+                name: variant.ident.to_string(),
+                ty: Type::User(body_name.clone()),
+                has_cap: false,
+            });
+            bodies.push(Snippet {
+                docs: String::new(),
+                name: body_name,
+                item: body,
+            });
+        }
+
+        self.enums.push(Snippet {
+            docs: String::new(),
+            name: tag,
+            item: Enum {
+                variants,
+                ty: tag_ty,
+            },
+        });
+        self.structs.extend(bodies);
+
+        let union = Struct {
+            fields: union_fields,
+            pack: None,
+            union: true,
+        };
+        if repr_c {
+            let body_name = format!("{}Body", name);
+            self.structs.push(Snippet {
+                docs: String::new(),
+                name: body_name.clone(),
+                item: union,
+            });
+
+            let body_field = StructField {
+                docs: String::new(),
+                name: "body".to_string(),
+                ty: Type::User(body_name),
+                has_cap: false,
+            };
+            self.structs.push(Snippet {
+                docs,
+                name,
+                item: Struct {
+                    fields: vec![tag_field, body_field],
+                    pack: None,
+                    union: false,
+                },
+            });
+        } else {
+            self.structs.push(Snippet {
+                docs,
+                name,
+                item: union,
+            });
+        }
+        self.resolve_native_types();
+
+        Ok(())
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        let namespace = match module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            Some(namespace) => namespace.clone(),
+            None => return,
+        };
+        let _ = self.provided_types.insert(name.to_string());
+        let _ = self.provided_namespaces.insert(namespace);
+    }
+
+    /// Emit `using` directives for the namespaces of the provided types.
+    fn emit_provided_usings(&self, writer: &mut IndentedWriter) {
+        for namespace in &self.provided_namespaces {
+            emitln!(writer, "using {};", namespace);
+        }
+        emitln!(writer, "");
+    }
+
     fn is_ignored(&self, ident: &str) -> bool {
         match self.filter_mode {
             FilterMode::Blacklist => self.filter.contains(ident),
@@ -351,19 +553,26 @@ impl Lang for LangCSharp {
     fn parse_enum(
         &mut self,
         item: &syn::ItemEnum,
-        _module: &[String],
+        module: &[String],
         _outputs: &mut Outputs,
     ) -> Result<(), Error> {
         let name = item.ident.to_string();
         if self.is_ignored(&name.as_str()) {
             return Ok(());
         }
+        self.add_if_provided(&name, module);
 
-        let (repr_c, docs) =
-            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
 
-        // If it's not #[repr(C)] ignore it.
-        if !repr_c {
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+
+        // C# enums can't have a pointer-sized underlying type.
+        if let Some("usize") | Some("isize") = repr.int.as_deref() {
+            println!("pointer-sized enums not supported ({}). Skipping.", name);
             return Ok(());
         }
 
@@ -371,12 +580,22 @@ impl Lang for LangCSharp {
         if !item.generics.params.is_empty() {
             return Err(Error::unsupported_generics_error("enums"));
         }
+        let ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        if common::has_data_variants(item) {
+            return self.parse_tagged_enum(item, docs, repr.c, ty);
+        }
+
         let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
-        let item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
             level: Level::Error,
             span: None, //NONE FOR NOW
-            message: format!("bindgen cannot handle enum {}", item.ident.to_string()),
+            message: format!("bindgen cannot handle enum {}", name),
         })?;
+        item.ty = ty;
 
         self.enums.push(Snippet { docs, name, item });
 
@@ -386,13 +605,64 @@ impl Lang for LangCSharp {
     fn parse_struct(
         &mut self,
         item: &syn::ItemStruct,
-        _module: &[String],
+        module: &[String],
         _outputs: &mut Outputs,
     ) -> Result<(), Error> {
         let name = item.ident.to_string();
         if self.is_ignored(&name) {
             return Ok(());
         }
+        self.add_if_provided(&name, module);
+
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        if repr.transparent {
+            return self.parse_transparent_struct(item, docs);
+        }
+
+        // `StructLayout` can't raise the alignment of a struct, so its layout can't be matched.
+        if repr.align.is_some() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, //NONE FOR NOW
+                message: format!("bindgen cannot handle aligned struct {}", name),
+            });
+        }
+
+        let fields = common::named_fields(&item.fields);
+        let mut item = transform_struct(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+        item.pack = repr.packed;
+        self.structs.push(Snippet { docs, name, item });
+        self.resolve_native_types();
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
 
         let (repr_c, docs) =
             common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
@@ -403,29 +673,66 @@ impl Lang for LangCSharp {
         }
 
         if !item.generics.params.is_empty() {
-            return Err(Error::unsupported_generics_error("structs"));
+            return Err(Error::unsupported_generics_error("unions"));
         }
-        //TODO: syn doesn't have support for StructVariants
-        //            if   {
-        //                return Err(Error {
-        //                    level: Level::Error,
-        //                    span: Some(item.span),
-        //                    message: format!("bindgen cannot handle unit or tuple structs ({})", name),
-        //                });
-        //            }
 
-        let item = transform_struct(item.to_owned().fields).ok_or_else(|| Error {
+        let mut item = transform_struct(&item.fields.named).ok_or_else(|| Error {
             level: Level::Error,
             span: None, //NONE FOR NOW
-            message: format!("bindgen cannot handle struct {}", item.ident.to_string()),
+            message: format!("bindgen cannot handle union {}", name),
         })?;
-        let name = name.to_string();
+        // The fields overlap, so they can't be marshalled
+        if item.fields.iter().any(|field| !field.ty.is_blittable()) {
+            println!(
+                "unions with non-blittable fields not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+        item.union = true;
         self.structs.push(Snippet { docs, name, item });
         self.resolve_native_types();
 
         Ok(())
     }
 
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let ty = match transform_type(&item.ty) {
+            Some(ref ty) if static_accessors(ty, "", "", "").is_some() => ty.clone(),
+            _ => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+        let item = Static {
+            ty,
+            mutable: item.mutability.is_some(),
+        };
+        self.statics.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
     fn parse_fn(
         &mut self,
         item: &syn::ItemFn,
@@ -472,7 +779,7 @@ impl Lang for LangCSharp {
     fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
         self.resolve_aliases();
 
-        if !self.functions.is_empty() {
+        if !self.functions.is_empty() || !self.statics.is_empty() {
             // Functions
             let mut writer = IndentedWriter::new(INDENT_WIDTH);
 
@@ -480,7 +787,8 @@ impl Lang for LangCSharp {
             emitln!(writer, "using System.Collections.Generic;");
             emitln!(writer, "using System.Linq;");
             emitln!(writer, "using System.Runtime.InteropServices;");
-            emitln!(writer, "using System.Threading.Tasks;\n");
+            emitln!(writer, "using System.Threading.Tasks;");
+            self.emit_provided_usings(&mut writer);
             emitln!(
                 writer,
                 "namespace {}\n{{",
@@ -508,6 +816,31 @@ impl Lang for LangCSharp {
             );
             emitln!(writer, "#endif\n");
 
+            // Exported statics, accessed through their addresses.
+            if !self.statics.is_empty() {
+                emit_static_library_handle(&mut writer, &self.context);
+
+                for snippet in &self.statics {
+                    let enum_ty = match snippet.item.ty {
+                        Type::User(ref name) => self
+                            .enums
+                            .iter()
+                            .find(|snippet| snippet.name == *name)
+                            .map(|snippet| snippet.item.ty.clone().unwrap_or(Type::I32)),
+                        _ => None,
+                    };
+
+                    emit_docs(&mut writer, &self.context, &snippet.docs);
+                    emit_static(
+                        &mut writer,
+                        &self.context,
+                        &snippet.name,
+                        &snippet.item,
+                        enum_ty.as_ref(),
+                    );
+                }
+            }
+
             for snippet in &self.functions {
                 emit_docs(&mut writer, &self.context, &snippet.docs);
                 if self.is_interface_function(&snippet.name, &snippet.item) {
@@ -553,7 +886,8 @@ impl Lang for LangCSharp {
 
                 emitln!(writer, "using System;");
                 emitln!(writer, "using System.Collections.Generic;");
-                emitln!(writer, "using System.Threading.Tasks;\n");
+                emitln!(writer, "using System.Threading.Tasks;");
+                self.emit_provided_usings(&mut writer);
                 emitln!(
                     writer,
                     "namespace {}\n{{",
@@ -643,14 +977,23 @@ impl Lang for LangCSharp {
             );
         }
 
-        // Types
-        if self.types_enabled && (!self.enums.is_empty() || !self.structs.is_empty()) {
+        // Types, except for the ones generated by the bindings of dependencies
+        let provided_types = mem::take(&mut self.provided_types);
+        self.enums
+            .retain(|snippet| !provided_types.contains(&snippet.name));
+        let has_structs = self
+            .structs
+            .iter()
+            .any(|snippet| !provided_types.contains(&snippet.name));
+
+        if self.types_enabled && (!self.enums.is_empty() || has_structs) {
             let mut writer = IndentedWriter::new(INDENT_WIDTH);
 
             emitln!(writer, "using System;");
             emitln!(writer, "using System.Collections.Generic;");
             emitln!(writer, "using System.Runtime.InteropServices;");
-            emitln!(writer, "using JetBrains.Annotations;\n");
+            emitln!(writer, "using JetBrains.Annotations;");
+            self.emit_provided_usings(&mut writer);
 
             emitln!(
                 writer,
@@ -667,6 +1010,9 @@ impl Lang for LangCSharp {
 
             // Structs
             for snippet in &self.structs {
+                if provided_types.contains(&snippet.name) {
+                    continue;
+                }
                 emit_docs(&mut writer, &self.context, &snippet.docs);
 
                 if self.context.is_native_name(&snippet.name) {
@@ -694,45 +1040,17 @@ impl Lang for LangCSharp {
         // Other cleanup.
         self.context.opaque_types.clear();
         self.context.native_types.clear();
+        self.provided_namespaces.clear();
 
         Ok(())
     }
-}
-
-fn resolve_alias(aliases: &HashMap<String, Type>, new_ty: &mut Type) {
-    let mut orig_new_ty = mem::replace(new_ty, Type::Unit);
 
-    match orig_new_ty {
-        Type::User(ref name) => {
-            if let Some(old_ty) = lookup_alias(aliases, name) {
-                *new_ty = old_ty.clone();
-                return;
-            }
-        }
-        Type::Pointer(ref mut ty) | Type::Array(ref mut ty, _) => {
-            resolve_alias(aliases, ty);
-        }
-        Type::Function(ref mut fun) => {
-            resolve_alias(aliases, &mut fun.output);
-            for &mut (_, ref mut input) in &mut fun.inputs {
-                resolve_alias(aliases, input);
-            }
-        }
-        _ => (),
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.context.types_section.namespace.clone())
     }
 
-    mem::replace(new_ty, orig_new_ty);
-}
-
-fn lookup_alias<'a>(aliases: &'a HashMap<String, Type>, name: &str) -> Option<&'a Type> {
-    if let Some(ty) = aliases.get(name) {
-        if let Type::User(ref name) = *ty {
-            Some(lookup_alias(aliases, name).unwrap_or(ty))
-        } else {
-            Some(ty)
-        }
-    } else {
-        None
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
     }
 }
 
@@ -1,5 +1,6 @@
 use super::*;
-use crate::test_utils::fetch;
+use crate::test_utils::{fetch, write_crate};
+use crate::Bindgen;
 use indoc::indoc;
 
 #[test]
@@ -65,6 +66,57 @@ fn structs() {
     assert_multiline_eq!(actual, expected);
 }
 
+#[test]
+fn packed_structs() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(C, packed(2))]
+        pub struct Header {
+            tag: u8,
+            len: u32,
+        }
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             [StructLayout(LayoutKind.Sequential, Pack = 2)]
+             public struct Header
+             {
+                 public byte Tag;
+                 public uint Len;
+             }
+
+         }
+         "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn aligned_structs() {
+    let errors = unwrap!(try_compile!(LangCSharp::default(), {
+        #[repr(C, align(16))]
+        pub struct Block {
+            data: [u8; 4],
+        }
+    })
+    .err());
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "bindgen cannot handle aligned struct Block"
+    );
+}
+
 #[test]
 fn native_structs() {
     let outputs = compile!(LangCSharp::default(), {
@@ -413,6 +465,60 @@ fn type_aliases() {
     assert_multiline_eq!(actual, expected);
 }
 
+#[test]
+fn generic_structs_in_modules() {
+    // The generic structs have the same name, so the aliases are resolved through their paths.
+    let outputs = compile!(LangCSharp::default(), {
+        pub mod sized {
+            #[repr(C)]
+            pub struct Value<T> {
+                pub value: T,
+                pub size: usize,
+            }
+        }
+
+        pub mod pairs {
+            #[repr(C)]
+            pub struct Value<T> {
+                pub first: T,
+                pub second: T,
+            }
+        }
+
+        pub type SizedByte = sized::Value<u8>;
+        pub type IntPair = crate::pairs::Value<i32>;
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+           using System.Collections.Generic;
+           using System.Runtime.InteropServices;
+           using JetBrains.Annotations;
+
+           namespace Backend
+           {
+               [PublicAPI]
+               public struct SizedByte
+               {
+                   public byte Value;
+                   public UIntPtr Size;
+               }
+
+               [PublicAPI]
+               public struct IntPair
+               {
+                   public int First;
+                   public int Second;
+               }
+
+           }
+           "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
 #[test]
 fn enums() {
     let outputs = compile!(LangCSharp::default(), {
@@ -461,6 +567,305 @@ fn enums() {
     assert_multiline_eq!(actual, expected);
 }
 
+#[test]
+fn sized_enums() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(u8)]
+        pub enum Mode {
+            ReadOnly,
+            WriteOnly,
+        }
+
+        #[repr(C, i64)]
+        pub enum Offset {
+            Start = 1,
+            End = 2,
+        }
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             public enum Mode : byte
+             {
+                 ReadOnly,
+                 WriteOnly,
+             }
+
+             [PublicAPI]
+             public enum Offset : long
+             {
+                 Start = 1,
+                 End = 2,
+             }
+
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn tagged_unions() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(C, u8)]
+        pub enum Shape {
+            Circle { radius: f32 },
+            Rect(u16, u16),
+            Empty,
+        }
+
+        #[repr(u8)]
+        pub enum Event {
+            Key(u32),
+            Quit,
+        }
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             public enum ShapeTag : byte
+             {
+                 Circle,
+                 Rect,
+                 Empty,
+             }
+
+             [PublicAPI]
+             public enum EventTag : byte
+             {
+                 Key,
+                 Quit,
+             }
+
+             [PublicAPI]
+             public struct ShapeCircleBody
+             {
+                 public float Radius;
+             }
+
+             [PublicAPI]
+             public struct ShapeRectBody
+             {
+                 public ushort Field0;
+                 public ushort Field1;
+             }
+
+             [PublicAPI]
+             [StructLayout(LayoutKind.Explicit)]
+             public struct ShapeBody
+             {
+                 [FieldOffset(0)]
+                 public ShapeCircleBody Circle;
+                 [FieldOffset(0)]
+                 public ShapeRectBody Rect;
+             }
+
+             [PublicAPI]
+             public struct Shape
+             {
+                 public ShapeTag Tag;
+                 public ShapeBody Body;
+             }
+
+             [PublicAPI]
+             public struct EventKeyBody
+             {
+                 public EventTag Tag;
+                 public uint Field0;
+             }
+
+             [PublicAPI]
+             [StructLayout(LayoutKind.Explicit)]
+             public struct Event
+             {
+                 [FieldOffset(0)]
+                 public EventTag Tag;
+                 [FieldOffset(0)]
+                 public EventKeyBody Key;
+             }
+
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn unions() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(C)]
+        pub union Value {
+            pub integer: i32,
+            pub real: f64,
+        }
+
+        #[repr(C)]
+        pub union Text {
+            pub ptr: *const c_char,
+            pub code: u32,
+        }
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             [StructLayout(LayoutKind.Explicit)]
+             public struct Value
+             {
+                 [FieldOffset(0)]
+                 public int Integer;
+                 [FieldOffset(0)]
+                 public double Real;
+             }
+
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn tuple_and_transparent_structs() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(transparent)]
+        pub struct Handle(u64);
+
+        #[repr(C)]
+        pub struct Pair(pub Handle, pub u8);
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             public struct Handle
+             {
+                 public ulong Value;
+             }
+
+             [PublicAPI]
+             public struct Pair
+             {
+                 public Handle Field0;
+                 public byte Field1;
+             }
+
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn statics() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(u8)]
+        pub enum Mode {
+            Fast,
+            Safe,
+        }
+
+        #[no_mangle]
+        pub static VERSION: *const c_char = ptr::null();
+
+        #[no_mangle]
+        pub static mut COUNTER: u64 = 0;
+
+        #[no_mangle]
+        pub static RATIO: f32 = 0.5;
+
+        #[no_mangle]
+        pub static mut MODE: Mode = Mode::Fast;
+
+        pub static IGNORED: u32 = 0;
+    });
+
+    let actual = fetch(&outputs, "Backend.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Linq;
+         using System.Runtime.InteropServices;
+         using System.Threading.Tasks;
+
+         namespace Backend
+         {
+             internal partial class Backend : IBackend
+             {
+                 #if __IOS__
+                 private const string DllName = \"__Internal\";
+                 #else
+                 private const string DllName = \"backend\";
+                 #endif
+
+                 private static readonly IntPtr NativeLibraryHandle = \
+                  NativeLibrary.Load(DllName, typeof(Backend).Assembly, null);
+
+                 public static string Version => \
+                  Marshal.PtrToStringUTF8(Marshal.ReadIntPtr(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"VERSION\")));
+
+                 public static ulong Counter
+                 {
+                     get => (ulong)Marshal.ReadInt64(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"COUNTER\"));
+                     set => Marshal.WriteInt64(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"COUNTER\"), (long)value);
+                 }
+
+                 public static float Ratio => \
+                  BitConverter.Int32BitsToSingle(Marshal.ReadInt32(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"RATIO\")));
+
+                 public static Mode Mode
+                 {
+                     get => (Mode)Marshal.ReadByte(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"MODE\"));
+                     set => Marshal.WriteByte(\
+                  NativeLibrary.GetExport(NativeLibraryHandle, \"MODE\"), (byte)value);
+                 }
+
+             }
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
 #[test]
 fn functions_without_extern_and_no_mangle_are_ignored() {
     let outputs = compile!(LangCSharp::default(), {
@@ -1256,3 +1661,213 @@ fn interface() {
 
     assert_multiline_eq!(actual, expected);
 }
+
+#[test]
+fn nullable_function_pointers() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[repr(C)]
+        pub struct Hooks {
+            on_progress: Option<extern "C" fn(done: u64, total: u64)>,
+            on_abort: extern "C" fn(),
+        }
+
+        #[no_mangle]
+        pub extern "C" fn set_logger(
+            logger: Option<extern "C" fn(level: i32, message: *const c_char)>,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            user_data: *mut c_void,
+            o_cb: Option<extern "C" fn(user_data: *mut c_void, result: *const FfiResult)>,
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             public struct Hooks
+             {
+                 public IntPtr OnProgress;
+                 public IntPtr OnAbort;
+             }
+
+         }
+        "
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Backend.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Linq;
+         using System.Runtime.InteropServices;
+         using System.Threading.Tasks;
+
+         namespace Backend
+         {
+             internal partial class Backend : IBackend
+             {
+                 #if __IOS__
+                 private const string DllName = \"__Internal\";
+                 #else
+                 private const string DllName = \"backend\";
+                 #endif
+
+                 public void SetLogger(IntPtr logger)
+                 {
+                     SetLoggerNative(logger);
+                 }
+
+                 [DllImport(DllName, EntryPoint = \"set_logger\")]
+                 private static extern void SetLoggerNative(IntPtr logger);
+
+                 public Task FlushAsync()
+                 {
+                     var (ret, userData) = Utils.PrepareTask();
+                     FlushNative(userData, DelegateOnFfiResultCb);
+                     return ret;
+                 }
+
+                 [DllImport(DllName, EntryPoint = \"flush\")]
+                 private static extern void FlushNative(IntPtr userData, FfiResultCb oCb);
+
+                 private delegate void FfiResultCb(IntPtr userData, IntPtr result);
+
+                 #if __IOS__
+                 [MonoPInvokeCallback(typeof(FfiResultCb))]
+                 #endif
+                 private static void OnFfiResultCb(IntPtr userData, IntPtr result)
+                 {
+                     Utils.CompleteTask(userData, Marshal.PtrToStructure<FfiResult>(result));
+                 }
+
+                 private static readonly FfiResultCb DelegateOnFfiResultCb = OnFfiResultCb;
+
+             }
+         }
+        "
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn references_and_smart_pointers() {
+    let outputs = compile!(LangCSharp::default(), {
+        #[no_mangle]
+        pub extern "C" fn fun1(name: &c_char, key: &[u8; 32], label: Option<&c_char>) {}
+    });
+
+    let actual = fetch(&outputs, "Backend.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Linq;
+         using System.Runtime.InteropServices;
+         using System.Threading.Tasks;
+
+         namespace Backend
+         {
+             internal partial class Backend : IBackend
+             {
+                 #if __IOS__
+                 private const string DllName = \"__Internal\";
+                 #else
+                 private const string DllName = \"backend\";
+                 #endif
+
+                 public void Fun1(string name, byte[] key, string label)
+                 {
+                     if (name == null)
+                     {
+                         throw new ArgumentNullException(nameof(name));
+                     }
+
+                     if (key == null)
+                     {
+                         throw new ArgumentNullException(nameof(key));
+                     }
+
+                     Fun1Native(name, key, label);
+                 }
+
+                 [DllImport(DllName, EntryPoint = \"fun1\")]
+                 private static extern void Fun1Native(\
+                   [MarshalAs(UnmanagedType.LPStr)] string name, \
+                   [MarshalAs(UnmanagedType.LPArray, SizeConst = 32)] byte[] key, \
+                   [MarshalAs(UnmanagedType.LPStr)] string label);
+
+             }
+         }
+        "
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_dependency_types() {
+    let ffi_utils = write_crate(
+        "csharp_provided_dependency_types",
+        &[(
+            "src/lib.rs",
+            indoc!(
+                "
+                #[repr(C)]
+                pub struct FfiResult {
+                    pub error_code: i32,
+                }
+                "
+            ),
+        )],
+    );
+    let source = indoc!(
+        "
+        #[repr(C)]
+        pub struct Status {
+            pub result: ffi_utils::FfiResult,
+            pub count: u32,
+        }
+        "
+    );
+
+    let mut lang = LangCSharp::default();
+    lang.add_provided_package("ffi_utils", "FfiUtils");
+    let mut outputs = Outputs::default();
+    unwrap!(unwrap!(Bindgen::new())
+        .source_code("ffi/src/lib.rs", source)
+        .dependency("ffi_utils", &ffi_utils)
+        .compile(&mut lang, &mut outputs, true));
+
+    let actual = fetch(&outputs, "Types.cs");
+    let expected = indoc!(
+        "using System;
+         using System.Collections.Generic;
+         using System.Runtime.InteropServices;
+         using JetBrains.Annotations;
+         using FfiUtils;
+
+         namespace Backend
+         {
+             [PublicAPI]
+             public struct Status
+             {
+                 public FfiResult Result;
+                 public uint Count;
+             }
+
+         }
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
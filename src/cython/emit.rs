@@ -0,0 +1,416 @@
+//! Utilities for emiting fragments of the target language code.
+use super::{Context, Declaration, NativeFunction, NativeStatic};
+use crate::intermediate::*;
+use crate::lang_c::types::{CPtrType, CType};
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Python and Cython keywords which can be used as identifiers in Rust.
+const KEYWORDS: &[&str] = &[
+    "and", "assert", "cdef", "cimport", "class", "cpdef", "ctypedef", "def", "del", "elif",
+    "except", "extern", "finally", "from", "global", "import", "include", "is", "lambda", "nogil",
+    "nonlocal", "not", "or", "pass", "raise", "try", "with", "yield",
+];
+
+/// Calls `f` with the C type `ty` and all the types it's made of.
+pub fn visit_c_type(ty: &CType, f: &mut dyn FnMut(&CType)) {
+    f(ty);
+    match *ty {
+        CType::Ptr(ref ty, _) | CType::NonNullPtr(ref ty, _) | CType::Array(ref ty, _) => {
+            visit_c_type(ty, f)
+        }
+        CType::FnDecl {
+            ref args,
+            ref return_type,
+            ..
+        } => {
+            for arg in args {
+                visit_c_type(&arg.1, f);
+            }
+            visit_c_type(return_type, f);
+        }
+        _ => (),
+    }
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "#");
+        } else {
+            emitln!(writer, "# {}", line);
+        }
+    }
+}
+
+pub fn emit_declaration(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Declaration) {
+    emit_docs(writer, docs);
+
+    match *item {
+        Declaration::Alias(ref ty) => emitln!(writer, "ctypedef {}", declaration(ty, name)),
+        Declaration::Enum { ref repr, ref item } => {
+            // Enums with an integer representation are a typedef along with named constants,
+            // as in the C header.
+            match *repr {
+                Some(ref ty) => {
+                    emitln!(writer, "ctypedef {} {}\n", type_name(ty), name);
+                    emitln!(writer, "enum:");
+                }
+                None => emitln!(writer, "ctypedef enum {}:", name),
+            }
+            writer.indent();
+            for variant in &item.variants {
+                emit_docs(writer, &variant.docs);
+                match variant.value {
+                    Some(value) => emitln!(writer, "{} = {}", variant.name, value),
+                    None => emitln!(writer, "{}", variant.name),
+                }
+            }
+            writer.unindent();
+        }
+        Declaration::Struct {
+            ref fields,
+            union,
+            packed,
+        } => {
+            emitln!(
+                writer,
+                "ctypedef {}{} {}:",
+                if packed { "packed " } else { "" },
+                if union { "union" } else { "struct" },
+                name
+            );
+            writer.indent();
+            if fields.is_empty() {
+                emitln!(writer, "pass");
+            }
+            for (field, ty) in fields {
+                emitln!(writer, "{}", declaration(ty, &c_name(field)));
+            }
+            writer.unindent();
+        }
+        Declaration::Opaque => {
+            emitln!(writer, "ctypedef struct {}:", name);
+            writer.indent();
+            emitln!(writer, "pass");
+            writer.unindent();
+        }
+    }
+}
+
+pub fn emit_static(writer: &mut IndentedWriter, name: &str, docs: &str, item: &NativeStatic) {
+    emit_docs(writer, docs);
+
+    let name = c_name(name);
+    let decl = match item.ty {
+        _ if item.mutable => declaration(&item.ty, &name),
+        CType::Ptr(..) | CType::NonNullPtr(..) => format!("{} const {}", type_name(&item.ty), name),
+        CType::FnDecl { .. } => declaration(&item.ty, &name),
+        ref ty => format!("const {}", declaration(ty, &name)),
+    };
+    emitln!(writer, "{}", decl);
+}
+
+pub fn emit_native_function(
+    writer: &mut IndentedWriter,
+    name: &str,
+    docs: &str,
+    fun: &NativeFunction,
+) {
+    emit_docs(writer, docs);
+
+    let params: Vec<_> = fun
+        .params
+        .iter()
+        .map(|(name, ty)| declaration(ty, &escape_keyword(name, KEYWORDS)))
+        .collect();
+    emitln!(
+        writer,
+        "{} {}({})",
+        type_name(&fun.output),
+        c_name(name),
+        params.join(", ")
+    );
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, item: &Const) {
+    emitln!(writer, "{} = {}", name, const_value(&item.value));
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    emitln!(writer, "\n\nclass {}(enum.IntEnum):", name);
+    writer.indent();
+    emit_docstring(writer, docs);
+    if !docs.is_empty() {
+        emitln!(writer, "");
+    }
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emitln!(
+            writer,
+            "{} = {}",
+            escape_keyword(&variant.name.to_screaming_snake_case(), KEYWORDS),
+            value
+        );
+        next = value + 1;
+    }
+
+    writer.unindent();
+}
+
+/// Whether the wrappers can convert all the parameters and the result of the function.
+pub fn is_wrappable(context: &Context, fun: &NativeFunction) -> bool {
+    let params = fun.item.inputs.iter().all(|(name, ty)| match *ty {
+        Type::Array(ref elem, ArraySize::Dynamic) => {
+            numeric_type(elem).is_some() && native_param(fun, name).is_some()
+        }
+        ref ty => is_value(context, ty),
+    });
+    let output = match fun.item.output {
+        Type::Unit => true,
+        ref ty => is_value(context, ty),
+    };
+    params && output
+}
+
+/// Emits the function calling the native function, which takes Python strings and buffers of
+/// numbers, e.g. `bytes` or NumPy arrays, instead of pointers.
+pub fn emit_wrapper_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &NativeFunction,
+) {
+    let params: Vec<_> = fun
+        .item
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, (param, ty))| {
+            let name = param_name(param, index, KEYWORDS);
+            match *ty {
+                Type::Bool => format!("bint {}", name),
+                Type::String => name,
+                Type::User(ref enum_name) => {
+                    format!(
+                        "{}.{} {}",
+                        unwrap!(context.enums.get(enum_name)),
+                        enum_name,
+                        name
+                    )
+                }
+                Type::Array(ref elem, ArraySize::Dynamic) => {
+                    let constness = match native_param(fun, param) {
+                        Some(CType::Ptr(_, CPtrType::Const))
+                        | Some(CType::NonNullPtr(_, CPtrType::Const)) => "const ",
+                        _ => "",
+                    };
+                    format!(
+                        "{}{}[::1] {} not None",
+                        constness,
+                        unwrap!(numeric_type(elem)),
+                        name
+                    )
+                }
+                ref ty => format!("{} {}", unwrap!(numeric_type(ty)), name),
+            }
+        })
+        .collect();
+
+    emitln!(
+        writer,
+        "\n\ndef {}({}):",
+        escape_keyword(name, KEYWORDS),
+        params.join(", ")
+    );
+    writer.indent();
+    emit_docstring(writer, docs);
+
+    let mut args = Vec::new();
+    for (index, (param, ty)) in fun.item.inputs.iter().enumerate() {
+        let param = param_name(param, index, KEYWORDS);
+        match *ty {
+            Type::String => {
+                emitln!(writer, "{0}_bytes = {0}.encode('utf-8')", param);
+                args.push(format!("{}_bytes", param));
+            }
+            Type::Array(..) => {
+                args.push(format!("&{0}[0] if {0}.shape[0] > 0 else NULL", param));
+                args.push(format!("{}.shape[0]", param));
+            }
+            _ => args.push(param),
+        }
+    }
+
+    let call = format!(
+        "{}.{}({})",
+        context.module,
+        escape_keyword(name, KEYWORDS),
+        args.join(", ")
+    );
+    match fun.item.output {
+        Type::Unit => emitln!(writer, "{}", call),
+        Type::String => {
+            emitln!(writer, "cdef const char* result = {}", call);
+            emitln!(
+                writer,
+                "return result.decode('utf-8') if result != NULL else None"
+            );
+        }
+        Type::User(ref name) if context.classes.contains(name) => {
+            emitln!(writer, "return {}({})", name, call)
+        }
+        _ => emitln!(writer, "return {}", call),
+    }
+
+    writer.unindent();
+}
+
+/// The C type of numbers, as mapped by `LangC`.
+pub fn numeric_type(ty: &Type) -> Option<&'static str> {
+    match *ty {
+        Type::Char => Some("uint32_t"),
+        Type::CChar => Some("char"),
+        Type::F32 => Some("float"),
+        Type::F64 => Some("double"),
+        Type::I8 => Some("int8_t"),
+        Type::I16 => Some("int16_t"),
+        Type::I32 => Some("int32_t"),
+        Type::I64 => Some("int64_t"),
+        Type::ISize => Some("intptr_t"),
+        Type::U8 => Some("uint8_t"),
+        Type::U16 => Some("uint16_t"),
+        Type::U32 => Some("uint32_t"),
+        Type::U64 => Some("uint64_t"),
+        Type::USize => Some("uintptr_t"),
+        _ => None,
+    }
+}
+
+/// Whether the wrappers convert values of the type from and to Python objects.
+fn is_value(context: &Context, ty: &Type) -> bool {
+    match *ty {
+        Type::Bool | Type::String => true,
+        Type::User(ref name) => context.enums.contains_key(name),
+        ref ty => numeric_type(ty).is_some(),
+    }
+}
+
+/// The C type of the parameter `name` of the function, or of the pointer to the elements of
+/// the dynamic array `name`.
+fn native_param<'a>(fun: &'a NativeFunction, name: &str) -> Option<&'a CType> {
+    let pointer = format!("{}_ptr", name);
+    fun.params
+        .iter()
+        .find(|(param, _)| *param == name || *param == pointer)
+        .map(|(_, ty)| ty)
+}
+
+/// Cython declaration of `name` with the C type `ty`.
+fn declaration(ty: &CType, name: &str) -> String {
+    match *ty {
+        CType::Array(ref elem, len) => format!("{} {}[{}]", type_name(elem), name, len),
+        CType::FnDecl {
+            ref args,
+            ref return_type,
+            ..
+        } => {
+            let args: Vec<_> = args
+                .iter()
+                .map(|arg| {
+                    let name = match arg.1 {
+                        CType::FnDecl { ref inner, .. } => inner,
+                        _ => &arg.0,
+                    };
+                    declaration(&arg.1, &escape_keyword(name, KEYWORDS))
+                })
+                .collect();
+            format!(
+                "{} (*{})({})",
+                type_name(return_type),
+                name,
+                args.join(", ")
+            )
+        }
+        ref ty => format!("{} {}", type_name(ty), name),
+    }
+}
+
+/// Cython name of the C type, with `const` before the type it qualifies where possible, as
+/// Cython doesn't parse it after.
+fn type_name(ty: &CType) -> String {
+    match *ty {
+        CType::Void => "void".to_string(),
+        CType::Mapping(ref name) => name.clone(),
+        CType::Native(name) => name.to_string(),
+        CType::Ptr(ref pointee, ref kind) | CType::NonNullPtr(ref pointee, ref kind) => {
+            match (&**pointee, kind) {
+                (_, CPtrType::Mutable) => format!("{}*", type_name(pointee)),
+                (CType::Ptr(..), _) | (CType::NonNullPtr(..), _) => {
+                    format!("{} const*", type_name(pointee))
+                }
+                (pointee, _) => format!("const {}*", type_name(pointee)),
+            }
+        }
+        CType::Array(ref elem, _) => format!("{}*", type_name(elem)),
+        CType::FnDecl { .. } => declaration(ty, ""),
+    }
+}
+
+/// Python docstring of a class or function.
+fn emit_docstring(writer: &mut IndentedWriter, docs: &str) {
+    let lines: Vec<_> = docs
+        .split("///")
+        .skip(1)
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect();
+
+    match lines.len() {
+        0 => (),
+        1 => emitln!(writer, "\"\"\"{}\"\"\"", lines[0]),
+        _ => {
+            emitln!(writer, "\"\"\"{}", lines[0]);
+            for line in &lines[1..] {
+                emitln!(writer, "{}", line);
+            }
+            emitln!(writer, "\"\"\"");
+        }
+    }
+}
+
+/// The Python literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(true) => "True".to_string(),
+        ConstValue::Bool(false) => "False".to_string(),
+        ConstValue::Char(value) => format!("{:?}", value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => format!("{:?}", value),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+/// Name of a declaration, along with the C name when it's renamed.
+fn c_name(name: &str) -> String {
+    let escaped = escape_keyword(name, KEYWORDS);
+    if escaped == name {
+        escaped
+    } else {
+        format!("{} \"{}\"", escaped, name)
+    }
+}
@@ -0,0 +1,771 @@
+//! Functions for converting Rust definitions into Cython declarations.
+//!
+//! The C header generated by `LangC` is declared in a `cdef extern from` block of the
+//! `c{lib}.pxd` file, using the same C types. The `{lib}.pyx` module wraps the functions which
+//! only take and return numbers, enums, strings and arrays of numbers, the latter as typed
+//! memoryviews, so that they can be called from Python.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, Lang, Outputs};
+use crate::intermediate::*;
+use crate::lang_c::types::CType;
+use crate::lang_c::{self, LangC};
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 4;
+
+pub struct LangCython {
+    c: LangC,
+    lib_name: String,
+    wrappers_enabled: bool,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    /// Types declared in the `.pxd` file, in the order they are parsed.
+    types: Vec<Snippet<Declaration>>,
+    statics: Vec<Snippet<NativeStatic>>,
+    functions: Vec<Snippet<NativeFunction>>,
+    aliases: HashMap<String, Type>,
+    /// Names of the types declared in the C header.
+    declared: HashSet<String>,
+    /// Names of the enums with no data variants, including the provided ones.
+    enum_names: HashSet<String>,
+    /// Names of the libraries generating the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their `.pxd` modules.
+    provided_types: BTreeMap<String, String>,
+}
+
+/// Type declared in the `cdef extern from` block.
+pub enum Declaration {
+    /// `typedef` of another type.
+    Alias(CType),
+    /// Enum with a fixed-width integer representation, or a C enum.
+    Enum { repr: Option<CType>, item: Enum },
+    /// Struct or union.
+    Struct {
+        fields: Vec<(String, CType)>,
+        union: bool,
+        packed: bool,
+    },
+    /// Type whose fields aren't declared, e.g. the tagged union of an enum with data variants.
+    Opaque,
+}
+
+pub struct NativeStatic {
+    pub ty: CType,
+    pub mutable: bool,
+}
+
+/// Function along with the C types of its parameters, as declared in the header.
+pub struct NativeFunction {
+    pub params: Vec<(String, CType)>,
+    pub output: CType,
+    /// The function as seen by the wrappers.
+    pub item: Function,
+}
+
+pub struct Context {
+    /// Name of the `.pxd` module of the library.
+    pub module: String,
+    /// `.pxd` modules of the enums, by name.
+    pub enums: HashMap<String, String>,
+    /// Names of the enums with an `IntEnum` class in the `.pyx` module.
+    pub classes: HashSet<String>,
+}
+
+impl LangCython {
+    pub fn new() -> Self {
+        let mut lang = LangCython {
+            c: LangC::new(),
+            lib_name: String::new(),
+            wrappers_enabled: true,
+            consts: Vec::new(),
+            enums: Vec::new(),
+            types: Vec::new(),
+            statics: Vec::new(),
+            functions: Vec::new(),
+            aliases: Default::default(),
+            declared: Default::default(),
+            enum_names: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        };
+        lang.c.set_split_headers_enabled(false);
+        lang.set_lib_name("backend");
+        lang
+    }
+
+    /// Set the name of the native library. The C header is generated as `{lib}.h`, declared in
+    /// `c{lib}.pxd` and wrapped by `{lib}.pyx`.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+        self.c.set_lib_name(self.lib_name.clone());
+        self.c
+            .set_amalgamated_header(format!("{}.h", self.lib_name));
+    }
+
+    /// Enable/disable the generation of the `.pyx` module wrapping the functions.
+    pub fn set_wrappers_enabled(&mut self, enabled: bool) {
+        self.wrappers_enabled = enabled;
+    }
+
+    /// Add custom code to the C header, e.g. declarations of additional types.
+    pub fn add_custom_code(&mut self, code: &str) {
+        self.c.add_custom_code(code);
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared by the bindings
+    /// generated for its library `lib`. They are included from `{lib}.h` and cimported from
+    /// `c{lib}.pxd` instead of being declared again.
+    pub fn add_provided_package<P, L>(&mut self, package: P, lib: L)
+    where
+        P: AsRef<str>,
+        L: Into<String>,
+    {
+        let lib = lib.into();
+        self.c
+            .add_provided_package(package.as_ref(), format!("{}.h", lib));
+        let _ = self
+            .provided_packages
+            .insert(package.as_ref().to_string(), lib);
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(lib) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self
+                .provided_types
+                .insert(name.to_string(), module_name(lib));
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// All the C types of the declarations.
+    fn c_types(&self) -> Vec<&CType> {
+        let mut types = Vec::new();
+        for snippet in &self.types {
+            match snippet.item {
+                Declaration::Alias(ref ty) => types.push(ty),
+                Declaration::Enum { ref repr, .. } => types.extend(repr),
+                Declaration::Struct { ref fields, .. } => {
+                    types.extend(fields.iter().map(|(_, ty)| ty))
+                }
+                Declaration::Opaque => (),
+            }
+        }
+        for snippet in &self.statics {
+            types.push(&snippet.item.ty);
+        }
+        for snippet in &self.functions {
+            types.extend(snippet.item.params.iter().map(|(_, ty)| ty));
+            types.push(&snippet.item.output);
+        }
+        types
+    }
+
+    fn emit_declarations(&self) -> String {
+        let mut natives = BTreeSet::new();
+        let mut mappings = BTreeSet::new();
+        for ty in self.c_types() {
+            visit_c_type(ty, &mut |ty| match *ty {
+                CType::Native(name) => {
+                    let _ = natives.insert(name);
+                }
+                CType::Mapping(ref name) => {
+                    let _ = mappings.insert(name.clone());
+                }
+                _ => (),
+            });
+        }
+
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+        emitln!(
+            writer,
+            "# Declarations of the native library `{}`, generated in `{}.h`.",
+            self.lib_name,
+            self.lib_name
+        );
+
+        let stdint: Vec<_> = natives.iter().filter(|name| name.ends_with("_t")).collect();
+        if !stdint.is_empty() {
+            emitln!(writer, "from libc.stdint cimport {}", join(stdint));
+        }
+
+        let mut cimports = BTreeMap::new();
+        for name in &mappings {
+            if let Some(module) = self.provided_types.get(name) {
+                cimports.entry(module).or_insert_with(Vec::new).push(name);
+            }
+        }
+        for (module, names) in cimports {
+            emitln!(writer, "from {} cimport {}", module, join(names));
+        }
+
+        emitln!(writer, "\n\ncdef extern from \"{}.h\":", self.lib_name);
+        writer.indent();
+
+        let mut empty = true;
+        if natives.contains("bool") {
+            emitln!(writer, "ctypedef bint bool");
+            empty = false;
+        }
+
+        // Types used by the functions without being declared are opaque.
+        let opaque: Vec<_> = mappings
+            .iter()
+            .filter(|name| {
+                !self.declared.contains(*name) && !self.provided_types.contains_key(*name)
+            })
+            .collect();
+        if !opaque.is_empty() {
+            if !empty {
+                emitln!(writer, "");
+            }
+            for name in opaque {
+                emitln!(writer, "ctypedef struct {}", name);
+            }
+            empty = false;
+        }
+
+        for snippet in &self.types {
+            if !empty {
+                emitln!(writer, "");
+            }
+            emit_declaration(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            empty = false;
+        }
+
+        for snippet in &self.statics {
+            if !empty {
+                emitln!(writer, "");
+            }
+            emit_static(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            empty = false;
+        }
+
+        for snippet in &self.functions {
+            if !empty {
+                emitln!(writer, "");
+            }
+            emit_native_function(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            empty = false;
+        }
+
+        if empty {
+            emitln!(writer, "pass");
+        }
+
+        writer.unindent();
+        writer.into_inner()
+    }
+
+    fn emit_wrappers(&self, context: &Context) -> String {
+        let mut body = IndentedWriter::new(INDENT_WIDTH);
+        let mut natives = BTreeSet::new();
+        let mut modules = BTreeSet::new();
+
+        // Constants of struct types can't be built without the struct types.
+        let consts: Vec<_> = self
+            .consts
+            .iter()
+            .filter(|snippet| !matches!(snippet.item.value, ConstValue::Struct(..)))
+            .collect();
+        if !consts.is_empty() {
+            emitln!(body, "");
+            for snippet in consts {
+                emit_const(&mut body, &snippet.name, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            if !self.provided_types.contains_key(&snippet.name) {
+                emit_enum(&mut body, &snippet.name, &snippet.docs, &snippet.item);
+            }
+        }
+
+        for snippet in &self.functions {
+            let fun = &snippet.item;
+            if !is_wrappable(context, fun) {
+                println!(
+                    "parameters or results of this type are not supported by the wrappers ({}). \
+                     Skipping its wrapper.",
+                    snippet.name
+                );
+                continue;
+            }
+
+            // Only the types of the parameters are named by the wrappers.
+            for (_, ty) in &fun.item.inputs {
+                visit_type(ty, &mut |ty| {
+                    if let Some(name) = numeric_type(ty) {
+                        let _ = natives.insert(name);
+                    }
+                    if let Type::User(ref name) = *ty {
+                        if let Some(module) = context.enums.get(name) {
+                            let _ = modules.insert(module.clone());
+                        }
+                    }
+                });
+            }
+            emit_wrapper_function(&mut body, context, &snippet.name, &snippet.docs, fun);
+        }
+
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+        emitln!(writer, "# cython: language_level=3");
+        emitln!(
+            writer,
+            "# Wrappers of the native library `{}`, declared in `{}.pxd`.\n",
+            self.lib_name,
+            context.module
+        );
+        emitln!(writer, "cimport {}", context.module);
+        for module in modules.iter().filter(|module| **module != context.module) {
+            emitln!(writer, "cimport {}", module);
+        }
+        if self
+            .enums
+            .iter()
+            .any(|snippet| context.classes.contains(&snippet.name))
+        {
+            emitln!(writer, "import enum");
+        }
+        let stdint: Vec<_> = natives.iter().filter(|name| name.ends_with("_t")).collect();
+        if !stdint.is_empty() {
+            emitln!(writer, "from libc.stdint cimport {}", join(stdint));
+        }
+
+        format!("{}{}", writer.into_inner(), body.into_inner())
+    }
+}
+
+impl Default for LangCython {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangCython {
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_const(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        // Constants aren't declared in the C header, so they're only defined by the wrappers.
+        if let ConstValue::Struct(..) = item.value {
+            println!("struct constants are not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_ty(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        if !item.generics.params.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(ty) = transform_type(&item.ty) {
+            self.aliases.insert(name.clone(), ty);
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+
+        if !self.provided_types.contains_key(&name) {
+            let ty = lang_c::alias_rust_to_c(&item.ty, &name)?.1;
+            self.types.push(Snippet {
+                docs,
+                name,
+                item: Declaration::Alias(ty),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_enum(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+
+        // The tagged unions of data-carrying enums are only declared in the C header.
+        if common::has_data_variants(item) {
+            if !self.provided_types.contains_key(&name) {
+                self.types.push(Snippet {
+                    docs,
+                    name,
+                    item: Declaration::Opaque,
+                });
+            }
+            return Ok(());
+        }
+        let _ = self.enum_names.insert(name.clone());
+        if self.provided_types.contains_key(&name) {
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+
+        self.types.push(Snippet {
+            docs: docs.clone(),
+            name: name.clone(),
+            item: Declaration::Enum {
+                repr: repr.int.as_ref().map(|int| lang_c::rust_ty_to_c(int)),
+                item: Enum {
+                    variants: item
+                        .variants
+                        .iter()
+                        .map(|variant| EnumVariant {
+                            docs: variant.docs.clone(),
+                            name: format!("{}_{}", name, variant.name),
+                            value: variant.value,
+                        })
+                        .collect(),
+                    ty: None,
+                },
+            },
+        });
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_struct(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+
+        // Transparent structs are declared as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                if let Some(ty) = transform_type(&field.ty) {
+                    self.aliases.insert(name.clone(), ty);
+                }
+                if !self.provided_types.contains_key(&name) {
+                    let ty = lang_c::field_rust_to_c(&field.ty, &name)?.1;
+                    self.types.push(Snippet {
+                        docs,
+                        name,
+                        item: Declaration::Alias(ty),
+                    });
+                }
+            }
+            return Ok(());
+        }
+
+        if self.provided_types.contains_key(&name) {
+            return Ok(());
+        }
+
+        let mut fields = Vec::new();
+        for (index, field) in item.fields.iter().enumerate() {
+            let field_name = match field.ident {
+                Some(ref ident) => ident.to_string(),
+                None => format!("_{}", index),
+            };
+            let ty = lang_c::field_rust_to_c(&field.ty, &field_name)?.1;
+            fields.push((field_name, ty));
+        }
+
+        self.types.push(Snippet {
+            docs,
+            name,
+            item: Declaration::Struct {
+                fields,
+                union: false,
+                packed: repr.packed.is_some(),
+            },
+        });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_union(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (repr_c, docs) =
+            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+
+        // If it's not #[repr(C)] ignore it.
+        if !repr_c {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+        if self.provided_types.contains_key(&name) {
+            return Ok(());
+        }
+
+        let mut fields = Vec::new();
+        for field in &item.fields.named {
+            let field_name = unwrap!(field.ident.as_ref()).to_string();
+            let ty = lang_c::field_rust_to_c(&field.ty, &field_name)?.1;
+            fields.push((field_name, ty));
+        }
+
+        self.types.push(Snippet {
+            docs,
+            name,
+            item: Declaration::Struct {
+                fields,
+                union: true,
+                packed: false,
+            },
+        });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_static(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let ty = lang_c::field_rust_to_c(&item.ty, &name)?.1;
+        self.statics.push(Snippet {
+            docs,
+            name,
+            item: NativeStatic {
+                ty,
+                mutable: item.mutability.is_some(),
+            },
+        });
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_fn(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let output = match item.decl.output {
+            syn::ReturnType::Default => CType::Void,
+            syn::ReturnType::Type(_, ref ty) => lang_c::rust_to_c(ty, "")?.1,
+        };
+        if let CType::FnDecl { .. } = output {
+            println!(
+                "functions returning function pointers are not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+
+        let mut params = Vec::new();
+        for arg in &item.decl.inputs {
+            if let Some(arg) = common::transform_fnarg_to_argcap(arg) {
+                if let Some(pat) = common::take_out_pat(&arg.pat) {
+                    let param = pat.ident.to_string();
+                    let ty = lang_c::rust_to_c(&arg.ty, &param)?.1;
+                    params.push((param, ty));
+                }
+            }
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: NativeFunction {
+                params,
+                output,
+                item: function,
+            },
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        // The opaque types used without being declared must be declared in the C header too.
+        let mut opaque = BTreeSet::new();
+        for ty in self.c_types() {
+            for name in ty.dependencies() {
+                if !self.declared.contains(&name) && !self.provided_types.contains_key(&name) {
+                    let _ = opaque.insert(name);
+                }
+            }
+        }
+        for name in opaque {
+            self.c
+                .add_custom_code(&format!("typedef struct {0} {0};\n", name));
+        }
+        self.c.finalise_output(outputs)?;
+
+        let context = Context {
+            module: module_name(&self.lib_name),
+            enums: self
+                .enum_names
+                .iter()
+                .map(|name| {
+                    let module = match self.provided_types.get(name) {
+                        Some(module) => module.clone(),
+                        None => module_name(&self.lib_name),
+                    };
+                    (name.clone(), module)
+                })
+                .collect(),
+            classes: self
+                .enums
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .filter(|name| !self.provided_types.contains_key(name))
+                .collect(),
+        };
+
+        outputs.insert(format!("{}.pxd", context.module), self.emit_declarations());
+        if self.wrappers_enabled {
+            outputs.insert(
+                format!("{}.pyx", self.lib_name),
+                self.emit_wrappers(&context),
+            );
+        }
+
+        self.consts.clear();
+        self.enums.clear();
+        self.types.clear();
+        self.statics.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.declared.clear();
+        self.enum_names.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
+
+/// Name of the `.pxd` module declaring the library `lib`.
+fn module_name(lib: &str) -> String {
+    format!("c{}", lib)
+}
+
+fn join<T: AsRef<str>, I: IntoIterator<Item = T>>(names: I) -> String {
+    names
+        .into_iter()
+        .map(|name| name.as_ref().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
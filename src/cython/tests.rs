@@ -0,0 +1,312 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangCython::default(), {
+        pub const PAGE_SIZE: u32 = 4096;
+        /// Default database file.
+        pub const DEFAULT_PATH: &'static str = "data.db";
+        pub const MAGIC: [u8; 3] = [83, 81, 76];
+        pub type Digest = [u8; 20];
+
+        /// Isolation of a transaction.
+        #[repr(i16)]
+        pub enum Isolation {
+            Snapshot = -1,
+            ReadCommitted,
+            Serializable = 8,
+        }
+
+        #[repr(C)]
+        pub enum Order {
+            Ascending,
+            Descending,
+        }
+
+        #[repr(C)]
+        pub enum Cell {
+            Null,
+            Integer(i64),
+            Text { len: u32 },
+        }
+
+        /// A row of a table.
+        #[repr(C)]
+        pub struct Row {
+            rowid: i64,
+            columns: *const *const c_char,
+            isolation: Isolation,
+            digest: Digest,
+            flags: [u16; 2],
+            lambda: f32,
+            cursor: *mut Cursor,
+            on_commit: Option<extern "C" fn(rowid: i64, global: bool)>,
+        }
+
+        #[repr(C)]
+        pub union Lookup {
+            id: i64,
+            hash: u32,
+        }
+
+        #[repr(C, packed)]
+        pub struct PageHeader {
+            kind: u8,
+            free: u16,
+        }
+
+        #[no_mangle]
+        pub static mut OPEN_CURSORS: usize = 0;
+
+        #[no_mangle]
+        pub static SCHEMA_VERSION: i32 = 7;
+    });
+
+    let actual = fetch(&outputs, "cbackend.pxd");
+    let expected = indoc!(
+        r#"# Declarations of the native library `backend`, generated in `backend.h`.
+           from libc.stdint cimport int16_t, int32_t, int64_t, uint16_t, uint32_t, uint8_t, uintptr_t
+
+
+           cdef extern from "backend.h":
+               ctypedef bint bool
+
+               ctypedef struct Cursor
+
+               ctypedef uint8_t Digest[20]
+
+               # Isolation of a transaction.
+               ctypedef int16_t Isolation
+
+               enum:
+                   Isolation_Snapshot = -1
+                   Isolation_ReadCommitted
+                   Isolation_Serializable = 8
+
+               ctypedef enum Order:
+                   Order_Ascending
+                   Order_Descending
+
+               ctypedef struct Cell:
+                   pass
+
+               # A row of a table.
+               ctypedef struct Row:
+                   int64_t rowid
+                   const char* const* columns
+                   Isolation isolation
+                   Digest digest
+                   uint16_t flags[2]
+                   float lambda_ "lambda"
+                   Cursor* cursor
+                   void (*on_commit)(int64_t rowid, bool global_)
+
+               ctypedef union Lookup:
+                   int64_t id
+                   uint32_t hash
+
+               ctypedef packed struct PageHeader:
+                   uint8_t kind
+                   uint16_t free
+
+               uintptr_t OPEN_CURSORS
+
+               const int32_t SCHEMA_VERSION
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.pyx");
+    let expected = indoc!(
+        r#"# cython: language_level=3
+           # Wrappers of the native library `backend`, declared in `cbackend.pxd`.
+
+           cimport cbackend
+           import enum
+
+           PAGE_SIZE = 4096
+           DEFAULT_PATH = "data.db"
+           MAGIC = [83, 81, 76]
+
+
+           class Isolation(enum.IntEnum):
+               """Isolation of a transaction."""
+
+               SNAPSHOT = -1
+               READ_COMMITTED = 0
+               SERIALIZABLE = 8
+
+
+           class Order(enum.IntEnum):
+               ASCENDING = 0
+               DESCENDING = 1
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangCython::default(), {
+        #[repr(u8)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        /// Writes `data` under `name`.
+        #[no_mangle]
+        pub extern "C" fn write(
+            app: *mut App,
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+
+        /// Returns the checksum of `data`.
+        #[no_mangle]
+        pub extern "C" fn checksum(data: *const u8, data_len: usize, seed: u32) -> u64 {}
+
+        #[no_mangle]
+        pub extern "C" fn fill(out_ptr: *mut f64, out_len: usize, value: f64, exact: bool) {}
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn parse_mode(name: *const c_char) -> Mode {}
+
+        #[no_mangle]
+        pub extern "C" fn reset() {}
+    });
+
+    let actual = fetch(&outputs, "cbackend.pxd");
+    let expected = indoc!(
+        r#"# Declarations of the native library `backend`, generated in `backend.h`.
+           from libc.stdint cimport uint32_t, uint64_t, uint8_t, uintptr_t
+
+
+           cdef extern from "backend.h":
+               ctypedef bint bool
+
+               ctypedef struct App
+               ctypedef struct FfiResult
+
+               ctypedef uint8_t Mode
+
+               enum:
+                   Mode_Read
+                   Mode_Write
+
+               # Writes `data` under `name`.
+               void write(App* app, const char* name, const uint8_t* data, uintptr_t data_len, void* user_data, void (*o_cb)(void* user_data, const FfiResult* result))
+
+               # Returns the checksum of `data`.
+               uint64_t checksum(const uint8_t* data, uintptr_t data_len, uint32_t seed)
+
+               void fill(double* out_ptr, uintptr_t out_len, double value, bool exact)
+
+               const char* mode_name(Mode mode)
+
+               Mode parse_mode(const char* name)
+
+               void reset()
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.pyx");
+    let expected = indoc!(
+        r#"# cython: language_level=3
+           # Wrappers of the native library `backend`, declared in `cbackend.pxd`.
+
+           cimport cbackend
+           import enum
+           from libc.stdint cimport uint32_t, uint8_t
+
+
+           class Mode(enum.IntEnum):
+               READ = 0
+               WRITE = 1
+
+
+           def checksum(const uint8_t[::1] data not None, uint32_t seed):
+               """Returns the checksum of `data`."""
+               return cbackend.checksum(&data[0] if data.shape[0] > 0 else NULL, data.shape[0], seed)
+
+
+           def fill(double[::1] out not None, double value, bint exact):
+               cbackend.fill(&out[0] if out.shape[0] > 0 else NULL, out.shape[0], value, exact)
+
+
+           def mode_name(cbackend.Mode mode):
+               cdef const char* result = cbackend.mode_name(mode)
+               return result.decode('utf-8') if result != NULL else None
+
+
+           def parse_mode(name):
+               name_bytes = name.encode('utf-8')
+               return Mode(cbackend.parse_mode(name_bytes))
+
+
+           def reset():
+               cbackend.reset()
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let mut lang = LangCython::default();
+    lang.add_provided_package("net", "net_types");
+    lang.set_wrappers_enabled(false);
+
+    let mut outputs = Outputs::default();
+    let protocol: syn::ItemEnum =
+        unwrap!(syn::parse_str("#[repr(C)] pub enum Protocol { Tcp, Udp }"));
+    let address: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Address { octets: [u8; 4], port: u16 }"
+    ));
+    let connection: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Connection { peer: Address, protocol: Protocol, open: bool }"
+    ));
+    let listen: syn::ItemFn = unwrap!(syn::parse_str(
+        "#[no_mangle] pub extern \"C\" fn listen(local: *const Address, \
+         on_accept: extern \"C\" fn(connection: *const Connection, peer: *const Address)) {}"
+    ));
+
+    let net = ["net".to_string()];
+    let server = ["server".to_string()];
+    unwrap!(lang.parse_enum(&protocol, &net, &mut outputs));
+    unwrap!(lang.parse_struct(&address, &net, &mut outputs));
+    unwrap!(lang.parse_struct(&connection, &server, &mut outputs));
+    unwrap!(lang.parse_fn(&listen, &server, &mut outputs));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    // The provided types are cimported from the declarations of their own library, including
+    // the ones only used by a field or a callback.
+    let actual = fetch(&outputs, "cbackend.pxd");
+    let expected = indoc!(
+        r#"# Declarations of the native library `backend`, generated in `backend.h`.
+           from cnet_types cimport Address, Protocol
+
+
+           cdef extern from "backend.h":
+               ctypedef bint bool
+
+               ctypedef struct Connection:
+                   Address peer
+                   Protocol protocol
+                   bool open
+
+               void listen(const Address* local, void (*on_accept)(const Connection* connection, const Address* peer))
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
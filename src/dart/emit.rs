@@ -0,0 +1,786 @@
+//! Utilities for emiting fragments of the target language code.
+use super::Context;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Dart reserved words and built-in types which can be used as identifiers in Rust, along with
+/// the names of the local variables of the wrapper functions.
+const KEYWORDS: &[&str] = &[
+    "assert",
+    "bool",
+    "case",
+    "catch",
+    "class",
+    "default",
+    "do",
+    "double",
+    "dynamic",
+    "extends",
+    "finally",
+    "int",
+    "is",
+    "new",
+    "null",
+    "num",
+    "rethrow",
+    "switch",
+    "this",
+    "throw",
+    "var",
+    "void",
+    "with",
+    "arena",
+    "callback",
+    "completer",
+    "error",
+];
+
+/// Names of the members of Dart enums, which the variants can't be named as.
+const ENUM_MEMBERS: &[&str] = &["index", "value", "values"];
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "///");
+        } else {
+            emitln!(writer, "/// {}", line);
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "const {} {} = {};",
+        const_type(&item.value),
+        escape_keyword(&name.to_camel_case(), KEYWORDS),
+        const_value(&item.value)
+    );
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "enum {} {{", name);
+    writer.indent();
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for (index, variant) in item.variants.iter().enumerate() {
+        let value = variant.value.unwrap_or(next);
+        let separator = if index + 1 == item.variants.len() {
+            ";"
+        } else {
+            ","
+        };
+        emit_docs(writer, &variant.docs);
+        emitln!(
+            writer,
+            "{}({}){}",
+            variant_name(&variant.name),
+            value,
+            separator
+        );
+        next = value + 1;
+    }
+
+    emitln!(writer, "\nconst {}(this.value);\n", name);
+    emitln!(writer, "final int value;\n");
+    emitln!(writer, "static {} fromValue(int value) =>", name);
+    emitln!(
+        writer,
+        "    values.firstWhere((variant) => variant.value == value);"
+    );
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+pub fn emit_opaque(writer: &mut IndentedWriter, name: &str) {
+    emitln!(writer, "\nfinal class {} extends Opaque {{}}", name);
+}
+
+pub fn emit_struct(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &Struct,
+) {
+    let base = if item.union { "Union" } else { "Struct" };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    if let Some(pack) = item.pack {
+        emitln!(writer, "@Packed({})", pack);
+    }
+    emitln!(writer, "final class {} extends {} {{", name, base);
+    writer.indent();
+
+    for (index, field) in item.fields.iter().enumerate() {
+        if index > 0 {
+            emitln!(writer, "");
+        }
+        emit_docs(writer, &field.docs);
+
+        match field.ty {
+            Type::Array(ref ty, ArraySize::Dynamic) => {
+                emitln!(
+                    writer,
+                    "external Pointer<{}> {};\n",
+                    native_type(context, ty),
+                    escape_keyword(&field.name.to_camel_case(), KEYWORDS)
+                );
+                emitln!(writer, "@UintPtr()");
+                emitln!(
+                    writer,
+                    "external int {};",
+                    format!("{}_len", field.name).to_camel_case()
+                );
+                if field.has_cap {
+                    emitln!(writer, "\n@UintPtr()");
+                    emitln!(
+                        writer,
+                        "external int {};",
+                        format!("{}_cap", field.name).to_camel_case()
+                    );
+                }
+            }
+            ref ty => {
+                if let Some(annotation) = annotation(context, ty) {
+                    emitln!(writer, "{}", annotation);
+                }
+                emitln!(
+                    writer,
+                    "external {} {};",
+                    field_type(context, ty),
+                    escape_keyword(&field.name.to_camel_case(), KEYWORDS)
+                );
+            }
+        }
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Emits the pair of typedefs of the native function and the Dart function it's looked up as,
+/// and the typedef of the callback for the functions returning futures.
+pub fn emit_typedefs(writer: &mut IndentedWriter, context: &Context, name: &str, fun: &Function) {
+    let name = name.to_pascal_case();
+    let callback = async_callback(fun, |name| context.is_declared(name))
+        .map(|callback| (callback_typedef(&name), callback));
+
+    emitln!(writer, "");
+    if let Some((ref typedef, callback)) = callback {
+        emitln!(
+            writer,
+            "typedef {} = {};",
+            typedef,
+            function_type(context, callback, true, None)
+        );
+    }
+    emitln!(
+        writer,
+        "typedef {}Native = {};",
+        name,
+        function_type(context, fun, true, callback.as_ref())
+    );
+    emitln!(
+        writer,
+        "typedef {}Dart = {};",
+        name,
+        function_type(context, fun, false, callback.as_ref())
+    );
+}
+
+pub fn emit_utilities(writer: &mut IndentedWriter) {
+    emitln!(
+        writer,
+        r#"
+class FfiError implements Exception {{
+  final int code;
+  final String description;
+
+  FfiError(this.code, this.description);
+
+  @override
+  String toString() => 'FfiError($code): $description';
+}}
+
+/// Returns the error reported by the result passed to a callback, if any.
+FfiError? _error(Pointer<FfiResult> result) {{
+  if (result == nullptr || result.ref.errorCode == 0) {{
+    return null;
+  }}
+  return FfiError(result.ref.errorCode, _decode(result.ref.description) ?? '');
+}}"#
+    );
+}
+
+pub fn emit_decode(writer: &mut IndentedWriter) {
+    emitln!(
+        writer,
+        "\nString? _decode(Pointer<Utf8> value) =>\n    value == nullptr ? null : value.toDartString();"
+    );
+}
+
+/// Emits the class loading the native library, with the statics and functions it exports.
+pub fn emit_library_class(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    lib_name: &str,
+    functions: &[Snippet<Function>],
+    statics: &[Snippet<Static>],
+) {
+    let class = lib_name.to_pascal_case();
+
+    emitln!(
+        writer,
+        "\n/// Bindings of the native library `{}`.",
+        lib_name
+    );
+    emitln!(writer, "class {} {{", class);
+    writer.indent();
+    emitln!(writer, "final DynamicLibrary _lib;\n");
+    emitln!(
+        writer,
+        "/// Uses the symbols of the already opened library."
+    );
+    emitln!(writer, "{}(this._lib);\n", class);
+    emitln!(
+        writer,
+        "/// Opens the library by its platform-specific file name. On iOS, the library is linked"
+    );
+    emitln!(writer, "/// into the app.");
+    emitln!(writer, "{}.open() : this(_open());\n", class);
+    emitln!(writer, "static DynamicLibrary _open() {{");
+    emitln!(writer, "  if (Platform.isIOS) {{");
+    emitln!(writer, "    return DynamicLibrary.process();");
+    emitln!(writer, "  }}");
+    emitln!(writer, "  if (Platform.isMacOS) {{");
+    emitln!(
+        writer,
+        "    return DynamicLibrary.open('lib{}.dylib');",
+        lib_name
+    );
+    emitln!(writer, "  }}");
+    emitln!(writer, "  if (Platform.isWindows) {{");
+    emitln!(
+        writer,
+        "    return DynamicLibrary.open('{}.dll');",
+        lib_name
+    );
+    emitln!(writer, "  }}");
+    emitln!(
+        writer,
+        "  return DynamicLibrary.open('lib{}.so');",
+        lib_name
+    );
+    emitln!(writer, "}}");
+
+    for snippet in statics {
+        emit_static(writer, context, &snippet.name, &snippet.docs, &snippet.item);
+    }
+
+    for snippet in functions {
+        emit_function(writer, context, &snippet.name, &snippet.docs, &snippet.item);
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+fn emit_static(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &Static,
+) {
+    // Arrays are looked up as their first element.
+    let ty = match item.ty {
+        Type::Array(ref ty, _) => native_type(context, ty),
+        ref ty => native_type(context, ty),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "Pointer<{0}> get {1} => _lib.lookup<{0}>('{2}');",
+        ty,
+        escape_keyword(&name.to_camel_case(), KEYWORDS),
+        name
+    );
+}
+
+fn emit_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let callback = async_callback(fun, |name| context.is_declared(name));
+    let typedef = name.to_pascal_case();
+    let lookup = format!("_{}", name.to_camel_case());
+
+    emitln!(writer, "");
+    emitln!(
+        writer,
+        "late final {} = _lib.lookupFunction<{}Native, {}Dart>('{}');",
+        lookup,
+        typedef,
+        typedef,
+        name
+    );
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    // Statements copying the arguments into native memory, freed once the function returns.
+    let mut allocations = Vec::new();
+    let mut uses_arena = false;
+
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        if callback.is_some() {
+            if is_user_data(name, ty) {
+                args.push("nullptr".to_string());
+                continue;
+            }
+            if extract_callback(ty).is_some() {
+                args.push("callback.nativeFunction".to_string());
+                continue;
+            }
+        }
+
+        let param = param_name(&name.to_camel_case(), index, KEYWORDS);
+        match *ty {
+            Type::String => {
+                params.push(format!("String {}", param));
+                args.push(format!("{}.toNativeUtf8(allocator: arena)", param));
+                uses_arena = true;
+            }
+            Type::Array(ref elem, ArraySize::Dynamic) => match typed_list(elem) {
+                Some(list) => {
+                    let pointer = format!("{}Ptr", name.to_camel_case());
+                    params.push(format!("{} {}", list, param));
+                    allocations.push(format!(
+                        "final {} = arena<{}>({}.length);",
+                        pointer,
+                        native_type(context, elem),
+                        param
+                    ));
+                    allocations.push(format!(
+                        "{}.asTypedList({}.length).setAll(0, {});",
+                        pointer, param, param
+                    ));
+                    args.push(pointer);
+                    args.push(format!("{}.length", param));
+                    uses_arena = true;
+                }
+                None => {
+                    let len = len_name(name, index);
+                    params.push(format!("Pointer<{}> {}", native_type(context, elem), param));
+                    params.push(format!("int {}", len));
+                    args.push(param);
+                    args.push(len);
+                }
+            },
+            Type::User(ref name) if context.is_enum(name) => {
+                params.push(format!("{} {}", name, param));
+                args.push(format!("{}.value", param));
+            }
+            ref ty => {
+                params.push(format!("{} {}", dart_type(context, ty), param));
+                args.push(param);
+            }
+        }
+    }
+
+    let call = format!("{}({})", lookup, args.join(", "));
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+
+    if let Some(callback) = callback {
+        let (ty, value) = callback_value(context, callback);
+        let callback_type = callback_typedef(&typedef);
+
+        emitln!(
+            writer,
+            "Future<{}> {}({}) {{",
+            ty,
+            escape_keyword(&name.to_camel_case(), KEYWORDS),
+            params.join(", ")
+        );
+        writer.indent();
+        emitln!(writer, "final completer = Completer<{}>();", ty);
+        emitln!(
+            writer,
+            "late final NativeCallable<{}> callback;",
+            callback_type
+        );
+        emitln!(
+            writer,
+            "callback = NativeCallable<{}>.listener(({}) {{",
+            callback_type,
+            closure_params(context, callback).join(", ")
+        );
+        writer.indent();
+        emitln!(writer, "callback.close();");
+        emitln!(
+            writer,
+            "final error = _error({});",
+            param_name(&callback.inputs[1].0.to_camel_case(), 1, KEYWORDS)
+        );
+        emitln!(writer, "if (error != null) {{");
+        emitln!(writer, "  completer.completeError(error);");
+        emitln!(writer, "}} else {{");
+        emitln!(writer, "  completer.complete({});", value);
+        emitln!(writer, "}}");
+        writer.unindent();
+        emitln!(writer, "}});");
+        emit_call(writer, uses_arena, &allocations, &call, false);
+        emitln!(writer, "return completer.future;");
+    } else {
+        let (ty, value) = match fun.output {
+            Type::String => ("String?".to_string(), format!("_decode({})", call)),
+            Type::User(ref name) if context.is_enum(name) => {
+                (name.clone(), format!("{}.fromValue({})", name, call))
+            }
+            ref ty => (dart_type(context, ty), call),
+        };
+
+        emitln!(
+            writer,
+            "{} {}({}) {{",
+            ty,
+            escape_keyword(&name.to_camel_case(), KEYWORDS),
+            params.join(", ")
+        );
+        writer.indent();
+        emit_call(writer, uses_arena, &allocations, &value, ty != "void");
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Emits the call, within an arena freeing the arguments copied into native memory.
+fn emit_call(
+    writer: &mut IndentedWriter,
+    uses_arena: bool,
+    allocations: &[String],
+    call: &str,
+    returns: bool,
+) {
+    let prefix = if returns { "return " } else { "" };
+
+    if uses_arena {
+        emitln!(writer, "{}using((arena) {{", prefix);
+        writer.indent();
+        for statement in allocations {
+            emitln!(writer, "{}", statement);
+        }
+        emitln!(writer, "{}{};", prefix, call);
+        writer.unindent();
+        emitln!(writer, "}});");
+    } else {
+        emitln!(writer, "{}{};", prefix, call);
+    }
+}
+
+/// Type and value the future is completed with, copied out of the callback parameters.
+fn callback_value(context: &Context, callback: &Function) -> (String, String) {
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+
+    for (index, (name, ty)) in callback.inputs.iter().enumerate().skip(2) {
+        let param = param_name(&name.to_camel_case(), index, KEYWORDS);
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                let len = len_name(name, index);
+                match typed_list(elem) {
+                    Some(list) if list == "Uint8List" => {
+                        types.push(list.to_string());
+                        values.push(format!(
+                            "{0} == nullptr ? Uint8List(0) : Uint8List.fromList({0}.asTypedList({1}))",
+                            param, len
+                        ));
+                    }
+                    Some(list) => {
+                        let elem = &list["List<".len()..list.len() - 1];
+                        types.push(list.to_string());
+                        values.push(format!(
+                            "{0} == nullptr ? <{1}>[] : {0}.asTypedList({2}).toList()",
+                            param, elem, len
+                        ));
+                    }
+                    None => {
+                        types.push(format!("Pointer<{}>", native_type(context, elem)));
+                        values.push(param);
+                        types.push("int".to_string());
+                        values.push(len);
+                    }
+                }
+            }
+            Type::String => {
+                types.push("String".to_string());
+                values.push(format!("_decode({}) ?? ''", param));
+            }
+            Type::User(ref name) if context.is_enum(name) => {
+                types.push(name.clone());
+                values.push(format!("{}.fromValue({})", name, param));
+            }
+            ref ty => {
+                types.push(dart_type(context, ty));
+                values.push(param);
+            }
+        }
+    }
+
+    match types.len() {
+        0 => ("void".to_string(), String::new()),
+        1 => (types.remove(0), values.remove(0)),
+        _ => (
+            format!("({})", types.join(", ")),
+            format!("({})", values.join(", ")),
+        ),
+    }
+}
+
+/// Parameters of the closure of the callback, with dynamic arrays passed as pointer and
+/// length.
+fn closure_params(context: &Context, callback: &Function) -> Vec<String> {
+    flatten_params(callback)
+        .into_iter()
+        .map(|(name, ty)| format!("{} {}", dart_type(context, &ty), name))
+        .collect()
+}
+
+/// The Dart type of the function, with named parameters. The callback of a function returning
+/// a future refers to the typedef of the callback.
+fn function_type(
+    context: &Context,
+    fun: &Function,
+    native: bool,
+    callback: Option<&(String, &Function)>,
+) -> String {
+    let params: Vec<_> = flatten_params(fun)
+        .into_iter()
+        .map(|(name, ty)| {
+            let ty = match (callback, &ty) {
+                (Some((typedef, _)), Type::Function(..)) if extract_callback(&ty).is_some() => {
+                    format!("Pointer<NativeFunction<{}>>", typedef)
+                }
+                _ if native => native_type(context, &ty),
+                _ => dart_type(context, &ty),
+            };
+            format!("{} {}", ty, name)
+        })
+        .collect();
+    let output = if native {
+        native_type(context, &fun.output)
+    } else {
+        dart_type(context, &fun.output)
+    };
+
+    format!("{} Function({})", output, params.join(", "))
+}
+
+/// The parameters of the function, with dynamic arrays passed as pointer and length.
+fn flatten_params(fun: &Function) -> Vec<(String, Type)> {
+    let mut params = Vec::with_capacity(fun.inputs.len());
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                params.push((
+                    param_name(&name.to_camel_case(), index, KEYWORDS),
+                    Type::Pointer(elem.clone()),
+                ));
+                params.push((len_name(name, index), Type::USize));
+            }
+            ref ty => params.push((
+                param_name(&name.to_camel_case(), index, KEYWORDS),
+                ty.clone(),
+            )),
+        }
+    }
+    params
+}
+
+/// The `dart:ffi` native type of the Rust type.
+pub fn native_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "Void".to_string(),
+        Type::Bool => "Bool".to_string(),
+        Type::Char => "Uint32".to_string(),
+        Type::CChar => "Char".to_string(),
+        Type::F32 => "Float".to_string(),
+        Type::F64 => "Double".to_string(),
+        Type::I8 => "Int8".to_string(),
+        Type::I16 => "Int16".to_string(),
+        Type::I32 => "Int32".to_string(),
+        Type::I64 => "Int64".to_string(),
+        Type::ISize => "IntPtr".to_string(),
+        Type::U8 => "Uint8".to_string(),
+        Type::U16 => "Uint16".to_string(),
+        Type::U32 => "Uint32".to_string(),
+        Type::U64 => "Uint64".to_string(),
+        Type::USize => "UintPtr".to_string(),
+        Type::String => "Pointer<Utf8>".to_string(),
+        // Arrays are passed to functions as pointers.
+        Type::Pointer(ref ty) | Type::Array(ref ty, _) => {
+            format!("Pointer<{}>", native_type(context, ty))
+        }
+        Type::Function(ref fun) => format!(
+            "Pointer<NativeFunction<{}>>",
+            function_type(context, fun, true, None)
+        ),
+        Type::User(ref name) => match context.enums.get(name) {
+            Some(Some(ty)) => native_type(context, ty),
+            // The size of C enums is that of an `int`.
+            Some(None) => "Int32".to_string(),
+            None => name.clone(),
+        },
+    }
+}
+
+/// The Dart type values of the Rust type are converted to.
+pub fn dart_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "void".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::F32 | Type::F64 => "double".to_string(),
+        Type::Char
+        | Type::CChar
+        | Type::I8
+        | Type::I16
+        | Type::I32
+        | Type::I64
+        | Type::ISize
+        | Type::U8
+        | Type::U16
+        | Type::U32
+        | Type::U64
+        | Type::USize => "int".to_string(),
+        Type::User(ref name) if context.is_enum(name) => "int".to_string(),
+        Type::User(ref name) => name.clone(),
+        ref ty => native_type(context, ty),
+    }
+}
+
+/// The type of a struct field, where arrays are inline.
+fn field_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Array(ref ty, _) => format!("Array<{}>", native_type(context, ty)),
+        ref ty => dart_type(context, ty),
+    }
+}
+
+/// The annotation giving the native type of a struct field, for the fields of Dart types
+/// which don't tell it.
+fn annotation(context: &Context, ty: &Type) -> Option<String> {
+    match *ty {
+        Type::Array(_, ArraySize::Lit(size)) => Some(format!("@Array({})", size)),
+        Type::Array(_, ArraySize::Const(ref name)) => Some(format!(
+            "@Array({})",
+            escape_keyword(&name.to_camel_case(), KEYWORDS)
+        )),
+        Type::Unit | Type::String | Type::Pointer(..) | Type::Array(..) | Type::Function(..) => {
+            None
+        }
+        Type::User(ref name) if !context.is_enum(name) => None,
+        ref ty => Some(format!("@{}()", native_type(context, ty))),
+    }
+}
+
+/// The Dart list type for arrays of the element type, if the pointers to it can be viewed as
+/// typed lists.
+fn typed_list(elem: &Type) -> Option<&'static str> {
+    match *elem {
+        Type::U8 => Some("Uint8List"),
+        Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::U16 | Type::U32 | Type::U64 => {
+            Some("List<int>")
+        }
+        Type::F32 | Type::F64 => Some("List<double>"),
+        _ => None,
+    }
+}
+
+fn callback_typedef(name: &str) -> String {
+    format!("{}Callback", name)
+}
+
+/// The Dart literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => string_literal(&value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => string_literal(value),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+fn const_type(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(..) => "bool".to_string(),
+        ConstValue::Char(..) | ConstValue::String(..) => "String".to_string(),
+        ConstValue::Int(..) => "int".to_string(),
+        ConstValue::Float(..) => "double".to_string(),
+        ConstValue::Array(ref elements) => match elements.first() {
+            Some(element) => format!("List<{}>", const_type(element)),
+            None => "List<Never>".to_string(),
+        },
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("'");
+    for ch in value.chars() {
+        match ch {
+            '\\' => literal.push_str("\\\\"),
+            '\'' => literal.push_str("\\'"),
+            '$' => literal.push_str("\\$"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('\'');
+    literal
+}
+
+fn variant_name(name: &str) -> String {
+    let name = escape_keyword(&name.to_camel_case(), KEYWORDS);
+    if ENUM_MEMBERS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+fn len_name(name: &str, index: usize) -> String {
+    if name.is_empty() {
+        format!("arg{}Len", index)
+    } else {
+        format!("{}_len", name).to_camel_case()
+    }
+}
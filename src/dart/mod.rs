@@ -0,0 +1,619 @@
+//! Functions for converting Rust definitions into Dart `dart:ffi` bindings.
+//!
+//! The callbacks of the functions returning futures are `NativeCallable.listener`s, which run
+//! on the Dart event loop after the native call has returned. The memory passed to them must
+//! therefore outlive the callback.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, FilterMode, Lang, Outputs};
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 2;
+
+pub struct LangDart {
+    filter: HashSet<String>,
+    filter_mode: FilterMode,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    structs: Vec<Snippet<Struct>>,
+    functions: Vec<Snippet<Function>>,
+    statics: Vec<Snippet<Static>>,
+    aliases: HashMap<String, Type>,
+    /// Libraries of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their libraries.
+    provided_types: BTreeMap<String, String>,
+}
+
+pub struct Context {
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs and unions.
+    structs: HashSet<String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.is_enum(name) || self.structs.contains(name)
+    }
+}
+
+impl LangDart {
+    pub fn new() -> Self {
+        LangDart {
+            filter: Default::default(),
+            filter_mode: FilterMode::Blacklist,
+            lib_name: "backend".to_string(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+            aliases: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        }
+    }
+
+    /// Set the name of the native library. This is also the name of the generated Dart library,
+    /// with the loader class named after it in Pascal case.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated into the Dart
+    /// library `uri` (e.g. `ffi_utils.dart`) by its own bindings. They aren't generated again,
+    /// but imported from it instead.
+    pub fn add_provided_package<P, U>(&mut self, package: P, uri: U)
+    where
+        P: Into<String>,
+        U: Into<String>,
+    {
+        let _ = self.provided_packages.insert(package.into(), uri.into());
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.filter.clear();
+        self.filter_mode = filter_mode;
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        let _ = self.filter.insert(ident.into());
+    }
+
+    fn is_ignored(&self, ident: &str) -> bool {
+        match self.filter_mode {
+            FilterMode::Blacklist => self.filter.contains(ident),
+            FilterMode::Whitelist => !self.filter.contains(ident),
+        }
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(uri) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self.provided_types.insert(name.to_string(), uri.clone());
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.consts {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.structs {
+            for field in &mut snippet.item.fields {
+                resolve_alias(&self.aliases, &mut field.ty);
+            }
+        }
+
+        for snippet in &mut self.statics {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Collect the types used by the items, in the order they appear.
+    fn used_types(&self) -> Vec<&Type> {
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|snippet| snippet.item.fields.iter().map(|field| &field.ty));
+        let inputs = self.functions.iter().flat_map(|snippet| {
+            let inputs = snippet.item.inputs.iter().map(|(_, ty)| ty);
+            inputs.chain(Some(&snippet.item.output))
+        });
+        let statics = self.statics.iter().map(|snippet| &snippet.item.ty);
+
+        fields.chain(inputs).chain(statics).collect()
+    }
+
+    /// Names of the types used without being declared, which can only be used behind pointers.
+    fn opaque_types(&self, context: &Context) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        for ty in self.used_types() {
+            visit_type(ty, &mut |ty| {
+                if let Type::User(ref name) = *ty {
+                    if !context.is_declared(name) && !self.provided_types.contains_key(name) {
+                        let _ = names.insert(name.clone());
+                    }
+                }
+            });
+        }
+        names
+    }
+
+    /// Emit the imports of the provided types.
+    fn emit_provided_imports(&self, writer: &mut IndentedWriter) {
+        let mut libraries: BTreeMap<&String, Vec<&String>> = BTreeMap::new();
+        for (name, uri) in &self.provided_types {
+            libraries.entry(uri).or_default().push(name);
+        }
+
+        for (uri, names) in libraries {
+            let names: Vec<_> = names.iter().map(|name| name.as_str()).collect();
+            emitln!(writer, "import '{}' show {};", uri, names.join(", "));
+        }
+    }
+
+    fn emit_library(&self, context: &Context) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        let used_types = self.used_types();
+        let uses = |predicate: &dyn Fn(&Type) -> bool| {
+            used_types.iter().any(|ty| {
+                let mut found = false;
+                visit_type(ty, &mut |ty| found |= predicate(ty));
+                found
+            })
+        };
+        let futures = self.functions.iter().any(|snippet| {
+            async_callback(&snippet.item, |name| context.is_declared(name)).is_some()
+        });
+        let has_library = !self.functions.is_empty() || !self.statics.is_empty();
+
+        if futures {
+            emitln!(writer, "import 'dart:async';");
+        }
+        emitln!(writer, "import 'dart:ffi';");
+        if has_library {
+            emitln!(writer, "import 'dart:io';");
+        }
+        // Only the wrappers convert arrays of bytes into `Uint8List`s.
+        let uses_bytes = self.functions.iter().any(|snippet| {
+            let mut found = false;
+            for (_, ty) in &snippet.item.inputs {
+                visit_type(ty, &mut |ty| found |= is_byte_array(ty));
+            }
+            found
+        });
+        if uses_bytes {
+            emitln!(writer, "import 'dart:typed_data';");
+        }
+
+        let uses_package_ffi =
+            uses(&|ty| matches!(*ty, Type::String)) || uses(&Type::is_dynamic_array);
+        if uses_package_ffi || !self.provided_types.is_empty() {
+            emitln!(writer, "");
+        }
+        if uses_package_ffi {
+            emitln!(writer, "import 'package:ffi/ffi.dart';");
+        }
+        self.emit_provided_imports(&mut writer);
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.consts {
+                emit_const(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for name in self.opaque_types(context) {
+            emit_opaque(&mut writer, &name);
+        }
+
+        for snippet in &self.structs {
+            emit_struct(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        for snippet in &self.functions {
+            emit_typedefs(&mut writer, context, &snippet.name, &snippet.item);
+        }
+
+        if futures {
+            emit_utilities(&mut writer);
+        }
+        if uses(&|ty| matches!(*ty, Type::String)) && has_library {
+            emit_decode(&mut writer);
+        }
+
+        if has_library {
+            emit_library_class(
+                &mut writer,
+                context,
+                &self.lib_name,
+                &self.functions,
+                &self.statics,
+            );
+        }
+
+        writer.into_inner()
+    }
+}
+
+fn is_byte_array(ty: &Type) -> bool {
+    match *ty {
+        Type::Array(ref elem, ArraySize::Dynamic) => matches!(**elem, Type::U8),
+        _ => false,
+    }
+}
+
+impl Default for LangDart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangDart {
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            println!(
+                "parameterized type aliases not supported ({}). Skipping.",
+                name,
+            );
+            return Ok(());
+        }
+
+        let ty = transform_type(&item.ty).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle the type `{}`", name),
+        })?;
+
+        self.aliases.insert(name, ty);
+
+        Ok(())
+    }
+
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        // Structs are views of native memory, which can't be constant.
+        if let ConstValue::Struct(..) = item.value {
+            println!(
+                "constants of struct types not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("enums"));
+        }
+
+        if common::has_data_variants(item) {
+            println!("data-carrying enums not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                let ty = transform_type(&field.ty).ok_or_else(|| Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: format!("bindgen cannot handle struct {}", name),
+                })?;
+                self.aliases.insert(name, ty);
+            }
+            return Ok(());
+        }
+
+        if repr.align.is_some() {
+            println!(
+                "alignment modifiers are not supported ({}). Ignoring.",
+                name
+            );
+        }
+
+        let fields = common::named_fields(&item.fields);
+        let mut item = transform_struct(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+        item.pack = repr.packed;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (repr_c, docs) =
+            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+
+        // If it's not #[repr(C)] ignore it.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("unions"));
+        }
+
+        let mut item = transform_struct(&item.fields.named).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle union {}", name),
+        })?;
+        item.union = true;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let ty = match transform_type(&item.ty) {
+            None => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+            Some(ty) => ty,
+        };
+        let item = Static {
+            ty,
+            mutable: item.mutability.is_some(),
+        };
+        self.statics.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self
+                .structs
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .collect(),
+        };
+
+        // Types generated by the bindings of dependencies are imported instead.
+        let provided_types = &self.provided_types;
+        self.enums
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+        self.structs
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+
+        if !self.consts.is_empty()
+            || !self.enums.is_empty()
+            || !self.structs.is_empty()
+            || !self.statics.is_empty()
+            || !self.functions.is_empty()
+        {
+            let library = self.emit_library(&context);
+            outputs.insert(format!("{}.dart", self.lib_name), library);
+        }
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.statics.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        // The units of a workspace are generated into sibling directories.
+        Some(format!("../{0}/{0}.dart", self.lib_name))
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
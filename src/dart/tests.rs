@@ -0,0 +1,523 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangDart::default(), {
+        pub const MAX_SAMPLES: usize = 4;
+        pub const UNIT_LABEL: &'static str = "Cost in $ ('per unit')";
+
+        /// Severity of a reading.
+        #[repr(i8)]
+        pub enum Severity {
+            Low = -1,
+            Normal,
+            Critical = 10,
+        }
+
+        /// A sensor reading.
+        #[repr(C)]
+        pub struct Reading {
+            sensor_id: u32,
+            is_final: bool,
+            samples: [f32; MAX_SAMPLES],
+            severity: Severity,
+            device: *mut Device,
+            tags_ptr: *const u16,
+            tags_len: usize,
+            on_update: Option<extern "C" fn(sensor_id: u32, level: f64)>,
+        }
+
+        #[repr(C)]
+        pub union Payload {
+            int: i64,
+            double: f64,
+            raw: [u8; 8],
+        }
+
+        #[repr(C, packed)]
+        pub struct Frame {
+            kind: u8,
+            length: u16,
+        }
+
+        /// Samples per second.
+        #[no_mangle]
+        pub static mut SAMPLE_RATE: f64 = 0.0;
+    });
+
+    let actual = fetch(&outputs, "backend.dart");
+    let expected = indoc!(
+        r#"import 'dart:ffi';
+           import 'dart:io';
+
+           import 'package:ffi/ffi.dart';
+
+           const int maxSamples = 4;
+           const String unitLabel = 'Cost in \$ (\'per unit\')';
+
+           /// Severity of a reading.
+           enum Severity {
+             low(-1),
+             normal(0),
+             critical(10);
+
+             const Severity(this.value);
+
+             final int value;
+
+             static Severity fromValue(int value) =>
+                 values.firstWhere((variant) => variant.value == value);
+           }
+
+           final class Device extends Opaque {}
+
+           /// A sensor reading.
+           final class Reading extends Struct {
+             @Uint32()
+             external int sensorId;
+
+             @Bool()
+             external bool isFinal;
+
+             @Array(maxSamples)
+             external Array<Float> samples;
+
+             @Int8()
+             external int severity;
+
+             external Pointer<Device> device;
+
+             external Pointer<Uint16> tags;
+
+             @UintPtr()
+             external int tagsLen;
+
+             external Pointer<NativeFunction<Void Function(Uint32 sensorId, Double level)>> onUpdate;
+           }
+
+           final class Payload extends Union {
+             @Int64()
+             external int int_;
+
+             @Double()
+             external double double_;
+
+             @Array(8)
+             external Array<Uint8> raw;
+           }
+
+           @Packed(1)
+           final class Frame extends Struct {
+             @Uint8()
+             external int kind;
+
+             @Uint16()
+             external int length;
+           }
+
+           /// Bindings of the native library `backend`.
+           class Backend {
+             final DynamicLibrary _lib;
+
+             /// Uses the symbols of the already opened library.
+             Backend(this._lib);
+
+             /// Opens the library by its platform-specific file name. On iOS, the library is linked
+             /// into the app.
+             Backend.open() : this(_open());
+
+             static DynamicLibrary _open() {
+               if (Platform.isIOS) {
+                 return DynamicLibrary.process();
+               }
+               if (Platform.isMacOS) {
+                 return DynamicLibrary.open('libbackend.dylib');
+               }
+               if (Platform.isWindows) {
+                 return DynamicLibrary.open('backend.dll');
+               }
+               return DynamicLibrary.open('libbackend.so');
+             }
+
+             /// Samples per second.
+             Pointer<Double> get sampleRate => _lib.lookup<Double>('SAMPLE_RATE');
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangDart::default(), {
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn record_id(record: *const Record, from: *const App) -> u64 {}
+
+        /// Writes the data
+        /// and the numbers.
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            numbers: *const i32,
+            numbers_len: usize,
+            records: *const Record,
+            records_len: usize,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn default_mode() -> Mode {}
+    });
+
+    let actual = fetch(&outputs, "backend.dart");
+    let expected = indoc!(
+        r#"import 'dart:ffi';
+           import 'dart:io';
+           import 'dart:typed_data';
+
+           import 'package:ffi/ffi.dart';
+
+           enum Mode {
+             read(0),
+             write(1);
+
+             const Mode(this.value);
+
+             final int value;
+
+             static Mode fromValue(int value) =>
+                 values.firstWhere((variant) => variant.value == value);
+           }
+
+           final class App extends Opaque {}
+
+           final class Record extends Struct {
+             @Uint64()
+             external int id;
+           }
+
+           typedef RecordIdNative = Uint64 Function(Pointer<Record> record, Pointer<App> from);
+           typedef RecordIdDart = int Function(Pointer<Record> record, Pointer<App> from);
+
+           typedef WriteNative = Void Function(Pointer<Utf8> name, Pointer<Uint8> data, UintPtr dataLen, Pointer<Int32> numbers, UintPtr numbersLen, Pointer<Record> records, UintPtr recordsLen);
+           typedef WriteDart = void Function(Pointer<Utf8> name, Pointer<Uint8> data, int dataLen, Pointer<Int32> numbers, int numbersLen, Pointer<Record> records, int recordsLen);
+
+           typedef ModeNameNative = Pointer<Utf8> Function(Int32 mode);
+           typedef ModeNameDart = Pointer<Utf8> Function(int mode);
+
+           typedef DefaultModeNative = Int32 Function();
+           typedef DefaultModeDart = int Function();
+
+           String? _decode(Pointer<Utf8> value) =>
+               value == nullptr ? null : value.toDartString();
+
+           /// Bindings of the native library `backend`.
+           class Backend {
+             final DynamicLibrary _lib;
+
+             /// Uses the symbols of the already opened library.
+             Backend(this._lib);
+
+             /// Opens the library by its platform-specific file name. On iOS, the library is linked
+             /// into the app.
+             Backend.open() : this(_open());
+
+             static DynamicLibrary _open() {
+               if (Platform.isIOS) {
+                 return DynamicLibrary.process();
+               }
+               if (Platform.isMacOS) {
+                 return DynamicLibrary.open('libbackend.dylib');
+               }
+               if (Platform.isWindows) {
+                 return DynamicLibrary.open('backend.dll');
+               }
+               return DynamicLibrary.open('libbackend.so');
+             }
+
+             late final _recordId = _lib.lookupFunction<RecordIdNative, RecordIdDart>('record_id');
+
+             int recordId(Pointer<Record> record, Pointer<App> from) {
+               return _recordId(record, from);
+             }
+
+             late final _write = _lib.lookupFunction<WriteNative, WriteDart>('write');
+
+             /// Writes the data
+             /// and the numbers.
+             void write(String name, Uint8List data, List<int> numbers, Pointer<Record> records, int recordsLen) {
+               using((arena) {
+                 final dataPtr = arena<Uint8>(data.length);
+                 dataPtr.asTypedList(data.length).setAll(0, data);
+                 final numbersPtr = arena<Int32>(numbers.length);
+                 numbersPtr.asTypedList(numbers.length).setAll(0, numbers);
+                 _write(name.toNativeUtf8(allocator: arena), dataPtr, data.length, numbersPtr, numbers.length, records, recordsLen);
+               });
+             }
+
+             late final _modeName = _lib.lookupFunction<ModeNameNative, ModeNameDart>('mode_name');
+
+             String? modeName(Mode mode) {
+               return _decode(_modeName(mode.value));
+             }
+
+             late final _defaultMode = _lib.lookupFunction<DefaultModeNative, DefaultModeDart>('default_mode');
+
+             Mode defaultMode() {
+               return Mode.fromValue(_defaultMode());
+             }
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_callbacks() {
+    let outputs = compile!(LangDart::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(*mut c_void, *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.dart");
+    let expected = indoc!(
+        r#"import 'dart:async';
+           import 'dart:ffi';
+           import 'dart:io';
+           import 'dart:typed_data';
+
+           import 'package:ffi/ffi.dart';
+
+           final class App extends Opaque {}
+
+           final class FfiResult extends Struct {
+             @Int32()
+             external int errorCode;
+
+             external Pointer<Utf8> description;
+           }
+
+           typedef FetchCallback = Void Function(Pointer<Void> userData, Pointer<FfiResult> result, Pointer<Utf8> name, Pointer<Uint8> data, UintPtr dataLen);
+           typedef FetchNative = Void Function(Pointer<App> app, Pointer<Utf8> key, Pointer<Void> userData, Pointer<NativeFunction<FetchCallback>> oCb);
+           typedef FetchDart = void Function(Pointer<App> app, Pointer<Utf8> key, Pointer<Void> userData, Pointer<NativeFunction<FetchCallback>> oCb);
+
+           typedef FlushCallback = Void Function(Pointer<Void> arg0, Pointer<FfiResult> arg1);
+           typedef FlushNative = Void Function(Pointer<App> app, Pointer<Void> userData, Pointer<NativeFunction<FlushCallback>> oCb);
+           typedef FlushDart = void Function(Pointer<App> app, Pointer<Void> userData, Pointer<NativeFunction<FlushCallback>> oCb);
+
+           class FfiError implements Exception {
+             final int code;
+             final String description;
+
+             FfiError(this.code, this.description);
+
+             @override
+             String toString() => 'FfiError($code): $description';
+           }
+
+           /// Returns the error reported by the result passed to a callback, if any.
+           FfiError? _error(Pointer<FfiResult> result) {
+             if (result == nullptr || result.ref.errorCode == 0) {
+               return null;
+             }
+             return FfiError(result.ref.errorCode, _decode(result.ref.description) ?? '');
+           }
+
+           String? _decode(Pointer<Utf8> value) =>
+               value == nullptr ? null : value.toDartString();
+
+           /// Bindings of the native library `backend`.
+           class Backend {
+             final DynamicLibrary _lib;
+
+             /// Uses the symbols of the already opened library.
+             Backend(this._lib);
+
+             /// Opens the library by its platform-specific file name. On iOS, the library is linked
+             /// into the app.
+             Backend.open() : this(_open());
+
+             static DynamicLibrary _open() {
+               if (Platform.isIOS) {
+                 return DynamicLibrary.process();
+               }
+               if (Platform.isMacOS) {
+                 return DynamicLibrary.open('libbackend.dylib');
+               }
+               if (Platform.isWindows) {
+                 return DynamicLibrary.open('backend.dll');
+               }
+               return DynamicLibrary.open('libbackend.so');
+             }
+
+             late final _fetch = _lib.lookupFunction<FetchNative, FetchDart>('fetch');
+
+             Future<(String, Uint8List)> fetch(Pointer<App> app, String key) {
+               final completer = Completer<(String, Uint8List)>();
+               late final NativeCallable<FetchCallback> callback;
+               callback = NativeCallable<FetchCallback>.listener((Pointer<Void> userData, Pointer<FfiResult> result, Pointer<Utf8> name, Pointer<Uint8> data, int dataLen) {
+                 callback.close();
+                 final error = _error(result);
+                 if (error != null) {
+                   completer.completeError(error);
+                 } else {
+                   completer.complete((_decode(name) ?? '', data == nullptr ? Uint8List(0) : Uint8List.fromList(data.asTypedList(dataLen))));
+                 }
+               });
+               using((arena) {
+                 _fetch(app, key.toNativeUtf8(allocator: arena), nullptr, callback.nativeFunction);
+               });
+               return completer.future;
+             }
+
+             late final _flush = _lib.lookupFunction<FlushNative, FlushDart>('flush');
+
+             Future<void> flush(Pointer<App> app) {
+               final completer = Completer<void>();
+               late final NativeCallable<FlushCallback> callback;
+               callback = NativeCallable<FlushCallback>.listener((Pointer<Void> arg0, Pointer<FfiResult> arg1) {
+                 callback.close();
+                 final error = _error(arg1);
+                 if (error != null) {
+                   completer.completeError(error);
+                 } else {
+                   completer.complete();
+                 }
+               });
+               _flush(app, nullptr, callback.nativeFunction);
+               return completer.future;
+             }
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let mut lang = LangDart::default();
+    // Units of a workspace import each other by relative URIs.
+    lang.add_provided_unit("geo", "../geo/geo.dart");
+
+    let mut outputs = Outputs::default();
+    let coordinate: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Coordinate { lat: f64, lon: f64 }"
+    ));
+    let bounds: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Bounds { min: Coordinate, max: Coordinate }"
+    ));
+    let marker: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Marker { position: Coordinate, visible: bool }"
+    ));
+    let visit: syn::ItemFn = unwrap!(syn::parse_str(
+        "#[no_mangle] pub extern \"C\" fn visit_markers(\
+             bounds: *const Bounds, \
+             visitor: extern \"C\" fn(marker: *const Marker, nearest: *const Coordinate) -> bool\
+         ) {}"
+    ));
+
+    unwrap!(lang.parse_struct(&coordinate, &["geo".to_string()], &mut outputs));
+    unwrap!(lang.parse_struct(&bounds, &["geo".to_string()], &mut outputs));
+    unwrap!(lang.parse_struct(&marker, &["maps".to_string()], &mut outputs));
+    unwrap!(lang.parse_fn(&visit, &["maps".to_string()], &mut outputs));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    let actual = fetch(&outputs, "backend.dart");
+    let expected = indoc!(
+        r#"import 'dart:ffi';
+           import 'dart:io';
+
+           import '../geo/geo.dart' show Bounds, Coordinate;
+
+           final class Marker extends Struct {
+             external Coordinate position;
+
+             @Bool()
+             external bool visible;
+           }
+
+           typedef VisitMarkersNative = Void Function(Pointer<Bounds> bounds, Pointer<NativeFunction<Bool Function(Pointer<Marker> marker, Pointer<Coordinate> nearest)>> visitor);
+           typedef VisitMarkersDart = void Function(Pointer<Bounds> bounds, Pointer<NativeFunction<Bool Function(Pointer<Marker> marker, Pointer<Coordinate> nearest)>> visitor);
+
+           /// Bindings of the native library `backend`.
+           class Backend {
+             final DynamicLibrary _lib;
+
+             /// Uses the symbols of the already opened library.
+             Backend(this._lib);
+
+             /// Opens the library by its platform-specific file name. On iOS, the library is linked
+             /// into the app.
+             Backend.open() : this(_open());
+
+             static DynamicLibrary _open() {
+               if (Platform.isIOS) {
+                 return DynamicLibrary.process();
+               }
+               if (Platform.isMacOS) {
+                 return DynamicLibrary.open('libbackend.dylib');
+               }
+               if (Platform.isWindows) {
+                 return DynamicLibrary.open('backend.dll');
+               }
+               return DynamicLibrary.open('libbackend.so');
+             }
+
+             late final _visitMarkers = _lib.lookupFunction<VisitMarkersNative, VisitMarkersDart>('visit_markers');
+
+             void visitMarkers(Pointer<Bounds> bounds, Pointer<NativeFunction<Bool Function(Pointer<Marker> marker, Pointer<Coordinate> nearest)>> visitor) {
+               _visitMarkers(bounds, visitor);
+             }
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
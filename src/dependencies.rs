@@ -0,0 +1,345 @@
+//! Type declarations of dependency crates.
+//!
+//! FFI types are often shared by several crates through a common dependency. Its sources are
+//! parsed so that the types the crate refers to can be generated along with the crate's own
+//! bindings, or referred to in the bindings of the dependency.
+
+use crate::symbols::SymbolTable;
+use crate::{Error, Level};
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Parsed files of a crate, along with the modules they contain.
+pub type CrateFiles = Vec<(Vec<String>, syn::File)>;
+
+/// Parses the sources of the crate with the root source file `path`, following `mod`
+/// declarations, and resolves the type paths within it.
+pub fn parse_crate(path: &Path) -> Result<CrateFiles, Error> {
+    let mut files = Vec::new();
+    parse_module(path, Vec::new(), &mut files)?;
+
+    let mut symbols = SymbolTable::new();
+    for (module, file) in &files {
+        symbols.add_file(module, file);
+    }
+    for (module, file) in &mut files {
+        symbols.resolve_file(module, file);
+    }
+
+    Ok(files)
+}
+
+fn parse_module(path: &Path, module: Vec<String>, files: &mut CrateFiles) -> Result<(), Error> {
+    let content = fs::read_to_string(path).map_err(|error| Error {
+        level: Level::Fatal,
+        span: None,
+        message: format!("could not read {}: {}", path.display(), error),
+    })?;
+    let file: syn::File = syn::parse_str(&content).map_err(|error| Error {
+        level: Level::Fatal,
+        span: None,
+        message: format!("could not parse {}: {}", path.display(), error),
+    })?;
+
+    // Submodules of the crate root and of `mod.rs` files are next to them, otherwise in
+    // a directory named after the module.
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let dir = match path.file_stem() {
+        Some(stem) if !module.is_empty() && !path.ends_with("mod.rs") => parent.join(stem),
+        _ => parent.to_path_buf(),
+    };
+
+    for item in &file.items {
+        if let syn::Item::Mod(syn::ItemMod {
+            ref ident,
+            content: None,
+            ..
+        }) = *item
+        {
+            let mut child = module.clone();
+            child.push(ident.to_string());
+
+            let mut child_path = dir.join(format!("{}.rs", ident));
+            if !child_path.exists() {
+                child_path = dir.join(ident.to_string()).join("mod.rs");
+            }
+            parse_module(&child_path, child, files)?;
+        }
+    }
+
+    files.push((module, file));
+    Ok(())
+}
+
+/// Selects the type declarations of the dependency crate `krate` which are referred to by
+/// `files`, either directly or through other selected declarations. Type paths are expected to be
+/// resolved, so that `files` refer to the types of the dependency as `krate::Name`.
+pub fn referenced_items(files: &[syn::File], krate: &str, dependency: &CrateFiles) -> syn::File {
+    let mut declared = HashMap::new();
+    for (_, file) in dependency {
+        collect_declarations(&file.items, &mut declared);
+    }
+
+    let mut referenced = BTreeSet::new();
+    for file in files {
+        collect_type_paths(&file.items, &mut referenced);
+    }
+
+    let mut selected = BTreeSet::new();
+    let mut pending: Vec<String> = referenced
+        .into_iter()
+        .filter_map(|mut path| match path.as_slice() {
+            [first, _] if first == krate => path.pop(),
+            _ => None,
+        })
+        .collect();
+    while let Some(name) = pending.pop() {
+        if let Some(item) = declared.get(&name) {
+            if selected.insert(name) {
+                // The dependency refers to its own types by their names
+                let mut paths = BTreeSet::new();
+                collect_type_paths(std::slice::from_ref(*item), &mut paths);
+                pending.extend(paths.into_iter().filter_map(|mut path| match path.len() {
+                    1 => path.pop(),
+                    _ => None,
+                }));
+            }
+        }
+    }
+
+    // Keep the declaration order, as some languages rely on it
+    let mut items = Vec::new();
+    for (_, file) in dependency {
+        select_items(&file.items, &selected, &mut items);
+    }
+
+    syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items,
+    }
+}
+
+fn declared_name(item: &syn::Item) -> Option<String> {
+    match *item {
+        syn::Item::Struct(ref item) => Some(item.ident.to_string()),
+        syn::Item::Enum(ref item) => Some(item.ident.to_string()),
+        syn::Item::Union(ref item) => Some(item.ident.to_string()),
+        syn::Item::Type(ref item) => Some(item.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn collect_declarations<'a>(items: &'a [syn::Item], declared: &mut HashMap<String, &'a syn::Item>) {
+    for item in items {
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, ref items)),
+            ..
+        }) = *item
+        {
+            collect_declarations(items, declared);
+        } else if let Some(name) = declared_name(item) {
+            declared.entry(name).or_insert(item);
+        }
+    }
+}
+
+fn select_items(items: &[syn::Item], selected: &BTreeSet<String>, output: &mut Vec<syn::Item>) {
+    for item in items {
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, ref items)),
+            ..
+        }) = *item
+        {
+            select_items(items, selected, output);
+        } else if declared_name(item).is_some_and(|name| selected.contains(&name)) {
+            output.push(item.clone());
+        }
+    }
+}
+
+/// Collects the paths of the types referred to by the items.
+fn collect_type_paths(items: &[syn::Item], paths: &mut BTreeSet<Vec<String>>) {
+    for item in items {
+        match *item {
+            syn::Item::Struct(ref item) => {
+                for field in item.fields.iter() {
+                    collect_paths(&field.ty, paths);
+                }
+            }
+            syn::Item::Union(ref item) => {
+                for field in item.fields.named.iter() {
+                    collect_paths(&field.ty, paths);
+                }
+            }
+            syn::Item::Enum(ref item) => {
+                for variant in item.variants.iter() {
+                    for field in variant.fields.iter() {
+                        collect_paths(&field.ty, paths);
+                    }
+                }
+            }
+            syn::Item::Type(ref item) => collect_paths(&item.ty, paths),
+            syn::Item::Const(ref item) => collect_paths(&item.ty, paths),
+            syn::Item::Static(ref item) => collect_paths(&item.ty, paths),
+            syn::Item::Fn(ref item) => {
+                for input in item.decl.inputs.iter() {
+                    if let syn::FnArg::Captured(ref arg) = *input {
+                        collect_paths(&arg.ty, paths);
+                    }
+                }
+                if let syn::ReturnType::Type(_, ref output) = item.decl.output {
+                    collect_paths(output, paths);
+                }
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, ref items)),
+                ..
+            }) => collect_type_paths(items, paths),
+            _ => (),
+        }
+    }
+}
+
+fn collect_paths(ty: &syn::Type, paths: &mut BTreeSet<Vec<String>>) {
+    match *ty {
+        syn::Type::Path(ref path) => {
+            for segment in path.path.segments.iter() {
+                if let syn::PathArguments::AngleBracketed(ref args) = segment.arguments {
+                    for arg in args.args.iter() {
+                        if let syn::GenericArgument::Type(ref ty) = *arg {
+                            collect_paths(ty, paths);
+                        }
+                    }
+                }
+            }
+            paths.insert(
+                path.path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect(),
+            );
+        }
+        syn::Type::Ptr(ref ptr) => collect_paths(&ptr.elem, paths),
+        syn::Type::Reference(ref reference) => collect_paths(&reference.elem, paths),
+        syn::Type::Array(ref array) => collect_paths(&array.elem, paths),
+        syn::Type::Slice(ref slice) => collect_paths(&slice.elem, paths),
+        syn::Type::Paren(ref paren) => collect_paths(&paren.elem, paths),
+        syn::Type::Tuple(ref tuple) => {
+            for elem in tuple.elems.iter() {
+                collect_paths(elem, paths);
+            }
+        }
+        syn::Type::BareFn(ref bare_fn) => {
+            for input in bare_fn.inputs.iter() {
+                collect_paths(&input.ty, paths);
+            }
+            if let syn::ReturnType::Type(_, ref output) = bare_fn.output {
+                collect_paths(output, paths);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Locates the root source files of the dependency crates `names` using `cargo metadata`,
+/// without accessing the network.
+pub fn sources_from_cargo(names: &[String]) -> Result<HashMap<String, PathBuf>, Error> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--offline", "--format-version", "1"]);
+    if let Some(dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
+        command
+            .arg("--manifest-path")
+            .arg(Path::new(&dir).join("Cargo.toml"));
+    }
+
+    let output = command.output().map_err(|error| Error {
+        level: Level::Fatal,
+        span: None,
+        message: format!("could not run cargo metadata: {}", error),
+    })?;
+    if !output.status.success() {
+        return Err(Error {
+            level: Level::Fatal,
+            span: None,
+            message: format!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|error| Error {
+            level: Level::Fatal,
+            span: None,
+            message: format!("could not parse cargo metadata: {}", error),
+        })?;
+
+    names
+        .iter()
+        .map(|name| {
+            lib_source(&metadata, name)
+                .map(|path| (name.clone(), path))
+                .ok_or_else(|| Error {
+                    level: Level::Fatal,
+                    span: None,
+                    message: format!("could not find the sources of dependency {}", name),
+                })
+        })
+        .collect()
+}
+
+/// Finds the root source file of the library target of the package with the crate name `name`.
+fn lib_source(metadata: &serde_json::Value, name: &str) -> Option<PathBuf> {
+    metadata["packages"]
+        .as_array()?
+        .iter()
+        .filter(|package| {
+            package["name"].as_str().map(|n| n.replace('-', "_")) == Some(name.into())
+        })
+        .flat_map(|package| package["targets"].as_array().into_iter().flatten())
+        .find(|target| {
+            target["kind"].as_array().is_some_and(|kinds| {
+                kinds
+                    .iter()
+                    .any(|kind| kind.as_str().is_some_and(|kind| kind.ends_with("lib")))
+            })
+        })
+        .and_then(|target| target["src_path"].as_str())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    #[test]
+    fn lib_source_from_metadata() {
+        let metadata: serde_json::Value = unwrap!(serde_json::from_str(
+            r#"{
+                "packages": [
+                    {
+                        "name": "ffi-utils",
+                        "targets": [
+                            { "kind": ["bin"], "src_path": "/ffi_utils/src/main.rs" },
+                            { "kind": ["rlib"], "src_path": "/ffi_utils/src/lib.rs" }
+                        ]
+                    }
+                ]
+            }"#
+        ));
+
+        assert_eq!(
+            lib_source(&metadata, "ffi_utils"),
+            Some(PathBuf::from("/ffi_utils/src/lib.rs"))
+        );
+        assert_eq!(lib_source(&metadata, "other"), None);
+    }
+}
@@ -0,0 +1,1084 @@
+//! Utilities for emiting fragments of the target language code.
+use super::{Context, NativeStruct};
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Go keywords and the predeclared identifiers used by the generated code, which can be used as
+/// identifiers in Rust, along with the names of the local variables of the wrapper functions.
+const KEYWORDS: &[&str] = &[
+    "case",
+    "chan",
+    "default",
+    "defer",
+    "fallthrough",
+    "func",
+    "go",
+    "goto",
+    "import",
+    "interface",
+    "map",
+    "package",
+    "range",
+    "select",
+    "switch",
+    "var",
+    "int",
+    "len",
+    "string",
+    "alloc",
+    "err",
+    "reply",
+    "results",
+];
+
+/// Where a type is used, which decides how pointers to structs are converted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Usage {
+    /// Field of a struct, where pointers are kept as they are.
+    Field,
+    /// Parameter or result of a function, where the structs pointed to are copied.
+    Value,
+}
+
+/// Name of the Go function exported as the callback of the function `name`.
+pub fn trampoline_name(context: &Context, name: &str) -> String {
+    format!("{}_{}_callback", context.lib_name, name).to_camel_case()
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "//");
+        } else {
+            emitln!(writer, "// {}", line);
+        }
+    }
+}
+
+/// Emits the imports of the packages the code refers to, as unused imports don't compile.
+pub fn emit_imports(writer: &mut IndentedWriter, code: &str) {
+    let imports: Vec<_> = ["fmt", "sync", "unsafe"]
+        .iter()
+        .filter(|package| code.contains(&format!("{}.", package)))
+        .collect();
+
+    match imports.len() {
+        0 => (),
+        1 => emitln!(writer, "\nimport \"{}\"", imports[0]),
+        _ => {
+            emitln!(writer, "\nimport (");
+            for package in imports {
+                emitln!(writer, "\t\"{}\"", package);
+            }
+            emitln!(writer, ")");
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "const {} = {}",
+        name.to_pascal_case(),
+        const_value(&item.value)
+    );
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    // The size of C enums is that of an `int`.
+    let ty = match item.ty {
+        Some(ref ty) => primitive_type(ty).unwrap_or("int32"),
+        None => "int32",
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "type {} {}\n", name, ty);
+    emitln!(writer, "const (");
+    writer.indent();
+
+    // Variants without a value follow the previous one, as in C.
+    let width = item
+        .variants
+        .iter()
+        .map(|variant| variant_name(name, &variant.name).len())
+        .max()
+        .unwrap_or(0);
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emit_docs(writer, &variant.docs);
+        emitln!(
+            writer,
+            "{:width$} {} = {}",
+            variant_name(name, &variant.name),
+            name,
+            value,
+            width = width
+        );
+        next = value + 1;
+    }
+
+    writer.unindent();
+    emitln!(writer, ")");
+}
+
+/// Emits the Go mirror of the struct, along with the functions converting from and to the C
+/// struct.
+pub fn emit_struct(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &NativeStruct,
+) {
+    let fields = &item.item.fields;
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "type {} struct {{", name);
+    writer.indent();
+    let width = max_width(fields.iter().map(|field| field_name(&field.name)));
+    for field in fields {
+        emit_docs(writer, &field.docs);
+        emitln!(
+            writer,
+            "{:width$} {}",
+            field_name(&field.name),
+            go_type(context, &field.ty, Usage::Field),
+            width = width
+        );
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    // Fields converted by expressions go into the composite literal, arrays are copied after.
+    let mut values = Vec::new();
+    let mut copies = Vec::new();
+    for field in fields {
+        let c_field = format!("c.{}", c_field_name(&field.name));
+        match field.ty {
+            Type::Array(ref ty, ArraySize::Dynamic) => {
+                let pointer = format!("c.{}", c_field_name(&item.pointers[&field.name]));
+                let len = format!("c.{}", c_field_name(&format!("{}_len", field.name)));
+                values.push((
+                    field_name(&field.name),
+                    format!(
+                        "goSlice({}, int({}), {})",
+                        pointer,
+                        len,
+                        from_converter(context, ty, Usage::Field)
+                    ),
+                ));
+            }
+            Type::Array(ref ty, _) => copies.push((
+                field_name(&field.name),
+                c_field,
+                from_c(context, ty, "v", Usage::Field),
+            )),
+            ref ty => values.push((
+                field_name(&field.name),
+                from_c(context, ty, &c_field, Usage::Field),
+            )),
+        }
+    }
+
+    emitln!(
+        writer,
+        "\n// {}FromC copies the C struct into its Go mirror.",
+        from_c_name(name)
+    );
+    emitln!(
+        writer,
+        "func {}FromC(c C.{}) {} {{",
+        from_c_name(name),
+        name,
+        name
+    );
+    writer.indent();
+    emitln!(writer, "r := {}{{", name);
+    writer.indent();
+    let width = max_width(values.iter().map(|(name, _)| format!("{}:", name)));
+    for (name, value) in values {
+        emitln!(
+            writer,
+            "{:width$} {},",
+            format!("{}:", name),
+            value,
+            width = width
+        );
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+    for (name, c_field, value) in copies {
+        emitln!(writer, "for i, v := range {} {{", c_field);
+        emitln!(writer, "\tr.{}[i] = {}", name, value);
+        emitln!(writer, "}}");
+    }
+    emitln!(writer, "return r");
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    emitln!(
+        writer,
+        "\n// toC converts the struct into C, with the memory it points to owned by alloc."
+    );
+    emitln!(
+        writer,
+        "func (r {}) toC(alloc *allocator) C.{} {{",
+        name,
+        name
+    );
+    writer.indent();
+    emitln!(writer, "var c C.{}", name);
+    for field in fields {
+        let go_field = format!("r.{}", field_name(&field.name));
+        let c_field = format!("c.{}", c_field_name(&field.name));
+        match field.ty {
+            Type::Array(ref ty, ArraySize::Dynamic) => {
+                emitln!(
+                    writer,
+                    "c.{} = cArray(alloc, {}, {})",
+                    c_field_name(&item.pointers[&field.name]),
+                    go_field,
+                    to_converter(context, ty, Usage::Field)
+                );
+                emitln!(
+                    writer,
+                    "c.{} = C.uintptr_t(len({}))",
+                    c_field_name(&format!("{}_len", field.name)),
+                    go_field
+                );
+                if field.has_cap {
+                    emitln!(
+                        writer,
+                        "c.{} = C.uintptr_t(len({}))",
+                        c_field_name(&format!("{}_cap", field.name)),
+                        go_field
+                    );
+                }
+            }
+            Type::Array(ref ty, _) => {
+                emitln!(writer, "for i, v := range {} {{", go_field);
+                emitln!(
+                    writer,
+                    "\t{}[i] = {}",
+                    c_field,
+                    to_c(context, ty, "v", Usage::Field)
+                );
+                emitln!(writer, "}}");
+            }
+            ref ty => emitln!(
+                writer,
+                "{} = {}",
+                c_field,
+                to_c(context, ty, &go_field, Usage::Field)
+            ),
+        }
+    }
+    emitln!(writer, "return c");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+pub fn emit_static(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &Static,
+) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "func {}() {} {{",
+        name.to_pascal_case(),
+        go_type(context, &item.ty, Usage::Value)
+    );
+    emitln!(
+        writer,
+        "\treturn {}",
+        from_c(context, &item.ty, &format!("C.{}", name), Usage::Value)
+    );
+    emitln!(writer, "}}");
+}
+
+pub fn emit_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let (params, args, needs_alloc) = convert_params(context, fun, None);
+    let call = format!("C.{}({})", name, args.join(", "));
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    match fun.output {
+        Type::Unit => emitln!(
+            writer,
+            "func {}({}) {{",
+            name.to_pascal_case(),
+            params.join(", ")
+        ),
+        ref ty => emitln!(
+            writer,
+            "func {}({}) {} {{",
+            name.to_pascal_case(),
+            params.join(", "),
+            go_type(context, ty, Usage::Value)
+        ),
+    }
+    writer.indent();
+    if needs_alloc {
+        emitln!(writer, "alloc := &allocator{{}}");
+        emitln!(writer, "defer alloc.free()");
+    }
+    match fun.output {
+        Type::Unit => emitln!(writer, "{}", call),
+        ref ty => emitln!(
+            writer,
+            "return {}",
+            from_c(context, ty, &call, Usage::Value)
+        ),
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Emits the function returning a channel of the result passed to the callback, the callback
+/// exported as `trampoline` and the blocking function receiving from the channel.
+pub fn emit_async_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+    trampoline: &str,
+) {
+    let callback = unwrap!(async_callback(fun, |name| context.is_mirrored(name)));
+    let function = name.to_pascal_case();
+    let result_type = format!("{}Result", function);
+    let values: Vec<_> = callback
+        .inputs
+        .iter()
+        .enumerate()
+        .skip(2)
+        .map(|(index, (name, ty))| (index, field_name(&value_name(name, index)), name, ty))
+        .collect();
+    let channel = if values.is_empty() {
+        "error".to_string()
+    } else {
+        result_type.clone()
+    };
+
+    if !values.is_empty() {
+        emitln!(
+            writer,
+            "\n// {} is the result of {}.",
+            result_type,
+            function
+        );
+        emitln!(writer, "type {} struct {{", result_type);
+        let width = max_width(
+            values
+                .iter()
+                .map(|(_, field, _, _)| field.clone())
+                .chain(Some("Err".to_string())),
+        );
+        for (_, field, _, ty) in &values {
+            emitln!(
+                writer,
+                "\t{:width$} {}",
+                field,
+                go_type(context, ty, Usage::Value),
+                width = width
+            );
+        }
+        emitln!(writer, "\t{:width$} error", "Err", width = width);
+        emitln!(writer, "}}");
+    }
+
+    // The callback, receiving the C values.
+    let params: Vec<_> = flatten_params(callback)
+        .into_iter()
+        .map(|(name, ty)| format!("{} {}", name, cgo_type(&ty)))
+        .collect();
+    let result = param_name(&callback.inputs[1].0.to_camel_case(), 1, KEYWORDS);
+
+    emitln!(writer, "\n//export {}", trampoline);
+    emitln!(writer, "func {}({}) {{", trampoline, params.join(", "));
+    writer.indent();
+    emitln!(
+        writer,
+        "results := unregister({}).(chan {})",
+        param_name(&callback.inputs[0].0.to_camel_case(), 0, KEYWORDS),
+        channel
+    );
+    if values.is_empty() {
+        emitln!(writer, "results <- resultError({})", result);
+    } else {
+        emitln!(writer, "if err := resultError({}); err != nil {{", result);
+        emitln!(writer, "\tresults <- {}{{Err: err}}", result_type);
+        emitln!(writer, "\treturn");
+        emitln!(writer, "}}");
+        emitln!(writer, "results <- {}{{", result_type);
+        let width = max_width(values.iter().map(|(_, field, _, _)| format!("{}:", field)));
+        for (index, field, name, ty) in &values {
+            let param = param_name(&name.to_camel_case(), *index, KEYWORDS);
+            let value = match **ty {
+                Type::Array(ref ty, ArraySize::Dynamic) => format!(
+                    "goSlice({}, int({}), {})",
+                    param,
+                    len_name(name, *index),
+                    from_converter(context, ty, Usage::Value)
+                ),
+                ref ty => from_c(context, ty, &param, Usage::Value),
+            };
+            emitln!(
+                writer,
+                "\t{:width$} {},",
+                format!("{}:", field),
+                value,
+                width = width
+            );
+        }
+        emitln!(writer, "}}");
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    // The function returning the channel.
+    let (params, args, needs_alloc) = convert_params(context, fun, Some(trampoline));
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    if !docs.is_empty() {
+        emitln!(writer, "//");
+    }
+    emitln!(
+        writer,
+        "// The result is sent to the returned channel once the native library calls back."
+    );
+    emitln!(
+        writer,
+        "func {}Async({}) <-chan {} {{",
+        function,
+        params.join(", "),
+        channel
+    );
+    writer.indent();
+    if needs_alloc {
+        emitln!(writer, "alloc := &allocator{{}}");
+        emitln!(writer, "defer alloc.free()");
+    }
+    emitln!(writer, "results := make(chan {}, 1)", channel);
+    emitln!(writer, "C.{}({})", name, args.join(", "));
+    emitln!(writer, "return results");
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    // The blocking function.
+    let names: Vec<_> = fun
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, ty))| !is_user_data(name, ty) && extract_callback(ty).is_none())
+        .map(|(index, (name, _))| param_name(&name.to_camel_case(), index, KEYWORDS))
+        .collect();
+    let call = format!("{}Async({})", function, names.join(", "));
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    if values.is_empty() {
+        emitln!(writer, "func {}({}) error {{", function, params.join(", "));
+        emitln!(writer, "\treturn <-{}", call);
+    } else {
+        let types: Vec<_> = values
+            .iter()
+            .map(|(_, _, _, ty)| go_type(context, ty, Usage::Value))
+            .chain(Some("error".to_string()))
+            .collect();
+        let results: Vec<_> = values
+            .iter()
+            .map(|(_, field, _, _)| format!("reply.{}", field))
+            .chain(Some("reply.Err".to_string()))
+            .collect();
+        emitln!(
+            writer,
+            "func {}({}) ({}) {{",
+            function,
+            params.join(", "),
+            types.join(", ")
+        );
+        emitln!(writer, "\treply := <-{}", call);
+        emitln!(writer, "\treturn {}", results.join(", "));
+    }
+    emitln!(writer, "}}");
+}
+
+/// Emits the C declaration of the exported callback, through which the C code refers to it.
+pub fn emit_trampoline_declaration(
+    writer: &mut IndentedWriter,
+    trampoline: &str,
+    callback: &Function,
+) {
+    let params: Vec<_> = flatten_params(callback)
+        .into_iter()
+        .map(|(name, ty)| format!("{} {}", c_type(&ty), name))
+        .collect();
+
+    emitln!(
+        writer,
+        "extern {} {}({});",
+        c_type(&callback.output),
+        trampoline,
+        params.join(", ")
+    );
+}
+
+/// Returns the helpers referred to by the code.
+pub fn utilities(code: &str) -> String {
+    let mut writer = IndentedWriter::with_tabs();
+
+    if code.contains("resultError(") {
+        emitln!(
+            writer,
+            r#"
+// Error is the error reported by the native library through a FfiResult.
+type Error struct {{
+	Code        int64
+	Description string
+}}
+
+func (e *Error) Error() string {{
+	return fmt.Sprintf("%s (error %d)", e.Description, e.Code)
+}}
+
+// resultError returns the error reported by the result, or nil if the call succeeded.
+func resultError(result *C.FfiResult) error {{
+	if result == nil || result.error_code == 0 {{
+		return nil
+	}}
+	return &Error{{Code: int64(result.error_code), Description: C.GoString(result.description)}}
+}}"#
+        );
+    }
+
+    if code.contains("unregister(") {
+        emitln!(
+            writer,
+            r#"
+// registry keeps the channels of the pending calls, found through the user data passed to the
+// native library, which can't hold Go pointers.
+var registry = struct {{
+	sync.Mutex
+	channels map[unsafe.Pointer]any
+}}{{channels: make(map[unsafe.Pointer]any)}}
+
+// register returns the user data to find the channel with.
+func register(channel any) unsafe.Pointer {{
+	// A byte of C memory gives a unique address.
+	userData := C.malloc(1)
+	registry.Lock()
+	registry.channels[userData] = channel
+	registry.Unlock()
+	return userData
+}}
+
+// unregister returns the channel of the user data, which is freed.
+func unregister(userData unsafe.Pointer) any {{
+	registry.Lock()
+	channel := registry.channels[userData]
+	delete(registry.channels, userData)
+	registry.Unlock()
+	C.free(userData)
+	return channel
+}}"#
+        );
+    }
+
+    if code.contains("allocator") {
+        emitln!(
+            writer,
+            r#"
+// allocator owns the C memory of the converted arguments until the call returns.
+type allocator struct {{
+	pointers []unsafe.Pointer
+}}
+
+func (a *allocator) alloc(size uintptr) unsafe.Pointer {{
+	pointer := C.malloc(C.size_t(size))
+	a.pointers = append(a.pointers, pointer)
+	return pointer
+}}
+
+func (a *allocator) string(value string) *C.char {{
+	pointer := C.CString(value)
+	a.pointers = append(a.pointers, unsafe.Pointer(pointer))
+	return pointer
+}}
+
+func (a *allocator) free() {{
+	for _, pointer := range a.pointers {{
+		C.free(pointer)
+	}}
+}}"#
+        );
+    }
+
+    if code.contains("cArray(") || code.contains("cValue(") {
+        emitln!(
+            writer,
+            r#"
+// cArray copies the slice into C memory owned by alloc, converting the elements.
+func cArray[G, N any](alloc *allocator, slice []G, convert func(G) N) *N {{
+	if len(slice) == 0 {{
+		return nil
+	}}
+	var zero N
+	data := (*N)(alloc.alloc(uintptr(len(slice)) * unsafe.Sizeof(zero)))
+	native := unsafe.Slice(data, len(slice))
+	for i, value := range slice {{
+		native[i] = convert(value)
+	}}
+	return data
+}}"#
+        );
+    }
+
+    if code.contains("cValue(") {
+        emitln!(
+            writer,
+            r#"
+// cValue copies the value into C memory owned by alloc, or returns nil for nil.
+func cValue[G, N any](alloc *allocator, value *G, convert func(G) N) *N {{
+	if value == nil {{
+		return nil
+	}}
+	return cArray(alloc, []G{{*value}}, convert)
+}}"#
+        );
+    }
+
+    if code.contains("goSlice(") {
+        emitln!(
+            writer,
+            r#"
+// goSlice copies the C array into a slice, converting the elements.
+func goSlice[N, G any](data *N, length int, convert func(N) G) []G {{
+	if data == nil {{
+		return nil
+	}}
+	slice := make([]G, length)
+	for i, value := range unsafe.Slice(data, length) {{
+		slice[i] = convert(value)
+	}}
+	return slice
+}}"#
+        );
+    }
+
+    if code.contains("goValue(") {
+        emitln!(
+            writer,
+            r#"
+// goValue copies the C value, or returns nil for nil.
+func goValue[N, G any](value *N, convert func(N) G) *G {{
+	if value == nil {{
+		return nil
+	}}
+	converted := convert(*value)
+	return &converted
+}}"#
+        );
+    }
+
+    writer.into_inner()
+}
+
+/// Converts the parameters of the function into the parameters of the Go function and the
+/// arguments of the C function, also telling whether they're allocated. The user data and the
+/// callback of a function calling back `trampoline` are given by the wrapper.
+fn convert_params(
+    context: &Context,
+    fun: &Function,
+    trampoline: Option<&str>,
+) -> (Vec<String>, Vec<String>, bool) {
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut needs_alloc = false;
+
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        if let Some(trampoline) = trampoline {
+            if is_user_data(name, ty) {
+                args.push("register(results)".to_string());
+                continue;
+            }
+            if extract_callback(ty).is_some() {
+                args.push(format!("(*[0]byte)(C.{})", trampoline));
+                continue;
+            }
+        }
+
+        let param = param_name(&name.to_camel_case(), index, KEYWORDS);
+        params.push(format!("{} {}", param, go_type(context, ty, Usage::Value)));
+        needs_alloc |= is_allocated(context, ty);
+        match *ty {
+            Type::Array(ref ty, ArraySize::Dynamic) => {
+                args.push(format!(
+                    "cArray(alloc, {}, {})",
+                    param,
+                    to_converter(context, ty, Usage::Value)
+                ));
+                args.push(format!("C.uintptr_t(len({}))", param));
+            }
+            ref ty => args.push(to_c(context, ty, &param, Usage::Value)),
+        }
+    }
+
+    (params, args, needs_alloc)
+}
+
+/// Whether converting values of the type into C allocates memory.
+fn is_allocated(context: &Context, ty: &Type) -> bool {
+    match *ty {
+        Type::String | Type::Array(_, ArraySize::Dynamic) => true,
+        Type::User(ref name) => context.is_mirrored(name),
+        Type::Pointer(ref ty) => match **ty {
+            Type::User(ref name) => context.is_mirrored(name),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The parameters of the function, with dynamic arrays passed as pointer and length.
+fn flatten_params(fun: &Function) -> Vec<(String, Type)> {
+    let mut params = Vec::with_capacity(fun.inputs.len());
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                params.push((
+                    param_name(&name.to_camel_case(), index, KEYWORDS),
+                    Type::Pointer(elem.clone()),
+                ));
+                params.push((len_name(name, index), Type::USize));
+            }
+            ref ty => params.push((
+                param_name(&name.to_camel_case(), index, KEYWORDS),
+                ty.clone(),
+            )),
+        }
+    }
+    params
+}
+
+/// The Go type of the primitive type.
+fn primitive_type(ty: &Type) -> Option<&'static str> {
+    let ty = match *ty {
+        Type::Bool => "bool",
+        Type::Char => "rune",
+        Type::CChar => "byte",
+        Type::F32 => "float32",
+        Type::F64 => "float64",
+        Type::I8 => "int8",
+        Type::I16 => "int16",
+        Type::I32 => "int32",
+        Type::I64 => "int64",
+        Type::ISize => "int",
+        Type::U8 => "uint8",
+        Type::U16 => "uint16",
+        Type::U32 => "uint32",
+        Type::U64 => "uint64",
+        Type::USize => "uint",
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// The C type of the primitive type, as declared by the C header.
+fn primitive_c_type(ty: &Type) -> Option<&'static str> {
+    let ty = match *ty {
+        Type::Bool => "bool",
+        Type::Char | Type::CChar => "char",
+        Type::F32 => "float",
+        Type::F64 => "double",
+        Type::I8 => "int8_t",
+        Type::I16 => "int16_t",
+        Type::I32 => "int32_t",
+        Type::I64 => "int64_t",
+        Type::ISize => "intptr_t",
+        Type::U8 => "uint8_t",
+        Type::U16 => "uint16_t",
+        Type::U32 => "uint32_t",
+        Type::U64 => "uint64_t",
+        Type::USize => "uintptr_t",
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// The Go type the values of the type are converted to.
+pub fn go_type(context: &Context, ty: &Type, usage: Usage) -> String {
+    if let Some(ty) = primitive_type(ty) {
+        return ty.to_string();
+    }
+
+    match *ty {
+        Type::String => "string".to_string(),
+        Type::Array(ref ty, ArraySize::Dynamic) => format!("[]{}", go_type(context, ty, usage)),
+        Type::Array(ref ty, ArraySize::Lit(size)) => {
+            format!("[{}]{}", size, go_type(context, ty, usage))
+        }
+        Type::Array(ref ty, ArraySize::Const(ref size)) => {
+            format!("[{}]{}", size.to_pascal_case(), go_type(context, ty, usage))
+        }
+        Type::Pointer(ref pointee) => match **pointee {
+            Type::User(ref name) if context.is_mirrored(name) && usage == Usage::Value => {
+                format!("*{}", name)
+            }
+            Type::User(ref name) if !context.is_mirrored(name) && !context.is_enum(name) => {
+                format!("*{}", name)
+            }
+            _ => "unsafe.Pointer".to_string(),
+        },
+        Type::User(ref name) => name.clone(),
+        Type::Unit | Type::Function(..) => "unsafe.Pointer".to_string(),
+        _ => unreachable!(),
+    }
+}
+
+/// The Go name of the C type, as translated by cgo.
+fn cgo_type(ty: &Type) -> String {
+    if let Some(ty) = primitive_c_type(ty) {
+        return format!("C.{}", ty);
+    }
+
+    match *ty {
+        Type::String => "*C.char".to_string(),
+        Type::Pointer(ref ty) if matches!(**ty, Type::Unit) => "unsafe.Pointer".to_string(),
+        Type::Pointer(ref ty) | Type::Array(ref ty, ArraySize::Dynamic) => {
+            format!("*{}", cgo_type(ty))
+        }
+        Type::Array(ref ty, ArraySize::Lit(size)) => format!("[{}]{}", size, cgo_type(ty)),
+        Type::Array(ref ty, ArraySize::Const(ref size)) => {
+            format!("[{}]{}", size.to_pascal_case(), cgo_type(ty))
+        }
+        Type::Function(..) => "*[0]byte".to_string(),
+        Type::User(ref name) => format!("C.{}", name),
+        _ => "unsafe.Pointer".to_string(),
+    }
+}
+
+/// The C type, without qualifiers, as it's declared by the exported Go functions.
+fn c_type(ty: &Type) -> String {
+    if let Some(ty) = primitive_c_type(ty) {
+        return ty.to_string();
+    }
+
+    match *ty {
+        Type::Unit => "void".to_string(),
+        Type::String => "char*".to_string(),
+        Type::Pointer(ref ty) | Type::Array(ref ty, _) => format!("{}*", c_type(ty)),
+        Type::User(ref name) => name.clone(),
+        _ => "void*".to_string(),
+    }
+}
+
+/// Converts the C value `expr` into Go.
+fn from_c(context: &Context, ty: &Type, expr: &str, usage: Usage) -> String {
+    if let Some(go) = primitive_type(ty) {
+        return format!("{}({})", go, expr);
+    }
+
+    match *ty {
+        Type::String => format!("C.GoString({})", expr),
+        Type::User(ref name) if context.is_mirrored(name) => {
+            format!("{}FromC({})", from_c_name(name), expr)
+        }
+        Type::User(ref name) => format!("{}({})", name, expr),
+        ref ty => match go_type(context, ty, usage).as_str() {
+            "unsafe.Pointer" => format!("unsafe.Pointer({})", expr),
+            go => match *ty {
+                Type::Pointer(ref pointee)
+                    if usage == Usage::Value && is_mirrored(context, pointee) =>
+                {
+                    format!(
+                        "goValue({}, {})",
+                        expr,
+                        from_converter(context, pointee, usage)
+                    )
+                }
+                _ => format!("({})({})", go, expr),
+            },
+        },
+    }
+}
+
+/// Converts the Go value `expr` into C, allocating with `alloc`.
+fn to_c(context: &Context, ty: &Type, expr: &str, usage: Usage) -> String {
+    match *ty {
+        Type::String => format!("alloc.string({})", expr),
+        Type::User(ref name) if context.is_mirrored(name) => format!("{}.toC(alloc)", expr),
+        Type::Pointer(ref pointee) if matches!(**pointee, Type::Unit) => expr.to_string(),
+        Type::Pointer(ref pointee) if usage == Usage::Value && is_mirrored(context, pointee) => {
+            format!(
+                "cValue(alloc, {}, {})",
+                expr,
+                to_converter(context, pointee, usage)
+            )
+        }
+        Type::Pointer(..) | Type::Function(..) => format!("({})({})", cgo_type(ty), expr),
+        ref ty => format!("{}({})", cgo_type(ty), expr),
+    }
+}
+
+/// The function converting C elements into Go.
+fn from_converter(context: &Context, ty: &Type, usage: Usage) -> String {
+    match *ty {
+        Type::User(ref name) if context.is_mirrored(name) => format!("{}FromC", from_c_name(name)),
+        ref ty => format!(
+            "func(v {}) {} {{ return {} }}",
+            cgo_type(ty),
+            go_type(context, ty, usage),
+            from_c(context, ty, "v", usage)
+        ),
+    }
+}
+
+/// The function converting Go elements into C.
+fn to_converter(context: &Context, ty: &Type, usage: Usage) -> String {
+    match *ty {
+        Type::String => "alloc.string".to_string(),
+        ref ty => format!(
+            "func(v {}) {} {{ return {} }}",
+            go_type(context, ty, usage),
+            cgo_type(ty),
+            to_c(context, ty, "v", usage)
+        ),
+    }
+}
+
+fn is_mirrored(context: &Context, ty: &Type) -> bool {
+    match *ty {
+        Type::User(ref name) => context.is_mirrored(name),
+        _ => false,
+    }
+}
+
+/// The Go literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => match value {
+            '\'' => "'\\''".to_string(),
+            '\\' => "'\\\\'".to_string(),
+            value => format!("'{}'", value.escape_default()),
+        },
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => string_literal(value),
+        ConstValue::Array(..) | ConstValue::Struct(..) => {
+            unreachable!("array and struct constants are skipped")
+        }
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch if ch.is_control() => literal.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn max_width<I: IntoIterator<Item = String>>(names: I) -> usize {
+    names.into_iter().map(|name| name.len()).max().unwrap_or(0)
+}
+
+/// Name of the function converting the C struct into its Go mirror.
+fn from_c_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+fn variant_name(enum_name: &str, name: &str) -> String {
+    format!("{}{}", enum_name, name.to_pascal_case())
+}
+
+fn field_name(name: &str) -> String {
+    name.to_pascal_case()
+}
+
+/// Name of the field of the C struct as translated by cgo, which prefixes Go keywords with an
+/// underscore.
+fn c_field_name(name: &str) -> String {
+    const GO_KEYWORDS: &[&str] = &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ];
+
+    if GO_KEYWORDS.contains(&name) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn value_name(name: &str, index: usize) -> String {
+    if name.is_empty() {
+        format!("value{}", index)
+    } else {
+        name.to_string()
+    }
+}
+
+fn len_name(name: &str, index: usize) -> String {
+    if name.is_empty() {
+        format!("arg{}Len", index)
+    } else {
+        format!("{}_len", name).to_camel_case()
+    }
+}
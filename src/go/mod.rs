@@ -0,0 +1,546 @@
+//! Functions for converting Rust definitions into a cgo package, wrapping the C header with Go
+//! mirrors of the structs and functions taking and returning Go values.
+//!
+//! Structs passed by pointer are converted into temporary native copies, so the changes made to
+//! them by the native library aren't copied back. Functions taking a single callback, which
+//! receives the user data and a `FfiResult`, become both blocking functions and functions
+//! returning a channel. Their callbacks are exported from Go, finding the channel through a
+//! registry of the user data.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, Lang, Outputs};
+use crate::intermediate::*;
+use crate::lang_c::LangC;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+pub struct LangGo {
+    c: LangC,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    structs: Vec<Snippet<NativeStruct>>,
+    functions: Vec<Snippet<Function>>,
+    statics: Vec<Snippet<Static>>,
+    aliases: HashMap<String, Type>,
+    /// Names of the types declared in the C header.
+    declared: HashSet<String>,
+}
+
+/// Struct along with the names of the fields of its C declaration holding the pointers of the
+/// dynamic arrays, by the name of the array.
+pub struct NativeStruct {
+    pub item: Struct,
+    pub pointers: HashMap<String, String>,
+}
+
+pub struct Context {
+    lib_name: String,
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs mirrored by Go structs.
+    structs: HashSet<String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_mirrored(&self, name: &str) -> bool {
+        self.structs.contains(name)
+    }
+}
+
+impl LangGo {
+    pub fn new() -> Self {
+        let mut lang = LangGo {
+            c: LangC::new(),
+            lib_name: String::new(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+            aliases: Default::default(),
+            declared: Default::default(),
+        };
+        lang.c.set_split_headers_enabled(false);
+        lang.set_lib_name("backend");
+        lang
+    }
+
+    /// Set the name of the native library, which is also the name of the Go package and of
+    /// the C header it includes.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+        self.c.set_lib_name(self.lib_name.clone());
+        self.c
+            .set_amalgamated_header(format!("{}.h", self.lib_name));
+    }
+
+    /// Add custom code to the C header, e.g. declarations of additional types.
+    pub fn add_custom_code(&mut self, code: &str) {
+        self.c.add_custom_code(code);
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared in the C header
+    /// `header` of its own bindings, which is included instead. The Go mirrors of the types are
+    /// still generated, as cgo types can't be shared between packages.
+    pub fn add_provided_package<P: AsRef<str>, H: Into<String>>(&mut self, package: P, header: H) {
+        self.c.add_provided_package(package, header);
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.structs {
+            for field in &mut snippet.item.item.fields {
+                resolve_alias(&self.aliases, &mut field.ty);
+            }
+        }
+
+        for snippet in &mut self.statics {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Names of the user types used by the items, in alphabetical order.
+    fn used_types(&self) -> BTreeSet<String> {
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|snippet| snippet.item.item.fields.iter().map(|field| &field.ty));
+        let inputs = self.functions.iter().flat_map(|snippet| {
+            let inputs = snippet.item.inputs.iter().map(|(_, ty)| ty);
+            inputs.chain(Some(&snippet.item.output))
+        });
+        let statics = self.statics.iter().map(|snippet| &snippet.item.ty);
+
+        let mut names = BTreeSet::new();
+        for ty in fields.chain(inputs).chain(statics) {
+            visit_type(ty, &mut |ty| {
+                if let Type::User(ref name) = *ty {
+                    let _ = names.insert(name.clone());
+                }
+            });
+        }
+        names
+    }
+
+    fn emit_package(&self, context: &Context, native_types: &BTreeSet<String>) -> String {
+        let mut writer = IndentedWriter::with_tabs();
+
+        for snippet in &self.consts {
+            emit_const(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        if !native_types.is_empty() {
+            emitln!(writer, "");
+            for name in native_types {
+                emitln!(writer, "type {0} C.{0}", name);
+            }
+        }
+
+        for snippet in &self.structs {
+            emit_struct(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        for snippet in &self.statics {
+            emit_static(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        let mut callbacks = Vec::new();
+        for snippet in &self.functions {
+            let fun = &snippet.item;
+            match async_callback(fun, |name| context.is_mirrored(name)) {
+                Some(callback) => {
+                    let trampoline = trampoline_name(context, &snippet.name);
+                    emit_async_function(
+                        &mut writer,
+                        context,
+                        &snippet.name,
+                        &snippet.docs,
+                        fun,
+                        &trampoline,
+                    );
+                    callbacks.push((trampoline, callback));
+                }
+                None if fun
+                    .inputs
+                    .iter()
+                    .any(|(_, ty)| extract_callback(ty).is_some()) =>
+                {
+                    println!(
+                        "callbacks with user data are only supported by functions taking a \
+                         single `FfiResult` callback ({}). Skipping.",
+                        snippet.name
+                    );
+                }
+                None => emit_function(&mut writer, context, &snippet.name, &snippet.docs, fun),
+            }
+        }
+
+        let body = writer.into_inner();
+        let utilities = utilities(&body);
+        let mut writer = IndentedWriter::with_tabs();
+
+        emitln!(
+            writer,
+            "// Package {0} contains the bindings of the native library `{0}`.",
+            self.lib_name
+        );
+        emitln!(writer, "package {}\n", self.lib_name);
+        emitln!(writer, "/*");
+        emitln!(writer, "#cgo LDFLAGS: -l{}", self.lib_name);
+        emitln!(writer, "#include <stdlib.h>");
+        emitln!(writer, "#include \"{}.h\"", self.lib_name);
+        if !callbacks.is_empty() {
+            emitln!(writer, "");
+            for (trampoline, callback) in &callbacks {
+                emit_trampoline_declaration(&mut writer, trampoline, callback);
+            }
+        }
+        emitln!(writer, "*/");
+        emitln!(writer, "import \"C\"");
+        emit_imports(&mut writer, &format!("{}{}", body, utilities));
+
+        format!("{}{}{}", writer.into_inner(), body, utilities)
+    }
+}
+
+impl Default for LangGo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangGo {
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_const(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        // Go constants can only be of basic types.
+        match item.value {
+            ConstValue::Array(..) | ConstValue::Struct(..) => {
+                println!(
+                    "constants of array and struct types not supported ({}). Skipping.",
+                    name
+                );
+            }
+            _ => self.consts.push(Snippet { docs, name, item }),
+        }
+
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_ty(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        if !item.generics.params.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(ty) = transform_type(&item.ty) {
+            let _ = self.declared.insert(name.clone());
+            self.aliases.insert(name, ty);
+        }
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_enum(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+
+        // Data-carrying enums are only declared in the C header.
+        if common::has_data_variants(item) {
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_struct(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                if let Some(ty) = transform_type(&field.ty) {
+                    self.aliases.insert(name, ty);
+                }
+            }
+            return Ok(());
+        }
+
+        let fields = common::named_fields(&item.fields);
+        let struct_item = transform_struct(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+
+        // The pointers of the dynamic arrays are either named after them or suffixed by `_ptr`.
+        let field_names: HashSet<_> = fields
+            .iter()
+            .filter_map(|field| field.ident.as_ref().map(ToString::to_string))
+            .collect();
+        let pointers = struct_item
+            .fields
+            .iter()
+            .filter(|field| field.ty.is_dynamic_array())
+            .map(|field| {
+                let suffixed = format!("{}_ptr", field.name);
+                let pointer = if field_names.contains(&suffixed) {
+                    suffixed
+                } else {
+                    field.name.clone()
+                };
+                (field.name.clone(), pointer)
+            })
+            .collect();
+
+        self.structs.push(Snippet {
+            docs,
+            name,
+            item: NativeStruct {
+                item: struct_item,
+                pointers,
+            },
+        });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_union(item, module, outputs)?;
+
+        // Unions have no Go counterpart, so they're used as the C type.
+        if common::parse_attr(&item.attrs[..], common::check_repr_c, |_| None).0 {
+            let _ = self.declared.insert(item.ident.to_string());
+        }
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_static(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let ty = match transform_type(&item.ty) {
+            Some(Type::Array(..)) | None => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+            Some(ty) => ty,
+        };
+        let item = Static {
+            ty,
+            mutable: item.mutability.is_some(),
+        };
+        self.statics.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_fn(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            lib_name: self.lib_name.clone(),
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self
+                .structs
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .collect(),
+        };
+
+        // Types without Go mirrors are used as the C types, which the header only knows of
+        // through these declarations if they're opaque.
+        let native_types: BTreeSet<_> = self
+            .used_types()
+            .into_iter()
+            .filter(|name| !context.is_enum(name) && !context.is_mirrored(name))
+            .collect();
+        for name in &native_types {
+            if !self.declared.contains(name) {
+                self.c
+                    .add_custom_code(&format!("typedef struct {0} {0};\n", name));
+            }
+        }
+        self.c.finalise_output(outputs)?;
+
+        let package = self.emit_package(&context, &native_types);
+        outputs.insert(format!("{}.go", self.lib_name), package);
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.statics.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.declared.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    /// The header of the unit is included from the package generated next to this one.
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, format!("../{0}/{0}.h", reference));
+    }
+}
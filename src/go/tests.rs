@@ -0,0 +1,586 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangGo::default(), {
+        pub const MAX_ITEMS: usize = 16;
+        pub const CURRENCY: &'static str = "EUR \"€\"";
+
+        /// State of the payment.
+        #[repr(i32)]
+        pub enum Status {
+            Overdue = -1,
+            Pending,
+            /// Refunded to the customer.
+            Refunded = 8,
+        }
+
+        #[repr(C)]
+        pub struct LineItem {
+            sku: *const c_char,
+            quantity: u32,
+            unit_price: f64,
+            digest: [u8; 4],
+        }
+
+        /// An invoice.
+        #[repr(C)]
+        pub struct Invoice {
+            number: u64,
+            items: *const LineItem,
+            items_len: usize,
+            status: Status,
+            paid: bool,
+            customer: *mut Customer,
+            total: Amount,
+        }
+
+        #[repr(C)]
+        pub union Amount {
+            cents: i64,
+            ratio: f32,
+        }
+
+        /// Number of invoices issued so far.
+        #[no_mangle]
+        pub static mut ISSUED: u64 = 0;
+    });
+
+    let actual = fetch(&outputs, "backend.go");
+    let expected = indoc!(
+        r#"// Package backend contains the bindings of the native library `backend`.
+           package backend
+
+           /*
+           #cgo LDFLAGS: -lbackend
+           #include <stdlib.h>
+           #include "backend.h"
+           */
+           import "C"
+
+           import "unsafe"
+
+           const MaxItems = 16
+
+           const Currency = "EUR \"€\""
+
+           // State of the payment.
+           type Status int32
+
+           const (
+           	StatusOverdue  Status = -1
+           	StatusPending  Status = 0
+           	// Refunded to the customer.
+           	StatusRefunded Status = 8
+           )
+
+           type Amount C.Amount
+           type Customer C.Customer
+
+           type LineItem struct {
+           	Sku       string
+           	Quantity  uint32
+           	UnitPrice float64
+           	Digest    [4]uint8
+           }
+
+           // lineItemFromC copies the C struct into its Go mirror.
+           func lineItemFromC(c C.LineItem) LineItem {
+           	r := LineItem{
+           		Sku:       C.GoString(c.sku),
+           		Quantity:  uint32(c.quantity),
+           		UnitPrice: float64(c.unit_price),
+           	}
+           	for i, v := range c.digest {
+           		r.Digest[i] = uint8(v)
+           	}
+           	return r
+           }
+
+           // toC converts the struct into C, with the memory it points to owned by alloc.
+           func (r LineItem) toC(alloc *allocator) C.LineItem {
+           	var c C.LineItem
+           	c.sku = alloc.string(r.Sku)
+           	c.quantity = C.uint32_t(r.Quantity)
+           	c.unit_price = C.double(r.UnitPrice)
+           	for i, v := range r.Digest {
+           		c.digest[i] = C.uint8_t(v)
+           	}
+           	return c
+           }
+
+           // An invoice.
+           type Invoice struct {
+           	Number   uint64
+           	Items    []LineItem
+           	Status   Status
+           	Paid     bool
+           	Customer *Customer
+           	Total    Amount
+           }
+
+           // invoiceFromC copies the C struct into its Go mirror.
+           func invoiceFromC(c C.Invoice) Invoice {
+           	r := Invoice{
+           		Number:   uint64(c.number),
+           		Items:    goSlice(c.items, int(c.items_len), lineItemFromC),
+           		Status:   Status(c.status),
+           		Paid:     bool(c.paid),
+           		Customer: (*Customer)(c.customer),
+           		Total:    Amount(c.total),
+           	}
+           	return r
+           }
+
+           // toC converts the struct into C, with the memory it points to owned by alloc.
+           func (r Invoice) toC(alloc *allocator) C.Invoice {
+           	var c C.Invoice
+           	c.number = C.uint64_t(r.Number)
+           	c.items = cArray(alloc, r.Items, func(v LineItem) C.LineItem { return v.toC(alloc) })
+           	c.items_len = C.uintptr_t(len(r.Items))
+           	c.status = C.Status(r.Status)
+           	c.paid = C.bool(r.Paid)
+           	c.customer = (*C.Customer)(r.Customer)
+           	c.total = C.Amount(r.Total)
+           	return c
+           }
+
+           // Number of invoices issued so far.
+           func Issued() uint64 {
+           	return uint64(C.ISSUED)
+           }
+
+           // allocator owns the C memory of the converted arguments until the call returns.
+           type allocator struct {
+           	pointers []unsafe.Pointer
+           }
+
+           func (a *allocator) alloc(size uintptr) unsafe.Pointer {
+           	pointer := C.malloc(C.size_t(size))
+           	a.pointers = append(a.pointers, pointer)
+           	return pointer
+           }
+
+           func (a *allocator) string(value string) *C.char {
+           	pointer := C.CString(value)
+           	a.pointers = append(a.pointers, unsafe.Pointer(pointer))
+           	return pointer
+           }
+
+           func (a *allocator) free() {
+           	for _, pointer := range a.pointers {
+           		C.free(pointer)
+           	}
+           }
+
+           // cArray copies the slice into C memory owned by alloc, converting the elements.
+           func cArray[G, N any](alloc *allocator, slice []G, convert func(G) N) *N {
+           	if len(slice) == 0 {
+           		return nil
+           	}
+           	var zero N
+           	data := (*N)(alloc.alloc(uintptr(len(slice)) * unsafe.Sizeof(zero)))
+           	native := unsafe.Slice(data, len(slice))
+           	for i, value := range slice {
+           		native[i] = convert(value)
+           	}
+           	return data
+           }
+
+           // goSlice copies the C array into a slice, converting the elements.
+           func goSlice[N, G any](data *N, length int, convert func(N) G) []G {
+           	if data == nil {
+           		return nil
+           	}
+           	slice := make([]G, length)
+           	for i, value := range unsafe.Slice(data, length) {
+           		slice[i] = convert(value)
+           	}
+           	return slice
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangGo::default(), {
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+            name: *const c_char,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn record_id(record: *const Record, from: *const App) -> u64 {}
+
+        /// Writes the data
+        /// and the names.
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            names: *const *const c_char,
+            names_len: usize,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn first_record(app: *const App) -> Record {}
+
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, event: u32),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.go");
+    let expected = indoc!(
+        r#"// Package backend contains the bindings of the native library `backend`.
+           package backend
+
+           /*
+           #cgo LDFLAGS: -lbackend
+           #include <stdlib.h>
+           #include "backend.h"
+           */
+           import "C"
+
+           import "unsafe"
+
+           type Mode int32
+
+           const (
+           	ModeRead  Mode = 0
+           	ModeWrite Mode = 1
+           )
+
+           type App C.App
+
+           type Record struct {
+           	Id   uint64
+           	Name string
+           }
+
+           // recordFromC copies the C struct into its Go mirror.
+           func recordFromC(c C.Record) Record {
+           	r := Record{
+           		Id:   uint64(c.id),
+           		Name: C.GoString(c.name),
+           	}
+           	return r
+           }
+
+           // toC converts the struct into C, with the memory it points to owned by alloc.
+           func (r Record) toC(alloc *allocator) C.Record {
+           	var c C.Record
+           	c.id = C.uint64_t(r.Id)
+           	c.name = alloc.string(r.Name)
+           	return c
+           }
+
+           func RecordId(record *Record, from *App) uint64 {
+           	alloc := &allocator{}
+           	defer alloc.free()
+           	return uint64(C.record_id(cValue(alloc, record, func(v Record) C.Record { return v.toC(alloc) }), (*C.App)(from)))
+           }
+
+           // Writes the data
+           // and the names.
+           func Write(name string, data []uint8, names []string) {
+           	alloc := &allocator{}
+           	defer alloc.free()
+           	C.write(alloc.string(name), cArray(alloc, data, func(v uint8) C.uint8_t { return C.uint8_t(v) }), C.uintptr_t(len(data)), cArray(alloc, names, alloc.string), C.uintptr_t(len(names)))
+           }
+
+           func ModeName(mode Mode) string {
+           	return C.GoString(C.mode_name(C.Mode(mode)))
+           }
+
+           func FirstRecord(app *App) Record {
+           	return recordFromC(C.first_record((*C.App)(app)))
+           }
+
+           // allocator owns the C memory of the converted arguments until the call returns.
+           type allocator struct {
+           	pointers []unsafe.Pointer
+           }
+
+           func (a *allocator) alloc(size uintptr) unsafe.Pointer {
+           	pointer := C.malloc(C.size_t(size))
+           	a.pointers = append(a.pointers, pointer)
+           	return pointer
+           }
+
+           func (a *allocator) string(value string) *C.char {
+           	pointer := C.CString(value)
+           	a.pointers = append(a.pointers, unsafe.Pointer(pointer))
+           	return pointer
+           }
+
+           func (a *allocator) free() {
+           	for _, pointer := range a.pointers {
+           		C.free(pointer)
+           	}
+           }
+
+           // cArray copies the slice into C memory owned by alloc, converting the elements.
+           func cArray[G, N any](alloc *allocator, slice []G, convert func(G) N) *N {
+           	if len(slice) == 0 {
+           		return nil
+           	}
+           	var zero N
+           	data := (*N)(alloc.alloc(uintptr(len(slice)) * unsafe.Sizeof(zero)))
+           	native := unsafe.Slice(data, len(slice))
+           	for i, value := range slice {
+           		native[i] = convert(value)
+           	}
+           	return data
+           }
+
+           // cValue copies the value into C memory owned by alloc, or returns nil for nil.
+           func cValue[G, N any](alloc *allocator, value *G, convert func(G) N) *N {
+           	if value == nil {
+           		return nil
+           	}
+           	return cArray(alloc, []G{*value}, convert)
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_callbacks() {
+    let outputs = compile!(LangGo::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        /// Fetches the entry.
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.go");
+    let expected = indoc!(
+        r#"// Package backend contains the bindings of the native library `backend`.
+           package backend
+
+           /*
+           #cgo LDFLAGS: -lbackend
+           #include <stdlib.h>
+           #include "backend.h"
+
+           extern void backendFetchCallback(void* userData, FfiResult* result, char* name, uint8_t* data, uintptr_t dataLen);
+           extern void backendFlushCallback(void* userData, FfiResult* result);
+           */
+           import "C"
+
+           import (
+           	"fmt"
+           	"sync"
+           	"unsafe"
+           )
+
+           type App C.App
+
+           type FfiResult struct {
+           	ErrorCode   int32
+           	Description string
+           }
+
+           // ffiResultFromC copies the C struct into its Go mirror.
+           func ffiResultFromC(c C.FfiResult) FfiResult {
+           	r := FfiResult{
+           		ErrorCode:   int32(c.error_code),
+           		Description: C.GoString(c.description),
+           	}
+           	return r
+           }
+
+           // toC converts the struct into C, with the memory it points to owned by alloc.
+           func (r FfiResult) toC(alloc *allocator) C.FfiResult {
+           	var c C.FfiResult
+           	c.error_code = C.int32_t(r.ErrorCode)
+           	c.description = alloc.string(r.Description)
+           	return c
+           }
+
+           // FetchResult is the result of Fetch.
+           type FetchResult struct {
+           	Name string
+           	Data []uint8
+           	Err  error
+           }
+
+           //export backendFetchCallback
+           func backendFetchCallback(userData unsafe.Pointer, result *C.FfiResult, name *C.char, data *C.uint8_t, dataLen C.uintptr_t) {
+           	results := unregister(userData).(chan FetchResult)
+           	if err := resultError(result); err != nil {
+           		results <- FetchResult{Err: err}
+           		return
+           	}
+           	results <- FetchResult{
+           		Name: C.GoString(name),
+           		Data: goSlice(data, int(dataLen), func(v C.uint8_t) uint8 { return uint8(v) }),
+           	}
+           }
+
+           // Fetches the entry.
+           //
+           // The result is sent to the returned channel once the native library calls back.
+           func FetchAsync(app *App, key string) <-chan FetchResult {
+           	alloc := &allocator{}
+           	defer alloc.free()
+           	results := make(chan FetchResult, 1)
+           	C.fetch((*C.App)(app), alloc.string(key), register(results), (*[0]byte)(C.backendFetchCallback))
+           	return results
+           }
+
+           // Fetches the entry.
+           func Fetch(app *App, key string) (string, []uint8, error) {
+           	reply := <-FetchAsync(app, key)
+           	return reply.Name, reply.Data, reply.Err
+           }
+
+           //export backendFlushCallback
+           func backendFlushCallback(userData unsafe.Pointer, result *C.FfiResult) {
+           	results := unregister(userData).(chan error)
+           	results <- resultError(result)
+           }
+
+           // The result is sent to the returned channel once the native library calls back.
+           func FlushAsync(app *App) <-chan error {
+           	results := make(chan error, 1)
+           	C.flush((*C.App)(app), register(results), (*[0]byte)(C.backendFlushCallback))
+           	return results
+           }
+
+           func Flush(app *App) error {
+           	return <-FlushAsync(app)
+           }
+
+           // Error is the error reported by the native library through a FfiResult.
+           type Error struct {
+           	Code        int64
+           	Description string
+           }
+
+           func (e *Error) Error() string {
+           	return fmt.Sprintf("%s (error %d)", e.Description, e.Code)
+           }
+
+           // resultError returns the error reported by the result, or nil if the call succeeded.
+           func resultError(result *C.FfiResult) error {
+           	if result == nil || result.error_code == 0 {
+           		return nil
+           	}
+           	return &Error{Code: int64(result.error_code), Description: C.GoString(result.description)}
+           }
+
+           // registry keeps the channels of the pending calls, found through the user data passed to the
+           // native library, which can't hold Go pointers.
+           var registry = struct {
+           	sync.Mutex
+           	channels map[unsafe.Pointer]any
+           }{channels: make(map[unsafe.Pointer]any)}
+
+           // register returns the user data to find the channel with.
+           func register(channel any) unsafe.Pointer {
+           	// A byte of C memory gives a unique address.
+           	userData := C.malloc(1)
+           	registry.Lock()
+           	registry.channels[userData] = channel
+           	registry.Unlock()
+           	return userData
+           }
+
+           // unregister returns the channel of the user data, which is freed.
+           func unregister(userData unsafe.Pointer) any {
+           	registry.Lock()
+           	channel := registry.channels[userData]
+           	delete(registry.channels, userData)
+           	registry.Unlock()
+           	C.free(userData)
+           	return channel
+           }
+
+           // allocator owns the C memory of the converted arguments until the call returns.
+           type allocator struct {
+           	pointers []unsafe.Pointer
+           }
+
+           func (a *allocator) alloc(size uintptr) unsafe.Pointer {
+           	pointer := C.malloc(C.size_t(size))
+           	a.pointers = append(a.pointers, pointer)
+           	return pointer
+           }
+
+           func (a *allocator) string(value string) *C.char {
+           	pointer := C.CString(value)
+           	a.pointers = append(a.pointers, unsafe.Pointer(pointer))
+           	return pointer
+           }
+
+           func (a *allocator) free() {
+           	for _, pointer := range a.pointers {
+           		C.free(pointer)
+           	}
+           }
+
+           // goSlice copies the C array into a slice, converting the elements.
+           func goSlice[N, G any](data *N, length int, convert func(N) G) []G {
+           	if data == nil {
+           		return nil
+           	}
+           	slice := make([]G, length)
+           	for i, value := range unsafe.Slice(data, length) {
+           		slice[i] = convert(value)
+           	}
+           	return slice
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
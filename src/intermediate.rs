@@ -2,7 +2,8 @@
 //! and the target language code.
 
 use crate::common;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
 use std::ops::Deref;
 use syn::export::ToTokens;
 use unwrap::unwrap;
@@ -50,6 +51,12 @@ impl Type {
             false
         }
     }
+
+    /// Whether the type is marshalled as a value type, so that it can overlap other fields in
+    /// an explicit struct layout.
+    pub fn is_blittable(&self) -> bool {
+        !matches!(*self, Type::String | Type::Array(..) | Type::Function(..))
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -66,6 +73,10 @@ pub enum ArraySize {
 pub struct Function {
     pub inputs: Vec<(String, Type)>,
     pub output: Type,
+    /// Names of the inputs which must not be null (references, `NonNull` and `Box`).
+    pub non_null: Vec<String>,
+    /// Names of the inputs pointing to data which isn't modified (`*const T` and `&T`).
+    pub const_pointers: Vec<String>,
 }
 
 pub struct Snippet<T> {
@@ -89,11 +100,20 @@ pub enum ConstValue {
     Struct(String, BTreeMap<String, ConstValue>),
 }
 
+pub struct Static {
+    pub ty: Type,
+    pub mutable: bool,
+}
+
 pub struct Struct {
     pub fields: Vec<StructField>,
+    /// Field packing from `#[repr(packed)]`.
+    pub pack: Option<u64>,
+    /// All fields start at offset 0, as in a C union.
+    pub union: bool,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct StructField {
     pub docs: String,
     pub name: String,
@@ -103,6 +123,8 @@ pub struct StructField {
 
 pub struct Enum {
     pub variants: Vec<EnumVariant>,
+    /// Underlying integer type from `#[repr(u8)]` etc.
+    pub ty: Option<Type>,
 }
 
 pub struct EnumVariant {
@@ -114,11 +136,23 @@ pub struct EnumVariant {
 pub fn transform_type(input: &syn::Type) -> Option<Type> {
     match input {
         syn::Type::Array(ref ty) => transform_array(ty, &ty.len),
+        // `Option<extern fn>` is a nullable function pointer. Delegates are nullable already.
+        syn::Type::Path(..) if common::nullable_fn_ptr(input).is_some() => {
+            transform_function_from_type(unwrap!(common::nullable_fn_ptr(input)))
+                .map(|fun| Type::Function(Box::new(fun)))
+        }
+        // `&'static` references are only used by constants, which are emitted by value.
+        syn::Type::Reference(syn::TypeReference {
+            lifetime: Some(ref lifetime),
+            ref elem,
+            ..
+        }) if lifetime.ident == "static" => transform_reference(lifetime, elem.deref()),
+        // References, `NonNull` and `Box` are passed as pointers.
+        syn::Type::Reference(..) | syn::Type::Path(..) if common::safe_ptr(input).is_some() => {
+            transform_pointer(&unwrap!(common::safe_ptr(input)).to_raw())
+        }
         syn::Type::Path(ref path) => transform_path(path),
         syn::Type::Ptr(ref ptr) => transform_pointer(ptr),
-        syn::Type::Reference(ref rawptr) => {
-            transform_reference(&unwrap!(rawptr.to_owned().lifetime), rawptr.elem.deref())
-        }
         syn::Type::BareFn(ref bare_fn) => {
             transform_function_from_type(&bare_fn).map(|fun| Type::Function(Box::new(fun)))
         }
@@ -174,7 +208,12 @@ pub fn transform_function_from_type(decl: &syn::TypeBareFn) -> Option<Function>
         }
     }
 
-    Some(Function { inputs, output })
+    Some(Function {
+        inputs,
+        output,
+        non_null: Vec::new(),
+        const_pointers: Vec::new(),
+    })
 }
 pub fn transform_function(func: syn::FnDecl) -> Option<Function> {
     let output = match func.output {
@@ -223,7 +262,32 @@ pub fn transform_function(func: syn::FnDecl) -> Option<Function> {
             break;
         }
     }
-    Some(Function { inputs, output })
+    let non_null = func
+        .inputs
+        .iter()
+        .filter_map(common::transform_fnarg_to_argcap)
+        .filter(|arg| common::safe_ptr(&arg.ty).is_some_and(|ptr| !ptr.nullable))
+        .filter_map(|arg| common::take_out_pat(&arg.pat))
+        .map(|pat| pat.ident.to_string())
+        .collect();
+    let const_pointers = func
+        .inputs
+        .iter()
+        .filter_map(common::transform_fnarg_to_argcap)
+        .filter(|arg| match arg.ty {
+            syn::Type::Ptr(ref ptr) => ptr.const_token.is_some(),
+            ref ty => common::safe_ptr(ty).is_some_and(|ptr| !ptr.is_mutable()),
+        })
+        .filter_map(|arg| common::take_out_pat(&arg.pat))
+        .map(|pat| pat.ident.to_string())
+        .collect();
+
+    Some(Function {
+        inputs,
+        output,
+        non_null,
+        const_pointers,
+    })
 }
 
 pub fn transform_function_param_from_type(fnarg: &syn::BareFnArg) -> Option<(String, Type)> {
@@ -279,12 +343,15 @@ pub fn transform_enum(variants: &[syn::Variant]) -> Option<Enum> {
         })
         .collect();
 
-    variants.map(|variants| Enum { variants })
+    variants.map(|variants| Enum { variants, ty: None })
 }
 
-pub fn transform_struct(fields: syn::Fields) -> Option<Struct> {
+pub fn transform_struct<'a, I>(fields: I) -> Option<Struct>
+where
+    I: IntoIterator<Item = &'a syn::Field>,
+{
     let fields: Option<Vec<_>> = fields
-        .iter()
+        .into_iter()
         .map(|field| {
             let (_, docs) = common::parse_attr(&field.attrs[..], |_| true, retrieve_docstring);
             let name = unwrap!(field.to_owned().ident).to_string();
@@ -301,6 +368,8 @@ pub fn transform_struct(fields: syn::Fields) -> Option<Struct> {
 
     fields.map(|fields| Struct {
         fields: process_struct_fields(fields),
+        pack: None,
+        union: false,
     })
 }
 
@@ -348,6 +417,93 @@ pub fn extract_callback(ty: &Type) -> Option<&Function> {
     None
 }
 
+/// Returns the callback of a function which is turned into a future, promise or the like: the
+/// function takes the user data and a single callback, receiving the user data and a pointer
+/// to the `FfiResult` of the call. `is_declared` tells whether a type has bindings.
+pub fn async_callback(fun: &Function, is_declared: impl Fn(&str) -> bool) -> Option<&Function> {
+    let has_user_data = fun.inputs.iter().any(|(name, ty)| is_user_data(name, ty));
+    if !has_user_data || num_callbacks(&fun.inputs) != 1 || !is_declared("FfiResult") {
+        return None;
+    }
+
+    extract_first_callback(&fun.inputs).filter(|callback| match callback.inputs.get(1) {
+        Some((_, Type::Pointer(ty))) => matches!(**ty, Type::User(ref name) if name == "FfiResult"),
+        _ => false,
+    })
+}
+
+/// Calls `f` with `ty` and all the types it's made of.
+pub fn visit_type(ty: &Type, f: &mut dyn FnMut(&Type)) {
+    f(ty);
+    match *ty {
+        Type::Pointer(ref ty) | Type::Array(ref ty, _) => visit_type(ty, f),
+        Type::Function(ref fun) => {
+            for (_, ty) in &fun.inputs {
+                visit_type(ty, f);
+            }
+            visit_type(&fun.output, f);
+        }
+        _ => (),
+    }
+}
+
+/// Name of the parameter at `index`, made up for the unnamed ones, escaped from `keywords`.
+pub fn param_name(name: &str, index: usize, keywords: &[&str]) -> String {
+    if name.is_empty() {
+        format!("arg{}", index)
+    } else {
+        escape_keyword(name, keywords)
+    }
+}
+
+/// Appends an underscore to the name if it's one of the `keywords` of the target language.
+pub fn escape_keyword(name: &str, keywords: &[&str]) -> String {
+    if keywords.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Replaces the type aliases within `new_ty` by the types they stand for.
+pub fn resolve_alias(aliases: &HashMap<String, Type>, new_ty: &mut Type) {
+    let mut orig_new_ty = mem::replace(new_ty, Type::Unit);
+
+    match orig_new_ty {
+        Type::User(ref name) => {
+            if let Some(old_ty) = lookup_alias(aliases, name) {
+                *new_ty = old_ty.clone();
+                return;
+            }
+        }
+        Type::Pointer(ref mut ty) | Type::Array(ref mut ty, _) => {
+            resolve_alias(aliases, ty);
+        }
+        Type::Function(ref mut fun) => {
+            resolve_alias(aliases, &mut fun.output);
+            for &mut (_, ref mut input) in &mut fun.inputs {
+                resolve_alias(aliases, input);
+            }
+        }
+        _ => (),
+    }
+
+    *new_ty = orig_new_ty;
+}
+
+/// Looks up the type the alias `name` stands for, following aliases of aliases.
+pub fn lookup_alias<'a>(aliases: &'a HashMap<String, Type>, name: &str) -> Option<&'a Type> {
+    if let Some(ty) = aliases.get(name) {
+        if let Type::User(ref name) = *ty {
+            Some(lookup_alias(aliases, name).unwrap_or(ty))
+        } else {
+            Some(ty)
+        }
+    } else {
+        None
+    }
+}
+
 pub fn retrieve_docstring(attr: &syn::Attribute) -> Option<String> {
     common::retrieve_docstring(attr, "")
 }
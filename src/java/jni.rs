@@ -1,11 +1,11 @@
 //! Functions to generate JNI bindings
 
 use super::types::{callback_name, rust_ty_to_java};
-use super::{Context, Outputs};
+use super::{Context, EnumRepr, Outputs};
 use crate::common::{
     append_output, is_array_arg, is_array_arg_barefn, is_user_data_arg, is_user_data_arg_barefn,
-    take_out_ident_from_type, take_out_pat, transform_fnarg_to_argcap,
-    transform_fnarg_to_argcap_option,
+    nullable_fn_ptr, safe_ptr, take_out_ident_from_type, take_out_pat, transform_fnarg_to_argcap,
+    transform_fnarg_to_argcap_option, SafePtr, SafePtrKind,
 };
 use crate::struct_field::StructField;
 use inflector::Inflector;
@@ -23,15 +23,26 @@ fn to_jni_arg(arg: &syn::ArgCaptured, ty_name: &str) -> proc_macro2::TokenStream
     quote! { #pat: #ty_name }
 }
 
-fn transform_jni_arg(arg: &syn::ArgCaptured) -> proc_macro2::TokenStream {
+fn transform_jni_arg(arg: &syn::ArgCaptured, context: &Context) -> proc_macro2::TokenStream {
     match arg.ty {
         // Callback
         syn::Type::BareFn(ref _bare_fn) => to_jni_arg(arg, "JObject"),
+        syn::Type::Path(..) if nullable_fn_ptr(&arg.ty).is_some() => to_jni_arg(arg, "JObject"),
+
+        // References, `NonNull` and `Box` are passed like standard pointers.
+        syn::Type::Reference(..) | syn::Type::Path(..) if safe_ptr(&arg.ty).is_some() => {
+            let arg = syn::ArgCaptured {
+                ty: syn::Type::Ptr(unwrap!(safe_ptr(&arg.ty)).to_raw()),
+                ..arg.clone()
+            };
+            transform_jni_arg(&arg, context)
+        }
 
         // Plain old types.
         syn::Type::Path(ref path) => {
             let ty = unwrap!(path.path.segments.last()).into_value();
             let ty = ty.ident.to_string();
+            let ty = context.enum_repr(&ty).map(str::to_string).unwrap_or(ty);
 
             let jni_type = match ty.as_str() {
                 "c_char" | "u8" | "i8" => "jbyte",
@@ -67,7 +78,13 @@ fn transform_jni_arg(arg: &syn::ArgCaptured) -> proc_macro2::TokenStream {
 fn fully_qualified(ty: &str, context: &Context) -> String {
     match ty {
         "String" => "java/lang/String".to_string(),
-        ty => format!("{}/{}", context.namespace_model.replace(".", "/"), ty),
+        ty => {
+            let namespace = context
+                .provided_types
+                .get(ty)
+                .unwrap_or(&context.namespace_model);
+            format!("{}/{}", namespace.replace(".", "/"), ty)
+        }
     }
 }
 
@@ -85,12 +102,21 @@ fn rust_ty_to_signature(ty: &syn::Type, context: &Context) -> Option<JavaType> {
     match ty {
         // Callback
         syn::Type::BareFn(ref _bare_fn) => Some(JavaType::Object(From::from("java/lang/Object"))),
+        syn::Type::Path(..) if nullable_fn_ptr(ty).is_some() => {
+            Some(JavaType::Object(From::from("java/lang/Object")))
+        }
+
+        // References, `NonNull` and `Box`.
+        syn::Type::Reference(..) | syn::Type::Path(..) if safe_ptr(ty).is_some() => {
+            rust_ty_to_signature(unwrap!(safe_ptr(ty)).elem, context)
+        }
 
         // Plain old types.
         syn::Type::Path(ref path) => {
             let ty = unwrap!(path.path.segments.last()).into_value();
             let ty = ty.ident.to_string();
-            rust_ty_to_java(ty.as_str()).or_else(|| Some(lookup_object_type(ty.as_str(), context)))
+            let ty = context.enum_repr(&ty).unwrap_or(&ty);
+            rust_ty_to_java(ty).or_else(|| Some(lookup_object_type(ty, context)))
         }
 
         // Standard pointers.
@@ -109,6 +135,28 @@ fn rust_ty_to_signature(ty: &syn::Type, context: &Context) -> Option<JavaType> {
     }
 }
 
+/// Converts a JNI integer into a sized enum. Values which aren't the discriminant of any
+/// variant evaluate `invalid` instead.
+fn enum_from_int(
+    value: proc_macro2::TokenStream,
+    repr: &EnumRepr,
+    enum_ty: &syn::Type,
+    invalid: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let int = syn::Ident::new(&repr.int, Span::call_site());
+    let arms = repr.variants.iter().map(|(name, value)| {
+        let name = syn::Ident::new(name, Span::call_site());
+        let value = proc_macro2::Literal::i64_unsuffixed(*value);
+        quote! { #value => #enum_ty::#name, }
+    });
+    quote! {
+        match #value as #int {
+            #(#arms)*
+            _ => #invalid
+        }
+    }
+}
+
 struct JniArgResult {
     stmt: proc_macro2::TokenStream,
     call_args: Vec<proc_macro2::TokenStream>,
@@ -144,6 +192,100 @@ fn transform_struct_arg(arg_name: &str, arg_ty: &syn::Type) -> JniArgResult {
     JniArgResult { stmt, call_args }
 }
 
+/// Returns the name of the opaque type that a reference, `NonNull` or `Box` points to. Opaque
+/// types are passed as `long` handles instead of being converted from Java objects.
+pub fn opaque_pointee(ptr: &SafePtr) -> Option<String> {
+    take_out_ident_from_type(ptr.elem).filter(|ident| ident == "App" || ident == "Authenticator")
+}
+
+/// Converts a Java object into the pointee of a reference or `Box`. Non-null pointers throw
+/// `NullPointerException` for `null`, while nullable ones are given `None`.
+///
+/// The native function is given a copy of the Java object, so mutable references and `NonNull`
+/// are only supported for opaque types, whose handles are converted instead.
+fn transform_safe_ptr_arg(arg_name: &str, ptr: &SafePtr) -> JniArgResult {
+    if let Some(opaque) = opaque_pointee(ptr) {
+        return transform_opaque_safe_ptr_arg(arg_name, &opaque, ptr);
+    }
+
+    let null_msg = format!("{} must not be null", arg_name.to_camel_case());
+    let arg_name = syn::Ident::new(arg_name, Span::call_site());
+    let elem = ptr.elem;
+
+    // statements
+    let stmt = if ptr.nullable {
+        quote! {
+            let #arg_name = if #arg_name.is_null() {
+                None
+            } else {
+                Some(jni_unwrap!(#elem::from_java(&env, #arg_name)))
+            };
+        }
+    } else {
+        quote! {
+            if #arg_name.is_null() {
+                let _ = env.throw_new("java/lang/NullPointerException", #null_msg);
+                return;
+            }
+            let #arg_name = jni_unwrap!(#elem::from_java(&env, #arg_name));
+        }
+    };
+
+    // call arg value(s)
+    let call_arg = match (ptr.kind, ptr.nullable) {
+        (SafePtrKind::Ref, false) => quote! { &#arg_name },
+        (SafePtrKind::Box, false) => quote! { Box::new(#arg_name) },
+        (SafePtrKind::Ref, true) => quote! { #arg_name.as_ref() },
+        (SafePtrKind::Box, true) => quote! { #arg_name.map(Box::new) },
+        _ => unreachable!("mutable pointers to Java objects are rejected"),
+    };
+
+    JniArgResult {
+        stmt,
+        call_args: vec![call_arg],
+    }
+}
+
+/// Converts the handle of an opaque type into a reference, `NonNull` or `Box`. Non-null
+/// pointers throw `NullPointerException` for a `0` handle, while nullable ones are given `None`.
+fn transform_opaque_safe_ptr_arg(arg_name: &str, opaque: &str, ptr: &SafePtr) -> JniArgResult {
+    let null_msg = format!("{} must not be null", arg_name.to_camel_case());
+    let JniArgResult { stmt, .. } = transform_opaque_ptr(arg_name, opaque);
+    let arg_name = syn::Ident::new(arg_name, Span::call_site());
+
+    // statements
+    let stmt = if ptr.nullable {
+        stmt
+    } else {
+        quote! {
+            #stmt
+            if #arg_name.is_null() {
+                let _ = env.throw_new("java/lang/NullPointerException", #null_msg);
+                return;
+            }
+        }
+    };
+
+    // call arg value(s)
+    let call_arg = match (ptr.kind, ptr.nullable) {
+        (SafePtrKind::Ref, false) => quote! { &*#arg_name },
+        (SafePtrKind::RefMut, false) => quote! { &mut *#arg_name },
+        (SafePtrKind::NonNull, false) => quote! { ::std::ptr::NonNull::new_unchecked(#arg_name) },
+        (SafePtrKind::Box, false) => quote! { Box::from_raw(#arg_name) },
+        (SafePtrKind::Ref, true) => quote! { #arg_name.as_ref() },
+        (SafePtrKind::RefMut, true) => quote! { #arg_name.as_mut() },
+        (SafePtrKind::NonNull, true) => quote! { ::std::ptr::NonNull::new(#arg_name) },
+        (SafePtrKind::Box, true) => {
+            quote! { if #arg_name.is_null() { None } else { Some(Box::from_raw(#arg_name)) } }
+        }
+    };
+
+    JniArgResult {
+        stmt,
+        call_args: vec![call_arg],
+    }
+}
+
 fn transform_array_arg(arg_name: &str) -> JniArgResult {
     // statements
     let arg_name = syn::Ident::new(arg_name.to_string().as_str(), Span::call_site());
@@ -157,18 +299,32 @@ fn transform_array_arg(arg_name: &str) -> JniArgResult {
     JniArgResult { stmt, call_args }
 }
 
+/// Callbacks are given as their type, argument name and whether they are nullable
+/// (`Option<extern fn>`), in which case `None` is passed for a `null` Java callback.
 fn transform_callbacks_arg(
-    cb_idents: &[(&syn::TypeBareFn, syn::Ident)],
+    cb_idents: &[(&syn::TypeBareFn, syn::Ident, bool)],
     cb_base_name: &str,
 ) -> JniArgResult {
     // statements
     let cb_ids: Vec<syn::Ident> = cb_idents
         .iter()
-        .map(|&(_, ref ident)| ident.clone())
+        .map(|(_, ident, _)| ident.clone())
         .collect();
 
-    let stmt = quote! {
-        let ctx = gen_ctx!(env, #(#cb_ids),*);
+    // Without any callback to call there's no context to free, so none is created.
+    let cb_ids = &cb_ids;
+    let stmt = if cb_idents.iter().all(|&(_, _, nullable)| nullable) {
+        quote! {
+            let ctx = if #(#cb_ids.is_null())&&* {
+                ::std::ptr::null_mut()
+            } else {
+                gen_ctx!(env, #(#cb_ids),*)
+            };
+        }
+    } else {
+        quote! {
+            let ctx = gen_ctx!(env, #(#cb_ids),*);
+        }
     };
 
     // call arg value(s)
@@ -177,7 +333,7 @@ fn transform_callbacks_arg(
     let call_args = cb_idents
         .iter()
         .enumerate()
-        .map(|(idx, _)| {
+        .map(|(idx, &(_, ref ident, nullable))| {
             let cb_fn = if multi_callback {
                 syn::Ident::new(
                     format!("{}_{}", cb_base_name, idx).to_string().as_str(),
@@ -186,7 +342,11 @@ fn transform_callbacks_arg(
             } else {
                 syn::Ident::new(cb_base_name, Span::call_site())
             };
-            quote! { #cb_fn }
+            if nullable {
+                quote! { if #ident.is_null() { None } else { Some(#cb_fn) } }
+            } else {
+                quote! { #cb_fn }
+            }
         })
         .collect();
 
@@ -216,15 +376,7 @@ pub fn generate_jni_function(
     context: &mut Context,
     outputs: &mut Outputs,
 ) -> String {
-    let func_name = syn::Ident::new(
-        format!(
-            "Java_{}_NativeBindings_{}",
-            context.namespace.replace("_", "_1").replace(".", "_"),
-            func_name
-        )
-        .as_str(),
-        Span::call_site(),
-    );
+    let func_name = jni_function_name(func_name, context);
     let native_name_str = native_name;
     let native_name = syn::Ident::new(native_name, Span::call_site());
 
@@ -256,6 +408,22 @@ pub fn generate_jni_function(
                     callbacks.push((
                         bare_fn,
                         syn::Ident::new(arg_name.as_str(), Span::call_site()),
+                        false,
+                    ));
+                    None
+                }
+
+                // References, `NonNull` and `Box`
+                ref ty if safe_ptr(ty).is_some() => {
+                    Some(transform_safe_ptr_arg(&arg_name, &unwrap!(safe_ptr(ty))))
+                }
+
+                // Nullable callback
+                ref ty if nullable_fn_ptr(ty).is_some() => {
+                    callbacks.push((
+                        unwrap!(nullable_fn_ptr(ty)),
+                        syn::Ident::new(arg_name.as_str(), Span::call_site()),
+                        true,
                     ));
                     None
                 }
@@ -277,9 +445,26 @@ pub fn generate_jni_function(
                 // Native types and others
                 ref native_ty => {
                     let id = syn::Ident::new(arg_name.as_str(), Span::call_site());
+                    let call_arg = match take_out_ident_from_type(native_ty)
+                        .and_then(|ty| context.enum_reprs.get(&ty))
+                    {
+                        Some(repr) => {
+                            let msg = format!(
+                                "{} is not a valid {}",
+                                arg_name.to_camel_case(),
+                                quote!(#native_ty)
+                            );
+                            let invalid = quote! {{
+                                let _ = env.throw_new("java/lang/IllegalArgumentException", #msg);
+                                return;
+                            }};
+                            enum_from_int(quote! { #id }, repr, native_ty, invalid)
+                        }
+                        None => quote! { #id as #native_ty },
+                    };
                     Some(JniArgResult {
                         stmt: quote! {},
-                        call_args: vec![quote! { #id as #native_ty }],
+                        call_args: vec![call_arg],
                     })
                 }
             }
@@ -290,14 +475,17 @@ pub fn generate_jni_function(
             stmts.push(jni_arg_res.stmt);
         }
 
-        jni_fn_inputs.push(transform_jni_arg(unwrap!(transform_fnarg_to_argcap(&arg))));
+        jni_fn_inputs.push(transform_jni_arg(
+            unwrap!(transform_fnarg_to_argcap(arg)),
+            context,
+        ));
     }
 
     if !callbacks.is_empty() {
         let cb_base_name = if callbacks.len() > 1 {
             format!("call_{}", native_name_str)
         } else {
-            let &(ref cb, _) = &callbacks[0];
+            let (cb, _, _) = &callbacks[0];
             let vec: Vec<_> = cb.inputs.iter().cloned().collect();
             format!("call_{}", unwrap!(callback_name(&vec.as_slice(), context)))
         };
@@ -312,7 +500,7 @@ pub fn generate_jni_function(
         // Generate extra callbacks for multi-callback functions
         let count = callbacks.len();
 
-        for (idx, &(ref cb, _)) in callbacks.iter().enumerate() {
+        for (idx, (cb, _, _)) in callbacks.iter().enumerate() {
             let full_cb_name = format!("call_{}_{}", native_name_str, idx);
             eprintln!("Generating JNI CB {}", full_cb_name);
 
@@ -352,6 +540,140 @@ pub fn generate_jni_function(
     output
 }
 
+/// Returns the name of the JNI function implementing a native method of `NativeBindings`.
+fn jni_function_name(func_name: &str, context: &Context) -> syn::Ident {
+    syn::Ident::new(
+        format!(
+            "Java_{}_NativeBindings_{}",
+            context.namespace.replace("_", "_1").replace(".", "_"),
+            func_name
+        )
+        .as_str(),
+        Span::call_site(),
+    )
+}
+
+fn primitive_to_jni_type(primitive: Primitive) -> &'static str {
+    match primitive {
+        Primitive::Boolean => "jboolean",
+        Primitive::Byte => "jbyte",
+        Primitive::Char => "jchar",
+        Primitive::Double => "jdouble",
+        Primitive::Float => "jfloat",
+        Primitive::Int => "jint",
+        Primitive::Long => "jlong",
+        Primitive::Short => "jshort",
+        Primitive::Void => "()",
+    }
+}
+
+/// Generates JNI functions reading an exported static and, for mutable statics of primitive
+/// types, writing it. `java_ty` is the Java type of the static.
+pub fn generate_jni_static(
+    item: &syn::ItemStatic,
+    java_ty: &JavaType,
+    getter: &str,
+    setter: Option<&str>,
+    context: &Context,
+) -> String {
+    let name = &item.ident;
+    let ty = &*item.ty;
+    let getter = jni_function_name(getter, context);
+    let mutability = &item.mutability;
+
+    let getter = match *java_ty {
+        JavaType::Primitive(primitive) => {
+            let jni_ty = syn::Ident::new(primitive_to_jni_type(primitive), Span::call_site());
+            quote! {
+                #[no_mangle]
+                pub unsafe extern "system" fn #getter(_env: JNIEnv, _class: JClass) -> #jni_ty {
+                    #name as #jni_ty
+                }
+            }
+        }
+        _ => {
+            let value = if let JavaType::Object(ref obj) = *java_ty {
+                if obj == "String" {
+                    quote! {
+                        if #name.is_null() {
+                            return JObject::null().into_inner();
+                        }
+                    }
+                } else {
+                    quote! {}
+                }
+            } else {
+                quote! {}
+            };
+            quote! {
+                #[no_mangle]
+                pub unsafe extern "system" fn #getter(env: JNIEnv, _class: JClass) -> jobject {
+                    #value
+                    match #name.to_java(&env) {
+                        Ok(value) => JObject::from(value).into_inner(),
+                        Err(err) => {
+                            let _ = env.throw(format!("{:?}", err));
+                            JObject::null().into_inner()
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let setter = match (setter, java_ty) {
+        (Some(setter), &JavaType::Primitive(primitive)) => {
+            let setter = jni_function_name(setter, context);
+            let jni_ty = syn::Ident::new(primitive_to_jni_type(primitive), Span::call_site());
+            let repr =
+                take_out_ident_from_type(ty).and_then(|ident| context.enum_reprs.get(&ident));
+            // The environment is only used to reject invalid enum values.
+            let env = if repr.is_some() {
+                quote! { env }
+            } else {
+                quote! { _env }
+            };
+            let value = match repr {
+                Some(repr) => {
+                    let msg = format!("value is not a valid {}", quote!(#ty));
+                    let invalid = quote! {{
+                        let _ = env.throw_new("java/lang/IllegalArgumentException", #msg);
+                        return;
+                    }};
+                    enum_from_int(quote! { value }, repr, ty, invalid)
+                }
+                None if primitive == Primitive::Boolean => quote! { value != 0 },
+                None => quote! { value as #ty },
+            };
+            quote! {
+                #[no_mangle]
+                pub unsafe extern "system" fn #setter(
+                    #env: JNIEnv,
+                    _class: JClass,
+                    value: #jni_ty
+                ) {
+                    #name = #value;
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
+    let lib_name = &context.lib_name;
+    let tokens = quote! {
+        #[link(name = #lib_name)]
+        extern {
+            static #mutability #name: #ty;
+        }
+
+        #getter
+
+        #setter
+    };
+
+    tokens.to_string()
+}
+
 struct JniCallback {
     // Native function call parameters
     args: Vec<proc_macro2::TokenStream>,
@@ -539,17 +861,28 @@ pub fn generate_jni_callback(cb: &syn::TypeBareFn, cb_name: &str, context: &mut
     tokens.to_string()
 }
 
-fn generate_struct_to_java(
-    struct_ident: &syn::Ident,
-    java_class_name: &str,
+/// Fields of packed structs can't be borrowed, so they have to be copied out first.
+fn struct_field_value(field: proc_macro2::TokenStream, packed: bool) -> proc_macro2::TokenStream {
+    if packed {
+        quote! { { self.#field } }
+    } else {
+        quote! { self.#field }
+    }
+}
+
+/// Generates statements setting the fields of the Java `output` object. `value_of` returns
+/// the expression reading the value of a Rust field.
+fn generate_fields_to_java(
     fields: &[StructField],
+    value_of: &dyn Fn(&syn::Ident) -> proc_macro2::TokenStream,
     context: &Context,
-) -> proc_macro2::TokenStream {
+) -> Vec<proc_macro2::TokenStream> {
     let mut stmts = Vec::new();
 
     for f in fields {
         let field_name_str = f.name();
         let field_name = syn::Ident::new(field_name_str.as_str(), Span::call_site());
+        let field_value = value_of(&field_name);
         let java_field_name = field_name_str.to_camel_case();
 
         let stmt = match *f {
@@ -561,6 +894,7 @@ fn generate_struct_to_java(
                 if let syn::Type::Ptr(ref ptr) = field.ty {
                     let len_field_ident =
                         syn::Ident::new(len_field.clone().as_str(), Span::call_site());
+                    let len_value = value_of(&len_field_ident);
                     let len_field = len_field.to_camel_case();
                     let ty = &*ptr.elem;
                     let ty_str = format!("{}", quote! {#ty});
@@ -589,7 +923,7 @@ fn generate_struct_to_java(
                                 output,
                                 #len_field,
                                 "J",
-                                self.#len_field_ident.to_java(env)?.into()
+                                #len_value.to_java(env)?.into()
                             )?;
                         }
                     } else {
@@ -637,7 +971,7 @@ fn generate_struct_to_java(
                                 output,
                                 #len_field,
                                 "J",
-                                self.#len_field_ident.to_java(env)?.into()
+                                #len_value.to_java(env)?.into()
                             )?;
                         }
                     }
@@ -648,7 +982,7 @@ fn generate_struct_to_java(
             StructField::String(ref _f) => {
                 quote! {
                     if !self.#field_name.is_null() {
-                        let #field_name: JObject = self.#field_name.to_java(env)?.into();
+                        let #field_name: JObject = #field_value.to_java(env)?.into();
                         env.set_field(
                             output,
                             #java_field_name,
@@ -663,7 +997,7 @@ fn generate_struct_to_java(
                 let signature = format!("{}", unwrap!(rust_ty_to_signature(&*ty.elem, context)));
 
                 quote! {
-                    let jobj = self.#field_name.to_java(env)?;
+                    let jobj = #field_value.to_java(env)?;
                     env.set_field(
                         output,
                         #field_name_str,
@@ -681,6 +1015,14 @@ fn generate_struct_to_java(
                 syn::Type::Path(ref path) => {
                     let ty = unwrap!(path.path.segments.last()).into_value();
                     let ty = ty.ident.to_owned().to_string();
+                    // Sized enums are converted into their integer type first
+                    let (ty, field_value) = match context.enum_repr(&ty) {
+                        Some(int) => {
+                            let int_ty = syn::Ident::new(int, Span::call_site());
+                            (int.to_string(), quote! { (#field_value as #int_ty) })
+                        }
+                        None => (ty, field_value),
+                    };
                     let conv = rust_ty_to_java(ty.as_str())
                         .unwrap_or_else(|| lookup_object_type(ty.as_str(), context));
                     let signature = format!("{}", conv);
@@ -692,7 +1034,7 @@ fn generate_struct_to_java(
                         quote! {}
                     };
                     quote! {
-                        let jobj = self.#field_name.to_java(env)?;
+                        let jobj = #field_value.to_java(env)?;
                         env.set_field(
                             output,
                             #java_field_name,
@@ -709,6 +1051,28 @@ fn generate_struct_to_java(
         stmts.push(stmt);
     }
 
+    stmts
+}
+
+fn generate_struct_to_java(
+    struct_ident: &syn::Ident,
+    java_class_name: &str,
+    fields: &[StructField],
+    packed: bool,
+    tuple: bool,
+    context: &Context,
+) -> proc_macro2::TokenStream {
+    let value_of = |field: &syn::Ident| {
+        if tuple {
+            // Fields of tuple structs are accessed by their position
+            let idx = unwrap!(fields.iter().position(|f| *field == f.name()));
+            let idx = syn::Index::from(idx);
+            struct_field_value(quote! { #idx }, packed)
+        } else {
+            struct_field_value(quote! { #field }, packed)
+        }
+    };
+    let stmts = generate_fields_to_java(fields, &value_of, context);
     let fully_qualified_name = fully_qualified(java_class_name, context);
 
     quote! {
@@ -723,11 +1087,12 @@ fn generate_struct_to_java(
     }
 }
 
-fn generate_struct_from_java(
-    struct_ident: &syn::Ident,
+/// Generates statements reading the fields of the Java `input` object into local variables.
+/// Returns the names of the variables along with the statements.
+fn generate_fields_from_java(
     fields: &[StructField],
     context: &Context,
-) -> proc_macro2::TokenStream {
+) -> (Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>) {
     let mut fields_values = Vec::new();
     let mut conversions = Vec::new();
 
@@ -855,6 +1220,10 @@ fn generate_struct_from_java(
                         let ty = unwrap!(path.path.segments.last()).into_value();
 
                         let mut ty = ty.ident.to_owned().to_string();
+                        let enum_repr = context.enum_reprs.get(&ty);
+                        if let Some(repr) = enum_repr {
+                            ty = repr.int.clone();
+                        }
 
                         if let Some(rewrite_ty) = context.type_map.get(ty.as_str()) {
                             // Rewrite type (it could be e.g. a handle)
@@ -878,12 +1247,20 @@ fn generate_struct_from_java(
                         };
 
                         if let Some((signature, unwrap_method)) = conv {
+                            let value = quote! {
+                                env.get_field(input, #java_field_name, #signature)?.#unwrap_method?
+                            };
+                            let value = match enum_repr {
+                                Some(repr) => {
+                                    let enum_ty = &f.ty;
+                                    let msg = format!("unknown value of {}", quote!(#enum_ty));
+                                    let invalid = quote! { return Err(JniError::from(#msg)) };
+                                    enum_from_int(value, repr, &f.ty, invalid)
+                                }
+                                None => quote! { #value as #rust_ty },
+                            };
                             quote! {
-                                let #field_name = env.get_field(
-                                    input,
-                                    #java_field_name,
-                                    #signature
-                                )?.#unwrap_method? as #rust_ty;
+                                let #field_name = #value;
                             }
                         } else {
                             let obj_sig = format!("{}", lookup_object_type(ty.as_str(), context));
@@ -902,14 +1279,28 @@ fn generate_struct_from_java(
         conversions.push(conv);
     }
 
+    (fields_values, conversions)
+}
+
+fn generate_struct_from_java(
+    struct_ident: &syn::Ident,
+    fields: &[StructField],
+    tuple: bool,
+    context: &Context,
+) -> proc_macro2::TokenStream {
+    let (fields_values, conversions) = generate_fields_from_java(fields, context);
+    let value = if tuple {
+        quote! { (#(#fields_values),*) }
+    } else {
+        quote! { { #(#fields_values),* } }
+    };
+
     quote! {
         impl<'a> FromJava<JObject<'a>> for #struct_ident {
             fn from_java(env: &JNIEnv, input: JObject) -> Result<Self, JniError> {
                 #(#conversions)*
 
-                Ok(#struct_ident {
-                    #(#fields_values),*
-                })
+                Ok(#struct_ident #value)
             }
         }
     }
@@ -920,12 +1311,21 @@ pub fn generate_struct(
     fields: &[StructField],
     native_name: &str,
     java_class_name: &str,
+    packed: bool,
+    tuple: bool,
     context: &Context,
 ) -> String {
     let struct_ident = syn::Ident::new(native_name, Span::call_site());
 
-    let from_java = generate_struct_from_java(&struct_ident, fields, context);
-    let to_java = generate_struct_to_java(&struct_ident, java_class_name, fields, context);
+    let from_java = generate_struct_from_java(&struct_ident, fields, tuple, context);
+    let to_java = generate_struct_to_java(
+        &struct_ident,
+        java_class_name,
+        fields,
+        packed,
+        tuple,
+        context,
+    );
 
     let tokens = quote! {
         #from_java
@@ -936,11 +1336,212 @@ pub fn generate_struct(
     tokens.to_string()
 }
 
+/// Generates JNI binding for a `#[repr(transparent)]` struct. The Java class holds the wrapped
+/// primitive in its `value` field and is constructed through its `(value)` constructor.
+pub fn generate_transparent_struct(
+    field: &syn::Field,
+    native_name: &str,
+    java_class_name: &str,
+    context: &Context,
+) -> String {
+    let struct_ident = syn::Ident::new(native_name, Span::call_site());
+    let fully_qualified_name = fully_qualified(java_class_name, context);
+    let signature = format!("({})V", unwrap!(rust_ty_to_signature(&field.ty, context)));
+
+    let value_field = syn::Field {
+        ident: Some(syn::Ident::new("value", Span::call_site())),
+        ..field.clone()
+    };
+    let (_, conversions) =
+        generate_fields_from_java(&[StructField::Primitive(value_field)], context);
+    let (value, member) = match field.ident {
+        Some(ref ident) => (quote! { { #ident: value } }, quote! { #ident }),
+        None => (quote! { (value) }, quote! { 0 }),
+    };
+
+    let tokens = quote! {
+        impl<'a> FromJava<JObject<'a>> for #struct_ident {
+            fn from_java(env: &JNIEnv, input: JObject) -> Result<Self, JniError> {
+                #(#conversions)*
+
+                Ok(#struct_ident #value)
+            }
+        }
+
+        impl<'a> ToJava<'a, JObject<'a>> for #struct_ident {
+            fn to_java(&self, env: &'a JNIEnv) -> Result<JObject<'a>, JniError> {
+                let cls = unsafe { find_class(env, #fully_qualified_name)? };
+                let value = self.#member.to_java(env)?;
+                let output = env.new_object(&cls, #signature, &[value.into()])?;
+                Ok(output)
+            }
+        }
+    };
+
+    tokens.to_string()
+}
+
+/// Generates JNI union binding based on a native union. Every field of the union is copied into
+/// the Java object, while the native union is initialised from the first field only.
+pub fn generate_union(
+    fields: &[StructField],
+    native_name: &str,
+    java_class_name: &str,
+    context: &Context,
+) -> String {
+    let union_ident = syn::Ident::new(native_name, Span::call_site());
+    let fully_qualified_name = fully_qualified(java_class_name, context);
+
+    // Only the field set last on the Java side holds a value, so it's the one converted.
+    let arms = (0..fields.len()).map(|idx| {
+        let (fields_values, conversions) = generate_fields_from_java(&fields[idx..=idx], context);
+        let idx = idx as i32;
+        quote! {
+            #idx => {
+                #(#conversions)*
+
+                Ok(#union_ident {
+                    #(#fields_values),*
+                })
+            }
+        }
+    });
+    let unknown_field = format!("unknown active field of {}", java_class_name);
+
+    let stmts =
+        generate_fields_to_java(fields, &|field| quote! { unsafe { self.#field } }, context);
+    // The largest field holds all the bytes of the native union, so it's made the active one.
+    let sizes = fields.iter().map(|field| {
+        let ty = &field.struct_field().ty;
+        quote! { ::std::mem::size_of::<#ty>() }
+    });
+
+    let tokens = quote! {
+        impl<'a> FromJava<JObject<'a>> for #union_ident {
+            fn from_java(env: &JNIEnv, input: JObject) -> Result<Self, JniError> {
+                match env.get_field(input, "activeField", "I")?.i()? {
+                    #(#arms)*
+                    _ => Err(JniError::from(#unknown_field)),
+                }
+            }
+        }
+
+        impl<'a> ToJava<'a, JObject<'a>> for #union_ident {
+            fn to_java(&self, env: &'a JNIEnv) -> Result<JObject<'a>, JniError> {
+                let cls = unsafe { find_class(env, #fully_qualified_name)? };
+                let output = env.new_object(&cls, "()V", &[])?;
+                #(#stmts)*
+
+                let sizes = [#(#sizes),*];
+                let active = (0..sizes.len()).fold(0, |largest, idx| {
+                    if sizes[idx] > sizes[largest] { idx } else { largest }
+                });
+                env.set_field(output, "activeField", "I", (active as i32).to_java(env)?.into())?;
+                Ok(output)
+            }
+        }
+    };
+
+    tokens.to_string()
+}
+
+/// A variant of a data-carrying enum.
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<StructField>,
+    /// Whether the variant is tuple-like (`Foo(u32)`) rather than struct-like (`Foo { x: u32 }`).
+    pub tuple: bool,
+}
+
+/// Generates JNI bindings for a data-carrying enum. Each variant is converted into a nested Java
+/// class (`Enum$Variant`) extending the class of the enum.
+pub fn generate_enum(
+    variants: &[EnumVariant],
+    native_name: &str,
+    java_class_name: &str,
+    context: &Context,
+) -> String {
+    let enum_ident = syn::Ident::new(native_name, Span::call_site());
+
+    let mut from_java = Vec::new();
+    let mut to_java = Vec::new();
+
+    for variant in variants {
+        let variant_ident = syn::Ident::new(&variant.name, Span::call_site());
+        let fully_qualified_name =
+            fully_qualified(&format!("{}${}", java_class_name, variant.name), context);
+
+        let field_idents: Vec<_> = variant
+            .fields
+            .iter()
+            .map(|field| syn::Ident::new(&field.name(), Span::call_site()))
+            .collect();
+        let field_idents = &field_idents;
+        let (pattern, value) = if variant.fields.is_empty() {
+            (quote! {}, quote! {})
+        } else if variant.tuple {
+            (
+                quote! { (#(ref #field_idents),*) },
+                quote! { (#(#field_idents),*) },
+            )
+        } else {
+            (
+                quote! { { #(ref #field_idents),* } },
+                quote! { { #(#field_idents),* } },
+            )
+        };
+
+        let stmts =
+            generate_fields_to_java(&variant.fields, &|field| quote! { (*#field) }, context);
+        to_java.push(quote! {
+            #enum_ident::#variant_ident #pattern => {
+                let cls = unsafe { find_class(env, #fully_qualified_name)? };
+                let output = env.new_object(&cls, "()V", &[])?;
+                #(#stmts)*
+                Ok(output)
+            }
+        });
+
+        let (_, conversions) = generate_fields_from_java(&variant.fields, context);
+        from_java.push(quote! {
+            let cls = unsafe { find_class(env, #fully_qualified_name)? };
+            if env.is_instance_of(input, &cls)? {
+                #(#conversions)*
+                return Ok(#enum_ident::#variant_ident #value);
+            }
+        });
+    }
+
+    let unknown_variant = format!("unknown variant of {}", java_class_name);
+
+    let tokens = quote! {
+        impl<'a> FromJava<JObject<'a>> for #enum_ident {
+            fn from_java(env: &JNIEnv, input: JObject) -> Result<Self, JniError> {
+                #(#from_java)*
+                Err(JniError::from(#unknown_variant))
+            }
+        }
+
+        impl<'a> ToJava<'a, JObject<'a>> for #enum_ident {
+            // Length and capacity fields are only used together with the array fields
+            #[allow(unused_variables)]
+            fn to_java(&self, env: &'a JNIEnv) -> Result<JObject<'a>, JniError> {
+                match *self {
+                    #(#to_java)*
+                }
+            }
+        }
+    };
+
+    tokens.to_string()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{generate_callback, transform_jni_arg};
+    use super::{generate_callback, generate_struct_to_java, transform_jni_arg};
     use crate::java::jni::generate_jni_function;
-    use crate::java::Context;
+    use crate::java::{Context, EnumRepr};
+    use crate::struct_field::transform_struct_fields;
     use indoc::indoc;
     use std::collections::HashMap;
     use syn;
@@ -956,8 +1557,35 @@ mod tests {
         assert_eq!("let app = app as jlong ;", cb.stmts[0].to_string());
     }
 
+    #[test]
+    fn packed_struct_fields_are_copied() {
+        let ctx = Context::default();
+        let item: syn::ItemStruct = unwrap!(syn::parse_str(
+            "#[repr(C, packed)] pub struct Foo { pub bar: u64 }"
+        ));
+        let fields: Vec<_> = item.fields.iter().cloned().collect();
+        let fields = transform_struct_fields(&fields);
+        let ident = item.ident.clone();
+
+        let packed = generate_struct_to_java(&ident, "Foo", &fields, true, false, &ctx).to_string();
+        assert!(packed.contains("{ self . bar } . to_java ( env )"));
+
+        let unpacked =
+            generate_struct_to_java(&ident, "Foo", &fields, false, false, &ctx).to_string();
+        assert!(unpacked.contains("self . bar . to_java ( env )"));
+    }
+
     #[test]
     fn jni_arg_transformation() {
+        let mut ctx = Context::default();
+        let _ = ctx.enum_reprs.insert(
+            "Mode".to_string(),
+            EnumRepr {
+                int: "u8".to_string(),
+                variants: vec![("Read".to_string(), 0), ("Write".to_string(), 4)],
+            },
+        );
+
         let rust_to_jni = [
             // Primitive types
             ("x: c_char", "x : jbyte"),
@@ -968,12 +1596,16 @@ mod tests {
             ("x: u32", "x : jint"),
             ("x: libc::c_long", "x : jlong"),
             ("x: u64", "x : jlong"),
+            // Sized enums
+            ("x: Mode", "x : jbyte"),
             // String types
             ("x: *const c_char", "x : JString"),
             ("x: *mut c_char", "x : JString"),
             // Object types
             ("x: *mut Foo", "x : JObject"),
             ("x: *const Bar", "x : JObject"),
+            ("x: &Foo", "x : JObject"),
+            ("x: Option<Box<Bar>>", "x : JObject"),
             // Opaque pointers
             ("x: *mut App", "x : jlong"),
             ("x: *const App", "x : jlong"),
@@ -984,11 +1616,15 @@ mod tests {
                 "x: extern \"C\" fn(user_data: *const c_void)",
                 "x : JObject",
             ),
+            (
+                "x: Option<extern \"C\" fn(user_data: *const c_void)>",
+                "x : JObject",
+            ),
         ];
 
         for &(rust_code, expected_jni_code) in &rust_to_jni {
             let jni_code = match unwrap!(syn::parse_str(rust_code)) {
-                syn::FnArg::Captured(ref arg) => transform_jni_arg(arg),
+                syn::FnArg::Captured(ref arg) => transform_jni_arg(arg, &ctx),
                 x => panic!("unexpected parse result {:?}", x),
             };
             assert_eq!(
@@ -1048,6 +1684,112 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_gen_jni_function_nullable_callback() {
+        let mut ctx = Context::default();
+        let mut dummy_outputs = HashMap::new();
+        let dummy_func_str = indoc!(
+            "#[no_mangle]
+            pub unsafe extern \"C\" fn subscribe(
+                app: *mut App,
+                user_data: *mut c_void,
+                o_cb: Option<extern \"C\" fn(user_data: *mut c_void, result: *const FfiResult)>,
+            ) {}"
+        );
+        let expected_jni_str = "# [ no_mangle ] \
+        pub unsafe extern \"system\" fn Java_net_maidsafe_dummy_NativeBindings_subscribe ( \
+            env : JNIEnv , \
+            _class : JClass , \
+            app : jlong , \
+            o_cb : JObject \
+        ) { \
+        let app = app as * mut App ; \
+        let ctx = if o_cb . is_null ( ) { :: std :: ptr :: null_mut ( ) } \
+        else { gen_ctx ! ( env , o_cb ) } ; \
+        subscribe ( app , ctx , if o_cb . is_null ( ) { None } else { Some ( call_CallbackResult ) } ) ; }";
+
+        let file: syn::File = unwrap!(syn::parse_str(dummy_func_str));
+        for item in file.items {
+            if let syn::Item::Fn(ref func) = item {
+                let inputs: Vec<_> = func.decl.inputs.iter().cloned().collect();
+                let generated_jni_string = generate_jni_function(
+                    inputs.as_slice(),
+                    &func.attrs[..],
+                    "subscribe",
+                    "subscribe",
+                    &mut ctx,
+                    &mut dummy_outputs,
+                );
+                assert_eq!(expected_jni_str, &generated_jni_string);
+            }
+        }
+    }
+
+    #[test]
+    fn check_gen_jni_function_references_and_smart_pointers() {
+        let mut ctx = Context::default();
+        let mut dummy_outputs = HashMap::new();
+        let dummy_func_str = indoc!(
+            "#[no_mangle]
+            pub unsafe extern \"C\" fn update(
+                key: &Key,
+                app: &mut App,
+                parent: Option<&Entry>,
+                owner: Box<Owner>,
+                auth: Option<NonNull<Authenticator>>,
+            ) {}"
+        );
+        let expected_jni_str = "# [ no_mangle ] \
+        pub unsafe extern \"system\" fn Java_net_maidsafe_dummy_NativeBindings_update ( \
+            env : JNIEnv , \
+            _class : JClass , \
+            key : JObject , \
+            app : jlong , \
+            parent : JObject , \
+            owner : JObject , \
+            auth : jlong \
+        ) { \
+        if key . is_null ( ) { \
+            let _ = env . throw_new ( \"java/lang/NullPointerException\" , \"key must not be null\" ) ; \
+            return ; \
+        } \
+        let key = jni_unwrap ! ( Key :: from_java ( & env , key ) ) ; \
+        let app = app as * mut App ; \
+        if app . is_null ( ) { \
+            let _ = env . throw_new ( \"java/lang/NullPointerException\" , \"app must not be null\" ) ; \
+            return ; \
+        } \
+        let parent = if parent . is_null ( ) { \
+            None \
+        } else { \
+            Some ( jni_unwrap ! ( Entry :: from_java ( & env , parent ) ) ) \
+        } ; \
+        if owner . is_null ( ) { \
+            let _ = env . throw_new ( \"java/lang/NullPointerException\" , \"owner must not be null\" ) ; \
+            return ; \
+        } \
+        let owner = jni_unwrap ! ( Owner :: from_java ( & env , owner ) ) ; \
+        let auth = auth as * mut Authenticator ; \
+        update ( & key , & mut * app , parent . as_ref ( ) , Box :: new ( owner ) , \
+        :: std :: ptr :: NonNull :: new ( auth ) ) ; }";
+
+        let file: syn::File = unwrap!(syn::parse_str(dummy_func_str));
+        for item in file.items {
+            if let syn::Item::Fn(ref func) = item {
+                let inputs: Vec<_> = func.decl.inputs.iter().cloned().collect();
+                let generated_jni_string = generate_jni_function(
+                    inputs.as_slice(),
+                    &func.attrs[..],
+                    "update",
+                    "update",
+                    &mut ctx,
+                    &mut dummy_outputs,
+                );
+                assert_eq!(expected_jni_str, &generated_jni_string);
+            }
+        }
+    }
+
     #[test]
     fn check_gen_jni_function_primitive_and_opaque_types() {
         let func_name = "testDummyFuncName";
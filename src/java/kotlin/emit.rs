@@ -0,0 +1,344 @@
+//! Functions for emitting the Kotlin code.
+
+use super::{escape, kotlin_type, primitive_type, Arg, Property, Value, Wrapper};
+use crate::java::Context;
+use crate::output::IndentedWriter;
+use jni::signature::{JavaType, Primitive};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Emits a data class with a property for each field, which all have a default value so the
+/// class has the constructor without parameters used by the JNI glue code.
+pub fn emit_data_class(
+    class: &str,
+    docs: &str,
+    properties: &[Property],
+    context: &Context,
+) -> String {
+    let mut writer = IndentedWriter::new(4);
+    emitln!(writer, "package {}\n", context.namespace);
+    emit_docs(&mut writer, docs);
+
+    if properties.is_empty() {
+        emitln!(writer, "class {}", class);
+        return writer.into_inner();
+    }
+
+    emitln!(writer, "data class {}(", class);
+    writer.indent();
+    for (idx, property) in properties.iter().enumerate() {
+        emitln!(
+            writer,
+            "{} {}: {} = {}{}",
+            if property.mutable { "var" } else { "val" },
+            escape(&property.name),
+            kotlin_type(&property.ty),
+            default_value(&property.ty),
+            if idx + 1 < properties.len() { "," } else { "" }
+        );
+    }
+    writer.unindent();
+
+    // Data classes compare arrays by reference
+    if !properties.iter().any(|property| is_array(&property.ty)) {
+        emitln!(writer, ")");
+        return writer.into_inner();
+    }
+    emitln!(writer, ") {{");
+    writer.indent();
+
+    emitln!(writer, "override fun equals(other: Any?): Boolean {{");
+    writer.indent();
+    emitln!(writer, "if (this === other) return true");
+    emitln!(writer, "if (other !is {}) return false", class);
+    let comparisons: Vec<_> = properties
+        .iter()
+        .map(|property| {
+            let name = escape(&property.name);
+            if is_array(&property.ty) {
+                format!("{0}.contentEquals(other.{0})", name)
+            } else {
+                format!("{0} == other.{0}", name)
+            }
+        })
+        .collect();
+    emitln!(writer, "return {}", comparisons.join(" &&\n    "));
+    writer.unindent();
+    emitln!(writer, "}}\n");
+
+    emitln!(writer, "override fun hashCode(): Int {{");
+    writer.indent();
+    for (idx, property) in properties.iter().enumerate() {
+        let hash = format!(
+            "{}.{}()",
+            escape(&property.name),
+            if is_array(&property.ty) {
+                "contentHashCode"
+            } else {
+                "hashCode"
+            }
+        );
+        if idx == 0 {
+            emitln!(writer, "var result = {}", hash);
+        } else {
+            emitln!(writer, "result = 31 * result + {}", hash);
+        }
+    }
+    emitln!(writer, "return result");
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    writer.into_inner()
+}
+
+/// Emits an enum class holding the native value of each variant.
+pub fn emit_enum_class(
+    class: &str,
+    docs: &str,
+    primitive: Primitive,
+    variants: &[(String, i64, String)],
+    context: &Context,
+) -> String {
+    let ty = primitive_type(primitive);
+
+    let mut writer = IndentedWriter::new(4);
+    emitln!(writer, "package {}\n", context.namespace);
+    emit_docs(&mut writer, docs);
+    emitln!(writer, "enum class {}(val value: {}) {{", class, ty);
+    writer.indent();
+
+    for (idx, (name, value, docs)) in variants.iter().enumerate() {
+        emit_docs(&mut writer, docs);
+        emitln!(
+            writer,
+            "{}({}){}",
+            name,
+            literal(primitive, *value),
+            if idx + 1 < variants.len() { "," } else { ";" }
+        );
+    }
+    if variants.is_empty() {
+        emitln!(writer, ";");
+    }
+
+    emitln!(writer, "");
+    emitln!(writer, "companion object {{");
+    writer.indent();
+    emitln!(writer, "/**");
+    emitln!(writer, " * Returns the variant with the native `value`.");
+    emitln!(writer, " */");
+    emitln!(
+        writer,
+        "fun fromValue(value: {}): {} = values().first {{ it.value == value }}",
+        ty,
+        class
+    );
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    writer.into_inner()
+}
+
+/// Emits the `CoroutineBindings` object, holding the `suspend` wrappers of the functions.
+pub fn emit_coroutine_bindings(wrappers: &[Wrapper], context: &Context) -> String {
+    let mut writer = IndentedWriter::new(4);
+    emitln!(writer, "package {}\n", context.namespace);
+    // Callback interfaces are declared in the model namespace
+    if context.namespace_model != context.namespace {
+        emitln!(writer, "import {}.*", context.namespace_model);
+    }
+    emitln!(writer, "import kotlin.coroutines.resume");
+    emitln!(writer, "import kotlin.coroutines.resumeWithException");
+    emitln!(writer, "import kotlin.coroutines.suspendCoroutine\n");
+
+    emitln!(writer, "/**");
+    emitln!(
+        writer,
+        " * Suspending wrappers of the functions of `NativeBindings` calling back with an `FfiResult`,"
+    );
+    emitln!(
+        writer,
+        " * which throw an [FfiException] when the native library reports an error."
+    );
+    emitln!(writer, " */");
+    emitln!(writer, "object CoroutineBindings {{");
+    writer.indent();
+
+    for (idx, wrapper) in wrappers.iter().enumerate() {
+        if idx > 0 {
+            emitln!(writer, "");
+        }
+        emit_wrapper(&mut writer, wrapper);
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    writer.into_inner()
+}
+
+/// Emits the exception thrown by the `suspend` wrappers.
+pub fn emit_exception(context: &Context) -> String {
+    let mut writer = IndentedWriter::new(4);
+    emitln!(writer, "package {}\n", context.namespace);
+    emitln!(
+        writer,
+        "class FfiException(val code: Int, message: String?) : Exception(message)"
+    );
+
+    writer.into_inner()
+}
+
+fn emit_wrapper(writer: &mut IndentedWriter, wrapper: &Wrapper) {
+    let params: Vec<_> = wrapper
+        .args
+        .iter()
+        .filter_map(|arg| match *arg {
+            Arg::Value(ref value) => Some(format!("{}: {}", value.name, value.ty)),
+            Arg::Callback => None,
+        })
+        .collect();
+    let lambda_params: Vec<_> = Some("result")
+        .into_iter()
+        .chain(wrapper.values.iter().map(|value| value.name.as_str()))
+        .collect();
+    let args: Vec<_> = wrapper
+        .args
+        .iter()
+        .map(|arg| match *arg {
+            Arg::Value(ref value) if value.enumeration => format!("{}.value", value.name),
+            Arg::Value(ref value) => value.name.clone(),
+            Arg::Callback => format!("{} {{ {} ->", wrapper.callback, lambda_params.join(", ")),
+        })
+        .collect();
+
+    let results: Vec<_> = wrapper.values.iter().map(from_native).collect();
+    let types: Vec<_> = wrapper
+        .values
+        .iter()
+        .map(|value| value.ty.as_str())
+        .collect();
+    let (ret_ty, resumed) = match results.len() {
+        0 => (None, "Unit".to_string()),
+        1 => (Some(types[0].to_string()), results[0].clone()),
+        2 => (
+            Some(format!("Pair<{}>", types.join(", "))),
+            format!("Pair({})", results.join(", ")),
+        ),
+        _ => (
+            Some(format!("Triple<{}>", types.join(", "))),
+            format!("Triple({})", results.join(", ")),
+        ),
+    };
+
+    emit_docs(writer, &wrapper.docs);
+    match ret_ty {
+        Some(ty) => emitln!(
+            writer,
+            "suspend fun {}({}): {} = suspendCoroutine {{ continuation ->",
+            wrapper.name,
+            params.join(", "),
+            ty
+        ),
+        None => emitln!(
+            writer,
+            "suspend fun {}({}) = suspendCoroutine<Unit> {{ continuation ->",
+            wrapper.name,
+            params.join(", ")
+        ),
+    }
+    writer.indent();
+
+    // The arguments after the callback follow the body of its lambda
+    let callback = unwrap!(wrapper
+        .args
+        .iter()
+        .position(|arg| matches!(*arg, Arg::Callback)));
+    emitln!(
+        writer,
+        "NativeBindings.{}({}",
+        wrapper.name,
+        args[..=callback].join(", ")
+    );
+    writer.indent();
+    emitln!(writer, "if (result.errorCode != 0) {{");
+    emitln!(
+        writer,
+        "    continuation.resumeWithException(FfiException(result.errorCode, result.description))"
+    );
+    emitln!(writer, "}} else {{");
+    emitln!(writer, "    continuation.resume({})", resumed);
+    emitln!(writer, "}}");
+    writer.unindent();
+    let rest: String = args[callback + 1..]
+        .iter()
+        .map(|arg| format!(", {}", arg))
+        .collect();
+    emitln!(writer, "}}{})", rest);
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    if docs.is_empty() {
+        return;
+    }
+    emitln!(writer, "/**");
+    for line in docs.lines() {
+        emitln!(writer, "{}", line.replacen("///", " *", 1));
+    }
+    emitln!(writer, " */");
+}
+
+/// Returns the expression converting a value given to a callback into its Kotlin type.
+fn from_native(value: &Value) -> String {
+    if value.enumeration {
+        format!("{}.fromValue({})", value.ty, value.name)
+    } else {
+        value.name.clone()
+    }
+}
+
+fn default_value(ty: &JavaType) -> String {
+    match *ty {
+        JavaType::Primitive(Primitive::Boolean) => "false".to_string(),
+        JavaType::Primitive(Primitive::Char) => "'\\u0000'".to_string(),
+        JavaType::Primitive(Primitive::Float) => "0f".to_string(),
+        JavaType::Primitive(Primitive::Double) => "0.0".to_string(),
+        JavaType::Primitive(..) => "0".to_string(),
+        JavaType::Object(ref obj) if obj == "String" => "\"\"".to_string(),
+        JavaType::Object(ref obj) => format!("{}()", obj),
+        JavaType::Array(ref elem) => match **elem {
+            JavaType::Primitive(primitive) => format!("{}Array(0)", primitive_type(primitive)),
+            _ => "emptyArray()".to_string(),
+        },
+        JavaType::Method(..) => unreachable!(),
+    }
+}
+
+fn is_array(ty: &JavaType) -> bool {
+    matches!(*ty, JavaType::Array(..))
+}
+
+/// Returns the literal of an integer of a primitive type, converting the values out of its
+/// range (e.g. those of unsigned enums).
+fn literal(primitive: Primitive, value: i64) -> String {
+    let (min, max) = match primitive {
+        Primitive::Byte => (i64::from(i8::MIN), i64::from(i8::MAX)),
+        Primitive::Short => (i64::from(i16::MIN), i64::from(i16::MAX)),
+        Primitive::Int => (i64::from(i32::MIN), i64::from(i32::MAX)),
+        _ => (i64::MIN, i64::MAX),
+    };
+    if value < min || value > max {
+        format!("{}.to{}()", value, primitive_type(primitive))
+    } else {
+        value.to_string()
+    }
+}
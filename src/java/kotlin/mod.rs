@@ -0,0 +1,532 @@
+//! Functions for generating Kotlin bindings on top of the JNI glue code of `LangJava`.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use super::types::{callback_name, rust_to_java, rust_ty_to_java, struct_to_java_classname};
+use super::{transform_struct_into_class_fields, LangJava};
+use crate::common::{
+    self, check_no_mangle, is_array_arg, is_array_arg_barefn, is_result_arg_barefn,
+    is_user_data_arg, is_user_data_arg_barefn, nullable_fn_ptr, parse_attr, retrieve_docstring,
+    take_out_pat, transform_fnarg_to_argcap, FilterMode, Lang, Outputs,
+};
+use crate::struct_field::transform_struct_fields;
+use crate::Error;
+use inflector::Inflector;
+use jni::signature::{JavaType, Primitive};
+use std::collections::{BTreeSet, HashMap};
+use unwrap::unwrap;
+
+/// Generates the same JNI glue code, `NativeBindings` class and callback interfaces as
+/// `LangJava`, but with Kotlin data classes for the structures and enum classes for the sized
+/// enums. The functions calling back with an `FfiResult` are also wrapped into `suspend`
+/// functions of a `CoroutineBindings` object, throwing an `FfiException` on errors.
+pub struct LangKotlin {
+    java: LangJava,
+    functions: Vec<Function>,
+}
+
+struct Function {
+    name: String,
+    decl: syn::FnDecl,
+    docs: String,
+}
+
+/// A `suspend` wrapper of a function taking a callback.
+pub struct Wrapper {
+    name: String,
+    docs: String,
+    /// Arguments of the native function, with the callback in place.
+    args: Vec<Arg>,
+    /// Callback interface of the native function.
+    callback: String,
+    /// Values the callback is given after the result, which the wrapper returns.
+    values: Vec<Value>,
+}
+
+pub enum Arg {
+    Value(Value),
+    Callback,
+}
+
+pub struct Value {
+    name: String,
+    /// Kotlin type of the value.
+    ty: String,
+    /// Whether the value is a sized enum, passed to the native function as its integer value.
+    enumeration: bool,
+}
+
+impl LangKotlin {
+    pub fn new(type_map: HashMap<&'static str, JavaType>) -> Self {
+        LangKotlin {
+            java: LangJava::new(type_map),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Set the name of the native library.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.java.set_lib_name(name);
+    }
+
+    /// Set the namespace to put the NativeBindings class and the Kotlin classes in.
+    pub fn set_namespace<T: Into<String>>(&mut self, namespace: T) {
+        self.java.set_namespace(namespace);
+    }
+
+    /// Set the namespace to put the callback interfaces in.
+    pub fn set_model_namespace<T: Into<String>>(&mut self, namespace: T) {
+        self.java.set_model_namespace(namespace);
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated into the
+    /// namespace `namespace` by its own bindings. Their classes aren't generated again, but
+    /// imported instead.
+    pub fn add_provided_package<P, N>(&mut self, package: P, namespace: N)
+    where
+        P: Into<String>,
+        N: Into<String>,
+    {
+        self.java.add_provided_package(package, namespace);
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        self.java.filter(ident);
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.java.reset_filter(filter_mode);
+    }
+
+    /// Returns the conversion of a value of the type `ty`.
+    fn value(&self, name: String, ty: &syn::Type, array: bool) -> Result<Value, String> {
+        let context = &self.java.context;
+        let enumeration = match *ty {
+            syn::Type::Path(ref path) if !array => {
+                let ident = unwrap!(path.path.segments.last())
+                    .into_value()
+                    .ident
+                    .to_string();
+                context
+                    .enum_repr(&ident)
+                    .map(|_| struct_to_java_classname(&ident))
+            }
+            _ => None,
+        };
+        let ty = match enumeration {
+            Some(ref class) => class.clone(),
+            None => {
+                let mut java_ty = rust_to_java(ty, context).map_err(|err| err.message)?;
+                if array {
+                    java_ty = JavaType::Array(Box::new(java_ty));
+                }
+                kotlin_type(&java_ty)
+            }
+        };
+
+        Ok(Value {
+            name: escape(&name.to_camel_case()),
+            ty,
+            enumeration: enumeration.is_some(),
+        })
+    }
+
+    /// Transforms a function taking a callback into its `suspend` wrapper. Functions without
+    /// callbacks don't need one.
+    fn transform_fn(&self, function: &Function) -> Result<Option<Wrapper>, String> {
+        let inputs: Vec<_> = function
+            .decl
+            .inputs
+            .iter()
+            .map(|arg| unwrap!(transform_fnarg_to_argcap(arg)))
+            .collect();
+        let has_user_data = inputs.iter().any(|arg| is_user_data_arg(arg));
+
+        let mut args = Vec::new();
+        let mut callbacks = Vec::new();
+        let mut iter = inputs
+            .iter()
+            .filter(|arg| !is_user_data_arg(arg))
+            .peekable();
+        while let Some(arg) = iter.next() {
+            let bare_fn = match arg.ty {
+                syn::Type::BareFn(ref bare_fn) => Some(bare_fn),
+                ref ty => nullable_fn_ptr(ty),
+            };
+            if let Some(bare_fn) = bare_fn {
+                callbacks.push(bare_fn);
+                args.push(Arg::Callback);
+                continue;
+            }
+
+            let name = unwrap!(take_out_pat(&arg.pat)).ident.to_string();
+            let array = is_array_arg(arg, iter.peek().map(|arg| **arg));
+            if array {
+                let _ = iter.next();
+            }
+            args.push(Arg::Value(self.value(name, &arg.ty, array)?));
+        }
+
+        let bare_fn = match callbacks.as_slice() {
+            [] => return Ok(None),
+            [bare_fn] => *bare_fn,
+            _ => return Err("functions with several callbacks".to_string()),
+        };
+        if !has_user_data {
+            return Err("callbacks without user data".to_string());
+        }
+        if let syn::ReturnType::Type(..) = function.decl.output {
+            return Err("functions taking callbacks and returning values".to_string());
+        }
+
+        let cb_inputs: Vec<_> = bare_fn.inputs.iter().cloned().collect();
+        let callback = callback_name(&cb_inputs, &self.java.context).map_err(|err| err.message)?;
+
+        let mut cb_args = bare_fn
+            .inputs
+            .iter()
+            .filter(|arg| !is_user_data_arg_barefn(arg))
+            .peekable();
+        match cb_args.next() {
+            Some(arg) if is_result_arg_barefn(arg) => {}
+            _ => return Err("callbacks without a result".to_string()),
+        }
+        let mut values = Vec::new();
+        while let Some(arg) = cb_args.next() {
+            let name = match arg.name {
+                Some((syn::BareFnArgName::Named(ref name), _)) => name.to_string(),
+                _ => format!("value{}", values.len()),
+            };
+            let array = is_array_arg_barefn(arg, cb_args.peek().cloned());
+            if array {
+                let _ = cb_args.next();
+            }
+            values.push(self.value(name, &arg.ty, array)?);
+        }
+        // Larger tuples would need a class for the results
+        if values.len() > 3 {
+            return Err("callbacks with more than three values".to_string());
+        }
+
+        Ok(Some(Wrapper {
+            name: escape(&function.name.to_camel_case()),
+            docs: function.docs.clone(),
+            args,
+            callback,
+            values,
+        }))
+    }
+
+    /// Adds imports of the namespaces of the provided types to the Kotlin files.
+    fn add_provided_imports(&self, outputs: &mut Outputs) {
+        let namespaces: BTreeSet<_> = self.java.context.provided_types.values().collect();
+        if namespaces.is_empty() {
+            return;
+        }
+        let imports: String = namespaces
+            .iter()
+            .map(|namespace| format!("import {}.*\n", namespace))
+            .collect();
+
+        for (path, contents) in outputs.iter_mut() {
+            if !path.ends_with(".kt") {
+                continue;
+            }
+            if let Some(index) = contents.find('\n') {
+                contents.insert_str(index + 1, &format!("\n{}", imports));
+            }
+        }
+    }
+}
+
+impl Lang for LangKotlin {
+    fn parse_const(
+        &mut self,
+        _item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        _item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Convert a sized enum into a Kotlin enum class. Data-carrying enums keep the Java
+    /// classes of `LangJava`, as enum classes can't hold data per variant.
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.java.parse_enum(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let int = match self.java.context.enum_repr(&name) {
+            Some(int) => int.to_string(),
+            None => return Ok(()),
+        };
+        let class = struct_to_java_classname(&name);
+        // Ignored and provided enums don't have a class
+        if outputs.remove(&format!("{}.java", class)).is_none() {
+            return Ok(());
+        }
+
+        let primitive = match rust_ty_to_java(&int) {
+            Some(JavaType::Primitive(primitive)) => primitive,
+            _ => unreachable!(),
+        };
+        let docs = parse_attr(&item.attrs, |_| true, |attr| retrieve_docstring(attr, "")).1;
+        let mut variants = Vec::new();
+        let mut value = 0;
+        for variant in &item.variants {
+            if let Some(explicit) = common::extract_enum_variant_value(variant) {
+                value = explicit;
+            }
+            let docs = parse_attr(
+                &variant.attrs,
+                |_| true,
+                |attr| retrieve_docstring(attr, ""),
+            )
+            .1;
+            variants.push((
+                variant.ident.to_string().to_screaming_snake_case(),
+                value,
+                docs,
+            ));
+            value += 1;
+        }
+
+        let _ = outputs.insert(
+            format!("{}.kt", class),
+            emit_enum_class(&class, &docs, primitive, &variants, &self.java.context),
+        );
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.java.parse_fn(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        if self.java.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, |attr| {
+            retrieve_docstring(attr, "")
+        });
+        // If it's not #[no_mangle] then it can't be called from C.
+        if !no_mangle {
+            return Ok(());
+        }
+        // If it doesn't have a C ABI it can't be called from C.
+        match item.abi {
+            Some(ref abi) if common::is_extern(abi.clone()) => {}
+            _ => return Ok(()),
+        }
+
+        self.functions.push(Function {
+            name,
+            decl: (*item.decl).clone(),
+            docs,
+        });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.java.parse_static(item, module, outputs)
+    }
+
+    /// Convert a `#[repr(C)]` struct into a Kotlin data class, whose properties are the fields
+    /// of the Java class of `LangJava` so the JNI glue code can access them in the same way.
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.java.parse_struct(item, module, outputs)?;
+
+        let class = struct_to_java_classname(item.ident.to_string());
+        // Ignored, provided and skipped structs don't have a class
+        if outputs.remove(&format!("{}.java", class)).is_none() {
+            return Ok(());
+        }
+
+        let docs = parse_attr(&item.attrs, |_| true, |attr| retrieve_docstring(attr, "")).1;
+        let repr = common::parse_repr(&item.attrs);
+        let fields = if repr.transparent {
+            // The value class wraps a single primitive
+            let field = unwrap!(common::transparent_field(&item.fields));
+            let ty = rust_to_java(&field.ty, &self.java.context)?;
+            vec![Property {
+                name: "value".to_string(),
+                ty,
+                mutable: false,
+            }]
+        } else {
+            let struct_fields = transform_struct_fields(&common::named_fields(&item.fields));
+            transform_struct_into_class_fields(&struct_fields, &self.java.context)?
+                .into_iter()
+                .map(|field| Property {
+                    name: field.name,
+                    ty: field.ty,
+                    mutable: true,
+                })
+                .collect()
+        };
+
+        let _ = outputs.insert(
+            format!("{}.kt", class),
+            emit_data_class(&class, &docs, &fields, &self.java.context),
+        );
+
+        Ok(())
+    }
+
+    /// Unions keep the Java classes of `LangJava`, as their fields overlap.
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.java.parse_union(item, module, outputs)
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.java.finalise_output(outputs)?;
+
+        let mut wrappers = Vec::new();
+        for function in &self.functions {
+            match self.transform_fn(function) {
+                Ok(Some(wrapper)) => wrappers.push(wrapper),
+                Ok(None) => {}
+                Err(reason) => println!(
+                    "{} not supported ({}). Skipping its suspend wrapper.",
+                    reason, function.name
+                ),
+            }
+        }
+        if !wrappers.is_empty() {
+            let _ = outputs.insert(
+                "CoroutineBindings.kt".to_string(),
+                emit_coroutine_bindings(&wrappers, &self.java.context),
+            );
+            let _ = outputs.insert(
+                "FfiException.kt".to_string(),
+                emit_exception(&self.java.context),
+            );
+        }
+
+        self.add_provided_imports(outputs);
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        self.java.unit_reference()
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
+
+/// A property of a data class.
+pub struct Property {
+    name: String,
+    ty: JavaType,
+    mutable: bool,
+}
+
+/// Returns the Kotlin type of a Java type.
+fn kotlin_type(ty: &JavaType) -> String {
+    match *ty {
+        JavaType::Primitive(primitive) => primitive_type(primitive).to_string(),
+        JavaType::Object(ref obj) => obj.clone(),
+        JavaType::Array(ref elem) => match **elem {
+            JavaType::Primitive(primitive) => format!("{}Array", primitive_type(primitive)),
+            ref elem => format!("Array<{}>", kotlin_type(elem)),
+        },
+        JavaType::Method(..) => unreachable!(),
+    }
+}
+
+fn primitive_type(primitive: Primitive) -> &'static str {
+    match primitive {
+        Primitive::Boolean => "Boolean",
+        Primitive::Byte => "Byte",
+        Primitive::Char => "Char",
+        Primitive::Double => "Double",
+        Primitive::Float => "Float",
+        Primitive::Int => "Int",
+        Primitive::Long => "Long",
+        Primitive::Short => "Short",
+        Primitive::Void => "Unit",
+    }
+}
+
+/// Escapes identifiers which are hard keywords in Kotlin.
+fn escape(ident: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as",
+        "break",
+        "class",
+        "continue",
+        "do",
+        "else",
+        "false",
+        "for",
+        "fun",
+        "if",
+        "in",
+        "interface",
+        "is",
+        "null",
+        "object",
+        "package",
+        "return",
+        "super",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typealias",
+        "typeof",
+        "val",
+        "var",
+        "when",
+        "while",
+    ];
+    if KEYWORDS.contains(&ident) {
+        format!("`{}`", ident)
+    } else {
+        ident.to_string()
+    }
+}
@@ -0,0 +1,310 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+fn lang() -> LangKotlin {
+    let mut type_map = HashMap::new();
+    let _ = type_map.insert("App", JavaType::Primitive(Primitive::Long));
+    LangKotlin::new(type_map)
+}
+
+#[test]
+fn data_classes() {
+    let outputs = compile!(lang(), {
+        #[repr(C)]
+        pub struct Key(pub u64);
+
+        #[repr(transparent)]
+        pub struct Handle(u32);
+
+        /// A named shape.
+        #[repr(C)]
+        pub struct Shape {
+            name: *const c_char,
+            key: Key,
+            points: *const Point,
+            points_len: usize,
+            visible: bool,
+        }
+
+        #[repr(C)]
+        pub struct Point {
+            x: f32,
+            y: f64,
+        }
+
+        #[repr(C)]
+        pub union Extent {
+            radius: f32,
+            side: f64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn shape_area(shape: *const Shape, user_data: *mut c_void) {}
+    });
+
+    let actual = fetch(&outputs, "Shape.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           /**
+            * A named shape.
+            */
+           data class Shape(
+               var name: String = "",
+               var key: Key = Key(),
+               var points: Array<Point> = emptyArray(),
+               var pointsLen: Long = 0,
+               var visible: Boolean = false
+           ) {
+               override fun equals(other: Any?): Boolean {
+                   if (this === other) return true
+                   if (other !is Shape) return false
+                   return name == other.name &&
+                       key == other.key &&
+                       points.contentEquals(other.points) &&
+                       pointsLen == other.pointsLen &&
+                       visible == other.visible
+               }
+
+               override fun hashCode(): Int {
+                   var result = name.hashCode()
+                   result = 31 * result + key.hashCode()
+                   result = 31 * result + points.contentHashCode()
+                   result = 31 * result + pointsLen.hashCode()
+                   result = 31 * result + visible.hashCode()
+                   return result
+               }
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Point.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           data class Point(
+               var x: Float = 0f,
+               var y: Double = 0.0
+           )
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Handle.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           data class Handle(
+               val value: Int = 0
+           )
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    assert!(outputs.contains_key("Key.kt"));
+    assert!(!outputs.contains_key("Shape.java"));
+    // The fields of unions overlap, so they keep their Java classes
+    assert!(outputs.contains_key("Extent.java"));
+    assert!(outputs.contains_key("jni.rs"));
+    assert!(!outputs.contains_key("CoroutineBindings.kt"));
+}
+
+#[test]
+fn enum_classes() {
+    let outputs = compile!(lang(), {
+        /// Direction of the motor.
+        #[repr(i8)]
+        pub enum Direction {
+            /// Turns backwards.
+            Reverse = -1,
+            Idle,
+            Forward,
+        }
+
+        #[repr(u16)]
+        pub enum Port {
+            Http = 80,
+            Dynamic = 49152,
+        }
+
+        #[repr(C)]
+        pub enum Level {
+            Low,
+            High,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn set_direction(direction: Direction, port: Port) {}
+    });
+
+    let actual = fetch(&outputs, "Direction.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           /**
+            * Direction of the motor.
+            */
+           enum class Direction(val value: Byte) {
+               /**
+                * Turns backwards.
+                */
+               REVERSE(-1),
+               IDLE(0),
+               FORWARD(1);
+
+               companion object {
+                   /**
+                    * Returns the variant with the native `value`.
+                    */
+                   fun fromValue(value: Byte): Direction = values().first { it.value == value }
+               }
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Port.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           enum class Port(val value: Short) {
+               HTTP(80),
+               DYNAMIC(49152.toShort());
+
+               companion object {
+                   /**
+                    * Returns the variant with the native `value`.
+                    */
+                   fun fromValue(value: Short): Port = values().first { it.value == value }
+               }
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    assert!(!outputs.contains_key("Direction.java"));
+    assert!(!outputs.contains_key("Level.kt"));
+}
+
+#[test]
+fn suspend_wrappers() {
+    let outputs = compile!(lang(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[repr(u8)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        /// Fetches the value under `key`.
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, value: u64),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn app_free(
+            app: *mut App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn open(
+            data: *const u8,
+            data_len: usize,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                mode: Mode,
+                content: *const u8,
+                content_len: usize,
+            ),
+            is: bool,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            user_data: *mut c_void,
+            o_event: extern "C" fn(user_data: *mut c_void, event: u32),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "CoroutineBindings.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           import net.maidsafe.model.*
+           import kotlin.coroutines.resume
+           import kotlin.coroutines.resumeWithException
+           import kotlin.coroutines.suspendCoroutine
+
+           /**
+            * Suspending wrappers of the functions of `NativeBindings` calling back with an `FfiResult`,
+            * which throw an [FfiException] when the native library reports an error.
+            */
+           object CoroutineBindings {
+               /**
+                * Fetches the value under `key`.
+                */
+               suspend fun fetch(app: Long, key: String): Long = suspendCoroutine { continuation ->
+                   NativeBindings.fetch(app, key, CallbackResultLong { result, value ->
+                       if (result.errorCode != 0) {
+                           continuation.resumeWithException(FfiException(result.errorCode, result.description))
+                       } else {
+                           continuation.resume(value)
+                       }
+                   })
+               }
+
+               suspend fun appFree(app: Long) = suspendCoroutine<Unit> { continuation ->
+                   NativeBindings.appFree(app, CallbackResult { result ->
+                       if (result.errorCode != 0) {
+                           continuation.resumeWithException(FfiException(result.errorCode, result.description))
+                       } else {
+                           continuation.resume(Unit)
+                       }
+                   })
+               }
+
+               suspend fun open(data: ByteArray, `is`: Boolean): Pair<Mode, ByteArray> = suspendCoroutine { continuation ->
+                   NativeBindings.open(data, CallbackResultByteByteArrayLen { result, mode, content ->
+                       if (result.errorCode != 0) {
+                           continuation.resumeWithException(FfiException(result.errorCode, result.description))
+                       } else {
+                           continuation.resume(Pair(Mode.fromValue(mode), content))
+                       }
+                   }, `is`)
+               }
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "FfiException.kt");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings
+
+           class FfiException(val code: Int, message: String?) : Exception(message)
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    assert!(outputs.contains_key("CallbackResultLong.java"));
+    assert!(outputs.contains_key("NativeBindings.java"));
+}
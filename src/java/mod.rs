@@ -1,27 +1,36 @@
 //! Functions for generating Java glue code.
 
 mod jni;
+mod kotlin;
+mod panama;
 mod types;
 
+pub use self::kotlin::LangKotlin;
+pub use self::panama::LangJavaPanama;
+
 use crate::common::{
     self, append_output, check_no_mangle, is_array_arg, is_array_arg_barefn, is_user_data_arg,
-    is_user_data_arg_barefn, parse_attr, retrieve_docstring, take_out_pat,
-    transform_fnarg_to_argcap, FilterMode, Outputs,
+    is_user_data_arg_barefn, parse_attr, take_out_pat, transform_fnarg_to_argcap, FilterMode,
+    Outputs, SafePtrKind,
+};
+use crate::java::types::{
+    callback_name, java_type_to_str, rust_to_java, rust_ty_to_java, struct_to_java_classname,
 };
-use crate::java::types::{callback_name, java_type_to_str, rust_to_java, struct_to_java_classname};
 use crate::struct_field::{transform_struct_fields, StructField};
 use crate::{Error, Level};
-use ::jni::signature::JavaType;
+use ::jni::signature::{JavaType, Primitive};
 use ::rustfmt::{self, format_input};
 use inflector::Inflector;
 use quote::*;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use unwrap::unwrap;
 
 pub struct LangJava {
     context: Context,
     filter: HashSet<String>,
     filter_mode: FilterMode,
+    /// Namespaces of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
 }
 
 pub struct Context {
@@ -35,6 +44,25 @@ pub struct Context {
     type_map: HashMap<&'static str, JavaType>,
     /// Keeps track of which JNI callback functions has been generated already
     generated_jni_cbs: BTreeSet<String>,
+    /// Enums with an integer representation, by name
+    enum_reprs: HashMap<String, EnumRepr>,
+    /// Maps types provided by the bindings of dependency crates to their namespaces
+    provided_types: BTreeMap<String, String>,
+}
+
+/// An enum with an integer representation.
+struct EnumRepr {
+    /// Integer type (e.g. `u8`)
+    int: String,
+    /// Variant names and their discriminants
+    variants: Vec<(String, i64)>,
+}
+
+impl Context {
+    /// Returns the integer type of the enum `ty`, if it has an integer representation.
+    fn enum_repr(&self, ty: &str) -> Option<&str> {
+        self.enum_reprs.get(ty).map(|repr| repr.int.as_str())
+    }
 }
 
 impl Default for Context {
@@ -45,6 +73,8 @@ impl Default for Context {
             namespace_model: "net.maidsafe.dummy".to_string(),
             type_map: Default::default(),
             generated_jni_cbs: Default::default(),
+            enum_reprs: Default::default(),
+            provided_types: Default::default(),
         }
     }
 }
@@ -60,7 +90,10 @@ impl LangJava {
                 namespace: "net.maidsafe.bindings".to_owned(),
                 namespace_model: "net.maidsafe.model".to_owned(),
                 generated_jni_cbs: BTreeSet::new(),
+                enum_reprs: HashMap::new(),
+                provided_types: BTreeMap::new(),
             },
+            provided_packages: HashMap::new(),
         }
     }
 
@@ -79,6 +112,19 @@ impl LangJava {
         self.context.namespace_model = namespace.into();
     }
 
+    /// Declare that the types of the dependency crate `package` are generated into the
+    /// namespace `namespace` by its own bindings. Their classes aren't generated again, but
+    /// imported instead.
+    pub fn add_provided_package<P, N>(&mut self, package: P, namespace: N)
+    where
+        P: Into<String>,
+        N: Into<String>,
+    {
+        let _ = self
+            .provided_packages
+            .insert(package.into(), namespace.into());
+    }
+
     /// Applies rustfmt to JNI code to improve debuggability
     fn format_jni_output(&self, input: &mut String) {
         let mut output: Vec<u8> = Vec::with_capacity(input.len() * 2);
@@ -108,6 +154,170 @@ impl LangJava {
         );
     }
 
+    /// Convert a `#[repr(transparent)]` struct wrapping a primitive type into an immutable Java
+    /// value class.
+    fn parse_transparent_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        docs: &str,
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if !item.generics.params.is_empty() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, // NONE FOR NOW
+                message: "cheddar cannot handle parameterized `#[repr(transparent)]` structs"
+                    .into(),
+            });
+        }
+
+        let field = match common::transparent_field(&item.fields) {
+            Some(field) => field,
+            None => {
+                println!(
+                    "zero-sized transparent structs not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+        let ty = match field.ty {
+            syn::Type::Path(ref path) => path
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.value().ident.to_string()),
+            _ => None,
+        };
+        let primitive = match ty.as_ref().and_then(|ty| rust_ty_to_java(ty)) {
+            Some(JavaType::Primitive(primitive)) if primitive != Primitive::Void => primitive,
+            _ => {
+                println!(
+                    "transparent structs wrapping non-primitive types not supported ({}). \
+                     Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+        let java_ty = java_type_to_str(&JavaType::Primitive(primitive))?;
+        let boxed_ty = match primitive {
+            Primitive::Boolean => "Boolean",
+            Primitive::Byte => "Byte",
+            Primitive::Char => "Character",
+            Primitive::Double => "Double",
+            Primitive::Float => "Float",
+            Primitive::Int => "Integer",
+            Primitive::Long => "Long",
+            Primitive::Short => "Short",
+            Primitive::Void => unreachable!(),
+        };
+
+        let class_name = struct_to_java_classname(&name);
+        let mut buffer = String::new();
+        buffer.push_str(&format!("package {};\n\n", self.context.namespace));
+        buffer.push_str(&javadoc(docs, ""));
+        buffer.push_str(&format!("public final class {} {{\n", class_name));
+        buffer.push_str(&format!("\tprivate final {} value;\n\n", java_ty));
+        buffer.push_str(&format!(
+            "\tpublic {}(final {} value) {{\n\t\tthis.value = value;\n\t}}\n\n",
+            class_name, java_ty
+        ));
+        buffer.push_str(&format!(
+            "\tpublic {} getValue() {{\n\t\treturn value;\n\t}}\n\n",
+            java_ty
+        ));
+        buffer.push_str(&format!(
+            "\t@Override\n\
+             \tpublic boolean equals(final Object other) {{\n\
+             \t\treturn other instanceof {0} && (({0}) other).value == value;\n\
+             \t}}\n\n",
+            class_name
+        ));
+        buffer.push_str(&format!(
+            "\t@Override\n\
+             \tpublic int hashCode() {{\n\
+             \t\treturn {}.hashCode(value);\n\
+             \t}}\n",
+            boxed_ty
+        ));
+        buffer.push_str("}\n\n");
+
+        let jni = jni::generate_transparent_struct(field, &name, &class_name, &self.context);
+        append_output(jni, "jni.rs", outputs);
+
+        outputs.insert(format!("{}.java", class_name), buffer);
+
+        Ok(())
+    }
+
+    /// Convert a data-carrying enum into an abstract Java class, with a nested final class
+    /// extending it for each of the variants.
+    fn parse_data_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        docs: &str,
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        let class_name = struct_to_java_classname(&name);
+
+        let mut buffer = String::new();
+        buffer.push_str(&format!("package {};\n\n", self.context.namespace));
+        buffer.push_str(&javadoc(docs, ""));
+        buffer.push_str(&format!("public abstract class {} {{\n", class_name));
+        // Only the nested variant classes can extend the class
+        buffer.push_str(&format!("\tprivate {}() {{\n\t}}\n", class_name));
+
+        let mut variants = Vec::new();
+        for variant in &item.variants {
+            let variant_name = variant.ident.to_string();
+            let docs = parse_attr(&variant.attrs, |_| true, retrieve_docstring).1;
+
+            let struct_fields = transform_struct_fields(&common::named_fields(&variant.fields));
+            let fields = transform_struct_into_class_fields(&struct_fields, &self.context)?;
+
+            let mut class = javadoc(&docs, "");
+            class.push_str(&format!(
+                "public static final class {} extends {} {{\n",
+                variant_name, class_name
+            ));
+            if !fields.is_empty() {
+                class.push_str(&generate_class_fields(&fields)?);
+                class.push('\n');
+                class.push_str(&generate_default_constructor(&variant_name, &fields)?);
+                class.push_str(&generate_parametrised_constructor(&variant_name, &fields)?);
+                class.push_str(&generate_getters_setters(&fields)?);
+            }
+            class.push_str("}\n");
+
+            // Indent the nested class
+            buffer.push('\n');
+            for line in class.lines() {
+                if !line.is_empty() {
+                    buffer.push('\t');
+                }
+                buffer.push_str(line);
+                buffer.push('\n');
+            }
+
+            variants.push(jni::EnumVariant {
+                name: variant_name,
+                fields: struct_fields,
+                tuple: matches!(variant.fields, syn::Fields::Unnamed(..)),
+            });
+        }
+        buffer.push_str("}\n\n");
+
+        let jni = jni::generate_enum(&variants, &name, &class_name, &self.context);
+        append_output(jni, "jni.rs", outputs);
+
+        outputs.insert(format!("{}.java", class_name), buffer);
+
+        Ok(())
+    }
+
     /// Add the identifier to the filter.
     /// If the filter mode is `Blacklist` (the default), the identifiers in the
     /// filter are ignored.
@@ -128,6 +338,57 @@ impl LangJava {
             FilterMode::Whitelist => !self.filter.contains(ident),
         }
     }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) -> bool {
+        let namespace = match module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            Some(namespace) => namespace.clone(),
+            None => return false,
+        };
+        let _ = self
+            .context
+            .provided_types
+            .insert(struct_to_java_classname(name), namespace);
+        true
+    }
+
+    /// Generate only the JNI conversions of a provided type, as its class is generated by the
+    /// bindings of its crate.
+    fn parse_provided<F>(&mut self, outputs: &mut Outputs, parse: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self, &mut Outputs) -> Result<(), Error>,
+    {
+        let mut generated = Outputs::default();
+        parse(self, &mut generated)?;
+        if let Some(jni) = generated.remove("jni.rs") {
+            append_output(jni, "jni.rs", outputs);
+        }
+        Ok(())
+    }
+
+    /// Adds imports of the namespaces of the provided types to the Java classes.
+    fn add_provided_imports(&self, outputs: &mut Outputs) {
+        let namespaces: BTreeSet<_> = self.context.provided_types.values().collect();
+        if namespaces.is_empty() {
+            return;
+        }
+        let imports: String = namespaces
+            .iter()
+            .map(|namespace| format!("import {}.*;\n", namespace))
+            .collect();
+
+        for (path, contents) in outputs.iter_mut() {
+            if !path.ends_with(".java") {
+                continue;
+            }
+            if let Some(index) = contents.find(";\n") {
+                contents.insert_str(index + 2, &format!("\n{}", imports));
+            }
+        }
+    }
 }
 
 impl common::Lang for LangJava {
@@ -149,12 +410,81 @@ impl common::Lang for LangJava {
         Ok(())
     }
 
+    /// Convert a Rust enum with an integer representation (e.g. `#[repr(u8)]`) into a Java class
+    /// holding the variants as constants of the matching integer type.
     fn parse_enum(
         &mut self,
-        _item: &syn::ItemEnum,
-        _module: &[String],
-        _outputs: &mut Outputs,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
     ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        if self.add_if_provided(&name, module) {
+            return self
+                .parse_provided(outputs, |lang, outputs| lang.parse_enum(item, &[], outputs));
+        }
+        if !item.generics.params.is_empty() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, // NONE FOR NOW
+                message: "cheddar cannot handle parameterized enums".into(),
+            });
+        }
+        let repr = common::parse_repr(&item.attrs);
+        let docs = parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        if common::has_data_variants(item) {
+            // If it's not #[repr(C)] or #[repr(u8)] etc. then it can't be called from C.
+            if !repr.c && repr.int.is_none() {
+                return Ok(());
+            }
+            return self.parse_data_enum(item, &docs, outputs);
+        }
+
+        // `#[repr(C)]` enums don't have a fixed size, so they can't be passed through JNI.
+        let int = match repr.int {
+            Some(int) => int,
+            None => return Ok(()),
+        };
+        let java_ty = java_type_to_str(&unwrap!(rust_ty_to_java(&int)))?;
+
+        let mut buffer = String::new();
+        buffer.push_str(&format!("package {};\n\n", self.context.namespace));
+        buffer.push_str(&javadoc(&docs, ""));
+
+        let class_name = struct_to_java_classname(&name);
+        buffer.push_str(&format!("public final class {} {{\n", class_name));
+
+        let mut value = 0;
+        let mut variants = Vec::new();
+        for variant in &item.variants {
+            if let Some(explicit) = common::extract_enum_variant_value(variant) {
+                value = explicit;
+            }
+            variants.push((variant.ident.to_string(), value));
+
+            let docs = parse_attr(&variant.attrs, |_| true, retrieve_docstring).1;
+            buffer.push_str(&javadoc(&docs, "\t"));
+            buffer.push_str(&format!(
+                "\tpublic static final {ty} {name} = ({ty}) {value}{suffix};\n",
+                ty = java_ty,
+                name = variant.ident.to_string().to_screaming_snake_case(),
+                value = value,
+                suffix = if java_ty == "long" { "L" } else { "" },
+            ));
+            value += 1;
+        }
+        buffer.push_str("}\n\n");
+
+        outputs.insert(format!("{}.java", class_name), buffer);
+        let _ = self
+            .context
+            .enum_reprs
+            .insert(name, EnumRepr { int, variants });
+
         Ok(())
     }
 
@@ -171,9 +501,7 @@ impl common::Lang for LangJava {
             return Ok(());
         }
 
-        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, |attr| {
-            retrieve_docstring(attr, "")
-        });
+        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, retrieve_docstring);
         // If it's not #[no_mangle] then it can't be called from C.
         if !no_mangle {
             return Ok(());
@@ -203,28 +531,107 @@ impl common::Lang for LangJava {
         Ok(())
     }
 
+    /// Convert an exported Rust static into native getter (and setter, if the static is
+    /// mutable and primitive) methods of `NativeBindings`.
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, retrieve_docstring);
+        // If it's not #[no_mangle] then it can't be accessed from C.
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let is_ptr = matches!(*item.ty, syn::Type::Ptr(..));
+        let java_ty = match rust_to_java(&item.ty, &self.context) {
+            Ok(JavaType::Primitive(Primitive::Void)) => None,
+            Ok(ty @ JavaType::Primitive(..)) if !is_ptr => Some(ty),
+            // Pointers other than strings can't be converted
+            Ok(JavaType::Object(ref obj)) if is_ptr && obj != "String" => None,
+            Ok(ty @ JavaType::Object(..)) => Some(ty),
+            _ => None,
+        };
+        let java_ty = match java_ty {
+            Some(ty) => ty,
+            None => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+        let java_ty_str = java_type_to_str(&java_ty)?;
+
+        let getter = format!("get{}", name.to_pascal_case());
+        let setter = match java_ty {
+            JavaType::Primitive(..) if item.mutability.is_some() => {
+                Some(format!("set{}", name.to_pascal_case()))
+            }
+            _ => None,
+        };
+
+        let mut buffer = String::new();
+        buffer.push_str("/**\n");
+        buffer.push_str(&docs.replace("///", " *"));
+        buffer.push_str(" */\n");
+        buffer.push_str(&format!(
+            "public static native {} {}();\n\n",
+            java_ty_str, getter
+        ));
+        if let Some(ref setter) = setter {
+            buffer.push_str(&format!(
+                "public static native void {}({} value);\n\n",
+                setter, java_ty_str
+            ));
+        }
+        append_output(buffer, "NativeBindings.java", outputs);
+
+        let mut jni =
+            jni::generate_jni_static(item, &java_ty, &getter, setter.as_deref(), &self.context);
+        jni.push('\n');
+        append_output(jni, "jni.rs", outputs);
+
+        Ok(())
+    }
+
     /// Convert a Rust struct into a Java class.
     fn parse_struct(
         &mut self,
         item: &syn::ItemStruct,
-        _module: &[String],
+        module: &[String],
         outputs: &mut Outputs,
     ) -> Result<(), Error> {
         let name = item.ident.to_string();
         if self.is_ignored(&name) {
             return Ok(());
         }
-        let (repr_c, docs) = parse_attr(&item.attrs, common::check_repr_c, |attr| {
-            retrieve_docstring(attr, "")
-        });
+        if self.add_if_provided(&name, module) {
+            return self.parse_provided(outputs, |lang, outputs| {
+                lang.parse_struct(item, &[], outputs)
+            });
+        }
+        let docs = parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let repr = common::parse_repr(&item.attrs);
+        if repr.transparent {
+            return self.parse_transparent_struct(item, &docs, outputs);
+        }
         // If it's not #[repr(C)] then it can't be called from C.
-        if !repr_c {
+        if !repr.c {
             return Ok(());
         }
 
         let mut buffer = String::new();
         buffer.push_str(&format!("package {};\n\n", self.context.namespace));
-        buffer.push_str(&docs);
+        buffer.push_str(&javadoc(&docs, ""));
 
         let orig_name = item.ident.to_owned().to_string();
         let name = struct_to_java_classname(&*orig_name);
@@ -238,11 +645,7 @@ impl common::Lang for LangJava {
             });
         }
 
-        let mut vec = vec![];
-        for x in item.fields.iter() {
-            vec.push(x.clone());
-        }
-        let struct_fields = transform_struct_fields(vec.as_slice());
+        let struct_fields = transform_struct_fields(&common::named_fields(&item.fields));
         let fields = transform_struct_into_class_fields(&struct_fields, &self.context)?;
 
         buffer.push_str(" {\n");
@@ -261,7 +664,16 @@ impl common::Lang for LangJava {
         buffer.push_str(&generate_getters_setters(&fields)?);
         buffer.push_str("}");
 
-        let jni = jni::generate_struct(&struct_fields, &orig_name, &name, &self.context);
+        let packed = repr.packed.is_some();
+        let tuple = matches!(item.fields, syn::Fields::Unnamed(..));
+        let jni = jni::generate_struct(
+            &struct_fields,
+            &orig_name,
+            &name,
+            packed,
+            tuple,
+            &self.context,
+        );
         append_output(jni, "jni.rs", outputs);
 
         buffer.push_str("\n\n");
@@ -271,6 +683,77 @@ impl common::Lang for LangJava {
         Ok(())
     }
 
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        if self.add_if_provided(&name, module) {
+            return self.parse_provided(outputs, |lang, outputs| {
+                lang.parse_union(item, &[], outputs)
+            });
+        }
+        let (repr_c, docs) = parse_attr(&item.attrs, common::check_repr_c, retrieve_docstring);
+        // If it's not #[repr(C)] then it can't be called from C.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, // NONE FOR NOW
+                message: "cheddar cannot handle parameterized `#[repr(C)]` unions".into(),
+            });
+        }
+
+        let fields: Vec<_> = item.fields.named.iter().cloned().collect();
+        let struct_fields = transform_struct_fields(&fields);
+        // Pointers can't be safely read from the other fields of a union
+        if struct_fields
+            .iter()
+            .any(|field| !matches!(*field, StructField::Primitive(..)))
+        {
+            println!(
+                "unions with pointer fields not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+        let class_fields = transform_struct_into_class_fields(&struct_fields, &self.context)?;
+
+        let class_name = struct_to_java_classname(&name);
+        let mut buffer = String::new();
+        buffer.push_str(&format!("package {};\n\n", self.context.namespace));
+        buffer.push_str(&javadoc(&docs, ""));
+        buffer.push_str(&format!("public class {} {{\n", class_name));
+
+        // Class fields, along with the index of the one converted into the native union
+        buffer.push_str(&generate_class_fields(&class_fields)?);
+        buffer.push_str("\tprivate int activeField;\n");
+        buffer.push('\n');
+
+        // The fields overlap, so only the default constructor makes sense
+        buffer.push_str(&generate_default_constructor(&class_name, &class_fields)?);
+
+        // Getters & setters, which make the field they set the active one
+        buffer.push_str(&generate_union_getters_setters(&class_fields)?);
+        buffer.push_str("\tpublic int getActiveField() {\n\t\treturn activeField;\n\t}\n\n");
+        buffer.push_str("}\n\n");
+
+        let jni = jni::generate_union(&struct_fields, &name, &class_name, &self.context);
+        append_output(jni, "jni.rs", outputs);
+
+        outputs.insert(format!("{}.java", class_name), buffer);
+
+        Ok(())
+    }
+
     fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
         match outputs.get_mut("jni.rs") {
             Some(input) => {
@@ -288,14 +771,26 @@ impl common::Lang for LangJava {
         match outputs.get_mut("NativeBindings.java") {
             Some(input) => {
                 self.format_native_functions(input);
-                Ok(())
             }
-            None => Err(Error {
-                level: Level::Error,
-                span: None, //NONE FOR NOW
-                message: "no native bindings generated?".to_owned(),
-            }),
+            None => {
+                return Err(Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: "no native bindings generated?".to_owned(),
+                });
+            }
         }
+
+        self.add_provided_imports(outputs);
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.context.namespace.clone())
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
     }
 }
 
@@ -307,6 +802,24 @@ struct JavaClassField {
     ty_str: String,
 }
 
+/// Retrieves a line of docs, ending with a newline so the lines can be joined.
+fn retrieve_docstring(attr: &syn::Attribute) -> Option<String> {
+    common::retrieve_docstring(attr, "").map(|docs| format!("{}\n", docs))
+}
+
+/// Converts the `///` docs of an item into a Javadoc comment, indented by `indent`.
+fn javadoc(docs: &str, indent: &str) -> String {
+    if docs.is_empty() {
+        return String::new();
+    }
+    let mut buffer = format!("{}/**\n", indent);
+    for line in docs.lines() {
+        buffer.push_str(&format!("{}{}\n", indent, line.replacen("///", " *", 1)));
+    }
+    buffer.push_str(&format!("{} */\n", indent));
+    buffer
+}
+
 /// Transforms a list of struct fields into Java class fields
 fn transform_struct_into_class_fields(
     fields: &[StructField],
@@ -353,6 +866,31 @@ fn generate_getters_setters(fields: &[JavaClassField]) -> Result<String, Error>
     Ok(buffer)
 }
 
+/// Generates getters & setters for the fields of a union, where setting a field makes it the
+/// one converted into the native union
+fn generate_union_getters_setters(fields: &[JavaClassField]) -> Result<String, Error> {
+    let mut buffer = String::new();
+
+    for (idx, field) in fields.iter().enumerate() {
+        buffer.push_str(&format!(
+            "\tpublic {ty} get{capitalized}() {{\n\t\treturn {name};\n\t}}\n\n",
+            ty = field.ty_str,
+            name = field.name,
+            capitalized = field.name.to_class_case(),
+        ));
+        buffer.push_str(&format!(
+            "\tpublic void set{capitalized}(final {ty} val) {{\n\t\tthis.{name} \
+             = val;\n\t\tthis.activeField = {idx};\n\t}}\n\n",
+            ty = field.ty_str,
+            name = field.name,
+            capitalized = field.name.to_class_case(),
+            idx = idx,
+        ));
+    }
+
+    Ok(buffer)
+}
+
 /// Generates fields for a struct transformed into a Java class
 fn generate_class_fields(fields: &[JavaClassField]) -> Result<String, Error> {
     let mut buffer = String::new();
@@ -442,6 +980,21 @@ pub fn transform_native_fn(
         let pat = take_out_pat(&unwrap!(transform_fnarg_to_argcap(arg)).pat);
         let arg_name = unwrap!(pat).ident.to_string();
 
+        // The native function is given a copy of a Java object, so its changes would be lost.
+        if let Some(ptr) = common::safe_ptr(&unwrap!(transform_fnarg_to_argcap(arg)).ty) {
+            let mutable = ptr.kind == SafePtrKind::RefMut || ptr.kind == SafePtrKind::NonNull;
+            if mutable && jni::opaque_pointee(&ptr).is_none() {
+                return Err(Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: format!(
+                        "bindgen cannot handle mutable pointers to Java objects ({} in {})",
+                        arg_name, name
+                    ),
+                });
+            }
+        }
+
         // Generate function arguments
         let mut java_type = rust_to_java(&unwrap!(transform_fnarg_to_argcap(arg)).ty, context)?;
         let next_arg: Option<&syn::ArgCaptured> = if fn_args.peek().is_some() {
@@ -460,7 +1013,11 @@ pub fn transform_native_fn(
         args_str.push(format!("{} {}", java_type, arg_name.to_camel_case()));
         let argcap = unwrap!(transform_fnarg_to_argcap(arg));
         // Generate a callback class - if it wasn't generated already
-        if let syn::Type::BareFn(ref bare_fn) = argcap.ty {
+        let bare_fn = match argcap.ty {
+            syn::Type::BareFn(ref bare_fn) => Some(bare_fn),
+            ref ty => common::nullable_fn_ptr(ty),
+        };
+        if let Some(bare_fn) = bare_fn {
             let mut vec = vec![];
             for input in bare_fn.inputs.to_owned() {
                 vec.push(input);
@@ -569,8 +1126,14 @@ pub fn transform_callback<S: AsRef<str>>(
     class_name: S,
     context: &Context,
 ) -> Result<String, Error> {
-    match ty {
-        syn::Type::BareFn(ref bare_fn) => Ok(format!(
+    let bare_fn = match ty {
+        syn::Type::BareFn(ref bare_fn) => Some(bare_fn),
+        // Nullable callbacks share the interface of the non-nullable ones.
+        ty => common::nullable_fn_ptr(ty),
+    };
+
+    match bare_fn {
+        Some(bare_fn) => Ok(format!(
             "package {namespace};\n\n\
              public interface {name} {{\n\
              \tpublic void call({types});\n}}\n",
@@ -579,7 +1142,7 @@ pub fn transform_callback<S: AsRef<str>>(
             types = callback_to_java(bare_fn, context)?,
         )),
         // All other types just have a name associated with them.
-        _ => Err(Error {
+        None => Err(Error {
             level: Level::Error,
             span: None, //NONE FOR NOW
             message: "Invalid callback type".into(),
@@ -659,6 +1222,8 @@ mod tests {
             namespace: "net.maidsafe.bindings".to_owned(),
             namespace_model: "net.maidsafe.model".to_owned(),
             generated_jni_cbs: BTreeSet::new(),
+            enum_reprs: HashMap::new(),
+            provided_types: BTreeMap::new(),
         };
 
         let inputs = get_inputs("fn ()");
@@ -696,4 +1261,353 @@ mod tests {
             unwrap!(callback_name(&inputs, &context))
         );
     }
+
+    #[test]
+    fn sized_enums() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        let item: syn::ItemEnum = unwrap!(syn::parse_str(
+            "#[repr(u8)] pub enum Mode { ReadOnly, WriteOnly = 4, ReadAndWrite }"
+        ));
+        unwrap!(common::Lang::parse_enum(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+
+        assert_eq!(
+            outputs["Mode.java"],
+            "package net.maidsafe.bindings;\n\n\
+             public final class Mode {\n\
+             \tpublic static final byte READ_ONLY = (byte) 0;\n\
+             \tpublic static final byte WRITE_ONLY = (byte) 4;\n\
+             \tpublic static final byte READ_AND_WRITE = (byte) 5;\n\
+             }\n\n"
+        );
+        assert_eq!(lang.context.enum_repr("Mode"), Some("u8"));
+
+        let ty: syn::Type = unwrap!(syn::parse_str("Mode"));
+        assert_eq!(
+            unwrap!(rust_to_java(&ty, &lang.context)),
+            JavaType::Primitive(::jni::signature::Primitive::Byte)
+        );
+
+        let item: syn::ItemFn = unwrap!(syn::parse_str(
+            "#[no_mangle] pub extern \"C\" fn set_mode(mode: Mode) {}"
+        ));
+        unwrap!(common::Lang::parse_fn(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+
+        let jni = &outputs["jni.rs"];
+        assert!(jni.contains(
+            "match mode as u8 { 0 => Mode :: ReadOnly , 4 => Mode :: WriteOnly , \
+             5 => Mode :: ReadAndWrite , _ => { let _ = env . throw_new ( \
+             \"java/lang/IllegalArgumentException\" , \"mode is not a valid Mode\" ) ; return ; } }"
+        ));
+    }
+
+    #[test]
+    fn documented_classes() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        let item: syn::ItemEnum = unwrap!(syn::parse_str(
+            "/// Permission.\n\
+             /// Granted per file.\n\
+             #[repr(u8)] pub enum Permission { Read, /// Implies `Read`.\n Write }"
+        ));
+        unwrap!(common::Lang::parse_enum(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+        let item: syn::ItemStruct = unwrap!(syn::parse_str(
+            "/// Owner of a file.\n#[repr(C)] pub struct Owner { uid: u32 }"
+        ));
+        unwrap!(common::Lang::parse_struct(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+
+        assert_eq!(
+            outputs["Permission.java"],
+            "package net.maidsafe.bindings;\n\n\
+             /**\n\
+             \x20* Permission.\n\
+             \x20* Granted per file.\n\
+             \x20*/\n\
+             public final class Permission {\n\
+             \tpublic static final byte READ = (byte) 0;\n\
+             \t/**\n\
+             \t * Implies `Read`.\n\
+             \t */\n\
+             \tpublic static final byte WRITE = (byte) 1;\n\
+             }\n\n"
+        );
+        assert!(outputs["Owner.java"].starts_with(
+            "package net.maidsafe.bindings;\n\n\
+             /**\n\
+             \x20* Owner of a file.\n\
+             \x20*/\n\
+             public class Owner {\n"
+        ));
+    }
+
+    #[test]
+    fn mutable_pointers_to_objects() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        let item: syn::ItemFn = unwrap!(syn::parse_str(
+            "#[no_mangle] pub extern \"C\" fn update(entry: Option<&mut Entry>) {}"
+        ));
+        let error =
+            unwrap!(
+                common::Lang::parse_fn(&mut lang, &item, &["ffi".to_string()], &mut outputs).err()
+            );
+
+        assert_eq!(
+            error.message,
+            "bindgen cannot handle mutable pointers to Java objects (entry in update)"
+        );
+    }
+
+    #[test]
+    fn tagged_unions() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        let item: syn::ItemEnum = unwrap!(syn::parse_str(
+            "#[repr(C, u8)] pub enum Shape { Circle { diameter: f32 }, Empty }"
+        ));
+        unwrap!(common::Lang::parse_enum(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+
+        assert_eq!(
+            outputs["Shape.java"],
+            "package net.maidsafe.bindings;\n\n\
+             public abstract class Shape {\n\
+             \tprivate Shape() {\n\
+             \t}\n\
+             \n\
+             \tpublic static final class Circle extends Shape {\n\
+             \t\tprivate float diameter;\n\
+             \n\
+             \t\tpublic Circle() {\n\
+             \n\
+             \t\t}\n\
+             \t\tpublic Circle(float diameter) {\n\
+             \t\t\tthis.diameter = diameter;\n\
+             \t\t}\n\
+             \t\tpublic float getDiameter() {\n\
+             \t\t\treturn diameter;\n\
+             \t\t}\n\
+             \n\
+             \t\tpublic void setDiameter(final float val) {\n\
+             \t\t\tthis.diameter = val;\n\
+             \t\t}\n\
+             \n\
+             \t}\n\
+             \n\
+             \tpublic static final class Empty extends Shape {\n\
+             \t}\n\
+             }\n\n"
+        );
+
+        let jni = &outputs["jni.rs"];
+        assert!(jni.contains("Shape :: Circle"));
+        assert!(jni.contains("Shape$Circle"));
+        assert!(jni.contains("Shape :: Empty"));
+    }
+
+    #[test]
+    fn unions() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        let item: syn::ItemUnion = unwrap!(syn::parse_str(
+            "#[repr(C)] pub union Value { pub integer: i32, pub real: f64 }"
+        ));
+        unwrap!(common::Lang::parse_union(
+            &mut lang,
+            &item,
+            &["ffi".to_string()],
+            &mut outputs
+        ));
+
+        assert_eq!(
+            outputs["Value.java"],
+            "package net.maidsafe.bindings;\n\n\
+             public class Value {\n\
+             \tprivate int integer;\n\
+             \tprivate double real;\n\
+             \tprivate int activeField;\n\
+             \n\
+             \tpublic Value() {\n\
+             \n\
+             \t}\n\
+             \tpublic int getInteger() {\n\
+             \t\treturn integer;\n\
+             \t}\n\
+             \n\
+             \tpublic void setInteger(final int val) {\n\
+             \t\tthis.integer = val;\n\
+             \t\tthis.activeField = 0;\n\
+             \t}\n\
+             \n\
+             \tpublic double getReal() {\n\
+             \t\treturn real;\n\
+             \t}\n\
+             \n\
+             \tpublic void setReal(final double val) {\n\
+             \t\tthis.real = val;\n\
+             \t\tthis.activeField = 1;\n\
+             \t}\n\
+             \n\
+             \tpublic int getActiveField() {\n\
+             \t\treturn activeField;\n\
+             \t}\n\
+             \n\
+             }\n\n"
+        );
+
+        // The native union is initialised from the field set last
+        let jni = &outputs["jni.rs"];
+        assert!(jni.contains("0i32 => {"));
+        assert!(jni.contains("Ok ( Value { integer } )"));
+        assert!(jni.contains("1i32 => {"));
+        assert!(jni.contains("Ok ( Value { real } )"));
+        assert!(jni.contains("unsafe { self . real }"));
+        assert!(jni.contains(
+            "let sizes = [ :: std :: mem :: size_of :: < i32 > ( ) , \
+             :: std :: mem :: size_of :: < f64 > ( ) ] ;"
+        ));
+    }
+
+    #[test]
+    fn tuple_and_transparent_structs() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        for source in &[
+            "#[repr(transparent)] pub struct Handle(u64);",
+            "#[repr(C)] pub struct Pair(pub u32, pub u8);",
+        ] {
+            let item: syn::ItemStruct = unwrap!(syn::parse_str(source));
+            unwrap!(common::Lang::parse_struct(
+                &mut lang,
+                &item,
+                &["ffi".to_string()],
+                &mut outputs
+            ));
+        }
+
+        assert_eq!(
+            outputs["Handle.java"],
+            "package net.maidsafe.bindings;\n\n\
+             public final class Handle {\n\
+             \tprivate final long value;\n\
+             \n\
+             \tpublic Handle(final long value) {\n\
+             \t\tthis.value = value;\n\
+             \t}\n\
+             \n\
+             \tpublic long getValue() {\n\
+             \t\treturn value;\n\
+             \t}\n\
+             \n\
+             \t@Override\n\
+             \tpublic boolean equals(final Object other) {\n\
+             \t\treturn other instanceof Handle && ((Handle) other).value == value;\n\
+             \t}\n\
+             \n\
+             \t@Override\n\
+             \tpublic int hashCode() {\n\
+             \t\treturn Long.hashCode(value);\n\
+             \t}\n\
+             }\n\n"
+        );
+        assert!(outputs["Pair.java"].contains("\tprivate int field0;\n\tprivate byte field1;\n"));
+
+        let jni = &outputs["jni.rs"];
+        assert!(jni.contains("Ok ( Handle ( value ) )"));
+        assert!(jni.contains("\"(J)V\""));
+        assert!(jni.contains("Ok ( Pair ( field_0 , field_1 ) )"));
+        assert!(jni.contains("self . 1"));
+    }
+
+    #[test]
+    fn statics() {
+        let mut lang = LangJava::new(HashMap::new());
+        let mut outputs = Outputs::default();
+        for source in &[
+            "#[no_mangle] pub static VERSION: *const c_char = ptr::null();",
+            "#[no_mangle] pub static mut COUNTER: u64 = 0;",
+            "#[no_mangle] pub static HANDLER: *const Handler = ptr::null();",
+            "pub static IGNORED: u32 = 0;",
+        ] {
+            let item: syn::ItemStatic = unwrap!(syn::parse_str(source));
+            unwrap!(common::Lang::parse_static(
+                &mut lang,
+                &item,
+                &["ffi".to_string()],
+                &mut outputs
+            ));
+        }
+
+        assert_eq!(
+            outputs["NativeBindings.java"],
+            "/**\n */\n\
+             public static native String getVersion();\n\n\
+             /**\n */\n\
+             public static native long getCounter();\n\n\
+             public static native void setCounter(long value);\n\n"
+        );
+
+        let jni = &outputs["jni.rs"];
+        assert!(jni.contains("static VERSION : * const c_char ;"));
+        assert!(jni.contains("static mut COUNTER : u64 ;"));
+        assert!(jni.contains("fn Java_net_maidsafe_bindings_NativeBindings_getCounter"));
+        assert!(jni.contains("COUNTER = value as u64 ;"));
+        assert!(!jni.contains("HANDLER"));
+    }
+
+    #[test]
+    fn provided_types() {
+        let mut lang = LangJava::new(HashMap::new());
+        lang.add_provided_package("ffi_utils", "net.maidsafe.utils");
+        let mut outputs = Outputs::default();
+        for (source, module) in &[
+            (
+                "#[repr(C)] pub struct FfiResult { pub error_code: i32 }",
+                "ffi_utils",
+            ),
+            (
+                "#[repr(C)] pub struct Status { pub result: FfiResult }",
+                "ffi",
+            ),
+        ] {
+            let item: syn::ItemStruct = unwrap!(syn::parse_str(source));
+            unwrap!(common::Lang::parse_struct(
+                &mut lang,
+                &item,
+                &[module.to_string()],
+                &mut outputs
+            ));
+        }
+        lang.add_provided_imports(&mut outputs);
+
+        // Only the JNI conversions of the provided class are generated
+        assert!(!outputs.contains_key("FfiResult.java"));
+        assert!(outputs["jni.rs"].contains("net/maidsafe/utils/FfiResult"));
+        assert!(outputs["Status.java"]
+            .starts_with("package net.maidsafe.bindings;\n\nimport net.maidsafe.utils.*;\n"));
+    }
 }
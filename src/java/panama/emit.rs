@@ -0,0 +1,1009 @@
+//! Functions for emitting the Java code of the Panama bindings.
+
+use super::{Context, Field, Kind, Method, Param, StaticBinding, Struct, Stub};
+use crate::java::types::{java_type_to_str, struct_to_java_classname};
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use jni::signature::{JavaType, Primitive};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Classes the generated code may refer to, imported when they're used.
+const IMPORTS: &[(&str, &str)] = &[
+    ("Arena", "java.lang.foreign.Arena"),
+    ("FunctionDescriptor", "java.lang.foreign.FunctionDescriptor"),
+    ("GroupLayout", "java.lang.foreign.GroupLayout"),
+    ("Linker", "java.lang.foreign.Linker"),
+    ("MemoryLayout", "java.lang.foreign.MemoryLayout"),
+    ("MemorySegment", "java.lang.foreign.MemorySegment"),
+    ("SegmentAllocator", "java.lang.foreign.SegmentAllocator"),
+    ("StructLayout", "java.lang.foreign.StructLayout"),
+    ("SymbolLookup", "java.lang.foreign.SymbolLookup"),
+    ("UnionLayout", "java.lang.foreign.UnionLayout"),
+    ("MethodHandle", "java.lang.invoke.MethodHandle"),
+    ("MethodHandles", "java.lang.invoke.MethodHandles"),
+    ("ArrayList", "java.util.ArrayList"),
+    ("Arrays", "java.util.Arrays"),
+    ("List", "java.util.List"),
+    ("Map", "java.util.Map"),
+    ("Objects", "java.util.Objects"),
+    (
+        "ConcurrentHashMap",
+        "java.util.concurrent.ConcurrentHashMap",
+    ),
+    ("AtomicLong", "java.util.concurrent.atomic.AtomicLong"),
+    ("Function", "java.util.function.Function"),
+    ("IntFunction", "java.util.function.IntFunction"),
+];
+
+/// Generic conversions used by the conversions of the structures and by `NativeBindings`.
+const LAYOUT_HELPERS: &str = r#"
+/**
+ * Lays out the members of a structure, padding them as a C compiler does.
+ */
+static StructLayout structLayout(MemoryLayout... members) {
+	List<MemoryLayout> elements = new ArrayList<>();
+	long size = 0;
+	long alignment = 1;
+	for (MemoryLayout member : members) {
+		long padding = (member.byteAlignment() - size % member.byteAlignment()) % member.byteAlignment();
+		if (padding > 0) {
+			elements.add(MemoryLayout.paddingLayout(padding));
+		}
+		elements.add(member);
+		size += padding + member.byteSize();
+		alignment = Math.max(alignment, member.byteAlignment());
+	}
+	long padding = (alignment - size % alignment) % alignment;
+	if (padding > 0) {
+		elements.add(MemoryLayout.paddingLayout(padding));
+	}
+	return MemoryLayout.structLayout(elements.toArray(new MemoryLayout[0]));
+}
+
+static long offset(GroupLayout layout, String member) {
+	return layout.byteOffset(MemoryLayout.PathElement.groupElement(member));
+}
+
+static MemorySegment elements(MemorySegment address, long count, MemoryLayout layout) {
+	return address.reinterpret(count * layout.byteSize());
+}
+
+static String readString(MemorySegment address) {
+	return address.address() == 0 ? null : address.reinterpret(Long.MAX_VALUE).getString(0);
+}
+
+static MemorySegment allocateString(Arena arena, String value) {
+	return value == null ? MemorySegment.NULL : arena.allocateFrom(value);
+}
+
+static <T> T readPointer(MemorySegment address, MemoryLayout layout, Function<MemorySegment, T> read) {
+	return address.address() == 0 ? null : read.apply(address.reinterpret(layout.byteSize()));
+}
+
+static <T> MemorySegment allocatePointer(Arena arena, T value, MemoryLayout layout, Writer<T> write) {
+	return value == null ? MemorySegment.NULL : allocateStruct(arena, value, layout, write);
+}
+
+static <T> MemorySegment allocateStruct(Arena arena, T value, MemoryLayout layout, Writer<T> write) {
+	MemorySegment segment = arena.allocate(layout);
+	write.write(segment, arena, value);
+	return segment;
+}
+
+static <T> T[] readArray(MemorySegment address, long count, MemoryLayout layout, IntFunction<T[]> create, Function<MemorySegment, T> read) {
+	MemorySegment items = elements(address, count, layout);
+	T[] values = create.apply((int) count);
+	for (int i = 0; i < values.length; i++) {
+		values[i] = read.apply(items.asSlice(i * layout.byteSize(), layout.byteSize()));
+	}
+	return values;
+}
+
+static <T> MemorySegment allocateArray(Arena arena, T[] values, MemoryLayout layout, Writer<T> write) {
+	MemorySegment items = arena.allocate(layout, values.length);
+	for (int i = 0; i < values.length; i++) {
+		write.write(items.asSlice(i * layout.byteSize(), layout.byteSize()), arena, values[i]);
+	}
+	return items;
+}
+
+/**
+ * Writes a value into native memory, allocating the memory it points to in the arena.
+ */
+@FunctionalInterface
+interface Writer<T> {
+	void write(MemorySegment segment, Arena arena, T value);
+}
+"#;
+
+const LOOKUP_HELPERS: &str = r#"
+private static SymbolLookup library(String name) {
+	System.loadLibrary(name);
+	return SymbolLookup.loaderLookup();
+}
+
+private static MemorySegment find(String name) {
+	return LOOKUP.find(name).orElseThrow(() -> new UnsatisfiedLinkError("symbol not found: " + name));
+}
+"#;
+
+const DOWNCALL_HELPERS: &str = r#"
+private static MethodHandle downcall(String name, FunctionDescriptor descriptor) {
+	return LINKER.downcallHandle(find(name), descriptor);
+}
+
+private static RuntimeException propagate(Throwable error) {
+	if (error instanceof RuntimeException) {
+		return (RuntimeException) error;
+	}
+	if (error instanceof Error) {
+		throw (Error) error;
+	}
+	return new IllegalStateException(error);
+}
+"#;
+
+const UPCALL_HELPERS: &str = r#"
+private static MemorySegment upcall(String method, FunctionDescriptor descriptor) {
+	try {
+		MethodHandle target = MethodHandles.lookup()
+			.findStatic(NativeBindings.class, method, descriptor.toMethodType());
+		return LINKER.upcallStub(target, descriptor, Arena.global());
+	} catch (ReflectiveOperationException e) {
+		throw new AssertionError(e);
+	}
+}
+
+/**
+ * Keeps the callbacks of a call until one of them is called, returning the user data
+ * identifying them.
+ */
+private static MemorySegment register(Object callbacks) {
+	long id = NEXT_CALLBACK.getAndIncrement();
+	CALLBACKS.put(id, callbacks);
+	return MemorySegment.ofAddress(id);
+}
+
+/**
+ * Exceptions can't propagate into native code, so they're reported as uncaught instead.
+ */
+private static void report(Throwable error) {
+	Thread thread = Thread.currentThread();
+	thread.getUncaughtExceptionHandler().uncaughtException(thread, error);
+}
+"#;
+
+const TAKE_HELPER: &str = r#"
+/**
+ * Takes one of the callbacks of a call, forgetting them once they've all been taken.
+ */
+private static Object take(MemorySegment userData, int index) {
+	Object[] callbacks = (Object[]) CALLBACKS.get(userData.address());
+	if (callbacks == null) {
+		return null;
+	}
+	synchronized (callbacks) {
+		Object callback = callbacks[index];
+		callbacks[index] = null;
+		if (Arrays.stream(callbacks).allMatch(Objects::isNull)) {
+			CALLBACKS.remove(userData.address());
+		}
+		return callback;
+	}
+}
+"#;
+
+const STATIC_HELPERS: &str = r#"
+private static MemorySegment symbol(String name, MemoryLayout layout) {
+	return find(name).reinterpret(layout.byteSize());
+}
+"#;
+
+/// Emits the `NativeLayouts` class, holding the layouts of the structures and their
+/// conversions from and into their classes.
+pub fn emit_layouts(layouts: &[(&Struct, &[Field])], context: &Context) -> String {
+    let mut body = IndentedWriter::with_tabs();
+    body.indent();
+
+    for (item, fields) in layouts {
+        emit_layout(&mut body, item, fields, context);
+    }
+    if !layouts.is_empty() {
+        emitln!(body, "");
+    }
+    emitln!(body, "private NativeLayouts() {{");
+    emitln!(body, "}}");
+
+    for (item, fields) in layouts {
+        emit_read(&mut body, item, fields, context);
+        emit_write(&mut body, item, fields, context);
+    }
+    emit_code(&mut body, LAYOUT_HELPERS);
+    body.unindent();
+
+    let mut writer = IndentedWriter::with_tabs();
+    emit_header(&mut writer, &body, context);
+    emitln!(writer, "/**");
+    emitln!(
+        writer,
+        " * Native layouts of the structures of the library {{@code {}}}, along with the",
+        context.java.lib_name
+    );
+    emitln!(writer, " * conversions from and into their classes.");
+    emitln!(writer, " */");
+    emitln!(writer, "public final class NativeLayouts {{");
+    emit!(writer, "{}", *body);
+    emitln!(writer, "}}");
+
+    writer.into_inner()
+}
+
+/// Emits the `NativeBindings` class, wrapping the native functions and statics into static
+/// methods.
+pub fn emit_bindings(
+    methods: &[Method],
+    statics: &[StaticBinding],
+    stubs: &[Stub],
+    context: &Context,
+) -> String {
+    let mut body = IndentedWriter::with_tabs();
+    body.indent();
+
+    emitln!(
+        body,
+        "private static final Linker LINKER = Linker.nativeLinker();"
+    );
+    emitln!(
+        body,
+        "private static final SymbolLookup LOOKUP = library(\"{}\");",
+        context.java.lib_name
+    );
+    if !stubs.is_empty() {
+        emitln!(body, "/**");
+        emitln!(
+            body,
+            " * Callbacks of the pending calls, by their user data."
+        );
+        emitln!(body, " */");
+        emitln!(
+            body,
+            "private static final Map<Long, Object> CALLBACKS = new ConcurrentHashMap<>();"
+        );
+        emitln!(
+            body,
+            "private static final AtomicLong NEXT_CALLBACK = new AtomicLong(1);"
+        );
+    }
+    emitln!(body, "");
+
+    for method in methods {
+        let params = descriptor_params(&method.params, context);
+        let descriptor = match method.ret {
+            Some(ref kind) => format!(
+                "FunctionDescriptor.of({})",
+                join(Some(layout(kind, context)), params)
+            ),
+            None => format!("FunctionDescriptor.ofVoid({})", params.join(", ")),
+        };
+        emitln!(
+            body,
+            "private static final MethodHandle {} = downcall(\"{}\", {});",
+            handle_constant(&method.name),
+            method.name,
+            descriptor
+        );
+    }
+    for stub in stubs {
+        emitln!(
+            body,
+            "private static final MemorySegment {} = upcall(\"{}\", FunctionDescriptor.ofVoid({}));",
+            stub_constant(&stub.method),
+            stub.method,
+            descriptor_params(&stub.params, context).join(", ")
+        );
+    }
+    for binding in statics {
+        emitln!(
+            body,
+            "private static final MemorySegment {} = symbol(\"{}\", {});",
+            symbol_constant(&binding.name),
+            binding.name,
+            layout(&binding.kind, context)
+        );
+    }
+
+    emitln!(body, "");
+    emitln!(body, "private NativeBindings() {{");
+    emitln!(body, "}}");
+
+    for method in methods {
+        emit_method(&mut body, method, context);
+    }
+    for binding in statics {
+        emit_static(&mut body, binding, context);
+    }
+    for stub in stubs {
+        emit_stub(&mut body, stub, context);
+    }
+
+    emit_code(&mut body, LOOKUP_HELPERS);
+    if !methods.is_empty() {
+        emit_code(&mut body, DOWNCALL_HELPERS);
+    }
+    if !stubs.is_empty() {
+        emit_code(&mut body, UPCALL_HELPERS);
+    }
+    if stubs.iter().any(|stub| stub.index.is_some()) {
+        emit_code(&mut body, TAKE_HELPER);
+    }
+    if !statics.is_empty() {
+        emit_code(&mut body, STATIC_HELPERS);
+    }
+    body.unindent();
+
+    let mut writer = IndentedWriter::with_tabs();
+    emit_header(&mut writer, &body, context);
+    emitln!(writer, "/**");
+    emitln!(
+        writer,
+        " * Calls into the native library {{@code {}}} through the Foreign Function & Memory API,",
+        context.java.lib_name
+    );
+    emitln!(
+        writer,
+        " * which requires native access to be enabled (e.g. {{@code --enable-native-access=ALL-UNNAMED}})."
+    );
+    emitln!(writer, " */");
+    emitln!(writer, "public final class NativeBindings {{");
+    emit!(writer, "{}", *body);
+    emitln!(writer, "}}");
+
+    writer.into_inner()
+}
+
+/// Emits the package declaration and the imports of the classes `body` refers to.
+fn emit_header(writer: &mut IndentedWriter, body: &str, context: &Context) {
+    emitln!(writer, "package {};\n", context.java.namespace);
+
+    // Callback interfaces are declared in the model namespace
+    if context.java.namespace_model != context.java.namespace && body.contains("Callback") {
+        emitln!(writer, "import {}.*;", context.java.namespace_model);
+    }
+    for (name, import) in IMPORTS {
+        if contains_word(body, name) {
+            emitln!(writer, "import {};", import);
+        }
+    }
+    if body.contains("JAVA_") || contains_word(body, "ADDRESS") {
+        emitln!(writer, "\nimport static java.lang.foreign.ValueLayout.*;");
+    }
+    emitln!(writer, "");
+}
+
+/// Emits a block of code, indented at the current level.
+fn emit_code(writer: &mut IndentedWriter, code: &str) {
+    emit!(writer, "{}", code);
+}
+
+fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    if docs.is_empty() {
+        return;
+    }
+    emitln!(writer, "/**");
+    for line in docs.lines() {
+        emitln!(writer, "{}", line.replacen("///", " *", 1));
+    }
+    emitln!(writer, " */");
+}
+
+fn emit_layout(writer: &mut IndentedWriter, item: &Struct, fields: &[Field], context: &Context) {
+    let (ty, constructor) = if item.union {
+        ("UnionLayout", "MemoryLayout.unionLayout")
+    } else {
+        ("StructLayout", "structLayout")
+    };
+    emitln!(
+        writer,
+        "public static final {} {} = {}(",
+        ty,
+        item.name.to_screaming_snake_case(),
+        constructor
+    );
+    writer.indent();
+    for (idx, field) in fields.iter().enumerate() {
+        let member = layout(&field.kind, context);
+        // Members of packed structures aren't aligned
+        let alignment = if item.packed {
+            ".withByteAlignment(1)"
+        } else {
+            ""
+        };
+        emitln!(
+            writer,
+            "{}{}.withName(\"{}\"){}",
+            member,
+            alignment,
+            field.name,
+            if idx + 1 < fields.len() { "," } else { "" }
+        );
+    }
+    writer.unindent();
+    emitln!(writer, ");");
+}
+
+fn emit_read(writer: &mut IndentedWriter, item: &Struct, fields: &[Field], context: &Context) {
+    let class = struct_to_java_classname(&item.name);
+    let layout = item.name.to_screaming_snake_case();
+
+    emitln!(writer, "");
+    emitln!(writer, "/**");
+    emitln!(writer, " * Reads a {{@code {}}} from native memory.", class);
+    emitln!(writer, " */");
+    emitln!(
+        writer,
+        "public static {} read{}(MemorySegment segment) {{",
+        class,
+        class
+    );
+    writer.indent();
+    emitln!(writer, "{0} value = new {0}();", class);
+    // The largest field of a union holds all its bytes, so it's set last to be the active one
+    let mut fields: Vec<_> = fields.iter().collect();
+    if item.union {
+        let largest = (0..fields.len()).fold(0, |largest, idx| {
+            if size(&fields[idx].kind) > size(&fields[largest].kind) {
+                idx
+            } else {
+                largest
+            }
+        });
+        let field = fields.remove(largest);
+        fields.push(field);
+    }
+    for field in fields {
+        let raw = read_member(&layout, &field.name, &field.kind, context);
+        let len = field
+            .len
+            .as_ref()
+            .map(|len| read_member(&layout, len, &Kind::Primitive(Primitive::Long), context));
+        emitln!(
+            writer,
+            "value.set{}({});",
+            accessor(&field.name),
+            from_native(&field.kind, &raw, len.as_deref(), context)
+        );
+    }
+    emitln!(writer, "return value;");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+fn emit_write(writer: &mut IndentedWriter, item: &Struct, fields: &[Field], context: &Context) {
+    let class = struct_to_java_classname(&item.name);
+    let layout = item.name.to_screaming_snake_case();
+
+    emitln!(writer, "");
+    emitln!(writer, "/**");
+    emitln!(
+        writer,
+        " * Writes a {{@code {}}} into native memory, allocating the memory it points to in the arena.",
+        class
+    );
+    if item.union {
+        emitln!(
+            writer,
+            " * Only the active field is written, as the fields overlap."
+        );
+    }
+    emitln!(writer, " */");
+    emitln!(
+        writer,
+        "public static void write{}(MemorySegment segment, Arena arena, {} value) {{",
+        class,
+        class
+    );
+    writer.indent();
+
+    if item.union {
+        emitln!(writer, "switch (value.getActiveField()) {{");
+        writer.indent();
+        for (idx, field) in fields.iter().enumerate() {
+            emitln!(writer, "case {} -> {{", idx);
+            writer.indent();
+            emit_write_field(writer, &layout, field, fields, context);
+            writer.unindent();
+            emitln!(writer, "}}");
+        }
+        writer.unindent();
+        emitln!(writer, "}}");
+    } else {
+        for field in fields {
+            emit_write_field(writer, &layout, field, fields, context);
+        }
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Writes a field of a structure into native memory.
+fn emit_write_field(
+    writer: &mut IndentedWriter,
+    layout: &str,
+    field: &Field,
+    fields: &[Field],
+    context: &Context,
+) {
+    // The lengths and capacities of arrays are the lengths of the Java arrays
+    let is_len = fields.iter().any(|array| {
+        array.len.as_ref() == Some(&field.name) || array.cap.as_ref() == Some(&field.name)
+    });
+    if is_len {
+        return;
+    }
+
+    let value = format!("value.get{}()", accessor(&field.name));
+    let offset = format!("offset({}, \"{}\")", layout, field.name);
+    match field.kind {
+        Kind::Struct(ref name) => emitln!(
+            writer,
+            "{}.write{}(segment.asSlice({}, {}.byteSize()), arena, {});",
+            layouts_class(name, context),
+            struct_to_java_classname(name),
+            offset,
+            layout_constant(name, context),
+            value
+        ),
+        ref kind => {
+            let native = to_native(kind, &value, context);
+            emitln!(
+                writer,
+                "segment.set({}, {}, {});",
+                self::layout(kind, context),
+                offset,
+                native[0]
+            );
+            for len in field.len.iter().chain(field.cap.iter()) {
+                emitln!(
+                    writer,
+                    "segment.set(JAVA_LONG, offset({}, \"{}\"), {});",
+                    layout,
+                    len,
+                    native[1]
+                );
+            }
+        }
+    }
+}
+
+fn emit_method(writer: &mut IndentedWriter, method: &Method, context: &Context) {
+    let callbacks: Vec<_> = method
+        .params
+        .iter()
+        .filter_map(|param| match *param {
+            Param::Callback {
+                ref name, nullable, ..
+            } => Some((name, nullable)),
+            _ => None,
+        })
+        .collect();
+
+    let mut args = Vec::new();
+    let mut needs_arena = false;
+    for param in &method.params {
+        match *param {
+            Param::UserData => args.push(match callbacks.as_slice() {
+                [] => "MemorySegment.NULL".to_string(),
+                [(name, true)] => format!("{0} == null ? MemorySegment.NULL : register({0})", name),
+                [(name, false)] => format!("register({})", name),
+                _ => {
+                    let names: Vec<_> = callbacks.iter().map(|(name, _)| name.as_str()).collect();
+                    format!("register(new Object[] {{{}}})", names.join(", "))
+                }
+            }),
+            Param::Value { ref name, ref kind } => {
+                needs_arena |= allocates(kind);
+                args.extend(to_native(kind, name, context));
+            }
+            Param::Callback {
+                ref name,
+                nullable,
+                ref stub,
+                ..
+            } => args.push(if nullable {
+                format!("{} == null ? MemorySegment.NULL : {}", name, stub)
+            } else {
+                stub.clone()
+            }),
+        }
+    }
+    // Structures returned by value are allocated in the arena
+    if let Some(Kind::Struct(..)) = method.ret {
+        needs_arena = true;
+        args.insert(0, "(SegmentAllocator) arena".to_string());
+    }
+
+    let call = format!(
+        "{}.invokeExact({})",
+        handle_constant(&method.name),
+        args.join(", ")
+    );
+    let stmt = match method.ret {
+        Some(ref kind) => {
+            let call = format!("({}) {}", native_type(kind), call);
+            format!("return {};", from_native(kind, &call, None, context))
+        }
+        None => format!("{};", call),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, &method.docs);
+    emitln!(
+        writer,
+        "public static {} {}({}) {{",
+        method.ret_ty,
+        method.java_name,
+        method.args.join(", ")
+    );
+    writer.indent();
+    if needs_arena {
+        emitln!(writer, "try (Arena arena = Arena.ofConfined()) {{");
+    } else {
+        emitln!(writer, "try {{");
+    }
+    writer.indent();
+    emitln!(writer, "{}", stmt);
+    writer.unindent();
+    emitln!(writer, "}} catch (Throwable e) {{");
+    writer.indent();
+    emitln!(writer, "throw propagate(e);");
+    writer.unindent();
+    emitln!(writer, "}}");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+fn emit_static(writer: &mut IndentedWriter, binding: &StaticBinding, context: &Context) {
+    let symbol = symbol_constant(&binding.name);
+    let layout = layout(&binding.kind, context);
+    let raw = match binding.kind {
+        Kind::Struct(..) => symbol.clone(),
+        _ => format!("{}.get({}, 0)", symbol, layout),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, &binding.docs);
+    emitln!(
+        writer,
+        "public static {} get{}() {{",
+        binding.java_ty,
+        binding.name.to_pascal_case()
+    );
+    writer.indent();
+    emitln!(
+        writer,
+        "return {};",
+        from_native(&binding.kind, &raw, None, context)
+    );
+    writer.unindent();
+    emitln!(writer, "}}");
+
+    if binding.mutable {
+        emitln!(writer, "");
+        emitln!(
+            writer,
+            "public static void set{}({} value) {{",
+            binding.name.to_pascal_case(),
+            binding.java_ty
+        );
+        writer.indent();
+        emitln!(writer, "{}.set({}, 0, value);", symbol, layout);
+        writer.unindent();
+        emitln!(writer, "}}");
+    }
+}
+
+fn emit_stub(writer: &mut IndentedWriter, stub: &Stub, context: &Context) {
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    for param in &stub.params {
+        match *param {
+            Param::UserData => params.push("MemorySegment userData".to_string()),
+            Param::Value { ref name, ref kind } => {
+                params.push(format!("{} {}", native_type(kind), name));
+                let len = format!("{}Len", name);
+                if let Kind::Array(..) = *kind {
+                    params.push(format!("long {}", len));
+                }
+                args.push(from_native(kind, name, Some(&len), context));
+            }
+            Param::Callback { .. } => unreachable!(),
+        }
+    }
+
+    emitln!(writer, "");
+    emitln!(
+        writer,
+        "private static void {}({}) {{",
+        stub.method,
+        params.join(", ")
+    );
+    writer.indent();
+    match stub.index {
+        Some(index) => {
+            emitln!(
+                writer,
+                "{0} callback = ({0}) take(userData, {1});",
+                stub.class,
+                index
+            );
+            emitln!(writer, "if (callback == null) {{");
+            emitln!(writer, "\treturn;");
+            emitln!(writer, "}}");
+        }
+        None => {
+            emitln!(
+                writer,
+                "{0} callback = ({0}) CALLBACKS.remove(userData.address());",
+                stub.class
+            );
+            emitln!(writer, "if (callback == null) {{");
+            emitln!(writer, "\treturn;");
+            emitln!(writer, "}}");
+        }
+    }
+    emitln!(writer, "try {{");
+    emitln!(writer, "\tcallback.call({});", args.join(", "));
+    emitln!(writer, "}} catch (Throwable e) {{");
+    emitln!(writer, "\treport(e);");
+    emitln!(writer, "}}");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Returns the expression reading a member of a structure from `segment`.
+fn read_member(layout: &str, member: &str, kind: &Kind, context: &Context) -> String {
+    let offset = format!("offset({}, \"{}\")", layout, member);
+    match *kind {
+        Kind::Struct(ref name) => format!(
+            "segment.asSlice({}, {}.byteSize())",
+            offset,
+            layout_constant(name, context)
+        ),
+        ref kind => format!("segment.get({}, {})", self::layout(kind, context), offset),
+    }
+}
+
+/// Returns the expression converting the native value `raw` into Java. Arrays are also given
+/// the expression of their length.
+fn from_native(kind: &Kind, raw: &str, len: Option<&str>, context: &Context) -> String {
+    match *kind {
+        Kind::Primitive(..) => raw.to_string(),
+        Kind::Address => format!("{}.address()", raw),
+        Kind::String => format!("NativeLayouts.readString({})", raw),
+        Kind::Struct(ref name) => format!(
+            "{}.read{}({})",
+            layouts_class(name, context),
+            struct_to_java_classname(name),
+            raw
+        ),
+        Kind::StructPtr { ref name, .. } => format!(
+            "NativeLayouts.readPointer({}, {}, {})",
+            raw,
+            layout_constant(name, context),
+            reader(name, context)
+        ),
+        Kind::Transparent(ref name, _) => {
+            format!("new {}({})", struct_to_java_classname(name), raw)
+        }
+        Kind::Array(ref elem) => {
+            let len = unwrap!(len);
+            match **elem {
+                Kind::Struct(ref name) => format!(
+                    "NativeLayouts.readArray({}, {}, {}, {}[]::new, {})",
+                    raw,
+                    len,
+                    layout_constant(name, context),
+                    struct_to_java_classname(name),
+                    reader(name, context)
+                ),
+                ref elem => {
+                    let layout = layout(elem, context);
+                    format!(
+                        "NativeLayouts.elements({}, {}, {}).toArray({})",
+                        raw, len, layout, layout
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Returns the expressions converting the Java `value` into native arguments: the pointer and
+/// length for arrays, a single value otherwise.
+fn to_native(kind: &Kind, value: &str, context: &Context) -> Vec<String> {
+    match *kind {
+        Kind::Primitive(..) => vec![value.to_string()],
+        Kind::Address => vec![format!("MemorySegment.ofAddress({})", value)],
+        Kind::String => vec![format!("NativeLayouts.allocateString(arena, {})", value)],
+        Kind::Struct(ref name) => vec![format!(
+            "NativeLayouts.allocateStruct(arena, {}, {}, {})",
+            value,
+            layout_constant(name, context),
+            writer(name, context)
+        )],
+        Kind::StructPtr { ref name, required } => {
+            let value = if required {
+                format!(
+                    "Objects.requireNonNull({0}, \"{0} must not be null\")",
+                    value
+                )
+            } else {
+                value.to_string()
+            };
+            vec![format!(
+                "NativeLayouts.allocatePointer(arena, {}, {}, {})",
+                value,
+                layout_constant(name, context),
+                writer(name, context)
+            )]
+        }
+        Kind::Transparent(..) => vec![format!("{}.getValue()", value)],
+        Kind::Array(ref elem) => {
+            let ptr = match **elem {
+                Kind::Struct(ref name) => format!(
+                    "NativeLayouts.allocateArray(arena, {}, {}, {})",
+                    value,
+                    layout_constant(name, context),
+                    writer(name, context)
+                ),
+                ref elem => format!("arena.allocateFrom({}, {})", layout(elem, context), value),
+            };
+            vec![ptr, format!("(long) {}.length", value)]
+        }
+    }
+}
+
+/// Whether converting the value into native memory allocates memory.
+fn allocates(kind: &Kind) -> bool {
+    match *kind {
+        Kind::Primitive(..) | Kind::Address | Kind::Transparent(..) => false,
+        Kind::String | Kind::Struct(..) | Kind::StructPtr { .. } | Kind::Array(..) => true,
+    }
+}
+
+/// Returns the layouts of the native parameters of a function or callback.
+fn descriptor_params(params: &[Param], context: &Context) -> Vec<String> {
+    let mut layouts = Vec::new();
+    for param in params {
+        match *param {
+            Param::Value { ref kind, .. } => {
+                layouts.push(layout(kind, context));
+                if let Kind::Array(..) = *kind {
+                    layouts.push("JAVA_LONG".to_string());
+                }
+            }
+            Param::UserData | Param::Callback { .. } => layouts.push("ADDRESS".to_string()),
+        }
+    }
+    layouts
+}
+
+/// Returns the layout of the native representation of values.
+fn layout(kind: &Kind, context: &Context) -> String {
+    match *kind {
+        Kind::Primitive(primitive) | Kind::Transparent(_, primitive) => {
+            value_layout(primitive).to_string()
+        }
+        Kind::Struct(ref name) => layout_constant(name, context),
+        Kind::Address | Kind::String | Kind::StructPtr { .. } | Kind::Array(..) => {
+            "ADDRESS".to_string()
+        }
+    }
+}
+
+/// Returns the Java type of the native representation of values.
+fn native_type(kind: &Kind) -> String {
+    match *kind {
+        Kind::Primitive(primitive) | Kind::Transparent(_, primitive) => {
+            unwrap!(java_type_to_str(&JavaType::Primitive(primitive)))
+        }
+        _ => "MemorySegment".to_string(),
+    }
+}
+
+fn value_layout(primitive: Primitive) -> &'static str {
+    match primitive {
+        Primitive::Boolean => "JAVA_BOOLEAN",
+        Primitive::Byte => "JAVA_BYTE",
+        Primitive::Char => "JAVA_CHAR",
+        Primitive::Double => "JAVA_DOUBLE",
+        Primitive::Float => "JAVA_FLOAT",
+        Primitive::Int => "JAVA_INT",
+        Primitive::Long => "JAVA_LONG",
+        Primitive::Short => "JAVA_SHORT",
+        Primitive::Void => unreachable!(),
+    }
+}
+
+/// Size in bytes of the members of unions, which are primitives.
+fn size(kind: &Kind) -> usize {
+    match *kind {
+        Kind::Primitive(primitive) | Kind::Transparent(_, primitive) => match primitive {
+            Primitive::Boolean | Primitive::Byte => 1,
+            Primitive::Char | Primitive::Short => 2,
+            Primitive::Float | Primitive::Int => 4,
+            Primitive::Double | Primitive::Long => 8,
+            Primitive::Void => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Returns the `NativeLayouts` class holding the layout of a structure, which is qualified
+/// for the structures of dependency crates.
+fn layouts_class(name: &str, context: &Context) -> String {
+    match context.provided(name) {
+        Some(namespace) => format!("{}.NativeLayouts", namespace),
+        None => "NativeLayouts".to_string(),
+    }
+}
+
+fn layout_constant(name: &str, context: &Context) -> String {
+    format!(
+        "{}.{}",
+        layouts_class(name, context),
+        name.to_screaming_snake_case()
+    )
+}
+
+fn reader(name: &str, context: &Context) -> String {
+    format!(
+        "{}::read{}",
+        layouts_class(name, context),
+        struct_to_java_classname(name)
+    )
+}
+
+fn writer(name: &str, context: &Context) -> String {
+    format!(
+        "{}::write{}",
+        layouts_class(name, context),
+        struct_to_java_classname(name)
+    )
+}
+
+/// Returns the name of the getters and setters of a field, as with `LangJava`.
+fn accessor(field: &str) -> String {
+    field.to_camel_case().to_class_case()
+}
+
+fn handle_constant(function: &str) -> String {
+    format!("{}_HANDLE", function.to_screaming_snake_case())
+}
+
+/// Returns the name of the constant holding the upcall stub calling `method`.
+pub fn stub_constant(method: &str) -> String {
+    format!(
+        "{}_STUB",
+        method.trim_start_matches("call").to_screaming_snake_case()
+    )
+}
+
+fn symbol_constant(name: &str) -> String {
+    format!("{}_SYMBOL", name.to_screaming_snake_case())
+}
+
+fn join(first: Option<String>, rest: Vec<String>) -> String {
+    first.into_iter().chain(rest).collect::<Vec<_>>().join(", ")
+}
+
+/// Whether `word` appears in `code` as a whole identifier.
+fn contains_word(code: &str, word: &str) -> bool {
+    code.match_indices(word).any(|(idx, _)| {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let before = code[..idx].chars().next_back();
+        let after = code[idx + word.len()..].chars().next();
+        before.filter(|&c| is_ident(c)).is_none() && after.filter(|&c| is_ident(c)).is_none()
+    })
+}
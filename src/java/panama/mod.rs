@@ -0,0 +1,877 @@
+//! Functions for generating Java bindings calling the native library through the Foreign
+//! Function & Memory API (JDK 22+), instead of the JNI glue of `LangJava`.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use super::types::{
+    callback_name, java_type_to_str, rust_to_java, rust_ty_to_java, struct_to_java_classname,
+};
+use super::{transform_callback, LangJava};
+use crate::common::{
+    self, check_no_mangle, is_array_arg, is_array_arg_barefn, is_user_data_arg,
+    is_user_data_arg_barefn, nullable_fn_ptr, parse_attr, retrieve_docstring, safe_ptr,
+    take_out_pat, transform_fnarg_to_argcap, FilterMode, Lang, Outputs,
+};
+use crate::struct_field::{transform_struct_fields, StructField};
+use crate::{Error, Level};
+use inflector::Inflector;
+use jni::signature::{JavaType, Primitive};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use unwrap::unwrap;
+
+/// Generates the same model classes and callback interfaces as `LangJava`, but with a
+/// `NativeBindings` class implemented in pure Java: functions are called through
+/// `MethodHandle` downcalls, structures are described with `MemoryLayout`s and callbacks are
+/// upcall stubs. The native library doesn't need any JNI code compiled into it.
+pub struct LangJavaPanama {
+    java: LangJava,
+    /// `#[repr(C)]` structures and unions, in declaration order.
+    structs: Vec<Struct>,
+    /// `#[repr(transparent)]` structures wrapping a primitive, by name.
+    transparent: HashMap<String, Primitive>,
+    functions: Vec<Function>,
+    statics: Vec<Static>,
+}
+
+/// A `#[repr(C)]` structure or union.
+pub struct Struct {
+    name: String,
+    fields: Vec<StructField>,
+    packed: bool,
+    union: bool,
+    /// Namespace of the bindings generating the layout, if provided by a dependency crate.
+    provided: Option<String>,
+}
+
+pub struct Function {
+    name: String,
+    decl: syn::FnDecl,
+    docs: String,
+}
+
+pub struct Static {
+    item: syn::ItemStatic,
+    docs: String,
+}
+
+/// How a value is converted between Java and native code.
+#[derive(Clone, Debug)]
+pub enum Kind {
+    /// Primitive (or sized enum) passed as is.
+    Primitive(Primitive),
+    /// Opaque pointer mapped to `long` by the type map, passed as its address.
+    Address,
+    /// `*const c_char`, converted from and into `String`.
+    String,
+    /// `#[repr(C)]` structure passed by value.
+    Struct(String),
+    /// Pointer to a `#[repr(C)]` structure, converted from and into its class. Required
+    /// pointers (references, `NonNull` and `Box`) don't accept `null`.
+    StructPtr { name: String, required: bool },
+    /// `#[repr(transparent)]` structure wrapping a primitive.
+    Transparent(String, Primitive),
+    /// Pointer and length, converted from and into an array of the elements.
+    Array(Box<Kind>),
+}
+
+/// A field of a structure, along with its conversion.
+pub struct Field {
+    name: String,
+    kind: Kind,
+    /// Length and capacity fields of an array field.
+    len: Option<String>,
+    cap: Option<String>,
+}
+
+/// A parameter of a native function, or of a callback.
+pub enum Param {
+    /// `user_data: *mut c_void`, identifying the callbacks of a call.
+    UserData,
+    /// A value, or a pointer and length converted from an array.
+    Value { name: String, kind: Kind },
+    /// A callback, with the name of its upcall stub.
+    Callback {
+        name: String,
+        nullable: bool,
+        stub: String,
+    },
+}
+
+/// The upcall stub of a callback, calling the Java callback object given in the user data.
+pub struct Stub {
+    /// Name of the static method called by the stub.
+    method: String,
+    class: String,
+    params: Vec<Param>,
+    /// Index of the callback in the user data, if the function has several callbacks.
+    index: Option<usize>,
+}
+
+/// A native function wrapped by a static method of `NativeBindings`.
+pub struct Method {
+    name: String,
+    java_name: String,
+    docs: String,
+    params: Vec<Param>,
+    /// Java parameters of the method.
+    args: Vec<String>,
+    ret: Option<Kind>,
+    ret_ty: String,
+}
+
+/// The bindings of a static: a getter, and a setter for mutable primitives.
+pub struct StaticBinding {
+    name: String,
+    docs: String,
+    kind: Kind,
+    java_ty: String,
+    mutable: bool,
+}
+
+/// Information about the generated types, needed to convert the values.
+pub struct Context<'a> {
+    java: &'a super::Context,
+    /// Structures with a layout, by name.
+    structs: HashMap<&'a str, &'a Struct>,
+    transparent: &'a HashMap<String, Primitive>,
+}
+
+impl<'a> Context<'a> {
+    /// Returns the conversion of values of the type `ty`, if it's supported.
+    fn kind(&self, ty: &syn::Type) -> Option<Kind> {
+        if let Some(ptr) = safe_ptr(ty) {
+            return self.pointee_kind(ptr.elem, !ptr.nullable);
+        }
+        match *ty {
+            syn::Type::Ptr(ref ptr) => self.pointee_kind(&ptr.elem, false),
+            syn::Type::Path(ref path) => {
+                let ident = unwrap!(path.path.segments.last())
+                    .into_value()
+                    .ident
+                    .to_string();
+                // Sized enums are passed as their integer type
+                let ident = self
+                    .java
+                    .enum_repr(&ident)
+                    .map(str::to_string)
+                    .unwrap_or(ident);
+                let java_ty = rust_ty_to_java(&ident).or_else(|| {
+                    self.java
+                        .type_map
+                        .get(ident.as_str())
+                        .filter(|ty| matches!(ty, JavaType::Primitive(..)))
+                        .cloned()
+                });
+                match java_ty {
+                    Some(JavaType::Primitive(Primitive::Void)) => None,
+                    Some(JavaType::Primitive(primitive)) => Some(Kind::Primitive(primitive)),
+                    Some(_) => None,
+                    None => {
+                        if let Some(primitive) = self.transparent.get(&ident) {
+                            Some(Kind::Transparent(ident.clone(), *primitive))
+                        } else if self.structs.contains_key(ident.as_str()) {
+                            Some(Kind::Struct(ident.clone()))
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the conversion of pointers to `elem`.
+    fn pointee_kind(&self, elem: &syn::Type, required: bool) -> Option<Kind> {
+        let ident = match *elem {
+            syn::Type::Path(ref path) => unwrap!(path.path.segments.last())
+                .into_value()
+                .ident
+                .to_string(),
+            _ => return None,
+        };
+        if ident == "c_char" {
+            Some(Kind::String)
+        } else if let Some(JavaType::Primitive(Primitive::Long)) =
+            self.java.type_map.get(ident.as_str())
+        {
+            Some(Kind::Address)
+        } else if self.structs.contains_key(ident.as_str()) {
+            Some(Kind::StructPtr {
+                name: ident,
+                required,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the conversion of arrays of `elem`. Arrays of primitives are copied in bulk,
+    /// which isn't possible for booleans.
+    fn array_kind(&self, elem: &syn::Type) -> Option<Kind> {
+        match self.kind(elem)? {
+            Kind::Primitive(Primitive::Boolean) => None,
+            kind @ Kind::Primitive(..) | kind @ Kind::Struct(..) => {
+                Some(Kind::Array(Box::new(kind)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the fields of the structure with their conversions, if they're all supported.
+    fn fields(&self, item: &Struct) -> Option<Vec<Field>> {
+        let mut fields = Vec::new();
+        for field in &item.fields {
+            let ty = &field.struct_field().ty;
+            let (kind, len, cap) = match *field {
+                StructField::Primitive(..) | StructField::LenField(..) => {
+                    (self.kind(ty)?, None, None)
+                }
+                StructField::String(..) => (Kind::String, None, None),
+                StructField::StructPtr { ref ty, .. } => {
+                    (self.pointee_kind(&ty.elem, false)?, None, None)
+                }
+                StructField::Array {
+                    ref len_field,
+                    ref cap_field,
+                    ..
+                } => {
+                    let elem = match *ty {
+                        syn::Type::Ptr(ref ptr) => &*ptr.elem,
+                        _ => return None,
+                    };
+                    (
+                        self.array_kind(elem)?,
+                        Some(len_field.clone()),
+                        cap_field.clone(),
+                    )
+                }
+            };
+            let supported = match kind {
+                Kind::Primitive(..) | Kind::Transparent(..) => true,
+                // Pointers can't be safely read from the other fields of a union
+                _ if item.union => false,
+                // The layouts of structures can't be unaligned
+                Kind::Struct(..) => !item.packed,
+                _ => true,
+            };
+            if !supported {
+                return None;
+            }
+            fields.push(Field {
+                name: field.name(),
+                kind,
+                len,
+                cap,
+            });
+        }
+        Some(fields)
+    }
+
+    /// Returns the namespace of the `NativeLayouts` class holding the layout of a structure,
+    /// if it's generated by the bindings of a dependency crate.
+    fn provided(&self, name: &str) -> Option<&str> {
+        self.structs
+            .get(name)
+            .and_then(|item| item.provided.as_ref())
+            .map(String::as_str)
+    }
+}
+
+impl LangJavaPanama {
+    pub fn new(type_map: HashMap<&'static str, JavaType>) -> Self {
+        LangJavaPanama {
+            java: LangJava::new(type_map),
+            structs: Vec::new(),
+            transparent: HashMap::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+        }
+    }
+
+    /// Set the name of the native library.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.java.set_lib_name(name);
+    }
+
+    /// Set the namespace to put the NativeBindings class in.
+    pub fn set_namespace<T: Into<String>>(&mut self, namespace: T) {
+        self.java.set_namespace(namespace);
+    }
+
+    /// Set the namespace to put the callback interfaces in.
+    pub fn set_model_namespace<T: Into<String>>(&mut self, namespace: T) {
+        self.java.set_model_namespace(namespace);
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated into the
+    /// namespace `namespace` by its own bindings. Their classes and layouts aren't generated
+    /// again, but imported instead.
+    pub fn add_provided_package<P, N>(&mut self, package: P, namespace: N)
+    where
+        P: Into<String>,
+        N: Into<String>,
+    {
+        self.java.add_provided_package(package, namespace);
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        self.java.filter(ident);
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.java.reset_filter(filter_mode);
+    }
+
+    /// Keeps the Java classes generated by `LangJava`, but not its JNI code.
+    fn add_classes(generated: Outputs, outputs: &mut Outputs) {
+        for (path, contents) in generated {
+            if path.ends_with(".java") && path != "NativeBindings.java" {
+                outputs.insert(path, contents);
+            }
+        }
+    }
+
+    /// Returns the bindings of a function, or the reason it isn't supported.
+    fn transform_fn(
+        &self,
+        function: &Function,
+        context: &Context,
+        stubs: &mut Vec<Stub>,
+        outputs: &mut Outputs,
+    ) -> Result<Method, String> {
+        let java_context = &self.java.context;
+        let name = &function.name;
+        let args: Vec<_> = function
+            .decl
+            .inputs
+            .iter()
+            .map(|arg| unwrap!(transform_fnarg_to_argcap(arg)))
+            .collect();
+        let num_callbacks = args.iter().filter(|arg| bare_fn(&arg.ty).is_some()).count();
+        let has_user_data = args.iter().any(|arg| is_user_data_arg(arg));
+        if num_callbacks > 0 && !has_user_data {
+            return Err("callbacks without user data".to_string());
+        }
+
+        let mut params = Vec::new();
+        let mut java_args = Vec::new();
+        let mut callbacks = Vec::new();
+        let mut iter = args.iter().peekable();
+        while let Some(arg) = iter.next() {
+            if is_user_data_arg(arg) {
+                params.push(Param::UserData);
+                continue;
+            }
+            let arg_name = unwrap!(take_out_pat(&arg.pat)).ident.to_string();
+            let java_name = arg_name.to_camel_case();
+            let mut java_ty = rust_to_java(&arg.ty, java_context).map_err(|err| err.message)?;
+
+            if let Some(bare_fn) = bare_fn(&arg.ty) {
+                let inputs: Vec<_> = bare_fn.inputs.iter().cloned().collect();
+                let class = callback_name(&inputs, java_context).map_err(|err| err.message)?;
+                callbacks.push((bare_fn, class, params.len()));
+                params.push(Param::Callback {
+                    name: java_name.clone(),
+                    nullable: nullable_fn_ptr(&arg.ty).is_some(),
+                    stub: String::new(),
+                });
+            } else if is_array_arg(arg, iter.peek().map(|arg| **arg)) {
+                let _ = iter.next();
+                let elem = match arg.ty {
+                    syn::Type::Ptr(ref ptr) => &*ptr.elem,
+                    _ => unreachable!(),
+                };
+                let kind = context.array_kind(elem).ok_or("arrays of this type")?;
+                java_ty = JavaType::Array(Box::new(java_ty));
+                params.push(Param::Value {
+                    name: java_name.clone(),
+                    kind,
+                });
+            } else {
+                let kind = context.kind(&arg.ty).ok_or("parameters of this type")?;
+                params.push(Param::Value {
+                    name: java_name.clone(),
+                    kind,
+                });
+            }
+            java_args.push(format!(
+                "{} {}",
+                java_type_to_str(&java_ty).map_err(|err| err.message)?,
+                java_name
+            ));
+        }
+
+        // Callbacks of functions taking a single one are shared by the functions taking the
+        // same callback interface, the others get a stub for each of their callbacks.
+        let multi = callbacks.len() > 1;
+        for (idx, (bare_fn, class, param)) in callbacks.into_iter().enumerate() {
+            let method = if multi {
+                format!("call{}{}", name.to_pascal_case(), idx)
+            } else {
+                format!("call{}", class)
+            };
+            if !stubs.iter().any(|stub| stub.method == method) {
+                let params = transform_callback_params(bare_fn, context)?;
+                if let Entry::Vacant(entry) = outputs.entry(format!("{}.java", class)) {
+                    let interface = transform_callback(
+                        &syn::Type::BareFn(bare_fn.clone()),
+                        &class,
+                        java_context,
+                    )
+                    .map_err(|err| err.message)?;
+                    let _ = entry.insert(interface);
+                }
+                stubs.push(Stub {
+                    method: method.clone(),
+                    class,
+                    params,
+                    index: if multi { Some(idx) } else { None },
+                });
+            }
+            if let Param::Callback { ref mut stub, .. } = params[param] {
+                *stub = stub_constant(&method);
+            }
+        }
+
+        let (ret, ret_ty) = match function.decl.output {
+            syn::ReturnType::Default => (None, "void".to_string()),
+            syn::ReturnType::Type(_, ref ty) => {
+                let kind = context.kind(ty).ok_or("return values of this type")?;
+                let java_ty = rust_to_java(ty, java_context).map_err(|err| err.message)?;
+                (
+                    Some(kind),
+                    java_type_to_str(&java_ty).map_err(|err| err.message)?,
+                )
+            }
+        };
+
+        Ok(Method {
+            name: name.clone(),
+            java_name: name.to_camel_case(),
+            docs: function.docs.clone(),
+            params,
+            args: java_args,
+            ret,
+            ret_ty,
+        })
+    }
+
+    /// Returns the bindings of a static, if its type is supported. These are the same types as
+    /// with `LangJava`: primitives, strings and structures.
+    fn transform_static(&self, item: &Static, context: &Context) -> Option<StaticBinding> {
+        let name = item.item.ident.to_string();
+        let kind = context.kind(&item.item.ty)?;
+        match kind {
+            Kind::Primitive(..) | Kind::String | Kind::Struct(..) | Kind::Transparent(..) => {}
+            _ => return None,
+        }
+        let java_ty = rust_to_java(&item.item.ty, &self.java.context).ok()?;
+        let java_ty = java_type_to_str(&java_ty).ok()?;
+        let mutable = item.item.mutability.is_some() && matches!(kind, Kind::Primitive(..));
+
+        Some(StaticBinding {
+            name,
+            docs: item.docs.clone(),
+            kind,
+            java_ty,
+            mutable,
+        })
+    }
+}
+
+impl Lang for LangJavaPanama {
+    fn parse_const(
+        &mut self,
+        _item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        _item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let mut generated = Outputs::default();
+        self.java.parse_enum(item, module, &mut generated)?;
+        Self::add_classes(generated, outputs);
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.java.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, |attr| {
+            retrieve_docstring(attr, "")
+        });
+        // If it's not #[no_mangle] then it can't be called from C.
+        if !no_mangle {
+            return Ok(());
+        }
+        // If it doesn't have a C ABI it can't be called from C.
+        match item.abi {
+            Some(ref abi) if common::is_extern(abi.clone()) => {}
+            _ => return Ok(()),
+        }
+
+        if !item.decl.generics.params.is_empty() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, //NONE FOR NOW
+                message: "cheddar cannot handle parameterized extern functions".into(),
+            });
+        }
+        if let syn::ReturnType::Type(_, ref ty) = item.decl.output {
+            if let syn::Type::Never(..) = **ty {
+                return Err(Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: "panics across a C boundary are naughty!".into(),
+                });
+            }
+        }
+
+        self.functions.push(Function {
+            name,
+            decl: (*item.decl).clone(),
+            docs,
+        });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        if self.java.is_ignored(&item.ident.to_string()) {
+            return Ok(());
+        }
+
+        let (no_mangle, docs) = parse_attr(&item.attrs[..], check_no_mangle, |attr| {
+            retrieve_docstring(attr, "")
+        });
+        // If it's not #[no_mangle] then it can't be accessed from C.
+        if !no_mangle {
+            return Ok(());
+        }
+
+        self.statics.push(Static {
+            item: item.clone(),
+            docs,
+        });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let mut generated = Outputs::default();
+        self.java.parse_struct(item, module, &mut generated)?;
+        Self::add_classes(generated, outputs);
+
+        let name = item.ident.to_string();
+        if self.java.is_ignored(&name) {
+            return Ok(());
+        }
+        let repr = common::parse_repr(&item.attrs);
+        if repr.transparent {
+            let primitive =
+                common::transparent_field(&item.fields).and_then(|field| match field.ty {
+                    syn::Type::Path(ref path) => path
+                        .path
+                        .segments
+                        .last()
+                        .and_then(|segment| rust_ty_to_java(&segment.value().ident.to_string())),
+                    _ => None,
+                });
+            if let Some(JavaType::Primitive(primitive)) = primitive {
+                if primitive != Primitive::Void {
+                    let _ = self.transparent.insert(name, primitive);
+                }
+            }
+            return Ok(());
+        }
+        if !repr.c {
+            return Ok(());
+        }
+
+        let provided = self
+            .java
+            .context
+            .provided_types
+            .get(&struct_to_java_classname(&name))
+            .cloned();
+        self.structs.push(Struct {
+            name,
+            fields: transform_struct_fields(&common::named_fields(&item.fields)),
+            packed: repr.packed.is_some(),
+            union: false,
+            provided,
+        });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let mut generated = Outputs::default();
+        self.java.parse_union(item, module, &mut generated)?;
+        Self::add_classes(generated, outputs);
+
+        let name = item.ident.to_string();
+        if self.java.is_ignored(&name) || !parse_attr(&item.attrs, common::check_repr_c, |_| None).0
+        {
+            return Ok(());
+        }
+
+        let provided = self
+            .java
+            .context
+            .provided_types
+            .get(&struct_to_java_classname(&name))
+            .cloned();
+        let fields: Vec<_> = item.fields.named.iter().cloned().collect();
+        self.structs.push(Struct {
+            name,
+            fields: transform_struct_fields(&fields),
+            packed: false,
+            union: true,
+            provided,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        // Drop the structures with unsupported fields, until the remaining ones only depend on
+        // each other.
+        let mut supported: HashSet<&str> =
+            self.structs.iter().map(|item| item.name.as_str()).collect();
+        let layouts = loop {
+            let context = Context {
+                java: &self.java.context,
+                structs: self
+                    .structs
+                    .iter()
+                    .filter(|item| supported.contains(item.name.as_str()))
+                    .map(|item| (item.name.as_str(), item))
+                    .collect(),
+                transparent: &self.transparent,
+            };
+            let mut layouts = Vec::new();
+            let mut unsupported = Vec::new();
+            for item in self
+                .structs
+                .iter()
+                .filter(|item| supported.contains(item.name.as_str()))
+            {
+                match context.fields(item) {
+                    Some(fields) => layouts.push((item, fields)),
+                    None => unsupported.push(item.name.as_str()),
+                }
+            }
+            if unsupported.is_empty() {
+                break layouts;
+            }
+            for name in unsupported {
+                println!(
+                    "fields of this type not supported ({}). Skipping its layout.",
+                    name
+                );
+                let _ = supported.remove(name);
+            }
+        };
+
+        let context = Context {
+            java: &self.java.context,
+            structs: layouts
+                .iter()
+                .map(|(item, _)| (item.name.as_str(), *item))
+                .collect(),
+            transparent: &self.transparent,
+        };
+
+        let mut stubs = Vec::new();
+        let mut methods = Vec::new();
+        for function in &self.functions {
+            match self.transform_fn(function, &context, &mut stubs, outputs) {
+                Ok(method) => methods.push(method),
+                Err(reason) => println!("{} not supported ({}). Skipping.", reason, function.name),
+            }
+        }
+
+        let mut statics = Vec::new();
+        for item in &self.statics {
+            match self.transform_static(item, &context) {
+                Some(binding) => statics.push(binding),
+                None => println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    item.item.ident
+                ),
+            }
+        }
+
+        // Layouts have to be declared after the layouts of the structures they contain.
+        let mut ordered = Vec::new();
+        let mut declared = HashSet::new();
+        for (item, fields) in &layouts {
+            order_layout(item, fields, &layouts, &mut declared, &mut ordered);
+        }
+        let ordered: Vec<_> = ordered
+            .into_iter()
+            .filter(|(item, _)| item.provided.is_none())
+            .collect();
+
+        if !methods.is_empty() || !statics.is_empty() {
+            outputs.insert(
+                "NativeBindings.java".to_string(),
+                emit_bindings(&methods, &statics, &stubs, &context),
+            );
+        }
+        if !ordered.is_empty() || outputs.contains_key("NativeBindings.java") {
+            outputs.insert(
+                "NativeLayouts.java".to_string(),
+                emit_layouts(&ordered, &context),
+            );
+        }
+        if outputs.is_empty() {
+            return Err(Error {
+                level: Level::Error,
+                span: None, //NONE FOR NOW
+                message: "no Java bindings generated?".to_owned(),
+            });
+        }
+
+        self.java.add_provided_imports(outputs);
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        self.java.unit_reference()
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
+
+/// Returns the function pointer type of a callback parameter, if it's one.
+fn bare_fn(ty: &syn::Type) -> Option<&syn::TypeBareFn> {
+    match *ty {
+        syn::Type::BareFn(ref bare_fn) => Some(bare_fn),
+        ref ty => nullable_fn_ptr(ty),
+    }
+}
+
+/// Returns the parameters of a callback, which are converted from native values before
+/// calling the Java callback object.
+fn transform_callback_params(
+    bare_fn: &syn::TypeBareFn,
+    context: &Context,
+) -> Result<Vec<Param>, String> {
+    if let syn::ReturnType::Type(..) = bare_fn.output {
+        return Err("callbacks returning values".to_string());
+    }
+    if bare_fn.inputs.iter().any(|arg| arg.name.is_none()) {
+        return Err("callbacks with unnamed parameters".to_string());
+    }
+    if !bare_fn.inputs.iter().any(is_user_data_arg_barefn) {
+        return Err("callbacks without user data".to_string());
+    }
+
+    let mut params = Vec::new();
+    let mut iter = bare_fn.inputs.iter().peekable();
+    while let Some(arg) = iter.next() {
+        if is_user_data_arg_barefn(arg) {
+            params.push(Param::UserData);
+            continue;
+        }
+        let name = match arg.name {
+            Some((syn::BareFnArgName::Named(ref name), _)) => name.to_string().to_camel_case(),
+            _ => return Err("callbacks with unnamed parameters".to_string()),
+        };
+        let kind = if is_array_arg_barefn(arg, iter.peek().cloned()) {
+            let _ = iter.next();
+            let elem = match arg.ty {
+                syn::Type::Ptr(ref ptr) => &*ptr.elem,
+                _ => unreachable!(),
+            };
+            context.array_kind(elem)
+        } else {
+            context.kind(&arg.ty)
+        };
+        let kind = kind.ok_or("callback parameters of this type")?;
+        params.push(Param::Value { name, kind });
+    }
+
+    Ok(params)
+}
+
+/// Adds the layout of a structure to `ordered`, after the layouts of the structures it
+/// contains.
+fn order_layout<'a>(
+    item: &'a Struct,
+    fields: &'a [Field],
+    layouts: &'a [(&'a Struct, Vec<Field>)],
+    declared: &mut HashSet<&'a str>,
+    ordered: &mut Vec<(&'a Struct, &'a [Field])>,
+) {
+    if !declared.insert(&item.name) {
+        return;
+    }
+    for field in fields {
+        let name = match field.kind {
+            Kind::Struct(ref name) => name,
+            Kind::Array(ref elem) => match **elem {
+                Kind::Struct(ref name) => name,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if let Some((item, fields)) = layouts.iter().find(|(item, _)| item.name == *name) {
+            order_layout(item, fields, layouts, declared, ordered);
+        }
+    }
+    ordered.push((item, fields));
+}
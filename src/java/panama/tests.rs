@@ -0,0 +1,693 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+fn lang() -> LangJavaPanama {
+    let mut type_map = HashMap::new();
+    let _ = type_map.insert("App", JavaType::Primitive(Primitive::Long));
+    LangJavaPanama::new(type_map)
+}
+
+#[test]
+fn layouts() {
+    let outputs = compile!(lang(), {
+        #[repr(C)]
+        pub struct Key(pub u64);
+
+        #[repr(C)]
+        pub struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        /// A named shape.
+        #[repr(C)]
+        pub struct Shape {
+            name: *const c_char,
+            key: Key,
+            origin: Point,
+            points: *const Point,
+            points_len: usize,
+            points_cap: usize,
+            parent: *const Point,
+        }
+
+        #[repr(C, packed)]
+        pub struct Header {
+            tag: u8,
+            size: u32,
+        }
+
+        #[repr(C)]
+        pub union Sample {
+            bits: u32,
+            level: f32,
+        }
+    });
+
+    let actual = fetch(&outputs, "NativeLayouts.java");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings;
+
+           import java.lang.foreign.Arena;
+           import java.lang.foreign.GroupLayout;
+           import java.lang.foreign.MemoryLayout;
+           import java.lang.foreign.MemorySegment;
+           import java.lang.foreign.StructLayout;
+           import java.lang.foreign.UnionLayout;
+           import java.util.ArrayList;
+           import java.util.List;
+           import java.util.function.Function;
+           import java.util.function.IntFunction;
+
+           import static java.lang.foreign.ValueLayout.*;
+
+           /**
+            * Native layouts of the structures of the library {@code backend}, along with the
+            * conversions from and into their classes.
+            */
+           public final class NativeLayouts {
+           	public static final StructLayout KEY = structLayout(
+           		JAVA_LONG.withName("field_0")
+           	);
+           	public static final StructLayout POINT = structLayout(
+           		JAVA_INT.withName("x"),
+           		JAVA_INT.withName("y")
+           	);
+           	public static final StructLayout SHAPE = structLayout(
+           		ADDRESS.withName("name"),
+           		NativeLayouts.KEY.withName("key"),
+           		NativeLayouts.POINT.withName("origin"),
+           		ADDRESS.withName("points"),
+           		JAVA_LONG.withName("points_len"),
+           		JAVA_LONG.withName("points_cap"),
+           		ADDRESS.withName("parent")
+           	);
+           	public static final StructLayout HEADER = structLayout(
+           		JAVA_BYTE.withByteAlignment(1).withName("tag"),
+           		JAVA_INT.withByteAlignment(1).withName("size")
+           	);
+           	public static final UnionLayout SAMPLE = MemoryLayout.unionLayout(
+           		JAVA_INT.withName("bits"),
+           		JAVA_FLOAT.withName("level")
+           	);
+
+           	private NativeLayouts() {
+           	}
+
+           	/**
+           	 * Reads a {@code Key} from native memory.
+           	 */
+           	public static Key readKey(MemorySegment segment) {
+           		Key value = new Key();
+           		value.setField0(segment.get(JAVA_LONG, offset(KEY, "field_0")));
+           		return value;
+           	}
+
+           	/**
+           	 * Writes a {@code Key} into native memory, allocating the memory it points to in the arena.
+           	 */
+           	public static void writeKey(MemorySegment segment, Arena arena, Key value) {
+           		segment.set(JAVA_LONG, offset(KEY, "field_0"), value.getField0());
+           	}
+
+           	/**
+           	 * Reads a {@code Point} from native memory.
+           	 */
+           	public static Point readPoint(MemorySegment segment) {
+           		Point value = new Point();
+           		value.setX(segment.get(JAVA_INT, offset(POINT, "x")));
+           		value.setY(segment.get(JAVA_INT, offset(POINT, "y")));
+           		return value;
+           	}
+
+           	/**
+           	 * Writes a {@code Point} into native memory, allocating the memory it points to in the arena.
+           	 */
+           	public static void writePoint(MemorySegment segment, Arena arena, Point value) {
+           		segment.set(JAVA_INT, offset(POINT, "x"), value.getX());
+           		segment.set(JAVA_INT, offset(POINT, "y"), value.getY());
+           	}
+
+           	/**
+           	 * Reads a {@code Shape} from native memory.
+           	 */
+           	public static Shape readShape(MemorySegment segment) {
+           		Shape value = new Shape();
+           		value.setName(NativeLayouts.readString(segment.get(ADDRESS, offset(SHAPE, "name"))));
+           		value.setKey(NativeLayouts.readKey(segment.asSlice(offset(SHAPE, "key"), NativeLayouts.KEY.byteSize())));
+           		value.setOrigin(NativeLayouts.readPoint(segment.asSlice(offset(SHAPE, "origin"), NativeLayouts.POINT.byteSize())));
+           		value.setPoint(NativeLayouts.readArray(segment.get(ADDRESS, offset(SHAPE, "points")), segment.get(JAVA_LONG, offset(SHAPE, "points_len")), NativeLayouts.POINT, Point[]::new, NativeLayouts::readPoint));
+           		value.setPointsLen(segment.get(JAVA_LONG, offset(SHAPE, "points_len")));
+           		value.setPointsCap(segment.get(JAVA_LONG, offset(SHAPE, "points_cap")));
+           		value.setParent(NativeLayouts.readPointer(segment.get(ADDRESS, offset(SHAPE, "parent")), NativeLayouts.POINT, NativeLayouts::readPoint));
+           		return value;
+           	}
+
+           	/**
+           	 * Writes a {@code Shape} into native memory, allocating the memory it points to in the arena.
+           	 */
+           	public static void writeShape(MemorySegment segment, Arena arena, Shape value) {
+           		segment.set(ADDRESS, offset(SHAPE, "name"), NativeLayouts.allocateString(arena, value.getName()));
+           		NativeLayouts.writeKey(segment.asSlice(offset(SHAPE, "key"), NativeLayouts.KEY.byteSize()), arena, value.getKey());
+           		NativeLayouts.writePoint(segment.asSlice(offset(SHAPE, "origin"), NativeLayouts.POINT.byteSize()), arena, value.getOrigin());
+           		segment.set(ADDRESS, offset(SHAPE, "points"), NativeLayouts.allocateArray(arena, value.getPoint(), NativeLayouts.POINT, NativeLayouts::writePoint));
+           		segment.set(JAVA_LONG, offset(SHAPE, "points_len"), (long) value.getPoint().length);
+           		segment.set(JAVA_LONG, offset(SHAPE, "points_cap"), (long) value.getPoint().length);
+           		segment.set(ADDRESS, offset(SHAPE, "parent"), NativeLayouts.allocatePointer(arena, value.getParent(), NativeLayouts.POINT, NativeLayouts::writePoint));
+           	}
+
+           	/**
+           	 * Reads a {@code Header} from native memory.
+           	 */
+           	public static Header readHeader(MemorySegment segment) {
+           		Header value = new Header();
+           		value.setTag(segment.get(JAVA_BYTE, offset(HEADER, "tag")));
+           		value.setSize(segment.get(JAVA_INT, offset(HEADER, "size")));
+           		return value;
+           	}
+
+           	/**
+           	 * Writes a {@code Header} into native memory, allocating the memory it points to in the arena.
+           	 */
+           	public static void writeHeader(MemorySegment segment, Arena arena, Header value) {
+           		segment.set(JAVA_BYTE, offset(HEADER, "tag"), value.getTag());
+           		segment.set(JAVA_INT, offset(HEADER, "size"), value.getSize());
+           	}
+
+           	/**
+           	 * Reads a {@code Sample} from native memory.
+           	 */
+           	public static Sample readSample(MemorySegment segment) {
+           		Sample value = new Sample();
+           		value.setLevel(segment.get(JAVA_FLOAT, offset(SAMPLE, "level")));
+           		value.setBit(segment.get(JAVA_INT, offset(SAMPLE, "bits")));
+           		return value;
+           	}
+
+           	/**
+           	 * Writes a {@code Sample} into native memory, allocating the memory it points to in the arena.
+           	 * Only the active field is written, as the fields overlap.
+           	 */
+           	public static void writeSample(MemorySegment segment, Arena arena, Sample value) {
+           		switch (value.getActiveField()) {
+           			case 0 -> {
+           				segment.set(JAVA_INT, offset(SAMPLE, "bits"), value.getBit());
+           			}
+           			case 1 -> {
+           				segment.set(JAVA_FLOAT, offset(SAMPLE, "level"), value.getLevel());
+           			}
+           		}
+           	}
+
+           	/**
+           	 * Lays out the members of a structure, padding them as a C compiler does.
+           	 */
+           	static StructLayout structLayout(MemoryLayout... members) {
+           		List<MemoryLayout> elements = new ArrayList<>();
+           		long size = 0;
+           		long alignment = 1;
+           		for (MemoryLayout member : members) {
+           			long padding = (member.byteAlignment() - size % member.byteAlignment()) % member.byteAlignment();
+           			if (padding > 0) {
+           				elements.add(MemoryLayout.paddingLayout(padding));
+           			}
+           			elements.add(member);
+           			size += padding + member.byteSize();
+           			alignment = Math.max(alignment, member.byteAlignment());
+           		}
+           		long padding = (alignment - size % alignment) % alignment;
+           		if (padding > 0) {
+           			elements.add(MemoryLayout.paddingLayout(padding));
+           		}
+           		return MemoryLayout.structLayout(elements.toArray(new MemoryLayout[0]));
+           	}
+
+           	static long offset(GroupLayout layout, String member) {
+           		return layout.byteOffset(MemoryLayout.PathElement.groupElement(member));
+           	}
+
+           	static MemorySegment elements(MemorySegment address, long count, MemoryLayout layout) {
+           		return address.reinterpret(count * layout.byteSize());
+           	}
+
+           	static String readString(MemorySegment address) {
+           		return address.address() == 0 ? null : address.reinterpret(Long.MAX_VALUE).getString(0);
+           	}
+
+           	static MemorySegment allocateString(Arena arena, String value) {
+           		return value == null ? MemorySegment.NULL : arena.allocateFrom(value);
+           	}
+
+           	static <T> T readPointer(MemorySegment address, MemoryLayout layout, Function<MemorySegment, T> read) {
+           		return address.address() == 0 ? null : read.apply(address.reinterpret(layout.byteSize()));
+           	}
+
+           	static <T> MemorySegment allocatePointer(Arena arena, T value, MemoryLayout layout, Writer<T> write) {
+           		return value == null ? MemorySegment.NULL : allocateStruct(arena, value, layout, write);
+           	}
+
+           	static <T> MemorySegment allocateStruct(Arena arena, T value, MemoryLayout layout, Writer<T> write) {
+           		MemorySegment segment = arena.allocate(layout);
+           		write.write(segment, arena, value);
+           		return segment;
+           	}
+
+           	static <T> T[] readArray(MemorySegment address, long count, MemoryLayout layout, IntFunction<T[]> create, Function<MemorySegment, T> read) {
+           		MemorySegment items = elements(address, count, layout);
+           		T[] values = create.apply((int) count);
+           		for (int i = 0; i < values.length; i++) {
+           			values[i] = read.apply(items.asSlice(i * layout.byteSize(), layout.byteSize()));
+           		}
+           		return values;
+           	}
+
+           	static <T> MemorySegment allocateArray(Arena arena, T[] values, MemoryLayout layout, Writer<T> write) {
+           		MemorySegment items = arena.allocate(layout, values.length);
+           		for (int i = 0; i < values.length; i++) {
+           			write.write(items.asSlice(i * layout.byteSize(), layout.byteSize()), arena, values[i]);
+           		}
+           		return items;
+           	}
+
+           	/**
+           	 * Writes a value into native memory, allocating the memory it points to in the arena.
+           	 */
+           	@FunctionalInterface
+           	interface Writer<T> {
+           		void write(MemorySegment segment, Arena arena, T value);
+           	}
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    assert!(outputs.contains_key("Shape.java"));
+    assert!(!outputs.contains_key("NativeBindings.java"));
+    assert!(!outputs.contains_key("jni.rs"));
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(lang(), {
+        #[repr(C)]
+        pub struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        /// Adds two numbers.
+        #[no_mangle]
+        pub extern "C" fn add(a: i32, b: i32) -> i32 {}
+
+        /// Sums the bytes.
+        #[no_mangle]
+        pub extern "C" fn checksum(data: *const u8, data_len: usize) -> u64 {}
+
+        #[no_mangle]
+        pub extern "C" fn greet(name: *const c_char) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn midpoint(points: *const Point, len: usize) -> Point {}
+
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+    });
+
+    let actual = fetch(&outputs, "NativeBindings.java");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings;
+
+           import java.lang.foreign.Arena;
+           import java.lang.foreign.FunctionDescriptor;
+           import java.lang.foreign.Linker;
+           import java.lang.foreign.MemorySegment;
+           import java.lang.foreign.SegmentAllocator;
+           import java.lang.foreign.SymbolLookup;
+           import java.lang.invoke.MethodHandle;
+
+           import static java.lang.foreign.ValueLayout.*;
+
+           /**
+            * Calls into the native library {@code backend} through the Foreign Function & Memory API,
+            * which requires native access to be enabled (e.g. {@code --enable-native-access=ALL-UNNAMED}).
+            */
+           public final class NativeBindings {
+           	private static final Linker LINKER = Linker.nativeLinker();
+           	private static final SymbolLookup LOOKUP = library("backend");
+
+           	private static final MethodHandle ADD_HANDLE = downcall("add", FunctionDescriptor.of(JAVA_INT, JAVA_INT, JAVA_INT));
+           	private static final MethodHandle CHECKSUM_HANDLE = downcall("checksum", FunctionDescriptor.of(JAVA_LONG, ADDRESS, JAVA_LONG));
+           	private static final MethodHandle GREET_HANDLE = downcall("greet", FunctionDescriptor.of(ADDRESS, ADDRESS));
+           	private static final MethodHandle MIDPOINT_HANDLE = downcall("midpoint", FunctionDescriptor.of(NativeLayouts.POINT, ADDRESS, JAVA_LONG));
+           	private static final MethodHandle APP_FREE_HANDLE = downcall("app_free", FunctionDescriptor.ofVoid(ADDRESS));
+
+           	private NativeBindings() {
+           	}
+
+           	/**
+           	 * Adds two numbers.
+           	 */
+           	public static int add(int a, int b) {
+           		try {
+           			return (int) ADD_HANDLE.invokeExact(a, b);
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	/**
+           	 * Sums the bytes.
+           	 */
+           	public static long checksum(byte[] data) {
+           		try (Arena arena = Arena.ofConfined()) {
+           			return (long) CHECKSUM_HANDLE.invokeExact(arena.allocateFrom(JAVA_BYTE, data), (long) data.length);
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	public static String greet(String name) {
+           		try (Arena arena = Arena.ofConfined()) {
+           			return NativeLayouts.readString((MemorySegment) GREET_HANDLE.invokeExact(NativeLayouts.allocateString(arena, name)));
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	public static Point midpoint(Point[] points) {
+           		try (Arena arena = Arena.ofConfined()) {
+           			return NativeLayouts.readPoint((MemorySegment) MIDPOINT_HANDLE.invokeExact((SegmentAllocator) arena, NativeLayouts.allocateArray(arena, points, NativeLayouts.POINT, NativeLayouts::writePoint), (long) points.length));
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	public static void appFree(long app) {
+           		try {
+           			APP_FREE_HANDLE.invokeExact(MemorySegment.ofAddress(app));
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	private static SymbolLookup library(String name) {
+           		System.loadLibrary(name);
+           		return SymbolLookup.loaderLookup();
+           	}
+
+           	private static MemorySegment find(String name) {
+           		return LOOKUP.find(name).orElseThrow(() -> new UnsatisfiedLinkError("symbol not found: " + name));
+           	}
+
+           	private static MethodHandle downcall(String name, FunctionDescriptor descriptor) {
+           		return LINKER.downcallHandle(find(name), descriptor);
+           	}
+
+           	private static RuntimeException propagate(Throwable error) {
+           		if (error instanceof RuntimeException) {
+           			return (RuntimeException) error;
+           		}
+           		if (error instanceof Error) {
+           			throw (Error) error;
+           		}
+           		return new IllegalStateException(error);
+           	}
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn callbacks() {
+    let outputs = compile!(lang(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        /// Fetches the value under `key`.
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, value: u64),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            user_data: *mut c_void,
+            o_event: extern "C" fn(user_data: *mut c_void, data: *const u8, data_len: usize),
+            o_done: Option<extern "C" fn(user_data: *mut c_void, result: *const FfiResult)>,
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "NativeBindings.java");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings;
+
+           import net.maidsafe.model.*;
+           import java.lang.foreign.Arena;
+           import java.lang.foreign.FunctionDescriptor;
+           import java.lang.foreign.Linker;
+           import java.lang.foreign.MemorySegment;
+           import java.lang.foreign.SymbolLookup;
+           import java.lang.invoke.MethodHandle;
+           import java.lang.invoke.MethodHandles;
+           import java.util.Arrays;
+           import java.util.Map;
+           import java.util.Objects;
+           import java.util.concurrent.ConcurrentHashMap;
+           import java.util.concurrent.atomic.AtomicLong;
+
+           import static java.lang.foreign.ValueLayout.*;
+
+           /**
+            * Calls into the native library {@code backend} through the Foreign Function & Memory API,
+            * which requires native access to be enabled (e.g. {@code --enable-native-access=ALL-UNNAMED}).
+            */
+           public final class NativeBindings {
+           	private static final Linker LINKER = Linker.nativeLinker();
+           	private static final SymbolLookup LOOKUP = library("backend");
+           	/**
+           	 * Callbacks of the pending calls, by their user data.
+           	 */
+           	private static final Map<Long, Object> CALLBACKS = new ConcurrentHashMap<>();
+           	private static final AtomicLong NEXT_CALLBACK = new AtomicLong(1);
+
+           	private static final MethodHandle FETCH_HANDLE = downcall("fetch", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, ADDRESS));
+           	private static final MethodHandle SUBSCRIBE_HANDLE = downcall("subscribe", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS));
+           	private static final MemorySegment CALLBACK_RESULT_LONG_STUB = upcall("callCallbackResultLong", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG));
+           	private static final MemorySegment SUBSCRIBE_0_STUB = upcall("callSubscribe0", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG));
+           	private static final MemorySegment SUBSCRIBE_1_STUB = upcall("callSubscribe1", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+
+           	private NativeBindings() {
+           	}
+
+           	/**
+           	 * Fetches the value under `key`.
+           	 */
+           	public static void fetch(long app, String key, CallbackResultLong oCb) {
+           		try (Arena arena = Arena.ofConfined()) {
+           			FETCH_HANDLE.invokeExact(MemorySegment.ofAddress(app), NativeLayouts.allocateString(arena, key), register(oCb), CALLBACK_RESULT_LONG_STUB);
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	public static void subscribe(CallbackByteArrayLen oEvent, CallbackResult oDone) {
+           		try {
+           			SUBSCRIBE_HANDLE.invokeExact(register(new Object[] {oEvent, oDone}), SUBSCRIBE_0_STUB, oDone == null ? MemorySegment.NULL : SUBSCRIBE_1_STUB);
+           		} catch (Throwable e) {
+           			throw propagate(e);
+           		}
+           	}
+
+           	private static void callCallbackResultLong(MemorySegment userData, MemorySegment result, long value) {
+           		CallbackResultLong callback = (CallbackResultLong) CALLBACKS.remove(userData.address());
+           		if (callback == null) {
+           			return;
+           		}
+           		try {
+           			callback.call(NativeLayouts.readPointer(result, NativeLayouts.FFI_RESULT, NativeLayouts::readFfiResult), value);
+           		} catch (Throwable e) {
+           			report(e);
+           		}
+           	}
+
+           	private static void callSubscribe0(MemorySegment userData, MemorySegment data, long dataLen) {
+           		CallbackByteArrayLen callback = (CallbackByteArrayLen) take(userData, 0);
+           		if (callback == null) {
+           			return;
+           		}
+           		try {
+           			callback.call(NativeLayouts.elements(data, dataLen, JAVA_BYTE).toArray(JAVA_BYTE));
+           		} catch (Throwable e) {
+           			report(e);
+           		}
+           	}
+
+           	private static void callSubscribe1(MemorySegment userData, MemorySegment result) {
+           		CallbackResult callback = (CallbackResult) take(userData, 1);
+           		if (callback == null) {
+           			return;
+           		}
+           		try {
+           			callback.call(NativeLayouts.readPointer(result, NativeLayouts.FFI_RESULT, NativeLayouts::readFfiResult));
+           		} catch (Throwable e) {
+           			report(e);
+           		}
+           	}
+
+           	private static SymbolLookup library(String name) {
+           		System.loadLibrary(name);
+           		return SymbolLookup.loaderLookup();
+           	}
+
+           	private static MemorySegment find(String name) {
+           		return LOOKUP.find(name).orElseThrow(() -> new UnsatisfiedLinkError("symbol not found: " + name));
+           	}
+
+           	private static MethodHandle downcall(String name, FunctionDescriptor descriptor) {
+           		return LINKER.downcallHandle(find(name), descriptor);
+           	}
+
+           	private static RuntimeException propagate(Throwable error) {
+           		if (error instanceof RuntimeException) {
+           			return (RuntimeException) error;
+           		}
+           		if (error instanceof Error) {
+           			throw (Error) error;
+           		}
+           		return new IllegalStateException(error);
+           	}
+
+           	private static MemorySegment upcall(String method, FunctionDescriptor descriptor) {
+           		try {
+           			MethodHandle target = MethodHandles.lookup()
+           				.findStatic(NativeBindings.class, method, descriptor.toMethodType());
+           			return LINKER.upcallStub(target, descriptor, Arena.global());
+           		} catch (ReflectiveOperationException e) {
+           			throw new AssertionError(e);
+           		}
+           	}
+
+           	/**
+           	 * Keeps the callbacks of a call until one of them is called, returning the user data
+           	 * identifying them.
+           	 */
+           	private static MemorySegment register(Object callbacks) {
+           		long id = NEXT_CALLBACK.getAndIncrement();
+           		CALLBACKS.put(id, callbacks);
+           		return MemorySegment.ofAddress(id);
+           	}
+
+           	/**
+           	 * Exceptions can't propagate into native code, so they're reported as uncaught instead.
+           	 */
+           	private static void report(Throwable error) {
+           		Thread thread = Thread.currentThread();
+           		thread.getUncaughtExceptionHandler().uncaughtException(thread, error);
+           	}
+
+           	/**
+           	 * Takes one of the callbacks of a call, forgetting them once they've all been taken.
+           	 */
+           	private static Object take(MemorySegment userData, int index) {
+           		Object[] callbacks = (Object[]) CALLBACKS.get(userData.address());
+           		if (callbacks == null) {
+           			return null;
+           		}
+           		synchronized (callbacks) {
+           			Object callback = callbacks[index];
+           			callbacks[index] = null;
+           			if (Arrays.stream(callbacks).allMatch(Objects::isNull)) {
+           				CALLBACKS.remove(userData.address());
+           			}
+           			return callback;
+           		}
+           	}
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    assert!(outputs.contains_key("CallbackResultLong.java"));
+}
+
+#[test]
+fn statics() {
+    let outputs = compile!(lang(), {
+        /// Number of retries.
+        #[no_mangle]
+        pub static mut RETRIES: u32 = 3;
+
+        #[no_mangle]
+        pub static VERSION: *const c_char = 0 as *const c_char;
+
+        #[no_mangle]
+        pub static NAMES: [u8; 4] = [0; 4];
+    });
+
+    let actual = fetch(&outputs, "NativeBindings.java");
+    let expected = indoc!(
+        r#"package net.maidsafe.bindings;
+
+           import java.lang.foreign.Linker;
+           import java.lang.foreign.MemoryLayout;
+           import java.lang.foreign.MemorySegment;
+           import java.lang.foreign.SymbolLookup;
+
+           import static java.lang.foreign.ValueLayout.*;
+
+           /**
+            * Calls into the native library {@code backend} through the Foreign Function & Memory API,
+            * which requires native access to be enabled (e.g. {@code --enable-native-access=ALL-UNNAMED}).
+            */
+           public final class NativeBindings {
+           	private static final Linker LINKER = Linker.nativeLinker();
+           	private static final SymbolLookup LOOKUP = library("backend");
+
+           	private static final MemorySegment RETRIES_SYMBOL = symbol("RETRIES", JAVA_INT);
+           	private static final MemorySegment VERSION_SYMBOL = symbol("VERSION", ADDRESS);
+
+           	private NativeBindings() {
+           	}
+
+           	/**
+           	 * Number of retries.
+           	 */
+           	public static int getRetries() {
+           		return RETRIES_SYMBOL.get(JAVA_INT, 0);
+           	}
+
+           	public static void setRetries(int value) {
+           		RETRIES_SYMBOL.set(JAVA_INT, 0, value);
+           	}
+
+           	public static String getVersion() {
+           		return NativeLayouts.readString(VERSION_SYMBOL.get(ADDRESS, 0));
+           	}
+
+           	private static SymbolLookup library(String name) {
+           		System.loadLibrary(name);
+           		return SymbolLookup.loaderLookup();
+           	}
+
+           	private static MemorySegment find(String name) {
+           		return LOOKUP.find(name).orElseThrow(() -> new UnsatisfiedLinkError("symbol not found: " + name));
+           	}
+
+           	private static MemorySegment symbol(String name, MemoryLayout layout) {
+           		return find(name).reinterpret(layout.byteSize());
+           	}
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
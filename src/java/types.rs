@@ -1,6 +1,8 @@
 //! Functions for converting Rust types to Java types.
 
-use crate::common::{is_array_arg_barefn, is_result_arg_barefn, is_user_data_arg_barefn};
+use crate::common::{
+    is_array_arg_barefn, is_result_arg_barefn, is_user_data_arg_barefn, nullable_fn_ptr, safe_ptr,
+};
 use crate::java::Context;
 use crate::{Error, Level};
 use jni::signature::{JavaType, Primitive};
@@ -120,6 +122,10 @@ pub fn rust_to_java(ty: &syn::Type, context: &Context) -> Result<JavaType, Error
     match ty {
         // This is a callback ref taken as a function argument
         syn::Type::BareFn(ref bare_fn) => callback_arg_to_java(bare_fn, context),
+        // Nullable callbacks, `Option<extern fn>`, take the same interface and accept `null`.
+        syn::Type::Path(..) if nullable_fn_ptr(ty).is_some() => {
+            callback_arg_to_java(unwrap!(nullable_fn_ptr(ty)), context)
+        }
 
         // All other types just have a name associated with them.
         _ => anon_rust_to_java(ty, context, true),
@@ -169,6 +175,12 @@ fn anon_rust_to_java(
             anon_rust_to_java(&*ptr.elem, context, use_type_map)
         }
 
+        // References, `NonNull` and `Box` map the same way as standard pointers.
+        syn::Type::Reference(..) | syn::Type::Path(..) if safe_ptr(ty).is_some() => {
+            let ptr = syn::Type::Ptr(unwrap!(safe_ptr(ty)).to_raw());
+            anon_rust_to_java(&ptr, context, use_type_map)
+        }
+
         // Plain old types.
         syn::Type::Path(ref path) => path_to_java(&path.path, context, use_type_map),
 
@@ -229,6 +241,8 @@ fn path_to_java(
         }
     } else {
         let ty: String = path.segments[0].ident.to_owned().to_string();
+        // Sized enums are passed as their integer type
+        let ty = context.enum_repr(&ty).map(str::to_string).unwrap_or(ty);
         let mapped = rust_ty_to_java(ty.as_str()).unwrap_or_else(|| {
             if !use_type_map {
                 // Unknown type - most likely it's a structure, so convert it into an object
@@ -282,6 +296,15 @@ mod tests {
             unwrap!(rust_to_java(&ty("*const c_char"), &context)),
             JavaType::Object("String".to_string())
         );
+
+        assert_eq!(
+            // Check nullable callbacks take the same interface as plain ones
+            unwrap!(rust_to_java(
+                &ty("Option<extern \"C\" fn(user_data: *mut c_void, result: *const FfiResult)>"),
+                &context
+            )),
+            JavaType::Object("CallbackResult".to_string())
+        );
     }
 
     #[test]
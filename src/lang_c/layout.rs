@@ -0,0 +1,287 @@
+//! Computes the memory layout of `#[repr(C)]` types for a given target.
+
+use super::types::{CType, CTypeNamed};
+use crate::common::Repr;
+use crate::{Error, Level};
+use std::collections::BTreeMap;
+
+/// Describes the properties of the target platform that affect the layout of C types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Target {
+    /// Size of pointers, `intptr_t` and `size_t` in bytes.
+    pub pointer_size: usize,
+    /// Size of `c_long` in bytes.
+    pub c_long_size: usize,
+    /// Alignment of 64-bit integers and `double`s in bytes.
+    pub int64_align: usize,
+}
+
+impl Target {
+    /// 64-bit Unix-like targets (Linux, macOS, iOS, Android).
+    pub fn lp64() -> Self {
+        Target {
+            pointer_size: 8,
+            c_long_size: 8,
+            int64_align: 8,
+        }
+    }
+
+    /// 64-bit Windows targets.
+    pub fn llp64() -> Self {
+        Target {
+            pointer_size: 8,
+            c_long_size: 4,
+            int64_align: 8,
+        }
+    }
+
+    /// 32-bit targets. Note that some of them (e.g. ARM) align 64-bit integers to 8 bytes, in
+    /// which case `int64_align` should be adjusted.
+    pub fn ilp32() -> Self {
+        Target {
+            pointer_size: 4,
+            c_long_size: 4,
+            int64_align: 4,
+        }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self::lp64()
+    }
+}
+
+/// Size and alignment of a type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl Layout {
+    fn scalar(size: usize) -> Self {
+        Layout { size, align: size }
+    }
+}
+
+/// Layout of a single struct field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Size, alignment and field offsets of a struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+    pub size: usize,
+    pub align: usize,
+    pub fields: Vec<FieldLayout>,
+}
+
+enum Definition {
+    Struct(Vec<CTypeNamed>, Repr),
+    Union(Vec<CTypeNamed>),
+    Alias(CType),
+    Enum,
+}
+
+/// Keeps track of user-defined types and computes their layouts.
+pub struct Layouts {
+    target: Target,
+    defs: BTreeMap<String, Definition>,
+}
+
+impl Layouts {
+    pub fn new(target: Target) -> Self {
+        Layouts {
+            target,
+            defs: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
+
+    /// Register a `#[repr(C)]` struct along with its `packed`/`align` modifiers.
+    pub fn add_struct<T: Into<String>>(&mut self, name: T, fields: Vec<CTypeNamed>, repr: Repr) {
+        self.defs
+            .insert(name.into(), Definition::Struct(fields, repr));
+    }
+
+    /// Register a C union.
+    pub fn add_union<T: Into<String>>(&mut self, name: T, fields: Vec<CTypeNamed>) {
+        self.defs.insert(name.into(), Definition::Union(fields));
+    }
+
+    /// Register a type alias (`pub type A = B;`).
+    pub fn add_alias<T: Into<String>>(&mut self, name: T, ty: CType) {
+        self.defs.insert(name.into(), Definition::Alias(ty));
+    }
+
+    /// Register a C-like enum.
+    pub fn add_enum<T: Into<String>>(&mut self, name: T) {
+        self.defs.insert(name.into(), Definition::Enum);
+    }
+
+    /// Compute the layout of a registered struct or union.
+    pub fn struct_layout(&self, name: &str) -> Result<StructLayout, Error> {
+        match self.defs.get(name) {
+            Some(Definition::Struct(fields, repr)) => self.fields_layout(fields, repr),
+            Some(Definition::Union(fields)) => self.union_layout(fields),
+            _ => Err(unknown_type_error(name)),
+        }
+    }
+
+    /// Compute the size and alignment of a C type.
+    pub fn type_layout(&self, ty: &CType) -> Result<Layout, Error> {
+        match *ty {
+            CType::Void => Err(Error {
+                level: Level::Error,
+                span: None,
+                message: "`void` has no layout".into(),
+            }),
+            CType::Native(name) => self.native_layout(name),
+            CType::Mapping(ref name) => self.named_layout(name),
+            CType::Ptr(..) | CType::NonNullPtr(..) | CType::FnDecl { .. } => {
+                Ok(Layout::scalar(self.target.pointer_size))
+            }
+            CType::Array(ref elem, len) => {
+                let elem = self.type_layout(elem)?;
+                Ok(Layout {
+                    size: elem.size * len,
+                    align: elem.align,
+                })
+            }
+        }
+    }
+
+    fn fields_layout(&self, fields: &[CTypeNamed], repr: &Repr) -> Result<StructLayout, Error> {
+        let mut offset = 0;
+        let mut align = repr.align.unwrap_or(1) as usize;
+        let mut field_layouts = Vec::with_capacity(fields.len());
+
+        for CTypeNamed(name, ty) in fields {
+            let layout = self.type_layout(ty)?;
+            // Packing caps the alignment of every field
+            let field_align = match repr.packed {
+                Some(pack) => layout.align.min(pack as usize),
+                None => layout.align,
+            };
+            offset = align_to(offset, field_align);
+            align = align.max(field_align);
+
+            field_layouts.push(FieldLayout {
+                name: field_name(name, ty),
+                offset,
+                size: layout.size,
+            });
+            offset += layout.size;
+        }
+
+        Ok(StructLayout {
+            size: align_to(offset, align),
+            align,
+            fields: field_layouts,
+        })
+    }
+
+    /// All fields of a union start at offset 0.
+    fn union_layout(&self, fields: &[CTypeNamed]) -> Result<StructLayout, Error> {
+        let mut size = 0;
+        let mut align = 1;
+        let mut field_layouts = Vec::with_capacity(fields.len());
+
+        for CTypeNamed(name, ty) in fields {
+            let layout = self.type_layout(ty)?;
+            size = size.max(layout.size);
+            align = align.max(layout.align);
+
+            field_layouts.push(FieldLayout {
+                name: field_name(name, ty),
+                offset: 0,
+                size: layout.size,
+            });
+        }
+
+        Ok(StructLayout {
+            size: align_to(size, align),
+            align,
+            fields: field_layouts,
+        })
+    }
+
+    fn named_layout(&self, name: &str) -> Result<Layout, Error> {
+        match self.defs.get(name) {
+            Some(Definition::Struct(..)) | Some(Definition::Union(..)) => {
+                let layout = self.struct_layout(name)?;
+                Ok(Layout {
+                    size: layout.size,
+                    align: layout.align,
+                })
+            }
+            Some(Definition::Alias(ref ty)) => self.type_layout(ty),
+            // C enums are represented as `int`
+            Some(Definition::Enum) => Ok(Layout::scalar(4)),
+            None => match name {
+                "size_t" | "ssize_t" | "ptrdiff_t" => Ok(Layout::scalar(self.target.pointer_size)),
+                _ => Err(unknown_type_error(name)),
+            },
+        }
+    }
+
+    fn native_layout(&self, name: &str) -> Result<Layout, Error> {
+        let layout = match name {
+            "bool" | "char" | "signed char" | "unsigned char" | "int8_t" | "uint8_t" => {
+                Layout::scalar(1)
+            }
+            "short" | "unsigned short" | "int16_t" | "uint16_t" => Layout::scalar(2),
+            "int" | "unsigned int" | "int32_t" | "uint32_t" | "float" => Layout::scalar(4),
+            "long long" | "unsigned long long" | "int64_t" | "uint64_t" | "double" => Layout {
+                size: 8,
+                align: self.target.int64_align,
+            },
+            "long" | "unsigned long" => Layout {
+                size: self.target.c_long_size,
+                align: self.target.c_long_size.min(self.target.int64_align),
+            },
+            "intptr_t" | "uintptr_t" => Layout::scalar(self.target.pointer_size),
+            name => return Err(unknown_type_error(name)),
+        };
+
+        Ok(layout)
+    }
+}
+
+impl Default for Layouts {
+    fn default() -> Self {
+        Self::new(Target::default())
+    }
+}
+
+/// Round `offset` up to the nearest multiple of `align`.
+fn align_to(offset: usize, align: usize) -> usize {
+    match offset % align {
+        0 => offset,
+        rem => offset + align - rem,
+    }
+}
+
+/// Function pointer fields carry their name inside of the declaration.
+fn field_name(name: &str, ty: &CType) -> String {
+    match *ty {
+        CType::FnDecl { ref inner, .. } if name.is_empty() => inner.clone(),
+        _ => name.to_string(),
+    }
+}
+
+fn unknown_type_error(name: &str) -> Error {
+    Error {
+        level: Level::Error,
+        span: None,
+        message: format!("bindgen cannot compute the layout of `{}`", name),
+    }
+}
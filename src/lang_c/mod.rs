@@ -1,12 +1,16 @@
 //! Functions for converting Rust types to C types.
 
+mod layout;
 #[cfg(test)]
 mod tests;
-mod types;
+pub mod types;
 
+use self::layout::Layouts;
+pub use self::layout::{FieldLayout, StructLayout, Target};
 use self::types::{CPtrType, CType, CTypeNamed};
-use crate::common::{self, Lang, Outputs};
+use crate::common::{self, Lang, Outputs, Repr, SafePtr};
 use crate::{Error, Level};
+use inflector::Inflector;
 use petgraph::{algo, Graph};
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
@@ -20,6 +24,15 @@ pub struct LangC {
     decls: BTreeMap<String, String>,
     deps: BTreeMap<String, Vec<String>>,
     custom_code: String,
+    split_headers_enabled: bool,
+    amalgamated_header: Option<String>,
+    layouts: Layouts,
+    layout_checks_enabled: bool,
+    /// Names of all parsed structs along with their headers, in the parsing order
+    structs: Vec<(String, String)>,
+    /// Headers of the bindings of dependency crates, by the headers which would be generated
+    /// for their types.
+    provided_headers: BTreeMap<String, String>,
 }
 
 /// Compile the header declarations then add the needed `#include`s.
@@ -28,6 +41,7 @@ pub struct LangC {
 ///
 /// - `stdint.h`
 /// - `stdbool.h`
+/// - `stddef.h` (when layout checks are enabled)
 impl LangC {
     pub fn new() -> Self {
         Self {
@@ -35,6 +49,12 @@ impl LangC {
             decls: BTreeMap::new(),
             deps: BTreeMap::new(),
             custom_code: Default::default(),
+            split_headers_enabled: true,
+            amalgamated_header: None,
+            layouts: Default::default(),
+            layout_checks_enabled: false,
+            structs: Vec::new(),
+            provided_headers: BTreeMap::new(),
         }
     }
 
@@ -49,6 +69,120 @@ impl LangC {
         self.custom_code.push_str(code);
     }
 
+    /// Enable/disable generation of one header per Rust module (and the top-level header
+    /// including all of them). Enabled by default.
+    pub fn set_split_headers_enabled(&mut self, enabled: bool) {
+        self.split_headers_enabled = enabled;
+    }
+
+    /// Also generate a single self-contained header at the given path, containing the
+    /// declarations from all modules in dependency order.
+    pub fn set_amalgamated_header<T: Into<String>>(&mut self, path: T) {
+        self.amalgamated_header = Some(path.into());
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared in `header` by its
+    /// own bindings. They aren't generated again, but `header` is included where they are used.
+    pub fn add_provided_package<P: AsRef<str>, H: Into<String>>(&mut self, package: P, header: H) {
+        let generated = unwrap!(header_name(&[package.as_ref().to_string()], &self.lib_name));
+        self.provided_headers.insert(generated, header.into());
+    }
+
+    /// Set the target platform used to compute struct layouts. Defaults to 64-bit Unix.
+    pub fn set_layout_target(&mut self, target: Target) {
+        self.layouts.set_target(target);
+    }
+
+    /// Enable/disable `_Static_assert` checks of struct sizes and field offsets, so that a
+    /// mismatch between the Rust and the C compiler layouts fails the C build.
+    pub fn set_layout_checks_enabled(&mut self, enabled: bool) {
+        self.layout_checks_enabled = enabled;
+    }
+
+    /// Compute the layout of a parsed `#[repr(C)]` struct for the configured target.
+    pub fn struct_layout(&self, name: &str) -> Result<StructLayout, Error> {
+        self.layouts.struct_layout(name)
+    }
+
+    /// Includes common for all generated headers, followed by `code`.
+    fn with_includes(&self, code: &str) -> String {
+        let mut includes = String::from("#include <stdint.h>\n#include <stdbool.h>\n");
+        if code.contains("_Nonnull") {
+            // Nullability qualifiers are only understood by Clang.
+            includes.push_str(
+                "\n#if !defined(__clang__) && !defined(_Nonnull)\n\
+                 #define _Nonnull\n\
+                 #endif\n",
+            );
+        }
+        if self.layout_checks_enabled {
+            includes.push_str(
+                "#include <stddef.h>\n\n\
+                 #if defined(__cplusplus) && !defined(_Static_assert)\n\
+                 #define _Static_assert static_assert\n\
+                 #endif\n",
+            );
+        }
+        includes.push('\n');
+        includes.push_str(code);
+        includes
+    }
+
+    /// Generate static assertions checking the size and field offsets of a struct.
+    fn layout_checks(&self, name: &str) -> Result<String, Error> {
+        let layout = self.layouts.struct_layout(name)?;
+        let mut buffer = format!(
+            "_Static_assert(sizeof({0}) == {1}, \"unexpected size of {0}\");\n",
+            name, layout.size
+        );
+
+        for field in layout.fields {
+            buffer.push_str(&format!(
+                "_Static_assert(offsetof({0}, {1}) == {2}, \"unexpected offset of {0}.{1}\");\n",
+                name, field.name, field.offset
+            ));
+        }
+        buffer.push('\n');
+
+        Ok(buffer)
+    }
+
+    /// Returns the names of generated module headers, sorted so that every header comes after
+    /// the headers it depends on.
+    fn sorted_headers(&self, outputs: &Outputs) -> Vec<String> {
+        let mut depgraph = Graph::<String, String>::new();
+        let nodes_map: HashMap<String, _> = outputs
+            .keys()
+            .map(|m| (m.clone(), depgraph.add_node(m.clone())))
+            .collect();
+        let node_ids_map: HashMap<_, String> =
+            nodes_map.iter().map(|(k, v)| (*v, k.clone())).collect();
+        let mut edges = BTreeSet::new();
+
+        // Building a graph of dependencies
+        for header_name in outputs.keys() {
+            if let Some(module_deps) = self.deps.get(header_name) {
+                for dep in module_deps {
+                    if let Some(mod_name) = self.decls.get(dep) {
+                        let pred = mod_name.to_string();
+                        let succ = header_name.to_string();
+                        if pred == succ {
+                            continue;
+                        }
+                        let _ = edges.insert((nodes_map[&pred], nodes_map[&succ]));
+                    }
+                }
+            }
+        }
+
+        // Build a full dependency graph and topologically sort dependencies
+        depgraph.extend_with_edges(&edges);
+        unwrap!(algo::toposort(&depgraph, None))
+            .into_iter()
+            .map(|node_id| node_ids_map[&node_id].clone())
+            .collect()
+    }
+
     fn add_dependencies(&mut self, module: &[String], cty: &CType) -> Result<(), Error> {
         let deps = cty.dependencies();
 
@@ -77,6 +211,182 @@ impl LangC {
         Ok(())
     }
 
+    /// Keep track of a struct or union declared in `header`.
+    fn add_struct_decl(&mut self, name: &str, header: &str) {
+        self.decls.insert(name.to_string(), header.to_string());
+        self.structs.push((name.to_string(), header.to_string()));
+    }
+
+    /// Convert a `#[repr(transparent)]` struct into a typedef of the type it wraps.
+    fn parse_transparent_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        docs: &str,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        let name = item.ident.to_string();
+        let field = match common::transparent_field(&item.fields) {
+            Some(field) => field,
+            None => {
+                println!(
+                    "zero-sized transparent structs not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+        };
+
+        let ty = field_rust_to_c(&field.ty, &name)?;
+        self.add_dependencies(module, &ty.1)?;
+
+        let mut buffer = String::new();
+        buffer.push_str(docs);
+        buffer.push_str(&format!("typedef {};\n\n", ty));
+        self.append_to_header(buffer, module, outputs)?;
+
+        self.layouts.add_alias(name.clone(), ty.1);
+        self.decls
+            .insert(name, header_name(module, &self.lib_name)?);
+
+        Ok(())
+    }
+
+    /// Convert an enum with data-carrying variants into a tagged union, following the layout
+    /// defined by RFC 2195.
+    ///
+    /// `#[repr(C)]` and `#[repr(C, u8)]` enums become a struct holding the tag and a union of
+    /// the variant bodies, while `#[repr(u8)]` enums become a union of the variant bodies, each
+    /// of which starts with the tag.
+    fn parse_tagged_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        repr: &Repr,
+        docs: &str,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        let tag = format!("{}_Tag", name);
+        let header = header_name(module, &self.lib_name)?;
+        let mut buffer = String::new();
+
+        // Tag
+        match repr.int {
+            Some(ref int) => {
+                let ty = rust_ty_to_c(int);
+                buffer.push_str(&format!("typedef {} {};\n\nenum {{\n", ty, tag));
+                self.layouts.add_alias(tag.clone(), ty);
+            }
+            None => {
+                buffer.push_str(&format!("typedef enum {} {{\n", tag));
+                self.layouts.add_enum(tag.clone());
+            }
+        }
+        for variant in &item.variants {
+            let (_, docs) = common::parse_attr(
+                &variant.attrs,
+                |_| true,
+                |attr| retrieve_docstring(attr, "    "),
+            );
+            buffer.push_str(&docs);
+
+            let variant_name = format!("{}_{}", name, variant.ident);
+            match common::extract_enum_variant_value(variant) {
+                Some(value) => buffer.push_str(&format!("    {} = {},\n", variant_name, value)),
+                None => buffer.push_str(&format!("    {},\n", variant_name)),
+            }
+        }
+        match repr.int {
+            Some(_) => buffer.push_str("};\n\n"),
+            None => buffer.push_str(&format!("}} {};\n\n", tag)),
+        }
+        self.decls.insert(tag.clone(), header.clone());
+
+        // Variant bodies
+        let tag_field = CTypeNamed("tag".to_string(), CType::Mapping(tag));
+        let mut union_fields = Vec::new();
+        if !repr.c {
+            union_fields.push(tag_field.clone());
+        }
+
+        for variant in item.variants.iter() {
+            if variant.fields == syn::Fields::Unit {
+                continue;
+            }
+
+            let body = format!("{}_{}_Body", name, variant.ident);
+            buffer.push_str(&format!("typedef struct {} {{\n", body));
+
+            let mut fields = Vec::new();
+            if !repr.c {
+                buffer.push_str(&format!("    {};\n", tag_field));
+                fields.push(tag_field.clone());
+            }
+            for field in common::named_fields(&variant.fields) {
+                let (_, docs) = common::parse_attr(
+                    &field.attrs[..],
+                    |_| true,
+                    |attr| retrieve_docstring(attr, "    "),
+                );
+                buffer.push_str(&docs);
+
+                let field_name = unwrap!(field.ident).to_string();
+                let ty = field_rust_to_c(&field.ty, &field_name)?;
+                self.add_dependencies(module, &ty.1)?;
+                buffer.push_str(&format!("    {};\n", ty));
+                fields.push(ty);
+            }
+            buffer.push_str(&format!("}} {};\n\n", body));
+
+            self.add_struct_decl(&body, &header);
+            self.layouts
+                .add_struct(body.clone(), fields, Repr::default());
+
+            let field_name = variant.ident.to_string().to_snake_case();
+            union_fields.push(CTypeNamed(field_name, CType::Mapping(body)));
+        }
+
+        // Union of the bodies, wrapped into a struct along with the tag for `#[repr(C)]` enums
+        let union_name = if repr.c {
+            format!("{}_Body", name)
+        } else {
+            buffer.push_str(docs);
+            name.clone()
+        };
+        buffer.push_str(&format!("typedef union {} {{\n", union_name));
+        for field in &union_fields {
+            buffer.push_str(&format!("    {};\n", field));
+        }
+        buffer.push_str(&format!("}} {};\n\n", union_name));
+
+        self.add_struct_decl(&union_name, &header);
+        self.layouts.add_union(union_name.clone(), union_fields);
+
+        if repr.c {
+            let fields = vec![
+                tag_field,
+                CTypeNamed("body".to_string(), CType::Mapping(union_name)),
+            ];
+
+            buffer.push_str(docs);
+            buffer.push_str(&format!("typedef struct {} {{\n", name));
+            for field in &fields {
+                buffer.push_str(&format!("    {};\n", field));
+            }
+            buffer.push_str(&format!("}} {};\n\n", name));
+
+            self.add_struct_decl(&name, &header);
+            self.layouts.add_struct(name, fields, Repr::default());
+        }
+
+        self.append_to_header(buffer, module, outputs)
+    }
+
     /// Transform a Rust FFI function into a C function decl
     pub fn transform_native_fn(
         &mut self,
@@ -181,7 +491,7 @@ impl Lang for LangC {
         let (_, docs) = common::parse_attr(
             &item.attrs[..],
             |_| true,
-            |attr| common::retrieve_docstring(attr, ""),
+            |attr| retrieve_docstring(attr, ""),
         );
 
         let mut buffer = String::new();
@@ -193,9 +503,10 @@ impl Lang for LangC {
         if !item.generics.params.is_empty() {
             return Ok(());
         }
-        let new_type = unwrap!(rust_to_c(item.ty.deref(), &name));
+        let new_type = alias_rust_to_c(item.ty.deref(), &name)?;
 
         buffer.push_str(&format!("typedef {};\n\n", new_type));
+        self.layouts.add_alias(name.clone(), new_type.1);
         self.append_to_header(buffer, module, outputs)?;
 
         self.decls
@@ -206,52 +517,58 @@ impl Lang for LangC {
 
     /// Converts a Rust enum into a C enum.
     ///
-    /// The Rust enum must be marked with `#[repr(C)]` and must be public otherwise the function
-    /// will abort.
+    /// The Rust enum must be marked with `#[repr(C)]` or an integer representation (e.g.
+    /// `#[repr(u8)]`) and must be public otherwise the function will abort. Enums with an
+    /// integer representation become a fixed-width typedef along with named constants, as the
+    /// size of a C enum is implementation-defined.
     ///
-    /// Bindgen will error if the enum is generic or if it contains non-unit variants.
+    /// Enums with data-carrying variants become tagged unions.
+    ///
+    /// Bindgen will error if the enum is generic.
     fn parse_enum(
         &mut self,
         item: &syn::ItemEnum,
         module: &[String],
         outputs: &mut Outputs,
     ) -> Result<(), Error> {
-        let (repr_c, docs) = common::parse_attr(&item.attrs[..], common::check_repr_c, |attr| {
-            common::retrieve_docstring(attr, "")
-        });
-        // If it's not #[repr(C)] then it can't be called from C.
-        if !repr_c {
+        let repr = common::parse_repr(&item.attrs);
+        // If it has no defined layout then it can't be used from C.
+        if !repr.c && repr.int.is_none() {
             return Ok(());
         }
+        let (_, docs) = common::parse_attr(
+            &item.attrs[..],
+            |_| true,
+            |attr| retrieve_docstring(attr, ""),
+        );
+
+        // Error if generic parameters are encountered.
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("enums"));
+        }
+        if common::has_data_variants(item) {
+            return self.parse_tagged_enum(item, &repr, &docs, module, outputs);
+        }
 
         let mut buffer = String::new();
         buffer.push_str(&docs);
 
         let name = item.ident.to_string();
-        buffer.push_str(&format!("typedef enum {} {{\n", name));
-
-        // Error if generic parameters are encountered.
-        if !item.generics.params.is_empty() {
-            return Err(Error::unsupported_generics_error("enums"));
+        let int_ty = repr.int.as_ref().map(|int| rust_ty_to_c(int));
+        match int_ty {
+            Some(ref ty) => buffer.push_str(&format!("typedef {} {};\n\nenum {{\n", ty, name)),
+            None => buffer.push_str(&format!("typedef enum {} {{\n", name)),
         }
-        for variant in item.variants.to_owned() {
-            if syn::Fields::Unit != variant.fields {
-                return Err(Error {
-                    level: Level::Error,
-                    span: None, //NONE FOR NOW
-                    message: "bindgen cannot handle `#[repr(C)]` enums with non-unit variants"
-                        .into(),
-                });
-            }
 
+        for variant in &item.variants {
             let (_, docs) = common::parse_attr(
                 &variant.attrs,
                 |_| true,
-                |attr| common::retrieve_docstring(attr, "    "),
+                |attr| retrieve_docstring(attr, "    "),
             );
             buffer.push_str(&docs);
 
-            let name = format!("{}_{}", name, variant.ident.to_string());
+            let name = format!("{}_{}", name, variant.ident);
             let enum_line = if let Some(value) = common::extract_enum_variant_value(&variant) {
                 format!("    {} = {},\n", name, value)
             } else {
@@ -260,67 +577,204 @@ impl Lang for LangC {
             buffer.push_str(&enum_line);
         }
 
-        buffer.push_str(&format!("}} {};\n\n", name));
+        match int_ty {
+            Some(ty) => {
+                buffer.push_str("};\n\n");
+                self.layouts.add_alias(name.clone(), ty);
+            }
+            None => {
+                buffer.push_str(&format!("}} {};\n\n", name));
+                self.layouts.add_enum(name.clone());
+            }
+        }
         self.append_to_header(buffer, module, outputs)?;
 
+        self.decls
+            .insert(name, header_name(module, &self.lib_name)?);
+
         Ok(())
     }
 
     /// Convert a Rust struct into a C struct.
     ///
-    /// The rust struct must be marked `#[repr(C)]` and must be public otherwise the function will
-    /// abort.
+    /// The rust struct must be marked `#[repr(C)]` or `#[repr(transparent)]` and must be public
+    /// otherwise the function will abort. The fields of tuple structs are named `field_0`,
+    /// `field_1`, etc., while `#[repr(transparent)]` structs become a typedef of the wrapped type.
     ///
-    /// Bindgen will error if the struct is generic or if the struct is a unit or tuple struct.
+    /// Bindgen will error if the struct is generic.
     fn parse_struct(
         &mut self,
         item: &syn::ItemStruct,
         module: &[String],
         outputs: &mut Outputs,
     ) -> Result<(), Error> {
-        let (repr_c, docs) = common::parse_attr(&item.attrs[..], common::check_repr_c, |attr| {
-            common::retrieve_docstring(attr, "")
-        });
+        let (_, docs) = common::parse_attr(
+            &item.attrs[..],
+            |_| true,
+            |attr| retrieve_docstring(attr, ""),
+        );
+        let repr = common::parse_repr(&item.attrs);
 
+        if repr.transparent {
+            return self.parse_transparent_struct(item, &docs, module, outputs);
+        }
         // If it's not #[repr(C)] then it can't be called from C.
-        if !repr_c {
+        if !repr.c {
             return Ok(());
         }
 
         let mut buffer = String::new();
         buffer.push_str(&docs);
 
+        if let Some(pack) = repr.packed {
+            buffer.push_str(&format!("#pragma pack(push, {})\n", pack));
+        }
+
         let name = item.ident.to_string();
-        buffer.push_str(&format!("typedef struct {}", name));
+        buffer.push_str("typedef struct ");
+        if let Some(align) = repr.align {
+            buffer.push_str(&format!("__attribute__((aligned({}))) ", align));
+        }
+        buffer.push_str(&name);
 
         if !item.generics.params.is_empty() {
             return Err(Error::unsupported_generics_error("structs"));
         }
         buffer.push_str(" {\n");
-        for field in item.fields.iter() {
+        let mut fields = Vec::new();
+        for field in common::named_fields(&item.fields) {
             let (_, docs) = common::parse_attr(
                 &field.attrs[..],
                 |_| true,
-                |attr| common::retrieve_docstring(attr, "    "),
+                |attr| retrieve_docstring(attr, "    "),
             );
             buffer.push_str(&docs);
 
-            let name = match field.ident.to_owned() {
-                Some(name) => name.to_string(),
-                None => unreachable!("a tuple struct snuck through"),
-            };
+            let name = unwrap!(field.ident).to_string();
 
-            let ty = rust_to_c(&field.ty, &name)?;
+            let ty = field_rust_to_c(&field.ty, &name)?;
             self.add_dependencies(module, &ty.1)?;
             buffer.push_str(&format!("    {};\n", ty));
+            fields.push(ty);
         }
         buffer.push_str("}");
 
-        buffer.push_str(&format!(" {};\n\n", name));
+        buffer.push_str(&format!(" {};\n", name));
+        if repr.packed.is_some() {
+            buffer.push_str("#pragma pack(pop)\n");
+        }
+        buffer.push('\n');
         self.append_to_header(buffer, module, outputs)?;
 
-        self.decls
-            .insert(name.to_string(), header_name(module, &self.lib_name)?);
+        let header = header_name(module, &self.lib_name)?;
+        self.add_struct_decl(&name, &header);
+        self.layouts.add_struct(name, fields, repr);
+
+        Ok(())
+    }
+
+    /// Convert a `#[repr(C)]` Rust union into a C union.
+    ///
+    /// If the union is generic then bindgen will error.
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let (repr_c, docs) = common::parse_attr(&item.attrs[..], common::check_repr_c, |attr| {
+            retrieve_docstring(attr, "")
+        });
+
+        // If it's not #[repr(C)] then it can't be called from C.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("unions"));
+        }
+
+        let name = item.ident.to_string();
+        let mut buffer = String::new();
+        buffer.push_str(&docs);
+        buffer.push_str(&format!("typedef union {} {{\n", name));
+
+        let mut fields = Vec::new();
+        for field in item.fields.named.iter() {
+            let (_, docs) = common::parse_attr(
+                &field.attrs[..],
+                |_| true,
+                |attr| retrieve_docstring(attr, "    "),
+            );
+            buffer.push_str(&docs);
+
+            let name = unwrap!(field.ident.to_owned()).to_string();
+            let ty = field_rust_to_c(&field.ty, &name)?;
+            self.add_dependencies(module, &ty.1)?;
+            buffer.push_str(&format!("    {};\n", ty));
+            fields.push(ty);
+        }
+        buffer.push_str(&format!("}} {};\n\n", name));
+        self.append_to_header(buffer, module, outputs)?;
+
+        let header = header_name(module, &self.lib_name)?;
+        self.add_struct_decl(&name, &header);
+        self.layouts.add_union(name, fields);
+
+        Ok(())
+    }
+
+    /// Convert an exported Rust static into an `extern` variable declaration.
+    ///
+    /// The static must be marked `#[no_mangle]` otherwise the function will abort. Immutable
+    /// statics are declared `const`.
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs[..], common::check_no_mangle, |attr| {
+                retrieve_docstring(attr, "")
+            });
+
+        // If it's not #[no_mangle] then it can't be accessed from C.
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let name = item.ident.to_string();
+        let ty = field_rust_to_c(&item.ty, &name)?;
+        self.add_dependencies(module, &ty.1)?;
+
+        let decl = if item.mutability.is_some() {
+            ty.to_string()
+        } else {
+            match ty.1 {
+                CType::Array(ref elem, len) => format!("{} const {}[{}]", elem, name, len),
+                CType::FnDecl {
+                    ref args,
+                    ref return_type,
+                    ..
+                } => CTypeNamed(
+                    name.clone(),
+                    CType::FnDecl {
+                        inner: format!("const {}", name),
+                        args: args.clone(),
+                        return_type: return_type.clone(),
+                    },
+                )
+                .to_string(),
+                ref cty => format!("{} const {}", cty, name),
+            }
+        };
+
+        let mut buffer = String::new();
+        buffer.push_str(&docs);
+        buffer.push_str(&format!("extern {};\n\n", decl));
+        self.append_to_header(buffer, module, outputs)?;
 
         Ok(())
     }
@@ -339,7 +793,7 @@ impl Lang for LangC {
     ) -> Result<(), Error> {
         let (no_mangle, docs) =
             common::parse_attr(&item.attrs[..], common::check_no_mangle, |attr| {
-                common::retrieve_docstring(attr, "")
+                retrieve_docstring(attr, "")
             });
 
         // If it's not #[no_mangle] then it can't be called from C.
@@ -366,57 +820,110 @@ impl Lang for LangC {
     }
 
     fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
-        let mut depgraph = Graph::<String, String>::new();
-        let nodes_map: HashMap<String, _> = outputs
-            .keys()
-            .map(|m| (m.clone(), depgraph.add_node(m.clone())))
-            .collect();
-        let node_ids_map: HashMap<_, String> =
-            nodes_map.iter().map(|(k, v)| (*v, k.clone())).collect();
-        let mut edges = BTreeSet::new();
-
-        // Wrap modules with common includes
-        for (header_name, value) in outputs.iter_mut() {
-            let code = format!("#include <stdint.h>\n#include <stdbool.h>\n\n{}", value);
+        // Layouts can only be computed once all types are known
+        if self.layout_checks_enabled {
+            for (name, header) in &self.structs {
+                match self.layout_checks(name) {
+                    Ok(checks) => common::append_output(checks, header, outputs),
+                    Err(error) => println!("{}. Skipping layout checks for {}.", error, name),
+                }
+            }
+        }
 
-            *value = wrap_guard(&wrap_extern(&code), header_name);
+        let mut sorted_headers = self.sorted_headers(outputs);
 
-            // Building a graph of dependencies
-            if let Some(module_deps) = self.deps.get(header_name) {
-                for dep in module_deps {
-                    if let Some(mod_name) = self.decls.get(dep) {
-                        let pred = mod_name.to_string();
-                        let succ = header_name.to_string();
-                        if pred == succ {
-                            continue;
-                        }
-                        let _ = edges.insert((nodes_map[&pred], nodes_map[&succ]));
-                    }
+        // Types provided by the bindings of dependencies are included instead
+        let mut provided_includes: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+        for (generated, header) in &self.provided_headers {
+            if outputs.remove(generated).is_none() {
+                continue;
+            }
+            for (dependent, deps) in &self.deps {
+                if deps
+                    .iter()
+                    .any(|dep| self.decls.get(dep) == Some(generated))
+                {
+                    provided_includes
+                        .entry(dependent.clone())
+                        .or_default()
+                        .insert(header);
                 }
             }
         }
+        sorted_headers.retain(|header| !self.provided_headers.contains_key(header));
+        let provided_includes = |headers: &[&String]| {
+            let includes: BTreeSet<_> = headers
+                .iter()
+                .filter_map(|header| provided_includes.get(*header))
+                .flatten()
+                .collect();
+            let mut code = String::new();
+            for include in &includes {
+                code.push_str(&format!("#include \"{}\"\n", include));
+            }
+            if !code.is_empty() {
+                code.push('\n');
+            }
+            code
+        };
 
-        // Build a full dependency graph and topologically sort dependencies
-        depgraph.extend_with_edges(&edges);
-        let sorted_deps = unwrap!(algo::toposort(&depgraph, None));
+        // Concatenate all module declarations into a single header, keeping the custom user
+        // code at the top
+        let amalgamated = self.amalgamated_header.as_ref().map(|path| {
+            let mut code = provided_includes(&sorted_headers.iter().collect::<Vec<_>>());
+            if !self.custom_code.is_empty() {
+                code.push_str(&format!("{}\n", self.custom_code));
+            }
+            for header_name in &sorted_headers {
+                code.push_str(&outputs[header_name]);
+            }
+            let code = self.with_includes(&code);
 
-        // Generate a top-level header and add custom user code
-        let mut top_level_header = String::new();
-        if !self.custom_code.is_empty() {
-            top_level_header.push_str(&format!("{}\n", self.custom_code));
-        }
-        for node_id in sorted_deps {
-            let header_name = &node_ids_map[&node_id];
-            top_level_header.push_str(&format!("#include \"{}\"\n", header_name));
+            (path.clone(), wrap_guard(&wrap_extern(&code), path))
+        });
+
+        if self.split_headers_enabled {
+            // Wrap modules with common includes
+            for (header_name, value) in outputs.iter_mut() {
+                let code = provided_includes(&[header_name]) + value;
+                let code = self.with_includes(&code);
+
+                *value = wrap_guard(&wrap_extern(&code), header_name);
+            }
+
+            // Generate a top-level header and add custom user code
+            let mut top_level_header = String::new();
+            if !self.custom_code.is_empty() {
+                top_level_header.push_str(&format!("{}\n", self.custom_code));
+            }
+            for header_name in &sorted_headers {
+                top_level_header.push_str(&format!("#include \"{}\"\n", header_name));
+            }
+
+            outputs.insert(
+                format!("{}.h", self.lib_name),
+                wrap_guard(&top_level_header, &format!("{}_root", self.lib_name)),
+            );
+        } else {
+            for header_name in &sorted_headers {
+                outputs.remove(header_name);
+            }
         }
 
-        outputs.insert(
-            format!("{}.h", self.lib_name),
-            wrap_guard(&top_level_header, &format!("{}_root", self.lib_name)),
-        );
+        if let Some((path, header)) = amalgamated {
+            outputs.insert(path, header);
+        }
 
         Ok(())
     }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(format!("{}.h", self.lib_name))
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
 }
 
 /// Turn a Rust type with an associated name or type into a C type.
@@ -426,22 +933,71 @@ pub fn rust_to_c(ty: &syn::Type, assoc: &str) -> Result<CTypeNamed, Error> {
         syn::Type::BareFn(ref bare_fn) => {
             Ok(CTypeNamed(Default::default(), fn_ptr_to_c(bare_fn, assoc)?))
         }
+        // `Option<extern fn>` is a nullable function pointer, which C function pointers can
+        // always be.
+        syn::Type::Path(..) if common::nullable_fn_ptr(ty).is_some() => Ok(CTypeNamed(
+            Default::default(),
+            fn_ptr_to_c(unwrap!(common::nullable_fn_ptr(ty)), assoc)?,
+        )),
         // All other types just have a name associated with them.
         _ => Ok(CTypeNamed(assoc.to_string(), anon_rust_to_c(ty)?)),
     }
 }
 
+/// Turn a Rust struct field type into a C type.
+///
+/// Unlike in function arguments, fixed-length arrays are kept as arrays so that the layout of
+/// the struct is preserved.
+pub fn field_rust_to_c(ty: &syn::Type, name: &str) -> Result<CTypeNamed, Error> {
+    if let syn::Type::Array(syn::TypeArray {
+        ref elem,
+        len: syn::Expr::Lit(ref len),
+        ..
+    }) = *ty
+    {
+        if let Some(len) = common::extract_int_literal(len) {
+            return Ok(CTypeNamed(
+                name.to_string(),
+                CType::Array(Box::new(anon_rust_to_c(elem)?), len as usize),
+            ));
+        }
+    }
+
+    rust_to_c(ty, name)
+}
+
+/// Turn the Rust type aliased by `name` into a C type.
+///
+/// Fixed-length arrays are kept as arrays, as in struct fields, so that the structs using the
+/// alias keep their layout.
+pub fn alias_rust_to_c(ty: &syn::Type, name: &str) -> Result<CTypeNamed, Error> {
+    if let syn::Type::Array(..) = *ty {
+        let new_type = field_rust_to_c(ty, name)?;
+        if let CType::Array(..) = new_type.1 {
+            Ok(new_type)
+        } else {
+            Err(Error {
+                level: Level::Error,
+                span: None, //NONE FOR NOW
+                message: format!(
+                    "bindgen cannot handle the length of the array aliased by {}",
+                    name
+                ),
+            })
+        }
+    } else {
+        rust_to_c(ty, name)
+    }
+}
+
 /// Turn a Rust type into a C type.
 fn anon_rust_to_c(ty: &syn::Type) -> Result<CType, Error> {
     match ty {
-        // Function pointers should not be in this function.
-        syn::Type::BareFn(..) => Err(Error {
-            level: Level::Error,
-            span: None, //there is no span for types in syn, hence none
-            message:
-                "C function ptrs must have a name or function declaration associated with them"
-                    .into(),
-        }),
+        // Function pointers (nullable or not) should not be in this function.
+        syn::Type::BareFn(..) => Err(fn_ptr_without_name_error()),
+        syn::Type::Path(..) if common::nullable_fn_ptr(ty).is_some() => {
+            Err(fn_ptr_without_name_error())
+        }
         // Fixed-length arrays, converted into pointers.
         syn::Type::Array(syn::TypeArray { ref elem, .. }) => Ok(CType::Ptr(
             Box::new(anon_rust_to_c(&*elem)?),
@@ -449,6 +1005,10 @@ fn anon_rust_to_c(ty: &syn::Type) -> Result<CType, Error> {
         )),
         // Standard pointers.
         syn::Type::Ptr(ref ptr) => ptr_to_c(ptr),
+        // References, `NonNull` and `Box`, possibly nullable.
+        syn::Type::Reference(..) | syn::Type::Path(..) if common::safe_ptr(ty).is_some() => {
+            safe_ptr_to_c(&unwrap!(common::safe_ptr(ty)))
+        }
         // Plain old types.
         syn::Type::Path(ref path) => path_to_c(path),
         // Tuple
@@ -472,6 +1032,15 @@ fn anon_rust_to_c(ty: &syn::Type) -> Result<CType, Error> {
     }
 }
 
+fn fn_ptr_without_name_error() -> Error {
+    Error {
+        level: Level::Error,
+        span: None, //there is no span for types in syn, hence none
+        message: "C function ptrs must have a name or function declaration associated with them"
+            .into(),
+    }
+}
+
 /// Turn a Rust pointer (*mut or *const) into the correct C form.
 fn ptr_to_c(typeptr: &syn::TypePtr) -> Result<CType, Error> {
     let new_type = anon_rust_to_c(&*typeptr.elem)?;
@@ -485,6 +1054,23 @@ fn ptr_to_c(typeptr: &syn::TypePtr) -> Result<CType, Error> {
     Ok(CType::Ptr(Box::new(new_type), const_type))
 }
 
+/// Turn a reference, `NonNull` or `Box` into a C pointer, annotated with `_Nonnull` unless it's
+/// wrapped in an `Option`.
+fn safe_ptr_to_c(ptr: &SafePtr) -> Result<CType, Error> {
+    let new_type = Box::new(anon_rust_to_c(ptr.elem)?);
+    let const_type = if ptr.is_mutable() {
+        CPtrType::Mutable
+    } else {
+        CPtrType::Const
+    };
+
+    if ptr.nullable {
+        Ok(CType::Ptr(new_type, const_type))
+    } else {
+        Ok(CType::NonNullPtr(new_type, const_type))
+    }
+}
+
 /// Turn a Rust function pointer into a C function pointer.
 ///
 /// Rust function pointers are of the form
@@ -640,7 +1226,7 @@ fn osraw_ty_to_c(ty: String) -> CType {
 ///
 /// This includes user-defined types. We currently trust the user not to use types which we don't
 /// know the structure of (like String).
-fn rust_ty_to_c(ty: &str) -> CType {
+pub fn rust_ty_to_c(ty: &str) -> CType {
     match ty {
         "()" => CType::Void,
         "f32" => CType::Native("float"),
@@ -711,6 +1297,12 @@ fn header_name(module: &[String], lib_name: &str) -> Result<String, Error> {
     Ok(header_name)
 }
 
+/// Retrieve a docstring as a line of C comment indented by `indent`, which must end before the
+/// declaration.
+fn retrieve_docstring(attr: &syn::Attribute, indent: &str) -> Option<String> {
+    common::retrieve_docstring(attr, "").map(|docs| format!("{}{}\n", indent, docs))
+}
+
 /// Remove illegal characters from the identifier.
 ///
 /// This is because macros names must be valid C identifiers. Note that the identifier will always
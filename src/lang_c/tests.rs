@@ -1,6 +1,10 @@
 use super::types::CType;
-use crate::lang_c::LangC;
-use crate::test_utils::{fetch, ty};
+use crate::common::{Lang, Outputs};
+use crate::lang_c::{LangC, Target};
+use crate::parse;
+use crate::symbols::SymbolTable;
+use crate::test_utils::{fetch, ty, write_crate};
+use crate::Bindgen;
 use indoc::indoc;
 use unwrap::unwrap;
 
@@ -427,3 +431,1359 @@ FfiResult const* new_block(uint8_t const* payload, uint8_t const* const* public_
 
     assert_multiline_eq!(actual, expected);
 }
+
+#[test]
+fn docs() {
+    let outputs = compile!(LangC::default(), {
+        /// A point.
+        ///
+        /// In two dimensions.
+        #[repr(C)]
+        pub struct Point {
+            /// Horizontal.
+            x: f64,
+            y: f64,
+        }
+
+        /// Distance to the origin.
+        #[no_mangle]
+        pub extern "C" fn norm(point: *const Point) -> f64 {}
+    });
+
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         /// A point.
+         ///
+         /// In two dimensions.
+         typedef struct Point {
+             /// Horizontal.
+             double x;
+             double y;
+         } Point;
+
+         /// Distance to the origin.
+         double norm(Point const* point);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    let actual = fetch(&outputs, ".h");
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn amalgamated_header() {
+    let mut lang = LangC::new();
+    lang.set_lib_name("backend");
+    lang.add_custom_code("typedef struct App App;\n");
+    lang.set_split_headers_enabled(false);
+    lang.set_amalgamated_header("backend_all.h");
+
+    let mut outputs = Outputs::default();
+
+    let ast: syn::File = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Config { pub limits: Limits }
+         #[no_mangle] pub extern \"C\" fn app_config(app: *const App) -> Config {}"
+    ));
+    unwrap!(parse::parse_file(
+        &mut lang,
+        &ast,
+        &["ffi".to_string()],
+        &mut outputs
+    ));
+
+    let ast: syn::File = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Limits { pub max: u32 }"
+    ));
+    unwrap!(parse::parse_file(
+        &mut lang,
+        &ast,
+        &["ffi".to_string(), "limits".to_string()],
+        &mut outputs
+    ));
+
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    assert_eq!(outputs.len(), 1);
+
+    let actual = fetch(&outputs, "backend_all.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backend_allh
+         #define bindgen_backend_allh
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         typedef struct App App;
+
+         typedef struct Limits {
+             uint32_t max;
+         } Limits;
+
+         typedef struct Config {
+             Limits limits;
+         } Config;
+
+         Config app_config(App const* app);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn struct_layouts() {
+    let source = "#[repr(C)] pub struct Header { pub tag: u8, pub len: usize }
+                  #[repr(C)] pub struct Packet {
+                      pub header: Header,
+                      pub checksum: u16,
+                      pub payload: [u8; 3],
+                      pub timestamp: u64,
+                      pub callback: extern \"C\" fn(code: i32),
+                  }";
+    let ast: syn::File = unwrap!(syn::parse_str(source));
+
+    let mut lang = LangC::new();
+    let mut outputs = Outputs::default();
+    unwrap!(parse::parse_file(
+        &mut lang,
+        &ast,
+        &["ffi".to_string()],
+        &mut outputs
+    ));
+
+    let layout = unwrap!(lang.struct_layout("Header"));
+    assert_eq!((layout.size, layout.align), (16, 8));
+
+    let layout = unwrap!(lang.struct_layout("Packet"));
+    assert_eq!((layout.size, layout.align), (40, 8));
+    let offsets: Vec<_> = layout
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.offset))
+        .collect();
+    assert_eq!(
+        offsets,
+        vec![
+            ("header", 0),
+            ("checksum", 16),
+            ("payload", 18),
+            ("timestamp", 24),
+            ("callback", 32),
+        ]
+    );
+
+    lang.set_layout_target(Target::ilp32());
+    let layout = unwrap!(lang.struct_layout("Packet"));
+    assert_eq!((layout.size, layout.align), (28, 4));
+    assert_eq!(layout.fields[3].offset, 16);
+
+    assert!(lang.struct_layout("Unknown").is_err());
+}
+
+#[test]
+fn layout_checks() {
+    let mut lang = LangC::new();
+    lang.set_split_headers_enabled(false);
+    lang.set_amalgamated_header("backend_all.h");
+    lang.set_layout_checks_enabled(true);
+
+    let mut outputs = Outputs::default();
+    let ast: syn::File = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Point { pub x: f32, pub y: f64, pub tags: [i8; 2] }"
+    ));
+    unwrap!(parse::parse_file(
+        &mut lang,
+        &ast,
+        &["ffi".to_string()],
+        &mut outputs
+    ));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    let actual = fetch(&outputs, "backend_all.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backend_allh
+         #define bindgen_backend_allh
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef struct Point {
+             float x;
+             double y;
+             int8_t tags[2];
+         } Point;
+
+         _Static_assert(sizeof(Point) == 24, \"unexpected size of Point\");
+         _Static_assert(offsetof(Point, x) == 0, \"unexpected offset of Point.x\");
+         _Static_assert(offsetof(Point, y) == 8, \"unexpected offset of Point.y\");
+         _Static_assert(offsetof(Point, tags) == 16, \"unexpected offset of Point.tags\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn packed_and_aligned_structs() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        #[repr(C, packed)]
+        pub struct Packed {
+            pub tag: u8,
+            pub value: u32,
+        }
+
+        #[repr(C)]
+        #[repr(align(16))]
+        pub struct Aligned {
+            pub value: u32,
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         #pragma pack(push, 1)
+         typedef struct Packed {
+             uint8_t tag;
+             uint32_t value;
+         } Packed;
+         #pragma pack(pop)
+
+         typedef struct __attribute__((aligned(16))) Aligned {
+             uint32_t value;
+         } Aligned;
+
+         _Static_assert(sizeof(Packed) == 5, \"unexpected size of Packed\");
+         _Static_assert(offsetof(Packed, tag) == 0, \"unexpected offset of Packed.tag\");
+         _Static_assert(offsetof(Packed, value) == 1, \"unexpected offset of Packed.value\");
+
+         _Static_assert(sizeof(Aligned) == 16, \"unexpected size of Aligned\");
+         _Static_assert(offsetof(Aligned, value) == 0, \"unexpected offset of Aligned.value\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn sized_enums() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        #[repr(u8)]
+        pub enum Mode {
+            ReadOnly,
+            WriteOnly = 4,
+        }
+
+        #[repr(i8)]
+        pub enum Seek {
+            Backward = -1,
+            Forward,
+        }
+
+        #[repr(C)]
+        pub struct File {
+            pub mode: Mode,
+            pub size: u32,
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef uint8_t Mode;
+
+         enum {
+             Mode_ReadOnly,
+             Mode_WriteOnly = 4,
+         };
+
+         typedef int8_t Seek;
+
+         enum {
+             Seek_Backward = -1,
+             Seek_Forward,
+         };
+
+         typedef struct File {
+             Mode mode;
+             uint32_t size;
+         } File;
+
+         _Static_assert(sizeof(File) == 8, \"unexpected size of File\");
+         _Static_assert(offsetof(File, mode) == 0, \"unexpected offset of File.mode\");
+         _Static_assert(offsetof(File, size) == 4, \"unexpected offset of File.size\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn tagged_unions() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        #[repr(C, u8)]
+        pub enum Shape {
+            Circle { radius: f32 },
+            Rect(u16, u16),
+            Empty,
+        }
+
+        #[repr(u8)]
+        pub enum Event {
+            Key(u32),
+            Quit,
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef uint8_t Shape_Tag;
+
+         enum {
+             Shape_Circle,
+             Shape_Rect,
+             Shape_Empty,
+         };
+
+         typedef struct Shape_Circle_Body {
+             float radius;
+         } Shape_Circle_Body;
+
+         typedef struct Shape_Rect_Body {
+             uint16_t field_0;
+             uint16_t field_1;
+         } Shape_Rect_Body;
+
+         typedef union Shape_Body {
+             Shape_Circle_Body circle;
+             Shape_Rect_Body rect;
+         } Shape_Body;
+
+         typedef struct Shape {
+             Shape_Tag tag;
+             Shape_Body body;
+         } Shape;
+
+         typedef uint8_t Event_Tag;
+
+         enum {
+             Event_Key,
+             Event_Quit,
+         };
+
+         typedef struct Event_Key_Body {
+             Event_Tag tag;
+             uint32_t field_0;
+         } Event_Key_Body;
+
+         typedef union Event {
+             Event_Tag tag;
+             Event_Key_Body key;
+         } Event;
+
+         _Static_assert(sizeof(Shape_Circle_Body) == 4, \"unexpected size of Shape_Circle_Body\");
+         _Static_assert(offsetof(Shape_Circle_Body, radius) == 0, \"unexpected offset of Shape_Circle_Body.radius\");
+
+         _Static_assert(sizeof(Shape_Rect_Body) == 4, \"unexpected size of Shape_Rect_Body\");
+         _Static_assert(offsetof(Shape_Rect_Body, field_0) == 0, \"unexpected offset of Shape_Rect_Body.field_0\");
+         _Static_assert(offsetof(Shape_Rect_Body, field_1) == 2, \"unexpected offset of Shape_Rect_Body.field_1\");
+
+         _Static_assert(sizeof(Shape_Body) == 4, \"unexpected size of Shape_Body\");
+         _Static_assert(offsetof(Shape_Body, circle) == 0, \"unexpected offset of Shape_Body.circle\");
+         _Static_assert(offsetof(Shape_Body, rect) == 0, \"unexpected offset of Shape_Body.rect\");
+
+         _Static_assert(sizeof(Shape) == 8, \"unexpected size of Shape\");
+         _Static_assert(offsetof(Shape, tag) == 0, \"unexpected offset of Shape.tag\");
+         _Static_assert(offsetof(Shape, body) == 4, \"unexpected offset of Shape.body\");
+
+         _Static_assert(sizeof(Event_Key_Body) == 8, \"unexpected size of Event_Key_Body\");
+         _Static_assert(offsetof(Event_Key_Body, tag) == 0, \"unexpected offset of Event_Key_Body.tag\");
+         _Static_assert(offsetof(Event_Key_Body, field_0) == 4, \"unexpected offset of Event_Key_Body.field_0\");
+
+         _Static_assert(sizeof(Event) == 8, \"unexpected size of Event\");
+         _Static_assert(offsetof(Event, tag) == 0, \"unexpected offset of Event.tag\");
+         _Static_assert(offsetof(Event, key) == 0, \"unexpected offset of Event.key\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn unions() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        #[repr(C)]
+        pub union Value {
+            pub integer: i32,
+            pub real: f64,
+            pub bytes: [u8; 12],
+        }
+
+        pub union Ignored {
+            pub integer: i32,
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef union Value {
+             int32_t integer;
+             double real;
+             uint8_t bytes[12];
+         } Value;
+
+         _Static_assert(sizeof(Value) == 16, \"unexpected size of Value\");
+         _Static_assert(offsetof(Value, integer) == 0, \"unexpected offset of Value.integer\");
+         _Static_assert(offsetof(Value, real) == 0, \"unexpected offset of Value.real\");
+         _Static_assert(offsetof(Value, bytes) == 0, \"unexpected offset of Value.bytes\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn tuple_and_transparent_structs() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        #[repr(transparent)]
+        pub struct Handle(u64);
+
+        #[repr(transparent)]
+        pub struct Tagged {
+            pub raw: u32,
+            pub marker: PhantomData<u8>,
+        }
+
+        #[repr(C)]
+        pub struct Pair(pub Handle, pub u8);
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef uint64_t Handle;
+
+         typedef uint32_t Tagged;
+
+         typedef struct Pair {
+             Handle field_0;
+             uint8_t field_1;
+         } Pair;
+
+         _Static_assert(sizeof(Pair) == 16, \"unexpected size of Pair\");
+         _Static_assert(offsetof(Pair, field_0) == 0, \"unexpected offset of Pair.field_0\");
+         _Static_assert(offsetof(Pair, field_1) == 8, \"unexpected offset of Pair.field_1\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn array_aliases() {
+    let mut lang = LangC::new();
+    lang.set_layout_checks_enabled(true);
+
+    let outputs = compile!(lang, {
+        pub type Key = [u8; 32];
+
+        #[repr(C)]
+        pub struct Record {
+            pub key: Key,
+            pub id: u64,
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+         #include <stddef.h>
+
+         #if defined(__cplusplus) && !defined(_Static_assert)
+         #define _Static_assert static_assert
+         #endif
+
+         typedef uint8_t Key[32];
+
+         typedef struct Record {
+             Key key;
+             uint64_t id;
+         } Record;
+
+         _Static_assert(sizeof(Record) == 40, \"unexpected size of Record\");
+         _Static_assert(offsetof(Record, key) == 0, \"unexpected offset of Record.key\");
+         _Static_assert(offsetof(Record, id) == 32, \"unexpected offset of Record.id\");
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let errors = unwrap!(try_compile!(LangC::default(), {
+        pub const KEY_LEN: usize = 32;
+        pub type Key = [u8; KEY_LEN];
+    })
+    .err());
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "bindgen cannot handle the length of the array aliased by Key"
+    );
+}
+
+#[test]
+fn statics() {
+    let outputs = compile!(LangC::default(), {
+        #[no_mangle]
+        pub static VERSION: *const c_char = ptr::null();
+
+        #[no_mangle]
+        pub static mut COUNTER: u64 = 0;
+
+        #[no_mangle]
+        pub static TABLE: [u8; 4] = [0; 4];
+
+        #[no_mangle]
+        pub static ON_EVENT: extern "C" fn(code: i32) = on_event;
+
+        pub static IGNORED: u32 = 0;
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         extern char const* const VERSION;
+
+         extern uint64_t COUNTER;
+
+         extern uint8_t const TABLE[4];
+
+         extern void (*const ON_EVENT)(int32_t code);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn generic_struct_instantiations() {
+    let outputs = compile!(LangC::default(), {
+        #[repr(C)]
+        pub struct FfiArray<T> {
+            pub ptr: *const T,
+            pub len: usize,
+        }
+
+        #[repr(C)]
+        pub struct Point {
+            pub x: f32,
+            pub y: f32,
+        }
+
+        pub type U8Array = FfiArray<u8>;
+        pub type PointArray = FfiArray<Point>;
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         typedef struct Point {
+             float x;
+             float y;
+         } Point;
+
+         typedef struct U8Array {
+             uint8_t const* ptr;
+             uintptr_t len;
+         } U8Array;
+
+         typedef struct PointArray {
+             Point const* ptr;
+             uintptr_t len;
+         } PointArray;
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn uninstantiated_generic_structs() {
+    let errors = unwrap!(try_compile!(LangC::default(), {
+        #[repr(C)]
+        pub struct FfiArray<T> {
+            pub ptr: *const T,
+            pub len: usize,
+        }
+    })
+    .err());
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "bindgen cannot handle parameterized structs"
+    );
+}
+
+#[test]
+fn nullable_function_pointers() {
+    let outputs = compile!(LangC::default(), {
+        #[repr(C)]
+        pub struct Callbacks {
+            pub on_data: Option<extern "C" fn(user_data: *mut c_void, len: usize)>,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            user_data: *mut c_void,
+            o_cb: Option<extern "C" fn(user_data: *mut c_void, result: *const FfiResult)>,
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         typedef struct Callbacks {
+             void (*on_data)(void* user_data, uintptr_t len);
+         } Callbacks;
+
+         void subscribe(void* user_data, void (*o_cb)(void* user_data, FfiResult const* result));
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn references_and_smart_pointers() {
+    let outputs = compile!(LangC::default(), {
+        #[repr(C)]
+        pub struct Point {
+            pub x: f32,
+            pub y: f32,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn point_new(x: f32, y: f32) -> Box<Point> {}
+
+        #[no_mangle]
+        pub extern "C" fn point_free(point: Option<Box<Point>>) {}
+
+        #[no_mangle]
+        pub extern "C" fn point_translate(
+            point: &mut Point,
+            by: &Point,
+            origin: Option<&Point>,
+            out: NonNull<Point>,
+            hint: Option<NonNull<f32>>,
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, ".h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_h
+         #define bindgen_h
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         #if !defined(__clang__) && !defined(_Nonnull)
+         #define _Nonnull
+         #endif
+
+         typedef struct Point {
+             float x;
+             float y;
+         } Point;
+
+         Point* _Nonnull point_new(float x, float y);
+
+         void point_free(Point* point);
+
+         void point_translate(Point* _Nonnull point, Point const* _Nonnull by, Point const* origin, Point* _Nonnull out, float* hint);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn qualified_paths_and_use_aliases() {
+    let mut lang = LangC::new();
+    lang.set_lib_name("backend");
+
+    let modules = [
+        vec!["ffi".to_string()],
+        vec!["ffi".to_string(), "types".to_string()],
+    ];
+    let mut files: Vec<syn::File> = vec![
+        unwrap!(syn::parse_str(
+            "use crate::ffi::types::Limits as FfiLimits;
+             use libc::c_char as Char;
+             #[repr(C)] pub struct Config {
+                 pub limits: FfiLimits,
+                 pub mode: crate::ffi::types::Mode,
+                 pub name: *const Char,
+                 pub result: ffi_utils::FfiResult,
+             }"
+        )),
+        unwrap!(syn::parse_str(
+            "#[repr(C)] pub struct Limits { pub max: u32, pub unit: super::types::Unit }
+             #[repr(C)] pub enum Unit { Bytes, Items }
+             #[repr(C)] pub enum Mode { Read, Write }"
+        )),
+    ];
+
+    let mut symbols = SymbolTable::new();
+    for (module, file) in modules.iter().zip(&files) {
+        symbols.add_file(module, file);
+    }
+    for (module, file) in modules.iter().zip(files.iter_mut()) {
+        symbols.resolve_file(module, file);
+    }
+
+    let mut outputs = Outputs::default();
+    for (module, file) in modules.iter().zip(&files) {
+        unwrap!(parse::parse_file(&mut lang, file, module, &mut outputs));
+    }
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    // The types module is included first, as `Config` depends on its declarations.
+    let actual = fetch(&outputs, "backend.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backend_root
+         #define bindgen_backend_root
+
+         #include \"backend/types.h\"
+         #include \"backend/backend.h\"
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend/backend.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backendbackendh
+         #define bindgen_backendbackendh
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         typedef struct Config {
+             Limits limits;
+             Mode mode;
+             char const* name;
+             FfiResult result;
+         } Config;
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn dependency_types() {
+    let ffi_utils = write_crate(
+        "c_dependency_types",
+        &[
+            ("src/lib.rs", "mod repr_c;\npub use crate::repr_c::*;\n"),
+            (
+                "src/repr_c.rs",
+                indoc!(
+                    "
+                    #[repr(C)]
+                    pub struct ErrorDetail {
+                        pub code: i32,
+                    }
+
+                    #[repr(C)]
+                    pub struct FfiResult {
+                        pub error_code: i32,
+                        pub detail: ErrorDetail,
+                    }
+
+                    #[repr(C)]
+                    pub struct Unused {
+                        pub value: u8,
+                    }
+                    "
+                ),
+            ),
+        ],
+    );
+    let source = indoc!(
+        "
+        use ffi_utils::FfiResult;
+
+        #[no_mangle]
+        pub extern \"C\" fn status(result: *const FfiResult) {}
+        "
+    );
+
+    let mut bindgen = unwrap!(Bindgen::new());
+    bindgen
+        .source_code("ffi/src/lib.rs", source)
+        .dependency("ffi_utils", &ffi_utils);
+
+    // Referenced types of the dependency are generated into their own header.
+    let mut lang = LangC::new();
+    let mut outputs = Outputs::default();
+    unwrap!(bindgen.compile(&mut lang, &mut outputs, true));
+
+    let actual = fetch(&outputs, "backend.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backend_root
+         #define bindgen_backend_root
+
+         #include \"ffi_utils.h\"
+         #include \"backend/backend.h\"
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "ffi_utils.h");
+    assert!(actual.contains("typedef struct ErrorDetail {"));
+    assert!(actual.contains("typedef struct FfiResult {"));
+    assert!(!actual.contains("Unused"));
+
+    // Types provided by the bindings of the dependency are included from them instead.
+    let mut lang = LangC::new();
+    lang.add_provided_package("ffi_utils", "ffi_utils/ffi_utils.h");
+    let mut outputs = Outputs::default();
+    unwrap!(bindgen.compile(&mut lang, &mut outputs, true));
+
+    assert!(!outputs.contains_key("ffi_utils.h"));
+
+    let actual = fetch(&outputs, "backend/backend.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_backendbackendh
+         #define bindgen_backendbackendh
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         #include \"ffi_utils/ffi_utils.h\"
+
+         void status(FfiResult const* result);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn dependency_types_sharing_a_name() {
+    let ffi_utils = write_crate(
+        "c_dependency_types_sharing_a_name",
+        &[(
+            "src/lib.rs",
+            indoc!(
+                "
+                #[repr(C)]
+                pub struct Handle {
+                    pub id: u64,
+                    pub generation: u32,
+                }
+
+                #[repr(C)]
+                pub struct FfiResult {
+                    pub error_code: i32,
+                }
+                "
+            ),
+        )],
+    );
+    let source = indoc!(
+        "
+        #[repr(C)]
+        pub struct Handle {
+            pub fd: i32,
+        }
+
+        #[no_mangle]
+        pub extern \"C\" fn close(handle: Handle, result: *mut ffi_utils::FfiResult) {}
+        "
+    );
+
+    let mut lang = LangC::new();
+    let mut outputs = Outputs::default();
+    unwrap!(unwrap!(Bindgen::new())
+        .source_code("ffi/src/lib.rs", source)
+        .dependency("ffi_utils", &ffi_utils)
+        .compile(&mut lang, &mut outputs, true));
+
+    // Only the dependency type the crate refers to is generated, not the one named like its own.
+    let actual = fetch(&outputs, "ffi_utils.h");
+    assert!(actual.contains("typedef struct FfiResult {"));
+    assert!(!actual.contains("Handle"));
+
+    let actual = fetch(&outputs, "backend/backend.h");
+    assert!(actual.contains("typedef struct Handle {\n    int32_t fd;\n} Handle;"));
+    assert!(actual.contains("void close(Handle handle, FfiResult* result);"));
+}
+
+#[test]
+fn workspace() {
+    let ffi_utils = write_crate(
+        "c_workspace/ffi_utils",
+        &[(
+            "src/lib.rs",
+            indoc!(
+                "
+                #[repr(C)]
+                pub struct FfiResult {
+                    pub error_code: i32,
+                }
+                "
+            ),
+        )],
+    );
+    let authenticator = write_crate(
+        "c_workspace/safe_authenticator",
+        &[
+            ("src/lib.rs", "pub use ffi::*;\n"),
+            (
+                "src/ffi.rs",
+                indoc!(
+                    "
+                    use ffi_utils::FfiResult;
+
+                    #[repr(C)]
+                    pub struct AuthGranted {
+                        pub result: FfiResult,
+                    }
+                    "
+                ),
+            ),
+        ],
+    );
+    let app = write_crate(
+        "c_workspace/safe_app",
+        &[
+            ("src/lib.rs", "pub use ffi::*;\n"),
+            (
+                "src/ffi.rs",
+                indoc!(
+                    "
+                    #[no_mangle]
+                    pub extern \"C\" fn app_registered(
+                        granted: *const safe_authenticator::AuthGranted,
+                    ) {
+                    }
+                    "
+                ),
+            ),
+        ],
+    );
+
+    let mut bindgen = unwrap!(Bindgen::new());
+    bindgen
+        .dependency("ffi_utils", &ffi_utils)
+        .workspace_member("safe_authenticator", &authenticator)
+        .workspace_member("safe_app", &app);
+
+    let units = unwrap!(bindgen.compile_workspace(|unit| {
+        let mut lang = LangC::new();
+        lang.set_lib_name(unit);
+        lang
+    }));
+
+    // Shared types are only generated in the common unit.
+    let common = &units["common"];
+    assert!(fetch(common, "ffi_utils.h").contains("typedef struct FfiResult {"));
+    assert!(fetch(common, "common.h").contains("#include \"ffi_utils.h\""));
+
+    let authenticator = fetch(
+        &units["safe_authenticator"],
+        "safe_authenticator/safe_authenticator.h",
+    );
+    assert!(authenticator.contains("#include \"common.h\""));
+    assert!(authenticator.contains("typedef struct AuthGranted {"));
+    assert!(!authenticator.contains("typedef struct FfiResult {"));
+
+    let app = &units["safe_app"];
+    assert!(!app.contains_key("ffi_utils.h"));
+    assert!(!app.contains_key("safe_authenticator.h"));
+
+    let actual = fetch(app, "safe_app/safe_app.h");
+    let expected = indoc!(
+        "
+
+         #ifndef bindgen_safe_appsafe_apph
+         #define bindgen_safe_appsafe_apph
+
+
+         #ifdef __cplusplus
+         extern \"C\" {
+         #endif
+
+         #include <stdint.h>
+         #include <stdbool.h>
+
+         #include \"safe_authenticator.h\"
+
+         void app_registered(AuthGranted const* granted);
+
+
+
+         #ifdef __cplusplus
+         }
+         #endif
+
+
+         #endif
+        "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
@@ -2,7 +2,7 @@
 
 use std::fmt::{self, Display, Formatter};
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CTypeNamed(pub String, pub CType);
 
 impl Display for CTypeNamed {
@@ -12,13 +12,16 @@ impl Display for CTypeNamed {
             // special case
             CType::FnDecl { .. } => write!(f, "{}", self.1),
 
+            // Array dimensions go after the name
+            CType::Array(ref cty, len) => write!(f, "{} {}[{}]", cty, self.0, len),
+
             // For all other cases we add a type prefix
             _ => write!(f, "{} {}", self.1, self.0),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum CPtrType {
     Const,
     Mutable,
@@ -33,12 +36,15 @@ impl Display for CPtrType {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum CType {
     Void,
     Mapping(String),
     Native(&'static str),
     Ptr(Box<CType>, CPtrType),
+    /// Pointer which is never null, annotated with `_Nonnull`.
+    NonNullPtr(Box<CType>, CPtrType),
+    Array(Box<CType>, usize),
     FnDecl {
         inner: String,
         args: Vec<CTypeNamed>,
@@ -63,7 +69,9 @@ impl CType {
                         .flat_map(|&CTypeNamed(_, ref cty)| cty.dependencies()),
                 )
                 .collect(),
-            CType::Ptr(ref cty, _) => cty.dependencies(),
+            CType::Ptr(ref cty, _) | CType::NonNullPtr(ref cty, _) | CType::Array(ref cty, _) => {
+                cty.dependencies()
+            }
             CType::Mapping(ref mapping) => vec![mapping.clone()],
             _ => Default::default(),
         }
@@ -77,6 +85,8 @@ impl Display for CType {
             CType::Mapping(ref s) => write!(f, "{}", s),
             CType::Native(s) => write!(f, "{}", s),
             CType::Ptr(ref cty, ref ptrty) => write!(f, "{}{}*", cty, ptrty),
+            CType::NonNullPtr(ref cty, ref ptrty) => write!(f, "{}{}* _Nonnull", cty, ptrty),
+            CType::Array(ref cty, len) => write!(f, "{}[{}]", cty, len),
             CType::FnDecl {
                 ref inner,
                 ref args,
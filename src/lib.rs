@@ -27,32 +27,56 @@
 )]
 
 pub use common::FilterMode;
+pub use cpp::LangCpp;
 pub use csharp::LangCSharp;
+pub use cython::LangCython;
+pub use dart::LangDart;
 pub use errors::Error;
 pub use errors::Level;
-pub use java::LangJava;
-pub use lang_c::LangC;
+pub use go::LangGo;
+pub use java::{LangJava, LangJavaPanama, LangKotlin};
+pub use lang_c::{FieldLayout, LangC, StructLayout, Target};
+pub use lua::LangLua;
+pub use python::LangPython;
+pub use ruby::LangRuby;
+pub use swift::LangSwift;
+pub use typescript::LangTypeScript;
 
 use common::{Lang, Outputs};
-use std::collections::{BTreeSet, HashMap};
+use dependencies::CrateFiles;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::fs::File;
 use std::io::Error as IoError;
 use std::io::{Read, Write};
 use std::path::{self, Component, Path, PathBuf};
+use symbols::SymbolTable;
 use unwrap::unwrap;
 
 #[cfg(test)]
 #[macro_use]
 mod test_utils;
+#[macro_use]
+mod output;
 mod common;
+mod cpp;
 mod csharp;
+mod cython;
+mod dart;
+mod dependencies;
 mod errors;
+mod go;
+mod intermediate;
 mod java;
 mod lang_c;
-mod output;
+mod lua;
 mod parse;
+mod python;
+mod ruby;
 mod struct_field;
+mod swift;
+mod symbols;
+mod typescript;
 
 enum Input {
     File(PathBuf),
@@ -90,6 +114,13 @@ enum Input {
 pub struct Bindgen {
     /// The root source file of the crate.
     input: Input,
+    /// Dependency crates declaring types used by the crate, along with their root source files
+    /// (located with `cargo metadata` if not given).
+    dependencies: Vec<(String, Option<PathBuf>)>,
+    /// Crates of the workspace to generate bindings for, along with their root source files.
+    members: Vec<(String, PathBuf)>,
+    /// Name of the bindings unit containing the types shared by the workspace members.
+    shared_unit: String,
 }
 
 impl Bindgen {
@@ -101,7 +132,12 @@ impl Bindgen {
         let source_path = source_file_from_cargo()?;
         let input = Input::File(PathBuf::from(source_path));
 
-        Ok(Bindgen { input })
+        Ok(Bindgen {
+            input,
+            dependencies: Vec::new(),
+            members: Vec::new(),
+            shared_unit: "common".to_owned(),
+        })
     }
 
     /// Set the path to the root source file of the crate.
@@ -127,6 +163,75 @@ impl Bindgen {
         self
     }
 
+    /// Generate the types referred to from the dependency crate `name`, parsing its sources from
+    /// the root source file at `path`.
+    ///
+    /// The types are generated as part of the module `name`, unless the language is told that
+    /// the crate has its own bindings (e.g. `LangC::add_provided_package`).
+    pub fn dependency<S, T>(&mut self, name: S, path: T) -> &mut Self
+    where
+        S: Into<String>,
+        PathBuf: From<T>,
+    {
+        self.dependencies
+            .push((name.into(), Some(PathBuf::from(path))));
+        self
+    }
+
+    /// Like `dependency`, but locates the sources of the crate `name` using `cargo metadata`,
+    /// without accessing the network.
+    pub fn dependency_from_cargo<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.dependencies.push((name.into(), None));
+        self
+    }
+
+    /// Add a crate of the workspace to generate bindings for with `compile_workspace`, given the
+    /// path to its root source file.
+    pub fn workspace_member<S, T>(&mut self, name: S, path: T) -> &mut Self
+    where
+        S: Into<String>,
+        PathBuf: From<T>,
+    {
+        self.members.push((name.into(), PathBuf::from(path)));
+        self
+    }
+
+    /// Set the name of the bindings unit which contains the types of the dependencies used by
+    /// the workspace members. Defaults to `common`.
+    pub fn shared_unit<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.shared_unit = name.into();
+        self
+    }
+
+    /// Parse the sources of the dependency crates.
+    fn parse_dependencies(&self) -> Result<Vec<(String, CrateFiles)>, Vec<Error>> {
+        let unlocated: Vec<String> = self
+            .dependencies
+            .iter()
+            .filter(|(_, path)| path.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        let mut located = if unlocated.is_empty() {
+            HashMap::new()
+        } else {
+            dependencies::sources_from_cargo(&unlocated).map_err(|error| vec![error])?
+        };
+
+        let mut parsed = Vec::new();
+        for (name, path) in &self.dependencies {
+            let path = match path {
+                Some(path) => path.clone(),
+                None => unwrap!(located.remove(name)),
+            };
+            println!("Parsing dependency {} ({:?})", name, path);
+
+            let files = dependencies::parse_crate(&path).map_err(|error| vec![error])?;
+            parsed.push((name.clone(), files));
+        }
+
+        Ok(parsed)
+    }
+
     /// Compile just the code into header declarations.
     ///
     /// This does not add any include-guards, includes, or extern declarations. It is mainly
@@ -158,50 +263,36 @@ impl Bindgen {
         outputs: &mut Outputs,
         path: &PathBuf,
     ) -> Result<(), Vec<Error>> {
-        let base_path = unwrap!(path.parent());
         let mod_path: String = unwrap!(path.to_str()).to_string();
+        let (ast, mut modules, mut files) = read_crate(path);
 
-        // Parse the top level mod.
-        // Creates AST for the entire file
-        let mut file = unwrap!(File::open(path));
-        let mut content = String::new();
-        unwrap!(file.read_to_string(&mut content));
-        let ast = unwrap!(syn::parse_file(&content));
-        let mut imported: BTreeSet<Vec<String>> = Default::default();
-        for item in ast.items {
-            match &item {
-                syn::Item::Use(ref itemuse) => {
-                    if parse::imported_mods(itemuse).is_some() {
-                        imported.insert(unwrap!(parse::imported_mods(itemuse)));
-                    }
-                }
-                // Parsing const in lib.rs for CSharp
-                syn::Item::Const(ref item) => {
-                    lang.parse_const(item, &[mod_path.clone()], outputs)?;
-                }
-                _ => {}
+        // Parsing const in lib.rs for CSharp
+        for item in &ast.items {
+            if let syn::Item::Const(ref item) = *item {
+                lang.parse_const(item, std::slice::from_ref(&mod_path), outputs)?;
             }
         }
-        for module in imported {
-            let mut mod_path = base_path.join(&format!(
-                "{}.rs",
-                module.join(&path::MAIN_SEPARATOR.to_string())
-            ));
-
-            if !mod_path.exists() {
-                mod_path = base_path.join(&format!(
-                    "{}/mod.rs",
-                    module.join(&path::MAIN_SEPARATOR.to_string())
-                ));
-            }
 
-            println!("Parsing {} ({:?})", module.join("::"), mod_path);
+        // Types can be referred to through paths and imports across the whole crate
+        let dependencies = self.parse_dependencies()?;
+        let mut symbols = SymbolTable::new();
+        symbols.add_file(&[], &ast);
+        for (module, file) in modules.iter().zip(&files) {
+            symbols.add_file(module, file);
+        }
+        add_dependencies(&mut symbols, &dependencies);
+        for (module, file) in modules.iter().zip(files.iter_mut()) {
+            symbols.resolve_file(module, file);
+        }
+
+        // Only the types of dependencies which are used are generated
+        let (dep_modules, dep_files) = referenced_dependency_items(&files, &dependencies);
+        files.iter_mut().for_each(symbols::unqualify_file);
+        modules.splice(0..0, dep_modules);
+        files.splice(0..0, dep_files);
 
-            let mut file = unwrap!(File::open(mod_path));
-            let mut content = String::new();
-            unwrap!(file.read_to_string(&mut content));
-            let ast = unwrap!(syn::parse_file(&content));
-            parse::parse_file(lang, &ast, &module, outputs)?;
+        for (module, ast) in modules.iter().zip(&files) {
+            parse::parse_file(lang, ast, module, outputs)?;
         }
         Ok(())
     }
@@ -215,9 +306,22 @@ impl Bindgen {
     ) -> Result<(), Vec<Error>> {
         let module = convert_lib_path_to_module(&PathBuf::from(file_name.clone()));
 
-        let _ast: syn::File = unwrap!(syn::parse_str(&source));
+        let mut files = vec![unwrap!(syn::parse_str::<syn::File>(&source))];
+        let dependencies = self.parse_dependencies()?;
+        let mut symbols = SymbolTable::new();
+        symbols.add_file(&[], &files[0]);
+        add_dependencies(&mut symbols, &dependencies);
+        symbols.resolve_file(&[], &mut files[0]);
 
-        for item in _ast.items {
+        let (dep_modules, dep_files) = referenced_dependency_items(&files, &dependencies);
+        symbols::unqualify_file(&mut files[0]);
+        files.splice(0..0, dep_files);
+
+        for (module, file) in dep_modules.iter().zip(&files) {
+            parse::parse_file(lang, file, module, outputs)?;
+        }
+
+        for item in files.remove(dep_modules.len()).items {
             match &item {
                 syn::Item::Mod(ref item) => {
                     parse::parse_mod(lang, item, &module[..], outputs)?;
@@ -237,6 +341,12 @@ impl Bindgen {
                 syn::Item::Struct(ref item) => {
                     lang.parse_struct(item, &module[..], outputs)?;
                 }
+                syn::Item::Union(ref item) => {
+                    lang.parse_union(item, &module[..], outputs)?;
+                }
+                syn::Item::Static(ref item) => {
+                    lang.parse_static(item, &module[..], outputs)?;
+                }
                 _ => {}
             }
         }
@@ -244,6 +354,145 @@ impl Bindgen {
         Ok(())
     }
 
+    /// Compile the bindings of all workspace members together, returning the outputs of each
+    /// bindings unit by its name.
+    ///
+    /// `new_lang` creates the target language of a unit given its name. Each member gets its own
+    /// unit, while the types of the dependencies (see `dependency`) used by the members are
+    /// generated once into the shared unit. Types of other units are included or imported
+    /// from them instead of being generated again.
+    pub fn compile_workspace<L, F>(
+        &mut self,
+        mut new_lang: F,
+    ) -> Result<BTreeMap<String, Outputs>, Vec<Error>>
+    where
+        L: Lang,
+        F: FnMut(&str) -> L,
+    {
+        let dependencies = self.parse_dependencies()?;
+        let mut roots = Vec::new();
+        let mut members: Vec<(String, CrateFiles)> = Vec::new();
+        for (name, path) in &self.members {
+            println!("Parsing workspace member {} ({:?})", name, path);
+            let (ast, modules, files) = read_crate(path);
+            roots.push((unwrap!(path.to_str()).to_string(), ast));
+            members.push((name.clone(), modules.into_iter().zip(files).collect()));
+        }
+
+        // Types can be referred to across the members and their dependencies
+        for index in 0..members.len() {
+            let mut symbols = SymbolTable::new();
+            symbols.add_file(&[], &roots[index].1);
+            for (module, file) in &members[index].1 {
+                symbols.add_file(module, file);
+            }
+            add_dependencies(&mut symbols, &dependencies);
+            add_dependencies(&mut symbols, other_members(&members, index));
+            for (other, (name, _)) in members.iter().enumerate() {
+                if other != index {
+                    symbols.add_dependency_file(name, &[], &roots[other].1);
+                }
+            }
+            for (module, file) in &mut members[index].1 {
+                symbols.resolve_file(module, file);
+            }
+        }
+
+        let mut langs: Vec<L> = members.iter().map(|(name, _)| new_lang(name)).collect();
+        let references: Vec<Option<String>> = langs.iter().map(Lang::unit_reference).collect();
+        let mut shared_lang = new_lang(&self.shared_unit);
+        let shared_reference = shared_lang.unit_reference();
+        let mut units = BTreeMap::new();
+
+        // Types of the dependencies used by any of the members, unless the members can't refer to
+        // them and generate them themselves
+        let member_files: Vec<syn::File> = members
+            .iter()
+            .flat_map(|(_, files)| files.iter().map(|(_, file)| file.clone()))
+            .collect();
+        let (modules, files) = referenced_dependency_items(&member_files, &dependencies);
+        if shared_reference.is_some() && files.iter().any(|file| !file.items.is_empty()) {
+            let mut outputs = Outputs::default();
+            for (module, file) in modules.iter().zip(&files) {
+                parse::parse_file(&mut shared_lang, file, module, &mut outputs)?;
+            }
+            shared_lang.finalise_output(&mut outputs)?;
+            units.insert(self.shared_unit.clone(), outputs);
+        }
+
+        for (index, lang) in langs.iter_mut().enumerate() {
+            if let Some(ref shared_reference) = shared_reference {
+                for (dependency, _) in &dependencies {
+                    lang.add_provided_unit(dependency, shared_reference);
+                }
+            }
+            for (other, (name, _)) in members.iter().enumerate() {
+                match references[other] {
+                    Some(ref reference) if other != index => {
+                        lang.add_provided_unit(name, reference)
+                    }
+                    _ => (),
+                }
+            }
+
+            // Types used from the dependencies and other members are parsed along with the
+            // member's own ones, so that they can be referred to
+            let (name, crate_files) = &members[index];
+            let own_files: Vec<syn::File> =
+                crate_files.iter().map(|(_, file)| file.clone()).collect();
+            let (mut modules, mut files) =
+                referenced_dependency_items(&own_files, other_members(&members, index));
+            let referencing: Vec<_> = own_files.iter().chain(&files).cloned().collect();
+            let (dep_modules, dep_files) = referenced_dependency_items(&referencing, &dependencies);
+            modules.splice(0..0, dep_modules);
+            files.splice(0..0, dep_files);
+            modules.extend(crate_files.iter().map(|(module, _)| module.clone()));
+            files.extend(own_files);
+            files.iter_mut().for_each(symbols::unqualify_file);
+
+            let mut outputs = Outputs::default();
+            let (mod_path, root) = &roots[index];
+            for item in &root.items {
+                if let syn::Item::Const(ref item) = *item {
+                    lang.parse_const(item, std::slice::from_ref(mod_path), &mut outputs)?;
+                }
+            }
+            for (module, file) in modules.iter().zip(&files) {
+                parse::parse_file(lang, file, module, &mut outputs)?;
+            }
+            lang.finalise_output(&mut outputs)?;
+            units.insert(name.clone(), outputs);
+        }
+
+        Ok(units)
+    }
+
+    /// Write the bindings of the workspace members into subdirectories of `output_dir` named
+    /// after their units, panicking on error.
+    ///
+    /// This is the workspace counterpart of `run_build`.
+    pub fn run_workspace_build<P, L, F>(&mut self, new_lang: F, output_dir: P)
+    where
+        P: AsRef<Path>,
+        L: Lang,
+        F: FnMut(&str) -> L,
+    {
+        match self.compile_workspace(new_lang) {
+            Ok(units) => {
+                for (name, outputs) in &units {
+                    self.write_outputs_or_panic(output_dir.as_ref().join(name), outputs);
+                }
+            }
+            Err(errors) => {
+                for error in &errors {
+                    self.print_error(error);
+                }
+
+                panic!("Failed to compile.");
+            }
+        }
+    }
+
     pub fn compile_or_panic<L: Lang>(
         &mut self,
         lang: &mut L,
@@ -306,6 +555,86 @@ impl Bindgen {
     }
 }
 
+/// Parse the root source file of a crate along with the modules it imports from `ffi`.
+fn read_crate(path: &Path) -> (syn::File, Vec<Vec<String>>, Vec<syn::File>) {
+    let base_path = unwrap!(path.parent());
+
+    // Parse the top level mod.
+    // Creates AST for the entire file
+    let mut file = unwrap!(File::open(path));
+    let mut content = String::new();
+    unwrap!(file.read_to_string(&mut content));
+    let ast = unwrap!(syn::parse_file(&content));
+    let mut imported: BTreeSet<Vec<String>> = Default::default();
+    for item in &ast.items {
+        if let syn::Item::Use(ref itemuse) = *item {
+            if parse::imported_mods(itemuse).is_some() {
+                imported.insert(unwrap!(parse::imported_mods(itemuse)));
+            }
+        }
+    }
+    let mut modules = Vec::new();
+    let mut files = Vec::new();
+    for module in imported {
+        let mut mod_path = base_path.join(format!("{}.rs", module.join(path::MAIN_SEPARATOR_STR)));
+
+        if !mod_path.exists() {
+            mod_path = base_path.join(format!("{}/mod.rs", module.join(path::MAIN_SEPARATOR_STR)));
+        }
+
+        println!("Parsing {} ({:?})", module.join("::"), mod_path);
+
+        let mut file = unwrap!(File::open(mod_path));
+        let mut content = String::new();
+        unwrap!(file.read_to_string(&mut content));
+        files.push(unwrap!(syn::parse_file(&content)));
+        modules.push(module);
+    }
+
+    (ast, modules, files)
+}
+
+/// Workspace members other than the one at `index`.
+fn other_members(
+    members: &[(String, CrateFiles)],
+    index: usize,
+) -> impl Iterator<Item = &(String, CrateFiles)> {
+    members
+        .iter()
+        .enumerate()
+        .filter(move |&(other, _)| other != index)
+        .map(|(_, member)| member)
+}
+
+/// Make the types of the dependency crates available under their crate names.
+fn add_dependencies<'a>(
+    symbols: &mut SymbolTable,
+    dependencies: impl IntoIterator<Item = &'a (String, CrateFiles)>,
+) {
+    for (name, files) in dependencies {
+        for (module, file) in files {
+            symbols.add_dependency_file(name, module, file);
+        }
+    }
+}
+
+/// Collect the types of each dependency crate which are referred to by `files`, as a module
+/// named after the crate.
+fn referenced_dependency_items<'a>(
+    files: &[syn::File],
+    dependencies: impl IntoIterator<Item = &'a (String, CrateFiles)>,
+) -> (Vec<Vec<String>>, Vec<syn::File>) {
+    dependencies
+        .into_iter()
+        .map(|(name, dependency)| {
+            (
+                vec![name.clone()],
+                dependencies::referenced_items(files, name, dependency),
+            )
+        })
+        .unzip()
+}
+
 /// Convert a path into a top-level module name (e.g. `ffi_utils/src/lib.rs` -> `ffi_libs`)
 fn convert_lib_path_to_module<P: AsRef<Path>>(path: &P) -> Vec<String> {
     let mut res = Vec::new();
@@ -0,0 +1,293 @@
+//! Utilities for emiting fragments of the target language code.
+use super::NativeFunction;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Lua keywords which can be used as identifiers in Rust.
+const KEYWORDS: &[&str] = &[
+    "and", "elseif", "end", "function", "goto", "local", "nil", "not", "or", "repeat", "then",
+    "until",
+];
+
+/// Removes the preprocessor directives from the C header, apart from `#pragma pack` which
+/// LuaJIT understands, along with the `extern "C"` block and the nullability qualifiers.
+pub fn flatten_header(header: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in header.lines() {
+        let trimmed = line.trim();
+        if (trimmed.starts_with('#') && !trimmed.starts_with("#pragma pack"))
+            || trimmed == "extern \"C\" {"
+            || trimmed == "}"
+        {
+            continue;
+        }
+        // Blank lines are collapsed, as the removed lines leave runs of them.
+        if trimmed.is_empty() && lines.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.trim().is_empty()) {
+        let _ = lines.pop();
+    }
+
+    lines
+        .iter()
+        .map(|line| format!("{}\n", line.replace(" _Nonnull", "")))
+        .collect()
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "--");
+        } else {
+            emitln!(writer, "-- {}", line);
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emit_docs(writer, docs);
+    emitln!(writer, "M.{} = {}", name, const_value(&item.value));
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "M.{} = {{", name);
+    writer.indent();
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emitln!(writer, "{} = {},", variant.name, value);
+        next = value + 1;
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Emits the function of the module calling the native function. Functions without strings,
+/// dynamic arrays or callbacks to convert are the native functions themselves.
+pub fn emit_function(writer: &mut IndentedWriter, name: &str, docs: &str, fun: &NativeFunction) {
+    let inputs = &fun.item.inputs;
+    let wrapped = matches!(fun.item.output, Type::String)
+        || inputs
+            .iter()
+            .any(|(_, ty)| ty.is_dynamic_array() || matches!(*ty, Type::Function(..)));
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    if !wrapped {
+        emitln!(writer, "M.{0} = lib.{0}", name);
+        return;
+    }
+
+    // The callback of a function taking user data is called once, with the result of the call.
+    let once = inputs.iter().any(|(name, ty)| is_user_data(name, ty)) && num_callbacks(inputs) == 1;
+
+    let params: Vec<_> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, (name, _))| param_name(name, index, KEYWORDS))
+        .collect();
+
+    emitln!(writer, "function M.{}({})", name, params.join(", "));
+    writer.indent();
+    if once {
+        emitln!(writer, "free_finished()");
+    }
+
+    let mut args = Vec::new();
+    for ((param, ty), name) in inputs.iter().zip(&params) {
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                match **elem {
+                    Type::U8 => emitln!(writer, "local {0}_ptr, {0}_len = to_bytes({0})", name),
+                    ref elem => emitln!(
+                        writer,
+                        "local {0}_ptr, {0}_len = to_array('{1}', {0})",
+                        name,
+                        c_type(elem)
+                    ),
+                }
+                args.push(format!("{}_ptr", name));
+                args.push(format!("{}_len", name));
+            }
+            Type::Function(..) => {
+                let c_type = unwrap!(fun.c_types.get(param));
+                let convert = if once && extract_callback(ty).is_some() {
+                    "to_callback_once"
+                } else {
+                    "to_callback"
+                };
+                args.push(format!("{}('{}', {})", convert, c_type, name));
+            }
+            _ => args.push(name.clone()),
+        }
+    }
+
+    let call = format!("lib.{}({})", name, args.join(", "));
+    match fun.item.output {
+        Type::Unit => emitln!(writer, "{}", call),
+        Type::String => {
+            emitln!(writer, "local result = {}", call);
+            emitln!(writer, "return result ~= nil and ffi.string(result) or nil");
+        }
+        _ => emitln!(writer, "return {}", call),
+    }
+
+    writer.unindent();
+    emitln!(writer, "end");
+}
+
+/// Emits the helpers used by the wrapper functions in `code`.
+pub fn emit_utilities(writer: &mut IndentedWriter, code: &str) {
+    if code.contains("to_bytes(") {
+        emitln!(
+            writer,
+            r#"
+-- Converts a Lua string or table of bytes into a pointer to them and their number.
+local function to_bytes(value)
+  if value == nil then
+    return nil, 0
+  elseif type(value) == 'string' then
+    return ffi.cast('const uint8_t *', value), #value
+  end
+  return ffi.new('uint8_t[?]', #value, value), #value
+end"#
+        );
+    }
+
+    if code.contains("to_array(") {
+        emitln!(
+            writer,
+            r#"
+-- Converts a Lua table into a native array of `ctype` and its length. The array is freed
+-- along with the returned cdata, which is kept alive by the caller during the call.
+local function to_array(ctype, value)
+  if value == nil then
+    return nil, 0
+  end
+  return ffi.new(ctype .. '[?]', #value, value), #value
+end"#
+        );
+    }
+
+    if code.contains("to_callback(") {
+        emitln!(
+            writer,
+            r#"
+-- Callbacks created for Lua functions, by function. They're kept until released, as the
+-- native library may call them at any time.
+local callbacks = {{}}
+
+-- Converts the Lua function `fn` into a callback of `ctype`, reusing the callback created
+-- for it by a previous call.
+local function to_callback(ctype, fn)
+  if type(fn) ~= 'function' then
+    return fn
+  end
+  local callback = callbacks[fn]
+  if callback == nil then
+    callback = ffi.cast(ctype, fn)
+    callbacks[fn] = callback
+  end
+  return callback
+end
+
+-- Frees the callback created for the Lua function `fn`, once the native library won't call it
+-- anymore.
+function M.release_callback(fn)
+  local callback = callbacks[fn]
+  if callback ~= nil then
+    callbacks[fn] = nil
+    callback:free()
+  end
+end"#
+        );
+    }
+
+    if code.contains("to_callback_once(") {
+        emitln!(
+            writer,
+            r#"
+-- Callbacks which have been called once. They can't free themselves while running, so they're
+-- freed by the next call taking such a callback.
+local finished = {{}}
+
+local function free_finished()
+  for index = #finished, 1, -1 do
+    finished[index]:free()
+    finished[index] = nil
+  end
+end
+
+-- Converts the Lua function `fn` into a callback of `ctype`, which is freed after its call.
+local function to_callback_once(ctype, fn)
+  if type(fn) ~= 'function' then
+    return fn
+  end
+  local callback
+  callback = ffi.cast(ctype, function(...)
+    local result = fn(...)
+    finished[#finished + 1] = callback
+    return result
+  end)
+  return callback
+end"#
+        );
+    }
+}
+
+/// The C type of the elements of dynamic arrays, as mapped by `LangC`.
+fn c_type(ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "void".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Char => "uint32_t".to_string(),
+        Type::CChar => "char".to_string(),
+        Type::F32 => "float".to_string(),
+        Type::F64 => "double".to_string(),
+        Type::I8 => "int8_t".to_string(),
+        Type::I16 => "int16_t".to_string(),
+        Type::I32 => "int32_t".to_string(),
+        Type::I64 => "int64_t".to_string(),
+        Type::ISize => "intptr_t".to_string(),
+        Type::U8 => "uint8_t".to_string(),
+        Type::U16 => "uint16_t".to_string(),
+        Type::U32 => "uint32_t".to_string(),
+        Type::U64 => "uint64_t".to_string(),
+        Type::USize => "uintptr_t".to_string(),
+        Type::String => "const char *".to_string(),
+        Type::Pointer(ref ty) | Type::Array(ref ty, _) => format!("{} *", c_type(ty)),
+        Type::Function(..) => "void *".to_string(),
+        Type::User(ref name) => name.clone(),
+    }
+}
+
+/// The Lua literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => format!("{:?}", value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => format!("{:?}", value),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("{{ {} }}", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
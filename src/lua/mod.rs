@@ -0,0 +1,451 @@
+//! Functions for converting Rust definitions into a LuaJIT module using the FFI library.
+//!
+//! The module declares the C header generated by `LangC` in a `ffi.cdef` block, flattened as
+//! the LuaJIT C parser doesn't handle the preprocessor. The statics are accessed through the
+//! loaded library, exposed as `lib`. Functions taking dynamic arrays or callbacks, or returning
+//! strings, are wrapped by Lua functions converting the strings and tables into pointers and
+//! lengths, and the Lua functions into callbacks.
+//!
+//! Callbacks are kept alive until they are released with `release_callback`, except those of
+//! functions taking a single callback with user data, which is expected to be called once and
+//! is freed afterwards.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, Lang, Outputs};
+use crate::intermediate::*;
+use crate::lang_c::{self, LangC};
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 2;
+
+pub struct LangLua {
+    c: LangC,
+    /// Headers generated by `LangC`, which are only used for the `cdef` block.
+    c_outputs: Outputs,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    /// Names of the structs and unions.
+    structs: Vec<String>,
+    /// Types of the fields of the structs and unions.
+    field_types: Vec<Type>,
+    functions: Vec<Snippet<NativeFunction>>,
+    aliases: HashMap<String, Type>,
+    /// Names of the types declared in the C header.
+    declared: HashSet<String>,
+    /// Modules of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their modules.
+    provided_types: BTreeMap<String, String>,
+}
+
+/// Function along with the C types of its parameters, as declared in the header.
+pub struct NativeFunction {
+    pub item: Function,
+    pub c_types: HashMap<String, String>,
+}
+
+impl LangLua {
+    pub fn new() -> Self {
+        let mut lang = LangLua {
+            c: LangC::new(),
+            c_outputs: Default::default(),
+            lib_name: String::new(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            field_types: Vec::new(),
+            functions: Vec::new(),
+            aliases: Default::default(),
+            declared: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        };
+        lang.c.set_split_headers_enabled(false);
+        lang.set_lib_name("backend");
+        lang
+    }
+
+    /// Set the name of the native library, which is also the name of the Lua module.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+        self.c.set_lib_name(self.lib_name.clone());
+        self.c
+            .set_amalgamated_header(format!("{}.h", self.lib_name));
+    }
+
+    /// Add custom code to the `cdef` block, e.g. declarations of additional types.
+    pub fn add_custom_code(&mut self, code: &str) {
+        self.c.add_custom_code(code);
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared by the Lua module
+    /// `module` of its own bindings. They aren't declared again, but the module is required
+    /// instead.
+    pub fn add_provided_package<P, M>(&mut self, package: P, module: M)
+    where
+        P: AsRef<str>,
+        M: Into<String>,
+    {
+        let module = module.into();
+        self.c
+            .add_provided_package(package.as_ref(), format!("{}.h", module));
+        let _ = self
+            .provided_packages
+            .insert(package.as_ref().to_string(), module);
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(lua_module) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self
+                .provided_types
+                .insert(name.to_string(), lua_module.clone());
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for ty in &mut self.field_types {
+            resolve_alias(&self.aliases, ty);
+        }
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    fn add_field_types(&mut self, fields: &[syn::Field]) {
+        self.field_types
+            .extend(fields.iter().filter_map(|field| transform_type(&field.ty)));
+    }
+
+    /// Names of the user types used by the structs and functions, in alphabetical order.
+    fn used_types(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        let mut types: Vec<&Type> = self.field_types.iter().collect();
+        for snippet in &self.functions {
+            types.extend(snippet.item.item.inputs.iter().map(|(_, ty)| ty));
+            types.push(&snippet.item.item.output);
+        }
+        for ty in types {
+            visit_type(ty, &mut |ty| {
+                if let Type::User(ref name) = *ty {
+                    let _ = names.insert(name.clone());
+                }
+            });
+        }
+        names
+    }
+
+    fn emit_module(&self, cdef: &str) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+        }
+        for snippet in &self.consts {
+            emit_const(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        if !self.structs.is_empty() {
+            emitln!(writer, "");
+            for name in &self.structs {
+                emitln!(writer, "M.{0} = ffi.typeof('{0}')", name);
+            }
+        }
+
+        for snippet in &self.functions {
+            emit_function(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        let body = writer.into_inner();
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(
+            writer,
+            "-- Bindings of the native library `{}` for LuaJIT.",
+            self.lib_name
+        );
+        emitln!(writer, "local ffi = require('ffi')");
+        let modules: BTreeSet<_> = self.provided_types.values().collect();
+        for module in modules {
+            emitln!(writer, "require('{}')", module);
+        }
+
+        emitln!(writer, "\nffi.cdef[[\n{}]]\n", cdef);
+        emitln!(
+            writer,
+            "local lib = ffi.load(os.getenv('{}_LIBRARY_PATH') or '{}')\n",
+            self.lib_name.to_uppercase(),
+            self.lib_name
+        );
+        emitln!(writer, "local M = {{ lib = lib }}");
+        emit_utilities(&mut writer, &body);
+
+        format!("{}{}\nreturn M\n", writer.into_inner(), body)
+    }
+}
+
+impl Default for LangLua {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangLua {
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_const(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        // Constants aren't declared in the C header, so they can't be built as cdata.
+        if let ConstValue::Struct(..) = item.value {
+            println!("struct constants are not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_ty(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        if !item.generics.params.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(ty) = transform_type(&item.ty) {
+            let _ = self.declared.insert(name.clone());
+            self.aliases.insert(name, ty);
+        }
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_enum(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+
+        // Data-carrying enums are only declared in the C header.
+        if common::has_data_variants(item) || self.provided_types.contains_key(&name) {
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_struct(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+        let _ = self.declared.insert(name.clone());
+        self.add_if_provided(&name, module);
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                if let Some(ty) = transform_type(&field.ty) {
+                    self.aliases.insert(name, ty);
+                }
+            }
+            return Ok(());
+        }
+
+        if !self.provided_types.contains_key(&name) {
+            self.structs.push(name);
+            self.add_field_types(&common::named_fields(&item.fields));
+        }
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_union(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        if common::parse_attr(&item.attrs[..], common::check_repr_c, |_| None).0 {
+            let _ = self.declared.insert(name.clone());
+            self.add_if_provided(&name, module);
+            if !self.provided_types.contains_key(&name) {
+                self.structs.push(name);
+                let fields: Vec<_> = item.fields.named.iter().cloned().collect();
+                self.add_field_types(&fields);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_static(item, module, &mut self.c_outputs)
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_fn(item, module, &mut self.c_outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        // The types of the callbacks are cast to, without the qualifiers LuaJIT doesn't know.
+        let mut c_types = HashMap::new();
+        for arg in &item.decl.inputs {
+            if let Some(arg) = common::transform_fnarg_to_argcap(arg) {
+                if let Some(pat) = common::take_out_pat(&arg.pat) {
+                    let c_type = lang_c::rust_to_c(&arg.ty, "")?;
+                    let c_type = c_type.to_string().replace(" _Nonnull", "");
+                    let _ = c_types.insert(pat.ident.to_string(), c_type.trim().to_string());
+                }
+            }
+        }
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: NativeFunction {
+                item: function,
+                c_types,
+            },
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        // The opaque types used by the structs and functions must be declared for LuaJIT.
+        for name in self.used_types() {
+            if !self.declared.contains(&name) {
+                self.c
+                    .add_custom_code(&format!("typedef struct {0} {0};\n", name));
+            }
+        }
+        self.c.finalise_output(&mut self.c_outputs)?;
+
+        let header = unwrap!(self.c_outputs.remove(&format!("{}.h", self.lib_name)));
+        let module = self.emit_module(&flatten_header(&header));
+        outputs.insert(format!("{}.lua", self.lib_name), module);
+
+        self.c_outputs.clear();
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.field_types.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.declared.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
@@ -0,0 +1,391 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangLua::default(), {
+        pub const TILE_SIZE: u32 = 16;
+        /// Shown before the first level.
+        pub const INTRO: &'static str = "Press \"start\"\n";
+        pub const SPAWN: [i16; 2] = [-4, 12];
+        pub type Palette = [u32; 4];
+
+        /// Layer a sprite is drawn on.
+        #[repr(i8)]
+        pub enum Layer {
+            Background = -1,
+            Main,
+            Overlay,
+        }
+
+        #[repr(C)]
+        pub struct Rect {
+            x: i32,
+            y: i32,
+        }
+
+        /// A sprite.
+        #[repr(C)]
+        pub struct Sprite {
+            layer: Layer,
+            palette: Palette,
+            atlas: &Atlas,
+            hitbox: Rect,
+            on_collide: Option<extern "C" fn(overlap: *const Rect) -> bool>,
+        }
+
+        #[repr(C)]
+        pub union Tint {
+            packed: u32,
+            channels: [u8; 4],
+        }
+
+        #[repr(C, packed)]
+        pub struct SaveHeader {
+            version: u8,
+            checksum: u32,
+        }
+
+        #[no_mangle]
+        pub static mut FRAME: u64 = 0;
+
+        #[no_mangle]
+        pub extern "C" fn sprite_new(layer: Layer, out: *mut *mut Sprite) -> i32 {}
+    });
+
+    let actual = fetch(&outputs, "backend.lua");
+    let expected = indoc!(
+        r#"-- Bindings of the native library `backend` for LuaJIT.
+           local ffi = require('ffi')
+
+           ffi.cdef[[
+           typedef struct Atlas Atlas;
+
+           typedef uint32_t Palette[4];
+
+           /// Layer a sprite is drawn on.
+           typedef int8_t Layer;
+
+           enum {
+               Layer_Background = -1,
+               Layer_Main,
+               Layer_Overlay,
+           };
+
+           typedef struct Rect {
+               int32_t x;
+               int32_t y;
+           } Rect;
+
+           /// A sprite.
+           typedef struct Sprite {
+               Layer layer;
+               Palette palette;
+               Atlas const* atlas;
+               Rect hitbox;
+               bool (*on_collide)(Rect const* overlap);
+           } Sprite;
+
+           typedef union Tint {
+               uint32_t packed;
+               uint8_t channels[4];
+           } Tint;
+
+           #pragma pack(push, 1)
+           typedef struct SaveHeader {
+               uint8_t version;
+               uint32_t checksum;
+           } SaveHeader;
+           #pragma pack(pop)
+
+           extern uint64_t FRAME;
+
+           int32_t sprite_new(Layer layer, Sprite** out);
+           ]]
+
+           local lib = ffi.load(os.getenv('BACKEND_LIBRARY_PATH') or 'backend')
+
+           local M = { lib = lib }
+
+           M.TILE_SIZE = 16
+           -- Shown before the first level.
+           M.INTRO = "Press \"start\"\n"
+           M.SPAWN = { -4, 12 }
+
+           -- Layer a sprite is drawn on.
+           M.Layer = {
+             Background = -1,
+             Main = 0,
+             Overlay = 1,
+           }
+
+           M.Rect = ffi.typeof('Rect')
+           M.Sprite = ffi.typeof('Sprite')
+           M.Tint = ffi.typeof('Tint')
+           M.SaveHeader = ffi.typeof('SaveHeader')
+
+           M.sprite_new = lib.sprite_new
+
+           return M
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangLua::default(), {
+        #[repr(C)]
+        pub struct Point {
+            x: f32,
+            y: f32,
+        }
+
+        /// Writes `data` under `name`.
+        #[no_mangle]
+        pub extern "C" fn write(
+            app: *mut App,
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn draw(points: *const Point, points_len: usize, end: u32) -> bool {}
+
+        #[no_mangle]
+        pub extern "C" fn app_name(app: *const App) -> *const c_char {}
+
+        /// Calls `on_event` for every event until unsubscribed.
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            app: *mut App,
+            on_event: extern "C" fn(kind: u32, payload: *const c_char),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.lua");
+    let expected = indoc!(
+        r#"-- Bindings of the native library `backend` for LuaJIT.
+           local ffi = require('ffi')
+
+           ffi.cdef[[
+           typedef struct App App;
+           typedef struct FfiResult FfiResult;
+
+           typedef struct Point {
+               float x;
+               float y;
+           } Point;
+
+           /// Writes `data` under `name`.
+           void write(App* app, char const* name, uint8_t const* data, uintptr_t data_len, void* user_data, void (*o_cb)(void* user_data, FfiResult const* result));
+
+           bool draw(Point const* points, uintptr_t points_len, uint32_t end);
+
+           char const* app_name(App const* app);
+
+           /// Calls `on_event` for every event until unsubscribed.
+           void subscribe(App* app, void (*on_event)(uint32_t kind, char const* payload));
+           ]]
+
+           local lib = ffi.load(os.getenv('BACKEND_LIBRARY_PATH') or 'backend')
+
+           local M = { lib = lib }
+
+           -- Converts a Lua string or table of bytes into a pointer to them and their number.
+           local function to_bytes(value)
+             if value == nil then
+               return nil, 0
+             elseif type(value) == 'string' then
+               return ffi.cast('const uint8_t *', value), #value
+             end
+             return ffi.new('uint8_t[?]', #value, value), #value
+           end
+
+           -- Converts a Lua table into a native array of `ctype` and its length. The array is freed
+           -- along with the returned cdata, which is kept alive by the caller during the call.
+           local function to_array(ctype, value)
+             if value == nil then
+               return nil, 0
+             end
+             return ffi.new(ctype .. '[?]', #value, value), #value
+           end
+
+           -- Callbacks created for Lua functions, by function. They're kept until released, as the
+           -- native library may call them at any time.
+           local callbacks = {}
+
+           -- Converts the Lua function `fn` into a callback of `ctype`, reusing the callback created
+           -- for it by a previous call.
+           local function to_callback(ctype, fn)
+             if type(fn) ~= 'function' then
+               return fn
+             end
+             local callback = callbacks[fn]
+             if callback == nil then
+               callback = ffi.cast(ctype, fn)
+               callbacks[fn] = callback
+             end
+             return callback
+           end
+
+           -- Frees the callback created for the Lua function `fn`, once the native library won't call it
+           -- anymore.
+           function M.release_callback(fn)
+             local callback = callbacks[fn]
+             if callback ~= nil then
+               callbacks[fn] = nil
+               callback:free()
+             end
+           end
+
+           -- Callbacks which have been called once. They can't free themselves while running, so they're
+           -- freed by the next call taking such a callback.
+           local finished = {}
+
+           local function free_finished()
+             for index = #finished, 1, -1 do
+               finished[index]:free()
+               finished[index] = nil
+             end
+           end
+
+           -- Converts the Lua function `fn` into a callback of `ctype`, which is freed after its call.
+           local function to_callback_once(ctype, fn)
+             if type(fn) ~= 'function' then
+               return fn
+             end
+             local callback
+             callback = ffi.cast(ctype, function(...)
+               local result = fn(...)
+               finished[#finished + 1] = callback
+               return result
+             end)
+             return callback
+           end
+
+           M.Point = ffi.typeof('Point')
+
+           -- Writes `data` under `name`.
+           function M.write(app, name, data, user_data, o_cb)
+             free_finished()
+             local data_ptr, data_len = to_bytes(data)
+             lib.write(app, name, data_ptr, data_len, user_data, to_callback_once('void (*)(void* user_data, FfiResult const* result)', o_cb))
+           end
+
+           function M.draw(points, end_)
+             local points_ptr, points_len = to_array('Point', points)
+             return lib.draw(points_ptr, points_len, end_)
+           end
+
+           function M.app_name(app)
+             local result = lib.app_name(app)
+             return result ~= nil and ffi.string(result) or nil
+           end
+
+           -- Calls `on_event` for every event until unsubscribed.
+           function M.subscribe(app, on_event)
+             lib.subscribe(app, to_callback('void (*)(uint32_t kind, char const* payload)', on_event))
+           end
+
+           return M
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let mut lang = LangLua::default();
+    lang.add_provided_package("audio", "audio.types");
+
+    let mut outputs = Outputs::default();
+    let sample_format: syn::ItemEnum = unwrap!(syn::parse_str(
+        "#[repr(C)] pub enum SampleFormat { S16, F32 }"
+    ));
+    let frame: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Frame { samples: *const f32, len: usize }"
+    ));
+    let stream: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Stream { format: SampleFormat, rate: u32, last: Frame }"
+    ));
+    let play: syn::ItemFn = unwrap!(syn::parse_str(
+        "#[no_mangle] pub extern \"C\" fn play(stream: *mut Stream, \
+         on_frame: extern \"C\" fn(frame: *const Frame, format: SampleFormat)) {}"
+    ));
+
+    let audio = ["audio".to_string()];
+    let player = ["player".to_string()];
+    unwrap!(lang.parse_enum(&sample_format, &audio, &mut outputs));
+    unwrap!(lang.parse_struct(&frame, &audio, &mut outputs));
+    unwrap!(lang.parse_struct(&stream, &player, &mut outputs));
+    unwrap!(lang.parse_fn(&play, &player, &mut outputs));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    // The provided types are only declared by the required module, even in
+    // the fields and callbacks of the local ones.
+    let actual = fetch(&outputs, "backend.lua");
+    let expected = indoc!(
+        r#"-- Bindings of the native library `backend` for LuaJIT.
+           local ffi = require('ffi')
+           require('audio.types')
+
+           ffi.cdef[[
+           typedef struct Stream {
+               SampleFormat format;
+               uint32_t rate;
+               Frame last;
+           } Stream;
+
+           void play(Stream* stream, void (*on_frame)(Frame const* frame, SampleFormat format));
+           ]]
+
+           local lib = ffi.load(os.getenv('BACKEND_LIBRARY_PATH') or 'backend')
+
+           local M = { lib = lib }
+
+           -- Callbacks created for Lua functions, by function. They're kept until released, as the
+           -- native library may call them at any time.
+           local callbacks = {}
+
+           -- Converts the Lua function `fn` into a callback of `ctype`, reusing the callback created
+           -- for it by a previous call.
+           local function to_callback(ctype, fn)
+             if type(fn) ~= 'function' then
+               return fn
+             end
+             local callback = callbacks[fn]
+             if callback == nil then
+               callback = ffi.cast(ctype, fn)
+               callbacks[fn] = callback
+             end
+             return callback
+           end
+
+           -- Frees the callback created for the Lua function `fn`, once the native library won't call it
+           -- anymore.
+           function M.release_callback(fn)
+             local callback = callbacks[fn]
+             if callback ~= nil then
+               callbacks[fn] = nil
+               callback:free()
+             end
+           end
+
+           M.Stream = ffi.typeof('Stream')
+
+           function M.play(stream, on_frame)
+             lib.play(stream, to_callback('void (*)(Frame const* frame, SampleFormat format)', on_frame))
+           end
+
+           return M
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
@@ -1,11 +1,26 @@
 use std::fmt::{self, Write};
 use std::ops::Deref;
 
+/// Writes formatted code, panicking on formatting errors.
+macro_rules! emit {
+    ($writer:expr, $($arg:tt)*) => {
+        unwrap!(write!($writer, $($arg)*))
+    }
+}
+
+/// Writes formatted code followed by a newline.
+macro_rules! emitln {
+    ($writer:expr, $($arg:tt)*) => {
+        unwrap!(writeln!($writer, $($arg)*))
+    }
+}
+
 pub struct IndentedWriter {
     inner: String,
     indent: bool,
     indent_level: usize,
     indent_width: usize,
+    indent_char: char,
 }
 
 impl IndentedWriter {
@@ -15,6 +30,15 @@ impl IndentedWriter {
             indent: true,
             indent_level: 0,
             indent_width: width,
+            indent_char: ' ',
+        }
+    }
+
+    /// Creates a writer indenting with a tab per level.
+    pub fn with_tabs() -> Self {
+        IndentedWriter {
+            indent_char: '\t',
+            ..Self::new(1)
         }
     }
 
@@ -32,7 +56,7 @@ impl IndentedWriter {
 
     fn write_indent(&mut self) -> Result<(), fmt::Error> {
         for _ in 0..(self.indent_level * self.indent_width) {
-            self.inner.write_char(' ')?;
+            self.inner.write_char(self.indent_char)?;
         }
 
         Ok(())
@@ -112,4 +136,17 @@ mod tests {
         let expected = "    foo\n\n\n    bar";
         assert_eq!(&*output, expected);
     }
+
+    #[test]
+    fn indents_with_tabs() {
+        let mut output = IndentedWriter::with_tabs();
+
+        unwrap!(writeln!(output, "func foo() {{"));
+        output.indent();
+        unwrap!(writeln!(output, "bar()"));
+        output.unindent();
+        unwrap!(writeln!(output, "}}"));
+
+        assert_eq!(&*output, "func foo() {\n\tbar()\n}\n");
+    }
 }
@@ -2,6 +2,8 @@
 
 use crate::common::{Lang, Outputs};
 use crate::Error;
+use std::collections::HashMap;
+use syn::export::ToTokens;
 use unwrap::unwrap;
 
 pub fn parse_usetree(usetree: &syn::UseTree) -> Vec<String> {
@@ -103,6 +105,22 @@ pub fn parse_file<L: Lang>(
                     continue;
                 }
             }
+            syn::Item::Union(ref item) => {
+                if let syn::Visibility::Inherited = item.vis {
+                    continue;
+                }
+                if let syn::Visibility::Crate(_) = item.vis {
+                    continue;
+                }
+            }
+            syn::Item::Static(ref item) => {
+                if let syn::Visibility::Inherited = item.vis {
+                    continue;
+                }
+                if let syn::Visibility::Crate(_) = item.vis {
+                    continue;
+                }
+            }
             _ => {}
         }
 
@@ -132,6 +150,14 @@ pub fn parse_file<L: Lang>(
                 lang.parse_struct(&item, mod_path, outputs)?;
                 Ok(())
             }
+            syn::Item::Union(ref item) => {
+                lang.parse_union(item, mod_path, outputs)?;
+                Ok(())
+            }
+            syn::Item::Static(ref item) => {
+                lang.parse_static(item, mod_path, outputs)?;
+                Ok(())
+            }
             _ => Ok(()),
         };
 
@@ -206,6 +232,22 @@ pub fn parse_mod<L: Lang>(
                         continue;
                     }
                 }
+                syn::Item::Union(ref item) => {
+                    if let syn::Visibility::Inherited = item.vis {
+                        continue;
+                    }
+                    if let syn::Visibility::Crate(_) = item.vis {
+                        continue;
+                    }
+                }
+                syn::Item::Static(ref item) => {
+                    if let syn::Visibility::Inherited = item.vis {
+                        continue;
+                    }
+                    if let syn::Visibility::Crate(_) = item.vis {
+                        continue;
+                    }
+                }
                 _ => {}
             }
 
@@ -235,6 +277,14 @@ pub fn parse_mod<L: Lang>(
                     lang.parse_struct(&item, mod_path, outputs)?;
                     Ok(())
                 }
+                syn::Item::Union(ref item) => {
+                    lang.parse_union(item, mod_path, outputs)?;
+                    Ok(())
+                }
+                syn::Item::Static(ref item) => {
+                    lang.parse_static(item, mod_path, outputs)?;
+                    Ok(())
+                }
                 _ => Ok(()),
             };
 
@@ -250,3 +300,104 @@ pub fn parse_mod<L: Lang>(
         Err(errors)
     }
 }
+
+/// Turns `pub type Alias = Generic<A, B>;` into a struct named `Alias`, substituting the type
+/// arguments of the alias for the type parameters. Returns `None` if the alias doesn't provide a
+/// concrete type for each type parameter.
+pub fn instantiate(alias: &syn::ItemType, generic: &syn::ItemStruct) -> Option<syn::ItemStruct> {
+    if !alias.generics.params.is_empty() {
+        return None;
+    }
+    let args = match *alias.ty {
+        syn::Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) => match path.segments.last()?.value().arguments {
+            syn::PathArguments::AngleBracketed(ref args) => args
+                .args
+                .iter()
+                .map(|arg| match *arg {
+                    syn::GenericArgument::Type(ref ty) => Some(ty.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let params = generic
+        .generics
+        .params
+        .iter()
+        .map(|param| match *param {
+            syn::GenericParam::Type(ref param) => Some(param.ident.to_string()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if args.len() != params.len() {
+        return None;
+    }
+    let substitutions: HashMap<_, _> = params.into_iter().zip(args).collect();
+
+    let mut item = generic.clone();
+    item.ident = alias.ident.clone();
+    item.vis = alias.vis.clone();
+    item.generics = syn::Generics::default();
+    // Prefer the docs of the alias over the docs of the generic struct
+    let is_doc = |attr: &syn::Attribute| attr.path.clone().into_token_stream().to_string() == "doc";
+    if alias.attrs.iter().any(is_doc) {
+        item.attrs.retain(|attr| !is_doc(attr));
+        item.attrs
+            .extend(alias.attrs.iter().filter(|attr| is_doc(attr)).cloned());
+    }
+    for field in item.fields.iter_mut() {
+        substitute_type(&mut field.ty, &substitutions);
+    }
+
+    Some(item)
+}
+
+/// Replaces the type parameters in `ty` with their concrete types.
+fn substitute_type(ty: &mut syn::Type, substitutions: &HashMap<String, syn::Type>) {
+    match *ty {
+        syn::Type::Path(ref mut path) => {
+            if path.qself.is_none() && path.path.segments.len() == 1 {
+                let ident = path.path.segments[0].ident.to_string();
+                if let Some(concrete) = substitutions.get(&ident) {
+                    *ty = concrete.clone();
+                    return;
+                }
+            }
+            for segment in path.path.segments.iter_mut() {
+                if let syn::PathArguments::AngleBracketed(ref mut args) = segment.arguments {
+                    for arg in args.args.iter_mut() {
+                        if let syn::GenericArgument::Type(ref mut ty) = *arg {
+                            substitute_type(ty, substitutions);
+                        }
+                    }
+                }
+            }
+        }
+        syn::Type::Ptr(ref mut ptr) => substitute_type(&mut ptr.elem, substitutions),
+        syn::Type::Reference(ref mut reference) => {
+            substitute_type(&mut reference.elem, substitutions)
+        }
+        syn::Type::Array(ref mut array) => substitute_type(&mut array.elem, substitutions),
+        syn::Type::Slice(ref mut slice) => substitute_type(&mut slice.elem, substitutions),
+        syn::Type::Paren(ref mut paren) => substitute_type(&mut paren.elem, substitutions),
+        syn::Type::Tuple(ref mut tuple) => {
+            for elem in tuple.elems.iter_mut() {
+                substitute_type(elem, substitutions);
+            }
+        }
+        syn::Type::BareFn(ref mut bare_fn) => {
+            for input in bare_fn.inputs.iter_mut() {
+                substitute_type(&mut input.ty, substitutions);
+            }
+            if let syn::ReturnType::Type(_, ref mut output) = bare_fn.output {
+                substitute_type(output, substitutions);
+            }
+        }
+        _ => (),
+    }
+}
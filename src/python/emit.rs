@@ -0,0 +1,713 @@
+//! Utilities for emiting fragments of the target language code.
+use super::Context;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Python keywords which can be used as identifiers in Rust.
+const KEYWORDS: &[&str] = &[
+    "and", "assert", "class", "def", "del", "elif", "except", "finally", "from", "global",
+    "import", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "try", "with", "yield",
+];
+
+/// Where a type is used, which decides its Python annotation.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Field of a `ctypes.Structure`.
+    Field,
+    /// Parameter of a wrapper function.
+    Arg,
+    /// Value returned by a wrapper function or passed to a future.
+    Value,
+}
+
+/// Name of the `CFUNCTYPE` type of the function pointer.
+pub fn callback_name(fun: &Function) -> String {
+    let mut name = String::from("Callback");
+
+    if extract_callback_from_function(fun) {
+        // Skip the user data param.
+        for (_, ty) in &fun.inputs[1..] {
+            name.push_str(&type_part_name(ty));
+        }
+        if fun.inputs.len() == 1 {
+            name.push_str("None");
+        }
+    } else {
+        name.push_str("Fn");
+        for (_, ty) in &fun.inputs {
+            name.push_str(&type_part_name(ty));
+        }
+    }
+
+    if let Type::Unit = fun.output {
+    } else {
+        name.push_str("Returns");
+        name.push_str(&type_part_name(&fun.output));
+    }
+
+    name
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    let lines: Vec<_> = docs
+        .split("///")
+        .skip(1)
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect();
+
+    match lines.len() {
+        0 => (),
+        1 => emitln!(writer, "\"\"\"{}\"\"\"", lines[0]),
+        _ => {
+            emitln!(writer, "\"\"\"{}", lines[0]);
+            for line in &lines[1..] {
+                emitln!(writer, "{}", line);
+            }
+            emitln!(writer, "\"\"\"");
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, item: &Const) {
+    emitln!(writer, "{} = {}", name, const_value(&item.value, false));
+}
+
+pub fn emit_const_stub(writer: &mut IndentedWriter, name: &str, item: &Const) {
+    emitln!(writer, "{}: {}", name, const_type(&item.value));
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    emitln!(writer, "\n\nclass {}(enum.IntEnum):", name);
+    writer.indent();
+    emit_docs(writer, docs);
+    if !docs.is_empty() {
+        emitln!(writer, "");
+    }
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emitln!(writer, "{} = {}", variant_name(&variant.name), value);
+        next = value + 1;
+    }
+
+    writer.unindent();
+}
+
+pub fn emit_enum_stub(writer: &mut IndentedWriter, name: &str, item: &Enum) {
+    emitln!(writer, "\n\nclass {}(enum.IntEnum):", name);
+    writer.indent();
+    for variant in &item.variants {
+        emitln!(writer, "{}: int", variant_name(&variant.name));
+    }
+    writer.unindent();
+}
+
+/// Declares the class of the struct. The fields are assigned once all classes are declared,
+/// so that the structs can refer to each other.
+pub fn emit_struct_class(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Struct) {
+    let base = if item.union { "Union" } else { "Structure" };
+    emitln!(writer, "\n\nclass {}(ctypes.{}):", name, base);
+    writer.indent();
+    emit_docs(writer, docs);
+
+    if let Some(pack) = item.pack {
+        emitln!(writer, "_pack_ = {}", pack);
+    } else if docs.is_empty() {
+        emitln!(writer, "pass");
+    }
+
+    writer.unindent();
+}
+
+pub fn emit_struct_fields(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    item: &Struct,
+) {
+    emitln!(writer, "\n{}._fields_ = [", name);
+    writer.indent();
+
+    for field in &item.fields {
+        let field_name = escape_keyword(&field.name, KEYWORDS);
+        match field.ty {
+            Type::Array(ref ty, ArraySize::Dynamic) => {
+                emitln!(
+                    writer,
+                    "(\"{}\", {}),",
+                    field_name,
+                    pointer_type(context, ty)
+                );
+                emitln!(writer, "(\"{}_len\", ctypes.c_size_t),", field.name);
+                if field.has_cap {
+                    emitln!(writer, "(\"{}_cap\", ctypes.c_size_t),", field.name);
+                }
+            }
+            ref ty => emitln!(writer, "(\"{}\", {}),", field_name, ctype(context, ty)),
+        }
+    }
+
+    writer.unindent();
+    emitln!(writer, "]");
+}
+
+pub fn emit_struct_stub(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Struct) {
+    let base = if item.union { "Union" } else { "Structure" };
+    emitln!(writer, "\n\nclass {}(ctypes.{}):", name, base);
+    writer.indent();
+
+    for field in &item.fields {
+        let field_name = escape_keyword(&field.name, KEYWORDS);
+        match field.ty {
+            Type::Array(_, ArraySize::Dynamic) => {
+                emitln!(writer, "{}: Any", field_name);
+                emitln!(writer, "{}_len: int", field.name);
+                if field.has_cap {
+                    emitln!(writer, "{}_cap: int", field.name);
+                }
+            }
+            ref ty => emitln!(
+                writer,
+                "{}: {}",
+                field_name,
+                py_type(context, ty, Mode::Field)
+            ),
+        }
+    }
+    if item.fields.is_empty() {
+        emitln!(writer, "...");
+    }
+
+    writer.unindent();
+}
+
+pub fn emit_callback_type(writer: &mut IndentedWriter, context: &Context, fun: &Function) {
+    let mut types = vec![ctype(context, &fun.output)];
+    types.extend(native_param_types(context, &fun.inputs));
+
+    emitln!(
+        writer,
+        "{} = ctypes.CFUNCTYPE({})",
+        callback_name(fun),
+        types.join(", ")
+    );
+}
+
+pub fn emit_static(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Static) {
+    let ty = match item.ty {
+        Type::Array(..) => format!("({})", ctype(context, &item.ty)),
+        ref ty => ctype(context, ty),
+    };
+    emitln!(writer, "{} = {}.in_dll(_lib, \"{}\")", name, ty, name);
+}
+
+pub fn emit_static_stub(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Static) {
+    let ty = match item.ty {
+        Type::Array(..) => py_type(context, &item.ty, Mode::Field),
+        ref ty => ctype(context, ty),
+    };
+    emitln!(writer, "{}: {}", name, ty);
+}
+
+/// Emits the helpers used by the wrapper functions, and the ones completing futures if
+/// `futures` is set.
+pub fn emit_utilities(writer: &mut IndentedWriter, futures: bool) {
+    emitln!(writer, "\n\ndef _encode(value):");
+    emitln!(
+        writer,
+        "    return value.encode() if value is not None else None"
+    );
+    emitln!(writer, "\n\ndef _decode(value):");
+    emitln!(
+        writer,
+        "    return value.decode() if value is not None else None"
+    );
+
+    if !futures {
+        return;
+    }
+
+    emitln!(writer, "\n\ndef _copy(pointer):");
+    emitln!(
+        writer,
+        "    return type(pointer.contents).from_buffer_copy(pointer.contents) if pointer else None"
+    );
+    emitln!(writer, "\n\nclass FfiError(Exception):");
+    emitln!(
+        writer,
+        "    \"\"\"Error reported by the native library through the result of a callback.\"\"\""
+    );
+    emitln!(writer, "");
+    emitln!(writer, "    def __init__(self, code, description):");
+    emitln!(writer, "        super().__init__(description)");
+    emitln!(writer, "        self.code = code");
+    emitln!(writer, "        self.description = description");
+    emitln!(writer, "\n\n_futures = {{}}");
+    emitln!(writer, "_future_ids = itertools.count(1)");
+    emitln!(writer, "\n\ndef _new_future():");
+    emitln!(writer, "    user_data = next(_future_ids)");
+    emitln!(writer, "    future = _futures[user_data] = Future()");
+    emitln!(writer, "    return user_data, future");
+    emitln!(
+        writer,
+        "\n\ndef _complete_future(user_data, result, value):"
+    );
+    emitln!(writer, "    future = _futures.pop(user_data)");
+    emitln!(writer, "    if result and result.contents.error_code != 0:");
+    emitln!(writer, "        error = result.contents");
+    emitln!(
+        writer,
+        "        future.set_exception(FfiError(error.error_code, _decode(error.description)))"
+    );
+    emitln!(writer, "    else:");
+    emitln!(writer, "        future.set_result(value())");
+}
+
+pub fn emit_utilities_stub(writer: &mut IndentedWriter) {
+    emitln!(writer, "\n\nclass FfiError(Exception):");
+    emitln!(writer, "    code: int");
+    emitln!(writer, "    description: Optional[str]");
+}
+
+/// Emits the function passed as the callback of the functions returning futures. It copies the
+/// values out of the native memory before completing the future identified by the user data.
+pub fn emit_completion_handler(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    callback: &Function,
+) {
+    let params = callback_params(&callback.inputs);
+
+    emitln!(writer, "\n\n@{}", callback_name(callback));
+    emitln!(
+        writer,
+        "def {}({}):",
+        completion_handler_name(callback),
+        params.join(", ")
+    );
+    writer.indent();
+
+    let values: Vec<_> = callback.inputs[2..]
+        .iter()
+        .enumerate()
+        .map(|(index, (name, ty))| value_expr(context, &param_name(name, index + 2, KEYWORDS), ty))
+        .collect();
+    let value = match values.len() {
+        0 => "None".to_string(),
+        1 => values[0].clone(),
+        _ => format!("({})", values.join(", ")),
+    };
+
+    emitln!(
+        writer,
+        "_complete_future({}, {}, lambda: {})",
+        param_name(&callback.inputs[0].0, 0, KEYWORDS),
+        param_name(&callback.inputs[1].0, 1, KEYWORDS),
+        value
+    );
+
+    writer.unindent();
+}
+
+/// Declares the argument and result types of the native function.
+pub fn emit_function_decl(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    fun: &Function,
+) {
+    emitln!(
+        writer,
+        "\n_lib.{}.argtypes = [{}]",
+        name,
+        native_param_types(context, &fun.inputs).join(", ")
+    );
+    emitln!(
+        writer,
+        "_lib.{}.restype = {}",
+        name,
+        ctype(context, &fun.output)
+    );
+}
+
+pub fn emit_wrapper_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let callback = async_callback(fun, |name| context.is_declared(name));
+    let params: Vec<_> = wrapper_params(fun, callback.is_some())
+        .map(|(name, _)| escape_keyword(name, KEYWORDS))
+        .collect();
+
+    emitln!(writer, "\n\ndef {}({}):", name, params.join(", "));
+    writer.indent();
+    emit_docs(writer, docs);
+
+    let mut args = Vec::new();
+    for (param, ty) in &fun.inputs {
+        let param = escape_keyword(param, KEYWORDS);
+
+        match *ty {
+            _ if callback.is_some() && is_user_data(&param, ty) => args.push(param),
+            Type::Function(ref fun) if callback.is_some() && extract_callback(ty).is_some() => {
+                args.push(completion_handler_name(fun))
+            }
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                args.push(array_expr(
+                    context,
+                    &param,
+                    elem,
+                    &format!("len({})", param),
+                ));
+                args.push(format!("len({})", param));
+            }
+            Type::Pointer(ref elem) => match **elem {
+                Type::Array(ref elem, ref size) if matches!(**elem, Type::U8) => {
+                    args.push(array_expr(context, &param, elem, &array_size(size)))
+                }
+                _ => args.push(param),
+            },
+            Type::String => args.push(format!("_encode({})", param)),
+            _ => args.push(param),
+        }
+    }
+    let call = format!("_lib.{}({})", name, args.join(", "));
+
+    if callback.is_some() {
+        emitln!(writer, "user_data, future = _new_future()");
+        emitln!(writer, "{}", call);
+        emitln!(writer, "return future");
+    } else {
+        match fun.output {
+            Type::Unit => emitln!(writer, "{}", call),
+            Type::String => emitln!(writer, "return _decode({})", call),
+            Type::User(ref name) if context.is_enum(name) => {
+                emitln!(writer, "return {}({})", name, call)
+            }
+            _ => emitln!(writer, "return {}", call),
+        }
+    }
+
+    writer.unindent();
+}
+
+pub fn emit_wrapper_function_stub(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    fun: &Function,
+) {
+    let callback = async_callback(fun, |name| context.is_declared(name));
+    let params: Vec<_> = wrapper_params(fun, callback.is_some())
+        .map(|(name, ty)| {
+            format!(
+                "{}: {}",
+                escape_keyword(name, KEYWORDS),
+                py_type(context, ty, Mode::Arg)
+            )
+        })
+        .collect();
+
+    let output = match callback {
+        Some(callback) => {
+            let values: Vec<_> = callback.inputs[2..]
+                .iter()
+                .map(|(_, ty)| py_type(context, ty, Mode::Value))
+                .collect();
+            let value = match values.len() {
+                0 => "None".to_string(),
+                1 => values[0].clone(),
+                _ => format!("Tuple[{}]", values.join(", ")),
+            };
+            format!("Future[{}]", value)
+        }
+        None => py_type(context, &fun.output, Mode::Value),
+    };
+
+    emitln!(
+        writer,
+        "def {}({}) -> {}: ...",
+        name,
+        params.join(", "),
+        output
+    );
+}
+
+/// The `ctypes` type of the Rust type.
+pub fn ctype(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "None".to_string(),
+        Type::Bool => "ctypes.c_bool".to_string(),
+        Type::Char => "ctypes.c_uint32".to_string(),
+        Type::CChar => "ctypes.c_char".to_string(),
+        Type::F32 => "ctypes.c_float".to_string(),
+        Type::F64 => "ctypes.c_double".to_string(),
+        Type::I8 => "ctypes.c_int8".to_string(),
+        Type::I16 => "ctypes.c_int16".to_string(),
+        Type::I32 => "ctypes.c_int32".to_string(),
+        Type::I64 => "ctypes.c_int64".to_string(),
+        Type::ISize => "ctypes.c_ssize_t".to_string(),
+        Type::U8 => "ctypes.c_uint8".to_string(),
+        Type::U16 => "ctypes.c_uint16".to_string(),
+        Type::U32 => "ctypes.c_uint32".to_string(),
+        Type::U64 => "ctypes.c_uint64".to_string(),
+        Type::USize => "ctypes.c_size_t".to_string(),
+        Type::String => "ctypes.c_char_p".to_string(),
+        Type::Pointer(ref ty) => pointer_type(context, ty),
+        Type::Array(ref ty, ArraySize::Dynamic) => pointer_type(context, ty),
+        Type::Array(ref ty, ref size) => format!("{} * {}", ctype(context, ty), array_size(size)),
+        Type::Function(ref fun) => callback_name(fun),
+        Type::User(ref name) => match context.enums.get(name) {
+            Some(Some(ty)) => ctype(context, ty),
+            // The size of C enums is the size of `int`.
+            Some(None) => "ctypes.c_int".to_string(),
+            None => name.clone(),
+        },
+    }
+}
+
+fn pointer_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "ctypes.c_void_p".to_string(),
+        // Opaque types are only handled through pointers.
+        Type::User(ref name) if !context.is_declared(name) => "ctypes.c_void_p".to_string(),
+        ref ty => format!("ctypes.POINTER({})", ctype(context, ty)),
+    }
+}
+
+/// The Python type annotation of the Rust type.
+fn py_type(context: &Context, ty: &Type, mode: Mode) -> String {
+    match *ty {
+        Type::Unit => "None".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::CChar => "bytes".to_string(),
+        Type::F32 | Type::F64 => "float".to_string(),
+        Type::Char
+        | Type::I8
+        | Type::I16
+        | Type::I32
+        | Type::I64
+        | Type::ISize
+        | Type::U8
+        | Type::U16
+        | Type::U32
+        | Type::U64
+        | Type::USize => "int".to_string(),
+        Type::String => match mode {
+            Mode::Field => "Optional[bytes]".to_string(),
+            Mode::Arg | Mode::Value => "Optional[str]".to_string(),
+        },
+        Type::Pointer(ref ty) => match (&**ty, mode) {
+            (&Type::Unit, _) => "Optional[int]".to_string(),
+            (&Type::User(ref name), Mode::Arg) | (&Type::User(ref name), Mode::Value)
+                if context.structs.contains(name) =>
+            {
+                format!("Optional[{}]", name)
+            }
+            (Type::Array(ty, _), Mode::Arg) if matches!(**ty, Type::U8) => "bytes".to_string(),
+            _ => "Any".to_string(),
+        },
+        Type::Array(ref ty, ArraySize::Dynamic) => match (&**ty, mode) {
+            (&Type::U8, Mode::Arg) | (&Type::U8, Mode::Value) => "bytes".to_string(),
+            (ty, Mode::Arg) => format!("Sequence[{}]", py_type(context, ty, mode)),
+            (ty, Mode::Value) => format!("List[{}]", py_type(context, ty, mode)),
+            (_, Mode::Field) => "Any".to_string(),
+        },
+        Type::Array(ref ty, _) => match mode {
+            Mode::Field => format!("ctypes.Array[{}]", ctype(context, ty)),
+            Mode::Arg | Mode::Value => "Any".to_string(),
+        },
+        Type::Function(ref fun) => callback_name(fun),
+        Type::User(ref name) => {
+            if context.is_enum(name) && mode == Mode::Field {
+                "int".to_string()
+            } else if context.is_declared(name) {
+                name.clone()
+            } else {
+                "Any".to_string()
+            }
+        }
+    }
+}
+
+/// Expression converting the callback parameter `name` into a Python value which stays valid
+/// after the callback returns.
+fn value_expr(context: &Context, name: &str, ty: &Type) -> String {
+    match *ty {
+        Type::String => format!("_decode({})", name),
+        Type::Pointer(ref ty) => match **ty {
+            Type::User(ref ty) if context.structs.contains(ty) => format!("_copy({})", name),
+            _ => name.to_string(),
+        },
+        Type::Array(ref ty, ArraySize::Dynamic) => match **ty {
+            Type::U8 => format!("ctypes.string_at({}, {}_len)", name, name),
+            Type::String => format!("[_decode(value) for value in {}[:{}_len]]", name, name),
+            Type::User(ref ty) if context.structs.contains(ty) => format!(
+                "[{}.from_buffer_copy({}[index]) for index in range({}_len)]",
+                ty, name, name
+            ),
+            Type::User(ref ty) if context.is_enum(ty) => {
+                format!("[{}(value) for value in {}[:{}_len]]", ty, name, name)
+            }
+            _ => format!("{}[:{}_len]", name, name),
+        },
+        Type::User(ref ty) if context.is_enum(ty) => format!("{}({})", ty, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Expression converting the Python sequence `name` into a native array.
+fn array_expr(context: &Context, name: &str, elem: &Type, len: &str) -> String {
+    if let Type::U8 = *elem {
+        format!("(ctypes.c_uint8 * {}).from_buffer_copy({})", len, name)
+    } else {
+        format!("({} * {})(*{})", ctype(context, elem), len, name)
+    }
+}
+
+fn array_size(size: &ArraySize) -> String {
+    match *size {
+        ArraySize::Lit(size) => size.to_string(),
+        ArraySize::Const(ref name) => name.clone(),
+        ArraySize::Dynamic => unreachable!("dynamic arrays have no static size"),
+    }
+}
+
+/// The `ctypes` types of the parameters, with dynamic arrays passed as pointer and length.
+fn native_param_types(context: &Context, inputs: &[(String, Type)]) -> Vec<String> {
+    let mut types = Vec::with_capacity(inputs.len());
+    for (_, ty) in inputs {
+        types.push(ctype(context, ty));
+        if ty.is_dynamic_array() {
+            types.push("ctypes.c_size_t".to_string());
+        }
+    }
+    types
+}
+
+fn callback_params(inputs: &[(String, Type)]) -> Vec<String> {
+    let mut params = Vec::with_capacity(inputs.len());
+    for (index, (name, ty)) in inputs.iter().enumerate() {
+        let name = param_name(name, index, KEYWORDS);
+        let is_array = ty.is_dynamic_array();
+        params.push(name.clone());
+        if is_array {
+            params.push(format!("{}_len", name));
+        }
+    }
+    params
+}
+
+/// The parameters of the wrapper function, without the user data and callback if it returns a
+/// future.
+fn wrapper_params(fun: &Function, future: bool) -> impl Iterator<Item = &(String, Type)> {
+    fun.inputs.iter().filter(move |(name, ty)| {
+        !(future && (is_user_data(name, ty) || extract_callback(ty).is_some()))
+    })
+}
+
+fn completion_handler_name(callback: &Function) -> String {
+    let name = callback_name(callback);
+    format!("_complete_{}", name["Callback".len()..].to_snake_case())
+}
+
+fn extract_callback_from_function(fun: &Function) -> bool {
+    fun.inputs
+        .first()
+        .is_some_and(|(name, ty)| is_user_data(name, ty))
+}
+
+fn type_part_name(ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "Void".to_string(),
+        Type::Bool => "Bool".to_string(),
+        Type::Char => "Char".to_string(),
+        Type::CChar => "CChar".to_string(),
+        Type::F32 => "F32".to_string(),
+        Type::F64 => "F64".to_string(),
+        Type::I8 => "I8".to_string(),
+        Type::I16 => "I16".to_string(),
+        Type::I32 => "I32".to_string(),
+        Type::I64 => "I64".to_string(),
+        Type::ISize => "ISize".to_string(),
+        Type::U8 => "U8".to_string(),
+        Type::U16 => "U16".to_string(),
+        Type::U32 => "U32".to_string(),
+        Type::U64 => "U64".to_string(),
+        Type::USize => "USize".to_string(),
+        Type::String => "String".to_string(),
+        Type::Pointer(ref ty) => type_part_name(ty),
+        Type::Array(ref ty, ref size) => match *size {
+            ArraySize::Lit(value) => format!("{}Array{}", type_part_name(ty), value),
+            ArraySize::Const(ref name) => {
+                format!("{}Array{}", type_part_name(ty), name.to_pascal_case())
+            }
+            ArraySize::Dynamic => format!("{}List", type_part_name(ty)),
+        },
+        Type::Function(..) => "Fn".to_string(),
+        Type::User(ref name) => name.clone(),
+    }
+}
+
+/// The Python literal of the constant. Strings are `bytes` within structs, as they are
+/// `c_char_p` fields.
+fn const_value(value: &ConstValue, field: bool) -> String {
+    match *value {
+        ConstValue::Bool(true) => "True".to_string(),
+        ConstValue::Bool(false) => "False".to_string(),
+        ConstValue::Char(value) => format!("{:?}", value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) if field => format!("b{:?}", value),
+        ConstValue::String(ref value) => format!("{:?}", value),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements
+                .iter()
+                .map(|element| const_value(element, field))
+                .collect();
+            format!("[{}]", elements.join(", "))
+        }
+        ConstValue::Struct(ref name, ref fields) => {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "{}={}",
+                        escape_keyword(name, KEYWORDS),
+                        const_value(value, true)
+                    )
+                })
+                .collect();
+            format!("{}({})", name, fields.join(", "))
+        }
+    }
+}
+
+fn const_type(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(..) => "bool".to_string(),
+        ConstValue::Char(..) | ConstValue::String(..) => "str".to_string(),
+        ConstValue::Int(..) => "int".to_string(),
+        ConstValue::Float(..) => "float".to_string(),
+        ConstValue::Array(ref elements) => match elements.first() {
+            Some(element) => format!("List[{}]", const_type(element)),
+            None => "List[Any]".to_string(),
+        },
+        ConstValue::Struct(ref name, _) => name.clone(),
+    }
+}
+
+fn variant_name(name: &str) -> String {
+    escape_keyword(&name.to_screaming_snake_case(), KEYWORDS)
+}
@@ -0,0 +1,665 @@
+//! Functions for converting Rust definitions into Python `ctypes` bindings and `.pyi` stubs.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, FilterMode, Lang, Outputs};
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 4;
+
+pub struct LangPython {
+    filter: HashSet<String>,
+    filter_mode: FilterMode,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    structs: Vec<Snippet<Struct>>,
+    functions: Vec<Snippet<Function>>,
+    statics: Vec<Snippet<Static>>,
+    aliases: HashMap<String, Type>,
+    /// Modules of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their modules.
+    provided_types: BTreeMap<String, String>,
+}
+
+pub struct Context {
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs and unions.
+    structs: HashSet<String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.is_enum(name) || self.structs.contains(name)
+    }
+}
+
+impl LangPython {
+    pub fn new() -> Self {
+        LangPython {
+            filter: Default::default(),
+            filter_mode: FilterMode::Blacklist,
+            lib_name: "backend".to_string(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+            aliases: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        }
+    }
+
+    /// Set the name of the native library. This is also the name of the generated module.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated into the Python
+    /// module `module` by its own bindings. They aren't generated again, but imported from it
+    /// instead.
+    pub fn add_provided_package<P, M>(&mut self, package: P, module: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        let _ = self.provided_packages.insert(package.into(), module.into());
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.filter.clear();
+        self.filter_mode = filter_mode;
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        let _ = self.filter.insert(ident.into());
+    }
+
+    fn is_ignored(&self, ident: &str) -> bool {
+        match self.filter_mode {
+            FilterMode::Blacklist => self.filter.contains(ident),
+            FilterMode::Whitelist => !self.filter.contains(ident),
+        }
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(python_module) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self
+                .provided_types
+                .insert(name.to_string(), python_module.clone());
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.consts {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.structs {
+            for field in &mut snippet.item.fields {
+                resolve_alias(&self.aliases, &mut field.ty);
+            }
+        }
+
+        for snippet in &mut self.statics {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Collect the function pointer types of the struct fields and function parameters, by
+    /// name of their `CFUNCTYPE` types.
+    fn collect_callbacks(&self) -> BTreeMap<String, &Function> {
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|snippet| snippet.item.fields.iter().map(|field| &field.ty));
+        let inputs = self
+            .functions
+            .iter()
+            .flat_map(|snippet| snippet.item.inputs.iter().map(|(_, ty)| ty));
+
+        fields
+            .chain(inputs)
+            .filter_map(|ty| match *ty {
+                Type::Function(ref fun) => Some((callback_name(fun), &**fun)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Emit the imports of the provided types.
+    fn emit_provided_imports(&self, writer: &mut IndentedWriter) {
+        let mut modules: BTreeMap<&String, Vec<&String>> = BTreeMap::new();
+        for (name, module) in &self.provided_types {
+            modules.entry(module).or_default().push(name);
+        }
+
+        for (module, names) in modules {
+            let names: Vec<_> = names.iter().map(|name| name.as_str()).collect();
+            emitln!(writer, "from {} import {}", module, names.join(", "));
+        }
+    }
+
+    fn emit_module(&self, context: &Context, futures: bool) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "import ctypes");
+        emitln!(writer, "import ctypes.util");
+        if !self.enums.is_empty() {
+            emitln!(writer, "import enum");
+        }
+        if futures {
+            emitln!(writer, "import itertools");
+            emitln!(writer, "from concurrent.futures import Future");
+        }
+        self.emit_provided_imports(&mut writer);
+
+        emitln!(
+            writer,
+            "\n_lib = ctypes.CDLL(ctypes.util.find_library(\"{0}\") or \"lib{0}.so\")",
+            self.lib_name
+        );
+
+        // Constants of struct types can only be built once the fields are assigned.
+        let (struct_consts, consts): (Vec<_>, Vec<_>) = self
+            .consts
+            .iter()
+            .partition(|snippet| matches!(snippet.item.value, ConstValue::Struct(..)));
+
+        if !consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in consts {
+                emit_const(&mut writer, &snippet.name, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for snippet in &self.structs {
+            emit_struct_class(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        let callbacks = self.collect_callbacks();
+        if !callbacks.is_empty() {
+            emitln!(writer, "\n");
+            for callback in callbacks.values() {
+                emit_callback_type(&mut writer, context, callback);
+            }
+        }
+
+        if !self.structs.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.structs {
+                emit_struct_fields(&mut writer, context, &snippet.name, &snippet.item);
+            }
+        }
+
+        if !struct_consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in struct_consts {
+                emit_const(&mut writer, &snippet.name, &snippet.item);
+            }
+        }
+
+        if !self.statics.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.statics {
+                emit_static(&mut writer, context, &snippet.name, &snippet.item);
+            }
+        }
+
+        if !self.functions.is_empty() {
+            emit_utilities(&mut writer, futures);
+
+            // Functions completing the futures, one per callback type.
+            let handlers: BTreeMap<_, _> = self
+                .functions
+                .iter()
+                .filter_map(|snippet| {
+                    async_callback(&snippet.item, |name| context.is_declared(name))
+                })
+                .map(|callback| (callback_name(callback), callback))
+                .collect();
+            for callback in handlers.values() {
+                emit_completion_handler(&mut writer, context, callback);
+            }
+
+            for snippet in &self.functions {
+                emitln!(writer, "");
+                emit_function_decl(&mut writer, context, &snippet.name, &snippet.item);
+                emit_wrapper_function(
+                    &mut writer,
+                    context,
+                    &snippet.name,
+                    &snippet.docs,
+                    &snippet.item,
+                );
+            }
+        }
+
+        writer.into_inner()
+    }
+
+    fn emit_stubs(&self, context: &Context, futures: bool) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "import ctypes");
+        if !self.enums.is_empty() {
+            emitln!(writer, "import enum");
+        }
+        if futures {
+            emitln!(writer, "from concurrent.futures import Future");
+        }
+        emitln!(
+            writer,
+            "from typing import Any, List, Optional, Sequence, Tuple"
+        );
+        self.emit_provided_imports(&mut writer);
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.consts {
+                emit_const_stub(&mut writer, &snippet.name, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum_stub(&mut writer, &snippet.name, &snippet.item);
+        }
+
+        for snippet in &self.structs {
+            emit_struct_stub(&mut writer, context, &snippet.name, &snippet.item);
+        }
+
+        let callbacks = self.collect_callbacks();
+        if !callbacks.is_empty() {
+            emitln!(writer, "\n");
+            for name in callbacks.keys() {
+                emitln!(writer, "{}: Any", name);
+            }
+        }
+
+        if !self.statics.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.statics {
+                emit_static_stub(&mut writer, context, &snippet.name, &snippet.item);
+            }
+        }
+
+        if futures {
+            emit_utilities_stub(&mut writer);
+        }
+
+        if !self.functions.is_empty() {
+            emitln!(writer, "\n");
+            for snippet in &self.functions {
+                emit_wrapper_function_stub(&mut writer, context, &snippet.name, &snippet.item);
+            }
+        }
+
+        writer.into_inner()
+    }
+}
+
+impl Default for LangPython {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangPython {
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            println!(
+                "parameterized type aliases not supported ({}). Skipping.",
+                name,
+            );
+            return Ok(());
+        }
+
+        let ty = transform_type(&item.ty).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle the type `{}`", name),
+        })?;
+
+        self.aliases.insert(name, ty);
+
+        Ok(())
+    }
+
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("enums"));
+        }
+
+        if common::has_data_variants(item) {
+            println!("data-carrying enums not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                let ty = transform_type(&field.ty).ok_or_else(|| Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: format!("bindgen cannot handle struct {}", name),
+                })?;
+                self.aliases.insert(name, ty);
+            }
+            return Ok(());
+        }
+
+        if repr.align.is_some() {
+            println!(
+                "alignment modifiers are not supported ({}). Ignoring.",
+                name
+            );
+        }
+
+        let fields = common::named_fields(&item.fields);
+        let mut item = transform_struct(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+        item.pack = repr.packed;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (repr_c, docs) =
+            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+
+        // If it's not #[repr(C)] ignore it.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("unions"));
+        }
+
+        let mut item = transform_struct(&item.fields.named).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle union {}", name),
+        })?;
+        item.union = true;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        let ty = match transform_type(&item.ty) {
+            Some(Type::Pointer(..)) | Some(Type::Function(..)) | None => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+            Some(ty) => ty,
+        };
+        let item = Static {
+            ty,
+            mutable: item.mutability.is_some(),
+        };
+        self.statics.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self
+                .structs
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .collect(),
+        };
+
+        // Types generated by the bindings of dependencies are imported instead.
+        let provided_types = &self.provided_types;
+        self.enums
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+        self.structs
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+
+        if !self.consts.is_empty()
+            || !self.enums.is_empty()
+            || !self.structs.is_empty()
+            || !self.statics.is_empty()
+            || !self.functions.is_empty()
+        {
+            let futures = self.functions.iter().any(|snippet| {
+                async_callback(&snippet.item, |name| context.is_declared(name)).is_some()
+            });
+
+            let module = self.emit_module(&context, futures);
+            outputs.insert(format!("{}.py", self.lib_name), module);
+
+            let stubs = self.emit_stubs(&context, futures);
+            outputs.insert(format!("{}.pyi", self.lib_name), stubs);
+        }
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.statics.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
@@ -0,0 +1,616 @@
+use super::*;
+use crate::test_utils::{fetch, write_crate};
+use crate::Bindgen;
+use indoc::indoc;
+
+#[test]
+fn non_repr_c_types_are_ignored() {
+    let outputs = compile!(LangPython::default(), {
+        pub struct Foo {
+            bar: i32,
+        }
+
+        pub enum Meta {
+            Foo,
+            Bar,
+        }
+    });
+
+    assert!(outputs.is_empty());
+}
+
+#[test]
+fn types() {
+    let outputs = compile!(LangPython::default(), {
+        pub const KEY_LEN: usize = 32;
+        pub type Key = [u8; KEY_LEN];
+
+        /// Access mode.
+        #[repr(u8)]
+        pub enum Mode {
+            Read,
+            Write = 4,
+            Append,
+        }
+
+        /// A record.
+        ///
+        /// Stored remotely.
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+            name: *const c_char,
+            data_ptr: *mut u8,
+            data_len: usize,
+            data_cap: usize,
+            mode: Mode,
+            key: Key,
+            next: *mut Record,
+            app: *mut App,
+            on_change: Option<extern "C" fn(id: u64) -> bool>,
+        }
+
+        #[repr(C)]
+        pub union Value {
+            int: i64,
+            real: f64,
+        }
+
+        #[repr(C, packed)]
+        pub struct Header {
+            tag: u8,
+            len: u32,
+        }
+
+        pub const ORIGIN: Record = Record {
+            id: 0,
+            name: "origin",
+        };
+
+        #[no_mangle]
+        pub static mut COUNTER: u32 = 0;
+    });
+
+    let actual = fetch(&outputs, "backend.py");
+    let expected = indoc!(
+        r#"import ctypes
+           import ctypes.util
+           import enum
+
+           _lib = ctypes.CDLL(ctypes.util.find_library("backend") or "libbackend.so")
+
+           KEY_LEN = 32
+
+
+           class Mode(enum.IntEnum):
+               """Access mode."""
+
+               READ = 0
+               WRITE = 4
+               APPEND = 5
+
+
+           class Record(ctypes.Structure):
+               """A record.
+
+               Stored remotely.
+               """
+
+
+           class Value(ctypes.Union):
+               pass
+
+
+           class Header(ctypes.Structure):
+               _pack_ = 1
+
+
+           CallbackFnU64ReturnsBool = ctypes.CFUNCTYPE(ctypes.c_bool, ctypes.c_uint64)
+
+
+           Record._fields_ = [
+               ("id", ctypes.c_uint64),
+               ("name", ctypes.c_char_p),
+               ("data", ctypes.POINTER(ctypes.c_uint8)),
+               ("data_len", ctypes.c_size_t),
+               ("data_cap", ctypes.c_size_t),
+               ("mode", ctypes.c_uint8),
+               ("key", ctypes.c_uint8 * KEY_LEN),
+               ("next", ctypes.POINTER(Record)),
+               ("app", ctypes.c_void_p),
+               ("on_change", CallbackFnU64ReturnsBool),
+           ]
+
+           Value._fields_ = [
+               ("int", ctypes.c_int64),
+               ("real", ctypes.c_double),
+           ]
+
+           Header._fields_ = [
+               ("tag", ctypes.c_uint8),
+               ("len", ctypes.c_uint32),
+           ]
+
+           ORIGIN = Record(id=0, name=b"origin")
+
+           COUNTER = ctypes.c_uint32.in_dll(_lib, "COUNTER")
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangPython::default(), {
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn record_id(record: *const Record, from: *const App) -> u64 {}
+
+        /// Writes the data
+        /// and the numbers.
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            numbers: *const i32,
+            numbers_len: usize,
+            key: *const [u8; 32],
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn default_mode() -> Mode {}
+    });
+
+    let actual = fetch(&outputs, "backend.py");
+    let expected = indoc!(
+        r#"import ctypes
+           import ctypes.util
+           import enum
+
+           _lib = ctypes.CDLL(ctypes.util.find_library("backend") or "libbackend.so")
+
+
+           class Mode(enum.IntEnum):
+               READ = 0
+               WRITE = 1
+
+
+           class Record(ctypes.Structure):
+               pass
+
+
+           Record._fields_ = [
+               ("id", ctypes.c_uint64),
+           ]
+
+
+           def _encode(value):
+               return value.encode() if value is not None else None
+
+
+           def _decode(value):
+               return value.decode() if value is not None else None
+
+
+           _lib.record_id.argtypes = [ctypes.POINTER(Record), ctypes.c_void_p]
+           _lib.record_id.restype = ctypes.c_uint64
+
+
+           def record_id(record, from_):
+               return _lib.record_id(record, from_)
+
+
+           _lib.write.argtypes = [ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_int32), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8 * 32)]
+           _lib.write.restype = None
+
+
+           def write(name, data, numbers, key):
+               """Writes the data
+               and the numbers.
+               """
+               _lib.write(_encode(name), (ctypes.c_uint8 * len(data)).from_buffer_copy(data), len(data), (ctypes.c_int32 * len(numbers))(*numbers), len(numbers), (ctypes.c_uint8 * 32).from_buffer_copy(key))
+
+
+           _lib.mode_name.argtypes = [ctypes.c_int]
+           _lib.mode_name.restype = ctypes.c_char_p
+
+
+           def mode_name(mode):
+               return _decode(_lib.mode_name(mode))
+
+
+           _lib.default_mode.argtypes = []
+           _lib.default_mode.restype = ctypes.c_int
+
+
+           def default_mode():
+               return Mode(_lib.default_mode())
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_callbacks() {
+    let outputs = compile!(LangPython::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(*mut c_void, *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.py");
+    let expected = indoc!(
+        r#"import ctypes
+           import ctypes.util
+           import itertools
+           from concurrent.futures import Future
+
+           _lib = ctypes.CDLL(ctypes.util.find_library("backend") or "libbackend.so")
+
+
+           class FfiResult(ctypes.Structure):
+               pass
+
+
+           CallbackFfiResult = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.POINTER(FfiResult))
+           CallbackFfiResultStringU8List = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.POINTER(FfiResult), ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t)
+
+
+           FfiResult._fields_ = [
+               ("error_code", ctypes.c_int32),
+               ("description", ctypes.c_char_p),
+           ]
+
+
+           def _encode(value):
+               return value.encode() if value is not None else None
+
+
+           def _decode(value):
+               return value.decode() if value is not None else None
+
+
+           def _copy(pointer):
+               return type(pointer.contents).from_buffer_copy(pointer.contents) if pointer else None
+
+
+           class FfiError(Exception):
+               """Error reported by the native library through the result of a callback."""
+
+               def __init__(self, code, description):
+                   super().__init__(description)
+                   self.code = code
+                   self.description = description
+
+
+           _futures = {}
+           _future_ids = itertools.count(1)
+
+
+           def _new_future():
+               user_data = next(_future_ids)
+               future = _futures[user_data] = Future()
+               return user_data, future
+
+
+           def _complete_future(user_data, result, value):
+               future = _futures.pop(user_data)
+               if result and result.contents.error_code != 0:
+                   error = result.contents
+                   future.set_exception(FfiError(error.error_code, _decode(error.description)))
+               else:
+                   future.set_result(value())
+
+
+           @CallbackFfiResult
+           def _complete_ffi_result(arg0, arg1):
+               _complete_future(arg0, arg1, lambda: None)
+
+
+           @CallbackFfiResultStringU8List
+           def _complete_ffi_result_string_u8_list(user_data, result, name, data, data_len):
+               _complete_future(user_data, result, lambda: (_decode(name), ctypes.string_at(data, data_len)))
+
+
+           _lib.fetch.argtypes = [ctypes.c_void_p, ctypes.c_void_p, CallbackFfiResultStringU8List]
+           _lib.fetch.restype = None
+
+
+           def fetch(app):
+               user_data, future = _new_future()
+               _lib.fetch(app, user_data, _complete_ffi_result_string_u8_list)
+               return future
+
+
+           _lib.flush.argtypes = [ctypes.c_void_p, ctypes.c_void_p, CallbackFfiResult]
+           _lib.flush.restype = None
+
+
+           def flush(app):
+               user_data, future = _new_future()
+               _lib.flush(app, user_data, _complete_ffi_result)
+               return future
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn callbacks_without_results_are_not_futures() {
+    let outputs = compile!(LangPython::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn observe(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, count: u32),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.py");
+    let expected = indoc!(
+        r#"import ctypes
+           import ctypes.util
+
+           _lib = ctypes.CDLL(ctypes.util.find_library("backend") or "libbackend.so")
+
+
+           class FfiResult(ctypes.Structure):
+               pass
+
+
+           CallbackU32 = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_uint32)
+
+
+           FfiResult._fields_ = [
+               ("error_code", ctypes.c_int32),
+               ("description", ctypes.c_char_p),
+           ]
+
+
+           def _encode(value):
+               return value.encode() if value is not None else None
+
+
+           def _decode(value):
+               return value.decode() if value is not None else None
+
+
+           _lib.observe.argtypes = [ctypes.c_void_p, ctypes.c_void_p, CallbackU32]
+           _lib.observe.restype = None
+
+
+           def observe(app, user_data, o_cb):
+               _lib.observe(app, user_data, o_cb)
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn stubs() {
+    let outputs = compile!(LangPython::default(), {
+        pub const VERSION: &'static str = "1.0";
+
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+            key: [u8; 32],
+            ids_ptr: *const u64,
+            ids_len: usize,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            mode: Mode,
+        ) -> bool {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn sum(numbers: *const i32, numbers_len: usize) -> i64 {}
+
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(*mut c_void, *const FfiResult, *const Record, u32),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.pyi");
+    let expected = indoc!(
+        "import ctypes
+         import enum
+         from concurrent.futures import Future
+         from typing import Any, List, Optional, Sequence, Tuple
+
+         VERSION: str
+
+
+         class Mode(enum.IntEnum):
+             READ: int
+             WRITE: int
+
+
+         class FfiResult(ctypes.Structure):
+             error_code: int
+             description: Optional[bytes]
+
+
+         class Record(ctypes.Structure):
+             id: int
+             key: ctypes.Array[ctypes.c_uint8]
+             ids: Any
+             ids_len: int
+
+
+         CallbackFfiResultRecordU32: Any
+
+
+         class FfiError(Exception):
+             code: int
+             description: Optional[str]
+
+
+         def write(name: Optional[str], data: bytes, mode: Mode) -> bool: ...
+         def sum(numbers: Sequence[int]) -> int: ...
+         def fetch() -> Future[Tuple[Optional[Record], int]]: ...
+         "
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let media_types = write_crate(
+        "python_provided_types",
+        &[(
+            "src/lib.rs",
+            indoc!(
+                "
+                #[repr(C)]
+                pub struct Codec {
+                    pub id: u32,
+                    pub profile: u8,
+                }
+
+                #[repr(C)]
+                pub enum Level {
+                    Debug,
+                    Error,
+                }
+                "
+            ),
+        )],
+    );
+    let source = indoc!(
+        "
+        #[repr(C)]
+        pub struct Track {
+            pub codec: media_types::Codec,
+            pub bitrate: u32,
+        }
+
+        #[no_mangle]
+        pub extern \"C\" fn set_log_handler(
+            handler: extern \"C\" fn(level: media_types::Level, message: *const c_char),
+        ) {
+        }
+        "
+    );
+
+    let mut lang = LangPython::default();
+    lang.add_provided_package("media_types", "media.types");
+    let mut outputs = Outputs::default();
+    unwrap!(unwrap!(Bindgen::new())
+        .source_code("ffi/src/lib.rs", source)
+        .dependency("media_types", &media_types)
+        .compile(&mut lang, &mut outputs, true));
+
+    // The provided types are imported from their module, even when only used by callbacks.
+    let actual = fetch(&outputs, "backend.py");
+    let expected = indoc!(
+        r#"import ctypes
+           import ctypes.util
+           from media.types import Codec, Level
+
+           _lib = ctypes.CDLL(ctypes.util.find_library("backend") or "libbackend.so")
+
+
+           class Track(ctypes.Structure):
+               pass
+
+
+           CallbackFnLevelString = ctypes.CFUNCTYPE(None, ctypes.c_int, ctypes.c_char_p)
+
+
+           Track._fields_ = [
+               ("codec", Codec),
+               ("bitrate", ctypes.c_uint32),
+           ]
+
+
+           def _encode(value):
+               return value.encode() if value is not None else None
+
+
+           def _decode(value):
+               return value.decode() if value is not None else None
+
+
+           _lib.set_log_handler.argtypes = [CallbackFnLevelString]
+           _lib.set_log_handler.restype = None
+
+
+           def set_log_handler(handler):
+               _lib.set_log_handler(handler)
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
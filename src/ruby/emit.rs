@@ -0,0 +1,517 @@
+//! Utilities for emiting fragments of the target language code.
+use super::Context;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Ruby keywords which can be used as identifiers in Rust.
+const KEYWORDS: &[&str] = &[
+    "alias", "and", "begin", "case", "class", "def", "defined", "do", "elsif", "end", "ensure",
+    "module", "next", "nil", "not", "or", "redo", "rescue", "retry", "then", "undef", "unless",
+    "until", "when", "yield",
+];
+
+/// Where a type is used, which decides its `ffi` type.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Field of an `FFI::Struct` layout, or attached variable.
+    Field,
+    /// Parameter or result of a callback.
+    Callback,
+    /// Parameter or result of an attached function.
+    Function,
+}
+
+/// Name of the `callback` declaration of the function pointer.
+pub fn callback_name(fun: &Function) -> String {
+    let mut name = String::from("Callback");
+
+    if extract_callback_from_function(fun) {
+        // Skip the user data param.
+        for (_, ty) in &fun.inputs[1..] {
+            name.push_str(&type_part_name(ty));
+        }
+        if fun.inputs.len() == 1 {
+            name.push_str("None");
+        }
+    } else {
+        name.push_str("Fn");
+        for (_, ty) in &fun.inputs {
+            name.push_str(&type_part_name(ty));
+        }
+    }
+
+    if let Type::Unit = fun.output {
+    } else {
+        name.push_str("Returns");
+        name.push_str(&type_part_name(&fun.output));
+    }
+
+    name.to_snake_case()
+}
+
+/// Name of the type declared by `enum`, used for the enum in layouts and signatures.
+pub fn enum_type_name(name: &str) -> String {
+    name.to_snake_case()
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "#");
+        } else {
+            emitln!(writer, "# {}", line);
+        }
+    }
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emit_docs(writer, docs);
+    emitln!(writer, "{} = {}", name, const_value(&item.value));
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    let native_type = match item.ty {
+        Some(Type::ISize) => "find_type(:ssize_t), ".to_string(),
+        Some(Type::USize) => "find_type(:size_t), ".to_string(),
+        Some(ref ty) => format!("FFI::Type::{}, ", array_elem_type(ty).to_uppercase()),
+        None => String::new(),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "{} = enum {}:{}, [",
+        name,
+        native_type,
+        enum_type_name(name)
+    );
+    writer.indent();
+
+    // Variants without a value follow the previous one, as in C.
+    let mut next = 0;
+    for variant in &item.variants {
+        let value = variant.value.unwrap_or(next);
+        emitln!(writer, ":{}, {},", variant.name.to_snake_case(), value);
+        next = value + 1;
+    }
+
+    writer.unindent();
+    emitln!(writer, "]");
+}
+
+pub fn emit_callback(writer: &mut IndentedWriter, context: &Context, fun: &Function) {
+    emitln!(
+        writer,
+        "callback :{}, [{}], {}",
+        callback_name(fun),
+        native_param_types(context, &fun.inputs, Mode::Callback).join(", "),
+        ffi_type(context, &fun.output, Mode::Callback)
+    );
+}
+
+pub fn emit_struct(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &Struct,
+) {
+    let base = if item.union { "Union" } else { "Struct" };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "class {} < FFI::{}", name, base);
+    writer.indent();
+
+    if let Some(pack) = item.pack {
+        emitln!(writer, "pack {}", pack);
+    }
+
+    let mut fields = Vec::with_capacity(item.fields.len());
+    for field in &item.fields {
+        match field.ty {
+            Type::Array(_, ArraySize::Dynamic) => {
+                fields.push(format!(":{}, :pointer", field.name));
+                fields.push(format!(":{}_len, :size_t", field.name));
+                if field.has_cap {
+                    fields.push(format!(":{}_cap, :size_t", field.name));
+                }
+            }
+            ref ty => fields.push(format!(
+                ":{}, {}",
+                field.name,
+                ffi_type(context, ty, Mode::Field)
+            )),
+        }
+    }
+
+    // The pairs of names and types are aligned after `layout`.
+    for (index, field) in fields.iter().enumerate() {
+        emitln!(
+            writer,
+            "{}{}{}",
+            if index == 0 { "layout " } else { "       " },
+            field,
+            if index + 1 < fields.len() { "," } else { "" }
+        );
+    }
+
+    writer.unindent();
+    emitln!(writer, "end");
+}
+
+pub fn emit_static(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Static) {
+    emitln!(
+        writer,
+        "attach_variable :{}, :{}, {}",
+        name.to_snake_case(),
+        name,
+        ffi_type(context, &item.ty, Mode::Field)
+    );
+}
+
+/// Whether the function is wrapped by a method converting its strings and arrays.
+pub fn needs_wrapper(context: &Context, name: &str, fun: &Function) -> bool {
+    let mut wrapped = matches!(fun.output, Type::String);
+
+    for (_, ty) in &fun.inputs {
+        match *ty {
+            Type::String => wrapped = true,
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                if !is_supported_elem(context, elem) {
+                    println!(
+                        "arrays of this type are not supported ({}). Skipping its wrapper.",
+                        name
+                    );
+                    return false;
+                }
+                wrapped = true;
+            }
+            _ => (),
+        }
+    }
+
+    wrapped
+}
+
+/// Attaches the native function. Wrapped functions are attached as private methods, with an
+/// underscore prefix, and documented by their wrappers.
+pub fn emit_attach_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+    wrapped: bool,
+) {
+    let params = native_param_types(context, &fun.inputs, Mode::Function).join(", ");
+    let output = ffi_type(context, &fun.output, Mode::Function);
+
+    emitln!(writer, "");
+    if wrapped {
+        emitln!(
+            writer,
+            "attach_function :_{0}, :{0}, [{1}], {2}",
+            name,
+            params,
+            output
+        );
+        emitln!(writer, "private_class_method :_{}", name);
+    } else {
+        emit_docs(writer, docs);
+        emitln!(
+            writer,
+            "attach_function :{}, [{}], {}",
+            name,
+            params,
+            output
+        );
+    }
+}
+
+pub fn emit_wrapper_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let params: Vec<_> = fun
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, (name, _))| param_name(name, index, KEYWORDS))
+        .collect();
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "def self.{}({})", name, params.join(", "));
+    writer.indent();
+
+    let mut args = Vec::new();
+    for ((_, ty), param) in fun.inputs.iter().zip(&params) {
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                emit_array_conversion(writer, context, param, elem);
+                args.push(format!("{}_ptr", param));
+                if let Type::U8 = **elem {
+                    args.push(format!("{}.bytesize", param));
+                } else {
+                    args.push(format!("{}.size", param));
+                }
+            }
+            Type::String => args.push(format!("{}&.encode(Encoding::UTF_8)", param)),
+            _ => args.push(param.clone()),
+        }
+    }
+
+    let call = format!("_{}({})", name, args.join(", "));
+    match fun.output {
+        Type::String => emitln!(writer, "{}&.force_encoding(Encoding::UTF_8)", call),
+        _ => emitln!(writer, "{}", call),
+    }
+
+    writer.unindent();
+    emitln!(writer, "end");
+}
+
+/// Copies the elements of the Ruby array `name` (or the bytes of the string for `u8`) into
+/// native memory, pointed to by `{name}_ptr`.
+fn emit_array_conversion(writer: &mut IndentedWriter, context: &Context, name: &str, elem: &Type) {
+    match *elem {
+        Type::U8 => {
+            emitln!(
+                writer,
+                "{0}_ptr = FFI::MemoryPointer.new(:uint8, {0}.bytesize)",
+                name
+            );
+            emitln!(writer, "{0}_ptr.put_bytes(0, {0})", name);
+        }
+        Type::String => {
+            emitln!(
+                writer,
+                "{0}_strings = {0}.map {{ |value| FFI::MemoryPointer.from_string(value.encode(Encoding::UTF_8)) }}",
+                name
+            );
+            emitln!(
+                writer,
+                "{0}_ptr = FFI::MemoryPointer.new(:pointer, {0}.size)",
+                name
+            );
+            emitln!(writer, "{0}_ptr.put_array_of_pointer(0, {0}_strings)", name);
+        }
+        Type::User(ref ty) if context.is_struct(ty) => {
+            emitln!(
+                writer,
+                "{0}_ptr = FFI::MemoryPointer.new({1}.size, {0}.size)",
+                name,
+                ty
+            );
+            emitln!(writer, "{}.each_with_index do |value, index|", name);
+            emitln!(
+                writer,
+                "  {0}_ptr.put_bytes(index * {1}.size, value.pointer.get_bytes(0, {1}.size))",
+                name,
+                ty
+            );
+            emitln!(writer, "end");
+        }
+        Type::User(ref ty) => {
+            let elem_type = match context.enums.get(ty) {
+                Some(Some(ty)) => array_elem_type(ty),
+                _ => array_elem_type(elem),
+            };
+            emitln!(
+                writer,
+                "{0}_ptr = FFI::MemoryPointer.new(:{1}, {0}.size)",
+                name,
+                elem_type
+            );
+            emitln!(
+                writer,
+                "{0}_ptr.put_array_of_{1}(0, {0}.map {{ |value| {2}.to_native(value, nil) }})",
+                name,
+                elem_type,
+                ty
+            );
+        }
+        ref elem => {
+            let elem_type = array_elem_type(elem);
+            emitln!(
+                writer,
+                "{0}_ptr = FFI::MemoryPointer.new(:{1}, {0}.size)",
+                name,
+                elem_type
+            );
+            emitln!(writer, "{0}_ptr.put_array_of_{1}(0, {0})", name, elem_type);
+        }
+    }
+}
+
+/// Whether the wrappers can copy arrays of the type into native memory.
+fn is_supported_elem(context: &Context, elem: &Type) -> bool {
+    match *elem {
+        Type::Char
+        | Type::F32
+        | Type::F64
+        | Type::I8
+        | Type::I16
+        | Type::I32
+        | Type::I64
+        | Type::U8
+        | Type::U16
+        | Type::U32
+        | Type::U64
+        | Type::String => true,
+        Type::User(ref name) => match context.enums.get(name) {
+            Some(Some(ty)) => is_supported_elem(context, ty),
+            Some(None) => true,
+            None => context.is_struct(name),
+        },
+        _ => false,
+    }
+}
+
+/// The type of the `put_array_of_*` methods writing the integer or float type. C enums have the
+/// size of `int`.
+fn array_elem_type(ty: &Type) -> &'static str {
+    match *ty {
+        Type::F32 => "float32",
+        Type::F64 => "float64",
+        Type::I8 => "int8",
+        Type::I16 => "int16",
+        Type::I64 => "int64",
+        Type::U8 => "uint8",
+        Type::U16 => "uint16",
+        Type::Char | Type::U32 => "uint32",
+        Type::U64 => "uint64",
+        _ => "int32",
+    }
+}
+
+/// The `ffi` type of the Rust type.
+pub fn ffi_type(context: &Context, ty: &Type, mode: Mode) -> String {
+    match *ty {
+        Type::Unit => ":void".to_string(),
+        Type::Bool => ":bool".to_string(),
+        Type::Char => ":uint32".to_string(),
+        Type::CChar => ":char".to_string(),
+        Type::F32 => ":float".to_string(),
+        Type::F64 => ":double".to_string(),
+        Type::I8 => ":int8".to_string(),
+        Type::I16 => ":int16".to_string(),
+        Type::I32 => ":int32".to_string(),
+        Type::I64 => ":int64".to_string(),
+        Type::ISize => ":ssize_t".to_string(),
+        Type::U8 => ":uint8".to_string(),
+        Type::U16 => ":uint16".to_string(),
+        Type::U32 => ":uint32".to_string(),
+        Type::U64 => ":uint64".to_string(),
+        Type::USize => ":size_t".to_string(),
+        Type::String => ":string".to_string(),
+        // Structs are declared after the callbacks, and may refer to each other.
+        Type::Pointer(ref ty) => match **ty {
+            Type::User(ref name) if mode == Mode::Function && context.is_struct(name) => {
+                format!("{}.by_ref", name)
+            }
+            _ => ":pointer".to_string(),
+        },
+        Type::Array(ref ty, ref size) if mode == Mode::Field && *size != ArraySize::Dynamic => {
+            format!("[{}, {}]", ffi_type(context, ty, mode), array_size(size))
+        }
+        Type::Array(..) => ":pointer".to_string(),
+        Type::Function(ref fun) => format!(":{}", callback_name(fun)),
+        Type::User(ref name) => {
+            if context.is_enum(name) {
+                format!(":{}", enum_type_name(name))
+            } else if context.is_struct(name) && mode != Mode::Field {
+                format!("{}.by_value", name)
+            } else {
+                name.clone()
+            }
+        }
+    }
+}
+
+fn array_size(size: &ArraySize) -> String {
+    match *size {
+        ArraySize::Lit(size) => size.to_string(),
+        ArraySize::Const(ref name) => name.clone(),
+        ArraySize::Dynamic => unreachable!("dynamic arrays have no static size"),
+    }
+}
+
+/// The `ffi` types of the parameters, with dynamic arrays passed as pointer and length.
+fn native_param_types(context: &Context, inputs: &[(String, Type)], mode: Mode) -> Vec<String> {
+    let mut types = Vec::with_capacity(inputs.len());
+    for (_, ty) in inputs {
+        types.push(ffi_type(context, ty, mode));
+        if ty.is_dynamic_array() {
+            types.push(":size_t".to_string());
+        }
+    }
+    types
+}
+
+fn extract_callback_from_function(fun: &Function) -> bool {
+    fun.inputs
+        .first()
+        .is_some_and(|(name, ty)| is_user_data(name, ty))
+}
+
+fn type_part_name(ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "Void".to_string(),
+        Type::Bool => "Bool".to_string(),
+        Type::Char => "Char".to_string(),
+        Type::CChar => "CChar".to_string(),
+        Type::F32 => "F32".to_string(),
+        Type::F64 => "F64".to_string(),
+        Type::I8 => "I8".to_string(),
+        Type::I16 => "I16".to_string(),
+        Type::I32 => "I32".to_string(),
+        Type::I64 => "I64".to_string(),
+        Type::ISize => "ISize".to_string(),
+        Type::U8 => "U8".to_string(),
+        Type::U16 => "U16".to_string(),
+        Type::U32 => "U32".to_string(),
+        Type::U64 => "U64".to_string(),
+        Type::USize => "USize".to_string(),
+        Type::String => "String".to_string(),
+        Type::Pointer(ref ty) => type_part_name(ty),
+        Type::Array(ref ty, ref size) => match *size {
+            ArraySize::Lit(value) => format!("{}Array{}", type_part_name(ty), value),
+            ArraySize::Const(ref name) => {
+                format!("{}Array{}", type_part_name(ty), name.to_pascal_case())
+            }
+            ArraySize::Dynamic => format!("{}List", type_part_name(ty)),
+        },
+        Type::Function(..) => "Fn".to_string(),
+        Type::User(ref name) => name.clone(),
+    }
+}
+
+/// The Ruby literal of the constant. Arrays are frozen, as strings are by the magic comment.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => format!("{:?}", value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        // `#` starts an interpolation in double-quoted strings
+        ConstValue::String(ref value) => format!("{:?}", value).replace('#', "\\#"),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("[{}].freeze", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
@@ -0,0 +1,600 @@
+//! Functions for converting Rust definitions into Ruby bindings using the `ffi` gem.
+//!
+//! The bindings are a single module extending `FFI::Library`, which declares the enums,
+//! callbacks and structs, and attaches every exported function. Functions taking strings or
+//! arrays are wrapped by module methods converting their arguments, while the attached native
+//! functions are kept private.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, FilterMode, Lang, Outputs};
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use inflector::Inflector;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 2;
+
+pub struct LangRuby {
+    filter: HashSet<String>,
+    filter_mode: FilterMode,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    structs: Vec<Snippet<Struct>>,
+    functions: Vec<Snippet<Function>>,
+    statics: Vec<Snippet<Static>>,
+    aliases: HashMap<String, Type>,
+    /// Names of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their bindings.
+    provided_types: BTreeMap<String, String>,
+}
+
+pub struct Context {
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs and unions.
+    structs: HashSet<String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_struct(&self, name: &str) -> bool {
+        self.structs.contains(name)
+    }
+}
+
+impl LangRuby {
+    pub fn new() -> Self {
+        LangRuby {
+            filter: Default::default(),
+            filter_mode: FilterMode::Blacklist,
+            lib_name: "backend".to_string(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+            aliases: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        }
+    }
+
+    /// Set the name of the native library. This is also the name of the generated file, and
+    /// of the module in Pascal case.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated by its own
+    /// bindings, named `lib_name`. They aren't generated again, but required from them instead.
+    pub fn add_provided_package<P, L>(&mut self, package: P, lib_name: L)
+    where
+        P: Into<String>,
+        L: Into<String>,
+    {
+        let _ = self
+            .provided_packages
+            .insert(package.into(), lib_name.into());
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.filter.clear();
+        self.filter_mode = filter_mode;
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        let _ = self.filter.insert(ident.into());
+    }
+
+    fn is_ignored(&self, ident: &str) -> bool {
+        match self.filter_mode {
+            FilterMode::Blacklist => self.filter.contains(ident),
+            FilterMode::Whitelist => !self.filter.contains(ident),
+        }
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(lib_name) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self
+                .provided_types
+                .insert(name.to_string(), lib_name.clone());
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.consts {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.structs {
+            for field in &mut snippet.item.fields {
+                resolve_alias(&self.aliases, &mut field.ty);
+            }
+        }
+
+        for snippet in &mut self.statics {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Collect the function pointer types of the struct fields and function parameters, by
+    /// name of their `callback` declarations.
+    fn collect_callbacks(&self) -> BTreeMap<String, &Function> {
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|snippet| snippet.item.fields.iter().map(|field| &field.ty));
+        let inputs = self
+            .functions
+            .iter()
+            .flat_map(|snippet| snippet.item.inputs.iter().map(|(_, ty)| ty));
+
+        fields
+            .chain(inputs)
+            .filter_map(|ty| match *ty {
+                Type::Function(ref fun) => Some((callback_name(fun), &**fun)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Emit the requires of the bindings declaring the provided types, and the aliases of
+    /// these types within this module.
+    fn emit_provided_types(&self, writer: &mut IndentedWriter, context: &Context) {
+        for (name, lib_name) in &self.provided_types {
+            let module = lib_name.to_pascal_case();
+            emitln!(writer, "{} = {}::{}", name, module, name);
+            if context.is_enum(name) {
+                emitln!(writer, "typedef {}, :{}", name, enum_type_name(name));
+            }
+        }
+    }
+
+    fn emit_module(&self, context: &Context) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "# frozen_string_literal: true\n");
+        emitln!(writer, "require 'ffi'");
+        let lib_names: HashSet<_> = self.provided_types.values().collect();
+        let mut lib_names: Vec<_> = lib_names.into_iter().collect();
+        lib_names.sort();
+        for lib_name in lib_names {
+            emitln!(writer, "require_relative '{}'", lib_name);
+        }
+
+        emitln!(writer, "\nmodule {}", self.lib_name.to_pascal_case());
+        writer.indent();
+        emitln!(writer, "extend FFI::Library\n");
+        emitln!(
+            writer,
+            "ffi_lib ENV.fetch('{}_LIBRARY_PATH', '{}')",
+            self.lib_name.to_screaming_snake_case(),
+            self.lib_name
+        );
+
+        if !self.provided_types.is_empty() {
+            emitln!(writer, "");
+            self.emit_provided_types(&mut writer, context);
+        }
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.consts {
+                emit_const(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        let callbacks = self.collect_callbacks();
+        if !callbacks.is_empty() {
+            emitln!(writer, "");
+            for callback in callbacks.values() {
+                emit_callback(&mut writer, context, callback);
+            }
+        }
+
+        for snippet in &self.structs {
+            emit_struct(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        if !self.statics.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.statics {
+                emit_static(&mut writer, context, &snippet.name, &snippet.item);
+            }
+        }
+
+        for snippet in &self.functions {
+            let wrapped = needs_wrapper(context, &snippet.name, &snippet.item);
+            emit_attach_function(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+                wrapped,
+            );
+            if wrapped {
+                emit_wrapper_function(
+                    &mut writer,
+                    context,
+                    &snippet.name,
+                    &snippet.docs,
+                    &snippet.item,
+                );
+            }
+        }
+
+        writer.unindent();
+        emitln!(writer, "end");
+
+        writer.into_inner()
+    }
+}
+
+impl Default for LangRuby {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangRuby {
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            println!(
+                "parameterized type aliases not supported ({}). Skipping.",
+                name,
+            );
+            return Ok(());
+        }
+
+        let ty = transform_type(&item.ty).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle the type `{}`", name),
+        })?;
+
+        self.aliases.insert(name, ty);
+
+        Ok(())
+    }
+
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        // The `:string` fields of `FFI::Struct` can't be assigned.
+        if let ConstValue::Struct(..) = item.value {
+            println!("struct constants are not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("enums"));
+        }
+
+        if common::has_data_variants(item) {
+            println!("data-carrying enums not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                let ty = transform_type(&field.ty).ok_or_else(|| Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: format!("bindgen cannot handle struct {}", name),
+                })?;
+                self.aliases.insert(name, ty);
+            }
+            return Ok(());
+        }
+
+        if repr.align.is_some() {
+            println!(
+                "alignment modifiers are not supported ({}). Ignoring.",
+                name
+            );
+        }
+
+        let fields = common::named_fields(&item.fields);
+        let mut item = transform_struct(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+        item.pack = repr.packed;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (repr_c, docs) =
+            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+
+        // If it's not #[repr(C)] ignore it.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("unions"));
+        }
+
+        let mut item = transform_struct(&item.fields.named).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle union {}", name),
+        })?;
+        item.union = true;
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore statics without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+
+        // `attach_variable` only handles scalars and structs.
+        let ty = match transform_type(&item.ty) {
+            Some(Type::Pointer(..)) | Some(Type::Function(..)) | Some(Type::Array(..)) | None => {
+                println!(
+                    "statics of this type are not supported ({}). Skipping.",
+                    name
+                );
+                return Ok(());
+            }
+            Some(ty) => ty,
+        };
+        let item = Static {
+            ty,
+            mutable: item.mutability.is_some(),
+        };
+        self.statics.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self
+                .structs
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .collect(),
+        };
+
+        // Types generated by the bindings of dependencies are required instead.
+        let provided_types = &self.provided_types;
+        self.enums
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+        self.structs
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+
+        if !self.consts.is_empty()
+            || !self.enums.is_empty()
+            || !self.structs.is_empty()
+            || !self.statics.is_empty()
+            || !self.functions.is_empty()
+        {
+            let module = self.emit_module(&context);
+            outputs.insert(format!("{}.rb", self.lib_name), module);
+        }
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.statics.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
@@ -0,0 +1,320 @@
+use super::*;
+use crate::test_utils::{fetch, write_crate};
+use crate::Bindgen;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangRuby::default(), {
+        pub const BOARD_SIZE: usize = 8;
+        /// Template of the move notation.
+        pub const NOTATION: &'static str = "#{piece}-\"x\"";
+        pub const OFFSETS: [i8; 2] = [-1, 1];
+
+        /// Side of a player.
+        #[repr(i8)]
+        pub enum Side {
+            Black = -1,
+            White = 1,
+        }
+
+        #[repr(C)]
+        pub enum Outcome {
+            Win,
+            Draw,
+        }
+
+        #[repr(C)]
+        pub struct Square {
+            file: u8,
+            rank: u8,
+        }
+
+        /// A move on the board.
+        #[repr(C)]
+        pub struct Move {
+            from: Square,
+            to: Square,
+            side: Side,
+            capture: bool,
+            comment: *const c_char,
+            board: *mut Board,
+            on_undo: Option<extern "C" fn(user_data: *mut c_void, side: Side)>,
+        }
+
+        #[repr(C)]
+        pub union Score {
+            centipawns: i32,
+            mate_in: u16,
+        }
+
+        #[repr(C, packed)]
+        pub struct Packed {
+            flags: u8,
+            hash: u64,
+        }
+
+        #[no_mangle]
+        pub static mut MOVE_COUNT: u64 = 0;
+    });
+
+    let actual = fetch(&outputs, "backend.rb");
+    let expected = indoc!(
+        r#"# frozen_string_literal: true
+
+           require 'ffi'
+
+           module Backend
+             extend FFI::Library
+
+             ffi_lib ENV.fetch('BACKEND_LIBRARY_PATH', 'backend')
+
+             BOARD_SIZE = 8
+             # Template of the move notation.
+             NOTATION = "\#{piece}-\"x\""
+             OFFSETS = [-1, 1].freeze
+
+             # Side of a player.
+             Side = enum FFI::Type::INT8, :side, [
+               :black, -1,
+               :white, 1,
+             ]
+
+             Outcome = enum :outcome, [
+               :win, 0,
+               :draw, 1,
+             ]
+
+             callback :callback_side, [:pointer, :side], :void
+
+             class Square < FFI::Struct
+               layout :file, :uint8,
+                      :rank, :uint8
+             end
+
+             # A move on the board.
+             class Move < FFI::Struct
+               layout :from, Square,
+                      :to, Square,
+                      :side, :side,
+                      :capture, :bool,
+                      :comment, :string,
+                      :board, :pointer,
+                      :on_undo, :callback_side
+             end
+
+             class Score < FFI::Union
+               layout :centipawns, :int32,
+                      :mate_in, :uint16
+             end
+
+             class Packed < FFI::Struct
+               pack 1
+               layout :flags, :uint8,
+                      :hash, :uint64
+             end
+
+             attach_variable :move_count, :MOVE_COUNT, :uint64
+           end
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangRuby::default(), {
+        #[repr(C)]
+        pub struct Point {
+            x: f32,
+            y: f32,
+        }
+
+        #[repr(u8)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        /// Returns the distance between the points.
+        #[no_mangle]
+        pub extern "C" fn distance(a: *const Point, b: Point) -> f64 {}
+
+        /// Writes `data` under `name`.
+        #[no_mangle]
+        pub extern "C" fn write(
+            app: *mut App,
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn draw(
+            points: *const Point,
+            points_len: usize,
+            modes: *const Mode,
+            modes_len: usize,
+            labels: *const *const c_char,
+            labels_len: usize,
+            weights: *const f32,
+            weights_len: usize,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn toggle(flags: *const bool, flags_len: usize, next: u32) {}
+    });
+
+    let actual = fetch(&outputs, "backend.rb");
+    let expected = indoc!(
+        r#"# frozen_string_literal: true
+
+           require 'ffi'
+
+           module Backend
+             extend FFI::Library
+
+             ffi_lib ENV.fetch('BACKEND_LIBRARY_PATH', 'backend')
+
+             Mode = enum FFI::Type::UINT8, :mode, [
+               :read, 0,
+               :write, 1,
+             ]
+
+             callback :callback_ffi_result, [:pointer, :pointer], :void
+
+             class Point < FFI::Struct
+               layout :x, :float,
+                      :y, :float
+             end
+
+             # Returns the distance between the points.
+             attach_function :distance, [Point.by_ref, Point.by_value], :double
+
+             attach_function :_write, :write, [:pointer, :string, :pointer, :size_t, :pointer, :callback_ffi_result], :void
+             private_class_method :_write
+
+             # Writes `data` under `name`.
+             def self.write(app, name, data, user_data, o_cb)
+               data_ptr = FFI::MemoryPointer.new(:uint8, data.bytesize)
+               data_ptr.put_bytes(0, data)
+               _write(app, name&.encode(Encoding::UTF_8), data_ptr, data.bytesize, user_data, o_cb)
+             end
+
+             attach_function :_mode_name, :mode_name, [:mode], :string
+             private_class_method :_mode_name
+
+             def self.mode_name(mode)
+               _mode_name(mode)&.force_encoding(Encoding::UTF_8)
+             end
+
+             attach_function :_draw, :draw, [:pointer, :size_t, :pointer, :size_t, :pointer, :size_t, :pointer, :size_t], :void
+             private_class_method :_draw
+
+             def self.draw(points, modes, labels, weights)
+               points_ptr = FFI::MemoryPointer.new(Point.size, points.size)
+               points.each_with_index do |value, index|
+                 points_ptr.put_bytes(index * Point.size, value.pointer.get_bytes(0, Point.size))
+               end
+               modes_ptr = FFI::MemoryPointer.new(:uint8, modes.size)
+               modes_ptr.put_array_of_uint8(0, modes.map { |value| Mode.to_native(value, nil) })
+               labels_strings = labels.map { |value| FFI::MemoryPointer.from_string(value.encode(Encoding::UTF_8)) }
+               labels_ptr = FFI::MemoryPointer.new(:pointer, labels.size)
+               labels_ptr.put_array_of_pointer(0, labels_strings)
+               weights_ptr = FFI::MemoryPointer.new(:float32, weights.size)
+               weights_ptr.put_array_of_float32(0, weights)
+               _draw(points_ptr, points.size, modes_ptr, modes.size, labels_ptr, labels.size, weights_ptr, weights.size)
+             end
+
+             attach_function :toggle, [:pointer, :size_t, :uint32], :void
+           end
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let geometry = write_crate(
+        "ruby_provided_types/geometry",
+        &[(
+            "src/lib.rs",
+            "#[repr(C)]\npub struct Vec2 {\n    pub x: f32,\n    pub y: f32,\n}\n",
+        )],
+    );
+    let palette = write_crate(
+        "ruby_provided_types/palette",
+        &[(
+            "src/lib.rs",
+            "#[repr(u8)]\npub enum Color {\n    Red,\n    Green,\n    Blue,\n}\n",
+        )],
+    );
+    let source = indoc!(
+        "
+        use geometry::Vec2;
+
+        #[repr(C)]
+        pub struct Particle {
+            pub position: Vec2,
+            pub velocity: Vec2,
+            pub tint: palette::Color,
+        }
+
+        #[no_mangle]
+        pub extern \"C\" fn emit_particles(
+            count: u32,
+            on_spawn: extern \"C\" fn(position: *const Vec2, tint: palette::Color),
+        ) {
+        }
+        "
+    );
+
+    let mut lang = LangRuby::default();
+    lang.add_provided_package("geometry", "geometry");
+    lang.add_provided_package("palette", "palette");
+    let mut outputs = Outputs::default();
+    unwrap!(unwrap!(Bindgen::new())
+        .source_code("ffi/src/lib.rs", source)
+        .dependency("geometry", &geometry)
+        .dependency("palette", &palette)
+        .compile(&mut lang, &mut outputs, true));
+
+    // Each of the bindings is required, and their types are aliased within the module.
+    let actual = fetch(&outputs, "backend.rb");
+    let expected = indoc!(
+        r#"# frozen_string_literal: true
+
+           require 'ffi'
+           require_relative 'geometry'
+           require_relative 'palette'
+
+           module Backend
+             extend FFI::Library
+
+             ffi_lib ENV.fetch('BACKEND_LIBRARY_PATH', 'backend')
+
+             Color = Palette::Color
+             typedef Color, :color
+             Vec2 = Geometry::Vec2
+
+             callback :callback_fn_vec_2_color, [:pointer, :color], :void
+
+             class Particle < FFI::Struct
+               layout :position, Vec2,
+                      :velocity, Vec2,
+                      :tint, :color
+             end
+
+             attach_function :emit_particles, [:uint32, :callback_fn_vec_2_color], :void
+           end
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
@@ -0,0 +1,607 @@
+//! Utilities for emiting fragments of the target language code.
+use super::{Context, NativeFunction};
+use crate::intermediate::*;
+use crate::lang_c::types::{CPtrType, CType};
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// Swift keywords which can be used as identifiers in Rust.
+const KEYWORDS: &[&str] = &[
+    "as",
+    "associatedtype",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "continue",
+    "default",
+    "defer",
+    "deinit",
+    "do",
+    "else",
+    "enum",
+    "extension",
+    "fallthrough",
+    "false",
+    "fileprivate",
+    "for",
+    "func",
+    "guard",
+    "if",
+    "import",
+    "in",
+    "init",
+    "inout",
+    "internal",
+    "is",
+    "let",
+    "nil",
+    "open",
+    "operator",
+    "private",
+    "protocol",
+    "public",
+    "repeat",
+    "rethrows",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "subscript",
+    "super",
+    "switch",
+    "throw",
+    "throws",
+    "true",
+    "try",
+    "typealias",
+    "var",
+    "where",
+    "while",
+];
+
+/// Parameter of a function, along with the C types it's passed as: the pointer and the length
+/// for dynamic arrays, the C type of the parameter otherwise.
+pub struct Param<'a> {
+    pub name: &'a str,
+    pub ty: &'a Type,
+    pub ctypes: &'a [CType],
+}
+
+/// Callback of a function which is turned into an `async throws` function: the function takes
+/// a single callback, receiving the user data and a `FfiResult`.
+pub struct AsyncCallback<'a> {
+    pub function: &'a Function,
+    /// C types of the callback parameters.
+    pub params: Vec<CType>,
+}
+
+/// Pairs the parameters with the C types they are passed as.
+pub fn params<'a>(inputs: &'a [(String, Type)], ctypes: &'a [CType]) -> Vec<Param<'a>> {
+    let mut index = 0;
+
+    inputs
+        .iter()
+        .map(|(name, ty)| {
+            let count = if ty.is_dynamic_array() { 2 } else { 1 };
+            let start = index.min(ctypes.len());
+            index += count;
+
+            Param {
+                name,
+                ty,
+                ctypes: &ctypes[start..index.min(ctypes.len())],
+            }
+        })
+        .collect()
+}
+
+/// Returns the callback of a function which is turned into an `async throws` function, along
+/// with the C types of its parameters.
+pub fn native_async_callback<'a>(
+    context: &Context,
+    fun: &'a NativeFunction,
+) -> Option<AsyncCallback<'a>> {
+    if let Type::Unit = fun.function.output {
+    } else {
+        return None;
+    }
+    let callback = async_callback(&fun.function, |name| context.is_declared(name))?;
+    if let Type::Unit = callback.output {
+    } else {
+        return None;
+    }
+
+    let inputs = &fun.function.inputs;
+    let ctypes = params(inputs, &fun.params)
+        .into_iter()
+        .find(|param| matches!(*param.ty, Type::Function(..)))
+        .and_then(|param| param.ctypes.first());
+    match ctypes {
+        Some(CType::FnDecl { args, .. }) => Some(AsyncCallback {
+            function: callback,
+            params: args.iter().map(|arg| arg.1.clone()).collect(),
+        }),
+        _ => None,
+    }
+}
+
+/// Swift identifier for the Rust identifier `name`.
+pub fn identifier(name: &str) -> String {
+    let name = name.to_camel_case();
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("`{}`", name)
+    } else {
+        name
+    }
+}
+
+/// The Swift type the C type is imported as.
+pub fn swift_type(context: &Context, cty: &CType) -> String {
+    match *cty {
+        CType::Void => "Void".to_string(),
+        CType::Native(name) => native_type(name).to_string(),
+        CType::Mapping(ref name) => name.clone(),
+        CType::Ptr(ref pointee, ref ptr) => format!("{}?", pointer_type(context, pointee, ptr)),
+        CType::NonNullPtr(ref pointee, ref ptr) => pointer_type(context, pointee, ptr),
+        CType::Array(ref elem, len) => {
+            let elem = swift_type(context, elem);
+            format!("({})", vec![elem; len].join(", "))
+        }
+        CType::FnDecl {
+            ref args,
+            ref return_type,
+            ..
+        } => {
+            let args: Vec<_> = args.iter().map(|arg| swift_type(context, &arg.1)).collect();
+            format!(
+                "(@convention(c) ({}) -> {})?",
+                args.join(", "),
+                swift_type(context, return_type)
+            )
+        }
+    }
+}
+
+fn pointer_type(context: &Context, pointee: &CType, ptr: &CPtrType) -> String {
+    match (pointee, ptr) {
+        (CType::Void, CPtrType::Const) => "UnsafeRawPointer".to_string(),
+        (CType::Void, CPtrType::Mutable) => "UnsafeMutableRawPointer".to_string(),
+        (CType::Mapping(name), _) if !context.is_declared(name) => "OpaquePointer".to_string(),
+        (_, CPtrType::Const) => format!("UnsafePointer<{}>", swift_type(context, pointee)),
+        (_, CPtrType::Mutable) => format!("UnsafeMutablePointer<{}>", swift_type(context, pointee)),
+    }
+}
+
+fn native_type(name: &str) -> &str {
+    match name {
+        "float" => "Float",
+        "double" => "Double",
+        "bool" => "Bool",
+        "int8_t" => "Int8",
+        "int16_t" => "Int16",
+        "int32_t" => "Int32",
+        "int64_t" => "Int64",
+        "intptr_t" => "Int",
+        "uint8_t" => "UInt8",
+        "uint16_t" => "UInt16",
+        "uint32_t" => "UInt32",
+        "uint64_t" => "UInt64",
+        "uintptr_t" => "UInt",
+        "char" => "CChar",
+        "signed char" => "CSignedChar",
+        "unsigned char" => "CUnsignedChar",
+        "short" => "CShort",
+        "unsigned short" => "CUnsignedShort",
+        "int" => "CInt",
+        "unsigned int" => "CUnsignedInt",
+        "long" => "CLong",
+        "unsigned long" => "CUnsignedLong",
+        "long long" => "CLongLong",
+        "unsigned long long" => "CUnsignedLongLong",
+        name => name,
+    }
+}
+
+/// Returns the pointee of a pointer to constant data.
+fn const_pointee(cty: &CType) -> Option<&CType> {
+    match *cty {
+        CType::Ptr(ref pointee, CPtrType::Const)
+        | CType::NonNullPtr(ref pointee, CPtrType::Const) => Some(pointee),
+        _ => None,
+    }
+}
+
+fn is_c_string(ty: &Type, cty: &CType) -> bool {
+    matches!(*ty, Type::String) && const_pointee(cty) == Some(&CType::Native("char"))
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    for line in docs.split("///").skip(1) {
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+        if line.is_empty() {
+            emitln!(writer, "///");
+        } else {
+            emitln!(writer, "/// {}", line);
+        }
+    }
+}
+
+pub fn emit_utilities(writer: &mut IndentedWriter) {
+    emitln!(
+        writer,
+        r#"
+public struct FfiError: Error {{
+    public let code: Int32
+    public let description: String
+
+    init(_ result: FfiResult) {{
+        code = result.error_code
+        description = result.description.map {{ String(cString: $0) }} ?? ""
+    }}
+}}
+
+/// Continuation of an async call, passed to its callback as the user data.
+private final class Completion<T> {{
+    private let continuation: CheckedContinuation<T, Error>
+
+    init(_ continuation: CheckedContinuation<T, Error>) {{
+        self.continuation = continuation
+    }}
+
+    /// Resumes the continuation passed as the user data, throwing if the result is an error.
+    static func resume(
+        _ userData: UnsafeMutableRawPointer?,
+        _ result: UnsafePointer<FfiResult>?,
+        _ value: () -> T
+    ) {{
+        let completion = Unmanaged<Completion<T>>.fromOpaque(userData!).takeRetainedValue()
+        if let result = result, result.pointee.error_code != 0 {{
+            completion.continuation.resume(throwing: FfiError(result.pointee))
+        }} else {{
+            completion.continuation.resume(returning: value())
+        }}
+    }}
+}}"#
+    );
+}
+
+pub fn emit_handle_class(writer: &mut IndentedWriter, context: &Context, name: &str, free: &str) {
+    emitln!(writer, "");
+    emitln!(
+        writer,
+        "/// Handle to a native `{}`, freed with `{}` when released.",
+        name,
+        free
+    );
+    emitln!(writer, "public final class {} {{", name);
+    writer.indent();
+    emitln!(writer, "public let handle: OpaquePointer\n");
+    emitln!(writer, "public init(handle: OpaquePointer) {{");
+    emitln!(writer, "    self.handle = handle");
+    emitln!(writer, "}}\n");
+    emitln!(writer, "deinit {{");
+    emitln!(writer, "    {}.{}(handle)", context.module, free);
+    emitln!(writer, "}}");
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+pub fn emit_wrapper_function(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &NativeFunction,
+) {
+    let callback = native_async_callback(context, item);
+    let mut decls = Vec::new();
+    let mut args = Vec::new();
+    // Closures giving access to the memory of the arrays and values.
+    let mut buffers = Vec::new();
+
+    for param in params(&item.function.inputs, &item.params) {
+        let ident = identifier(param.name);
+
+        if callback.is_some() {
+            if is_user_data(param.name, param.ty) {
+                args.push("userData".to_string());
+                continue;
+            }
+            if let Type::Function(..) = *param.ty {
+                args.push(callback_closure(context, unwrap!(callback.as_ref())));
+                continue;
+            }
+        }
+
+        let pointee = param.ctypes.first().and_then(const_pointee);
+        match (param.ty, pointee) {
+            (Type::Array(_, ArraySize::Dynamic), Some(pointee)) => {
+                if *pointee == CType::Native("uint8_t") {
+                    decls.push(format!("{}: Data", ident));
+                    buffers.push(format!("{0}.withUnsafeBytes {{ {0} in", ident));
+                    args.push(format!("{}.bindMemory(to: UInt8.self).baseAddress", ident));
+                } else {
+                    decls.push(format!("{}: [{}]", ident, swift_type(context, pointee)));
+                    buffers.push(format!("{0}.withUnsafeBufferPointer {{ {0} in", ident));
+                    args.push(format!("{}.baseAddress", ident));
+                }
+                args.push(format!("UInt({}.count)", ident));
+            }
+            (Type::String, _) if is_c_string(param.ty, &param.ctypes[0]) => {
+                decls.push(format!("{}: String", ident));
+                args.push(ident);
+            }
+            // References to values are passed by value, the pointer lasting for the call.
+            (_, Some(CType::Mapping(pointee)))
+                if matches!(param.ctypes[0], CType::NonNullPtr(..))
+                    && context.is_declared(pointee) =>
+            {
+                decls.push(format!("{}: {}", ident, pointee));
+                buffers.push(format!("withUnsafePointer(to: {0}) {{ {0} in", ident));
+                args.push(ident);
+            }
+            _ => {
+                if let Some(class) = param
+                    .ctypes
+                    .first()
+                    .and_then(|cty| context.handle_class(cty))
+                {
+                    decls.push(format!("{}: {}", ident, class));
+                    args.push(format!("{}.handle", ident));
+                    continue;
+                }
+
+                // Pointers to mutable arrays are passed as is, along with their length.
+                for (index, cty) in param.ctypes.iter().enumerate() {
+                    let ident = if index == 0 {
+                        ident.clone()
+                    } else {
+                        identifier(&format!("{}_len", param.name))
+                    };
+                    decls.push(format!("{}: {}", ident, swift_type(context, cty)));
+                    args.push(ident);
+                }
+            }
+        }
+    }
+
+    let call = format!("{}.{}({})", context.module, name, args.join(", "));
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+
+    if let Some(ref callback) = callback {
+        let (ty, _) = callback_values(context, callback);
+        if ty == "Void" {
+            emitln!(
+                writer,
+                "public func {}({}) async throws {{",
+                identifier(name),
+                decls.join(", ")
+            );
+        } else {
+            emitln!(
+                writer,
+                "public func {}({}) async throws -> {} {{",
+                identifier(name),
+                decls.join(", "),
+                ty
+            );
+        }
+        writer.indent();
+        emitln!(
+            writer,
+            "return try await withCheckedThrowingContinuation {{ (continuation: CheckedContinuation<{}, Error>) in",
+            ty
+        );
+        writer.indent();
+        emitln!(
+            writer,
+            "let userData = Unmanaged.passRetained(Completion(continuation)).toOpaque()"
+        );
+        emit_call(writer, &buffers, "", &call);
+        writer.unindent();
+        emitln!(writer, "}}");
+    } else {
+        let (ty, value) = return_value(context, &item.function.output, &item.output, &call);
+        match ty {
+            Some(ref ty) => emitln!(
+                writer,
+                "public func {}({}) -> {} {{",
+                identifier(name),
+                decls.join(", "),
+                ty
+            ),
+            None => emitln!(
+                writer,
+                "public func {}({}) {{",
+                identifier(name),
+                decls.join(", ")
+            ),
+        }
+        writer.indent();
+        let prefix = if ty.is_some() { "return " } else { "" };
+        emit_call(writer, &buffers, prefix, &value);
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// Emit the call nested in the closures accessing the arrays and values.
+fn emit_call(writer: &mut IndentedWriter, buffers: &[String], prefix: &str, call: &str) {
+    for buffer in buffers {
+        emitln!(writer, "{}{}", prefix, buffer);
+        writer.indent();
+    }
+    emitln!(writer, "{}{}", prefix, call);
+    for _ in buffers {
+        writer.unindent();
+        emitln!(writer, "}}");
+    }
+}
+
+/// Type and value returned by a wrapper function calling `call`.
+fn return_value(context: &Context, ty: &Type, cty: &CType, call: &str) -> (Option<String>, String) {
+    let nullable = matches!(*cty, CType::Ptr(..));
+
+    if *cty == CType::Void {
+        (None, call.to_string())
+    } else if is_c_string(ty, cty) {
+        if nullable {
+            (
+                Some("String?".to_string()),
+                format!("{}.map {{ String(cString: $0) }}", call),
+            )
+        } else {
+            (
+                Some("String".to_string()),
+                format!("String(cString: {})", call),
+            )
+        }
+    } else if let Some(class) = context.handle_class(cty) {
+        if nullable {
+            (
+                Some(format!("{}?", class)),
+                format!("{}.map({}.init(handle:))", call, class),
+            )
+        } else {
+            (
+                Some(class.to_string()),
+                format!("{}(handle: {})", class, call),
+            )
+        }
+    } else {
+        (Some(swift_type(context, cty)), call.to_string())
+    }
+}
+
+/// Closure passed as the callback of an async function, resuming its continuation.
+fn callback_closure(context: &Context, callback: &AsyncCallback) -> String {
+    let (ty, value) = callback_values(context, callback);
+    let names = callback_param_names(callback);
+
+    format!(
+        "{{ {} in\n    Completion<{}>.resume(userData, result) {{ {} }}\n}}",
+        names.join(", "),
+        ty,
+        value
+    )
+}
+
+/// Names of the parameters of the callback closure.
+fn callback_param_names(callback: &AsyncCallback) -> Vec<String> {
+    let mut names = vec!["userData".to_string(), "result".to_string()];
+    for (index, param) in params(&callback.function.inputs, &callback.params)
+        .iter()
+        .enumerate()
+        .skip(2)
+    {
+        let name = if param.name.is_empty() {
+            format!("arg{}", index)
+        } else {
+            identifier(param.name)
+        };
+        names.push(name);
+        if param.ctypes.len() == 2 {
+            names.push(identifier(&format!("{}_len", param.name)));
+        }
+    }
+    names
+}
+
+/// Type and value the continuation of an async function is resumed with, converted from the
+/// parameters of the callback.
+fn callback_values(context: &Context, callback: &AsyncCallback) -> (String, String) {
+    let names = callback_param_names(callback);
+    let mut names = names[2..].iter();
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+
+    for param in params(&callback.function.inputs, &callback.params)
+        .iter()
+        .skip(2)
+    {
+        let name = unwrap!(names.next());
+        let cty = &param.ctypes[0];
+        let nullable = matches!(*cty, CType::Ptr(..));
+        let unwrapped = if nullable {
+            format!("{}!", name)
+        } else {
+            name.clone()
+        };
+
+        let (ty, value) = match (param.ty, const_pointee(cty)) {
+            (Type::Array(_, ArraySize::Dynamic), Some(pointee)) => {
+                let len = unwrap!(names.next());
+                if *pointee == CType::Native("uint8_t") {
+                    (
+                        "Data".to_string(),
+                        format!(
+                            "{}.map {{ Data(bytes: $0, count: Int({})) }} ?? Data()",
+                            name, len
+                        ),
+                    )
+                } else {
+                    (
+                        format!("[{}]", swift_type(context, pointee)),
+                        format!(
+                            "Array(UnsafeBufferPointer(start: {}, count: Int({})))",
+                            name, len
+                        ),
+                    )
+                }
+            }
+            (Type::String, _) if is_c_string(param.ty, cty) => {
+                if nullable {
+                    (
+                        "String".to_string(),
+                        format!("{}.map {{ String(cString: $0) }} ?? \"\"", name),
+                    )
+                } else {
+                    ("String".to_string(), format!("String(cString: {})", name))
+                }
+            }
+            (_, pointee) => {
+                if let Some(class) = context.handle_class(cty) {
+                    (
+                        class.to_string(),
+                        format!("{}(handle: {})", class, unwrapped),
+                    )
+                } else {
+                    match pointee {
+                        // Structs are copied out of the memory owned by the library.
+                        Some(CType::Mapping(ref pointee)) if context.is_declared(pointee) => {
+                            (pointee.clone(), format!("{}.pointee", unwrapped))
+                        }
+                        _ => {
+                            // Mutable arrays are passed as is, along with their length.
+                            if let Some(len) = param.ctypes.get(1) {
+                                types.push(swift_type(context, cty));
+                                values.push(name.clone());
+                                (swift_type(context, len), unwrap!(names.next()).clone())
+                            } else {
+                                (swift_type(context, cty), name.clone())
+                            }
+                        }
+                    }
+                }
+            }
+        };
+
+        types.push(ty);
+        values.push(value);
+    }
+
+    match types.len() {
+        0 => ("Void".to_string(), "()".to_string()),
+        1 => (types.remove(0), values.remove(0)),
+        _ => (
+            format!("({})", types.join(", ")),
+            format!("({})", values.join(", ")),
+        ),
+    }
+}
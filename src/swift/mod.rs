@@ -0,0 +1,452 @@
+//! Functions for converting Rust definitions into a Swift package, wrapping the C header in a
+//! module map along with idiomatic Swift functions.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, Lang, Outputs};
+use crate::intermediate::*;
+use crate::lang_c::types::{CPtrType, CType};
+use crate::lang_c::{self, LangC};
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use inflector::Inflector;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::ops::Deref;
+use std::path::MAIN_SEPARATOR;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 4;
+
+pub struct LangSwift {
+    c: LangC,
+    lib_name: String,
+    /// Names of the types declared in the C header.
+    declared: HashSet<String>,
+    functions: Vec<Snippet<NativeFunction>>,
+    /// Headers of the units providing the types of dependency crates, by library name.
+    provided_units: BTreeMap<String, String>,
+}
+
+/// Function along with the C types of its parameters, which tell the constness and nullability
+/// of the pointers as seen by Swift.
+pub struct NativeFunction {
+    pub function: Function,
+    pub params: Vec<CType>,
+    pub output: CType,
+}
+
+pub struct Context {
+    /// Name of the Swift module of the C header.
+    module: String,
+    /// Swift modules of the C headers of the units providing types.
+    provided_modules: Vec<String>,
+    /// Names of the types declared in the C header. Pointers to other types are opaque.
+    declared: HashSet<String>,
+    /// Opaque types wrapped in classes, with the functions freeing them.
+    handles: BTreeMap<String, String>,
+}
+
+impl Context {
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.declared.contains(name)
+    }
+
+    /// Returns the name of the class wrapping the pointer type `cty`, if it's a handle.
+    pub fn handle_class<'a>(&self, cty: &'a CType) -> Option<&'a str> {
+        match *cty {
+            CType::Ptr(ref pointee, _) | CType::NonNullPtr(ref pointee, _) => match **pointee {
+                CType::Mapping(ref name) if self.handles.contains_key(name) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl LangSwift {
+    pub fn new() -> Self {
+        let mut lang = LangSwift {
+            c: LangC::new(),
+            lib_name: String::new(),
+            declared: Default::default(),
+            functions: Vec::new(),
+            provided_units: Default::default(),
+        };
+        lang.c.set_split_headers_enabled(false);
+        lang.set_lib_name("backend");
+        lang
+    }
+
+    /// Set the name of the native library. The Swift modules are named after it, e.g.
+    /// `CBackend` for the C header and `Backend` for the wrappers of the library `backend`.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+        self.c.set_lib_name(self.lib_name.clone());
+        let header = source_path(&self.c_module(), &format!("{}.h", self.lib_name));
+        self.c.set_amalgamated_header(header);
+    }
+
+    /// Add custom code to the C header, e.g. declarations of additional types.
+    pub fn add_custom_code(&mut self, code: &str) {
+        self.c.add_custom_code(code);
+    }
+
+    /// Declare that the types of the dependency crate `package` are declared in the C header
+    /// `header` of its own bindings, which is included instead.
+    pub fn add_provided_package<P: AsRef<str>, H: Into<String>>(&mut self, package: P, header: H) {
+        self.c.add_provided_package(package, header);
+    }
+
+    fn module(&self) -> String {
+        module_name(&self.lib_name)
+    }
+
+    fn c_module(&self) -> String {
+        c_module_name(&self.lib_name)
+    }
+
+    /// Names of the types which are only used behind pointers, without being declared.
+    fn opaque_types(&self) -> BTreeSet<String> {
+        self.functions
+            .iter()
+            .flat_map(|snippet| snippet.item.params.iter().chain(Some(&snippet.item.output)))
+            .flat_map(CType::dependencies)
+            .filter(|name| !self.declared.contains(name))
+            .collect()
+    }
+
+    /// Find the functions freeing the opaque types, which take a single mutable pointer and
+    /// are named `*_free`.
+    fn collect_handles(&self, opaque: &BTreeSet<String>) -> BTreeMap<String, String> {
+        self.functions
+            .iter()
+            .filter(|snippet| snippet.name.ends_with("_free"))
+            .filter(|snippet| snippet.item.output == CType::Void)
+            .filter_map(|snippet| match snippet.item.params[..] {
+                [CType::Ptr(ref pointee, CPtrType::Mutable)]
+                | [CType::NonNullPtr(ref pointee, CPtrType::Mutable)] => match **pointee {
+                    CType::Mapping(ref name) if opaque.contains(name) => {
+                        Some((name.clone(), snippet.name.clone()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn emit_package(&self, provided: &[&String]) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "// swift-tools-version:5.5");
+        emitln!(writer, "import PackageDescription\n");
+        emitln!(writer, "let package = Package(");
+        writer.indent();
+        emitln!(writer, "name: \"{}\",", self.module());
+        emitln!(writer, "products: [");
+        emitln!(
+            writer,
+            "    .library(name: \"{0}\", targets: [\"{0}\"]),",
+            self.module()
+        );
+        emitln!(writer, "],");
+        // Units of a workspace are generated into sibling directories.
+        if !provided.is_empty() {
+            emitln!(writer, "dependencies: [");
+            for lib in provided {
+                emitln!(writer, "    .package(path: \"../{}\"),", lib);
+            }
+            emitln!(writer, "],");
+        }
+        emitln!(writer, "targets: [");
+        writer.indent();
+        emitln!(writer, ".systemLibrary(name: \"{}\"),", self.c_module());
+        emit!(
+            writer,
+            ".target(name: \"{}\", dependencies: [\"{}\"",
+            self.module(),
+            self.c_module()
+        );
+        for lib in provided {
+            emit!(
+                writer,
+                ", .product(name: \"{}\", package: \"{}\")",
+                module_name(lib),
+                lib
+            );
+        }
+        emitln!(writer, "]),");
+        writer.unindent();
+        emitln!(writer, "]");
+        writer.unindent();
+        emitln!(writer, ")");
+
+        writer.into_inner()
+    }
+
+    fn emit_module_map(&self) -> String {
+        format!(
+            "module {} {{
+    header \"{}.h\"
+    link \"{}\"
+    export *
+}}
+",
+            self.c_module(),
+            self.lib_name,
+            self.lib_name
+        )
+    }
+
+    fn emit_wrappers(&self, context: &Context) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        emitln!(writer, "import {}", context.module);
+        for module in &context.provided_modules {
+            emitln!(writer, "import {}", module);
+        }
+        emitln!(writer, "import Foundation");
+
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .filter(|snippet| !context.handles.values().any(|free| *free == snippet.name))
+            .collect();
+
+        if functions
+            .iter()
+            .any(|snippet| native_async_callback(context, &snippet.item).is_some())
+        {
+            emit_utilities(&mut writer);
+        }
+
+        for (name, free) in &context.handles {
+            emit_handle_class(&mut writer, context, name, free);
+        }
+
+        for snippet in functions {
+            emit_wrapper_function(
+                &mut writer,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        writer.into_inner()
+    }
+}
+
+impl Default for LangSwift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangSwift {
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_const(item, module, outputs)
+    }
+
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_ty(item, module, outputs)?;
+        if item.generics.params.is_empty() {
+            let _ = self.declared.insert(item.ident.to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_enum(item, module, outputs)?;
+        let repr = common::parse_repr(&item.attrs);
+        if repr.c || repr.int.is_some() {
+            let _ = self.declared.insert(item.ident.to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_struct(item, module, outputs)?;
+        let repr = common::parse_repr(&item.attrs);
+        if repr.c || repr.transparent {
+            let _ = self.declared.insert(item.ident.to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_union(item, module, outputs)?;
+        if common::parse_attr(&item.attrs[..], common::check_repr_c, |_| None).0 {
+            let _ = self.declared.insert(item.ident.to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_static(item, module, outputs)
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        module: &[String],
+        outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        self.c.parse_fn(item, module, outputs)?;
+
+        let name = item.ident.to_string();
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        let mut params = Vec::new();
+        for arg in &item.decl.inputs {
+            if let syn::FnArg::Captured(ref arg) = *arg {
+                let name = common::take_out_pat(&arg.pat)
+                    .map(|pat| pat.ident.to_string())
+                    .unwrap_or_default();
+                params.push(lang_c::rust_to_c(&arg.ty, &name)?.1);
+            }
+        }
+        let output = match item.decl.output {
+            syn::ReturnType::Default => CType::Void,
+            syn::ReturnType::Type(_, ref ty) => lang_c::rust_to_c(ty.deref(), "")?.1,
+        };
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: NativeFunction {
+                function,
+                params,
+                output,
+            },
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        // The header only knows of the opaque types through these declarations.
+        let opaque = self.opaque_types();
+        for name in &opaque {
+            self.c
+                .add_custom_code(&format!("typedef struct {0} {0};\n", name));
+        }
+        self.c.finalise_output(outputs)?;
+
+        // Only the units whose headers are actually included are depended on.
+        let header = source_path(&self.c_module(), &format!("{}.h", self.lib_name));
+        let provided: Vec<_> = self
+            .provided_units
+            .iter()
+            .filter(|(_, include)| {
+                outputs
+                    .get(&header)
+                    .is_some_and(|code| code.contains(&format!("#include \"{}\"", include)))
+            })
+            .map(|(lib, _)| lib)
+            .collect();
+
+        let context = Context {
+            module: self.c_module(),
+            provided_modules: provided.iter().map(|lib| c_module_name(lib)).collect(),
+            declared: self.declared.clone(),
+            handles: self.collect_handles(&opaque),
+        };
+
+        outputs.insert("Package.swift".to_string(), self.emit_package(&provided));
+        outputs.insert(
+            source_path(&self.c_module(), "module.modulemap"),
+            self.emit_module_map(),
+        );
+        outputs.insert(
+            source_path(&self.module(), &format!("{}.swift", self.module())),
+            self.emit_wrappers(&context),
+        );
+
+        self.declared.clear();
+        self.functions.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        Some(self.lib_name.clone())
+    }
+
+    /// The header of the unit is included from the package generated next to this one.
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        let include = format!(
+            "../../../{}/Sources/{}/{}.h",
+            reference,
+            c_module_name(reference),
+            reference
+        );
+        self.add_provided_package(package, include.clone());
+        let _ = self.provided_units.insert(reference.to_string(), include);
+    }
+}
+
+/// Name of the Swift module of the wrappers for the library `lib_name`.
+fn module_name(lib_name: &str) -> String {
+    lib_name.to_pascal_case()
+}
+
+/// Name of the Swift module of the C header for the library `lib_name`.
+fn c_module_name(lib_name: &str) -> String {
+    format!("C{}", module_name(lib_name))
+}
+
+/// Path of a source file of the Swift package target `target`.
+fn source_path(target: &str, file: &str) -> String {
+    format!("Sources{0}{1}{0}{2}", MAIN_SEPARATOR, target, file)
+}
@@ -0,0 +1,376 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn package() {
+    let mut lang = LangSwift::new();
+    lang.set_lib_name("safe_app");
+
+    let outputs = compile!(lang, {
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+    });
+
+    assert_eq!(outputs.len(), 4);
+
+    let actual = fetch(&outputs, "Package.swift");
+    let expected = indoc!(
+        r#"// swift-tools-version:5.5
+           import PackageDescription
+
+           let package = Package(
+               name: "SafeApp",
+               products: [
+                   .library(name: "SafeApp", targets: ["SafeApp"]),
+               ],
+               targets: [
+                   .systemLibrary(name: "CSafeApp"),
+                   .target(name: "SafeApp", dependencies: ["CSafeApp"]),
+               ]
+           )
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Sources/CSafeApp/module.modulemap");
+    let expected = indoc!(
+        r#"module CSafeApp {
+               header "safe_app.h"
+               link "safe_app"
+               export *
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    // Opaque types are declared in the header.
+    let header = fetch(&outputs, "Sources/CSafeApp/safe_app.h");
+    assert!(header.contains("typedef struct App App;\n"));
+    assert!(header.contains("void app_free(App* app);\n"));
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangSwift::default(), {
+        #[repr(C)]
+        pub struct Point {
+            x: f64,
+            y: f64,
+        }
+
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        /// Opens an app.
+        ///
+        /// Returns null on failure.
+        #[no_mangle]
+        pub extern "C" fn app_open(name: *const c_char, mode: Mode) -> *mut App {}
+
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+
+        #[no_mangle]
+        pub extern "C" fn app_name(app: &App) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn app_write(app: *mut App, data_ptr: *const u8, data_len: usize) -> bool {}
+
+        #[no_mangle]
+        pub extern "C" fn sum(
+            values: *const u32,
+            values_len: usize,
+            out: *mut u8,
+            out_len: usize,
+            in_: i32,
+        ) -> u64 {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn nearest(points: *const Point, points_len: usize, to: &Point) -> usize {}
+
+        #[no_mangle]
+        pub extern "C" fn on_event(
+            cb: Option<extern "C" fn(id: u64, point: *const Point) -> bool>,
+            raw: *mut c_void,
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "Sources/Backend/Backend.swift");
+    let expected = indoc!(
+        r#"import CBackend
+           import Foundation
+
+           /// Handle to a native `App`, freed with `app_free` when released.
+           public final class App {
+               public let handle: OpaquePointer
+
+               public init(handle: OpaquePointer) {
+                   self.handle = handle
+               }
+
+               deinit {
+                   CBackend.app_free(handle)
+               }
+           }
+
+           /// Opens an app.
+           ///
+           /// Returns null on failure.
+           public func appOpen(name: String, mode: Mode) -> App? {
+               return CBackend.app_open(name, mode).map(App.init(handle:))
+           }
+
+           public func appName(app: App) -> String? {
+               return CBackend.app_name(app.handle).map { String(cString: $0) }
+           }
+
+           public func appWrite(app: App, data: Data) -> Bool {
+               return data.withUnsafeBytes { data in
+                   return CBackend.app_write(app.handle, data.bindMemory(to: UInt8.self).baseAddress, UInt(data.count))
+               }
+           }
+
+           public func sum(values: [UInt32], out: UnsafeMutablePointer<UInt8>?, outLen: UInt, `in`: Int32) -> UInt64 {
+               return values.withUnsafeBufferPointer { values in
+                   return CBackend.sum(values.baseAddress, UInt(values.count), out, outLen, `in`)
+               }
+           }
+
+           public func nearest(points: [Point], to: Point) -> UInt {
+               return points.withUnsafeBufferPointer { points in
+                   return withUnsafePointer(to: to) { to in
+                       return CBackend.nearest(points.baseAddress, UInt(points.count), to)
+                   }
+               }
+           }
+
+           public func onEvent(cb: (@convention(c) (UInt64, UnsafePointer<Point>?) -> Bool)?, raw: UnsafeMutableRawPointer?) {
+               CBackend.on_event(cb, raw)
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_functions() {
+    let outputs = compile!(LangSwift::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[repr(C)]
+        pub struct Point {
+            x: f64,
+            y: f64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn app_free(app: *mut App) {}
+
+        /// Stores the data under the name.
+        #[no_mangle]
+        pub extern "C" fn store(
+            app: *const App,
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, id: u64),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+                ids: *const u64,
+                ids_len: usize,
+                point: *const Point,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn app_clone(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, app: *mut App),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "Sources/Backend/Backend.swift");
+    let expected = indoc!(
+        r#"import CBackend
+           import Foundation
+
+           public struct FfiError: Error {
+               public let code: Int32
+               public let description: String
+
+               init(_ result: FfiResult) {
+                   code = result.error_code
+                   description = result.description.map { String(cString: $0) } ?? ""
+               }
+           }
+
+           /// Continuation of an async call, passed to its callback as the user data.
+           private final class Completion<T> {
+               private let continuation: CheckedContinuation<T, Error>
+
+               init(_ continuation: CheckedContinuation<T, Error>) {
+                   self.continuation = continuation
+               }
+
+               /// Resumes the continuation passed as the user data, throwing if the result is an error.
+               static func resume(
+                   _ userData: UnsafeMutableRawPointer?,
+                   _ result: UnsafePointer<FfiResult>?,
+                   _ value: () -> T
+               ) {
+                   let completion = Unmanaged<Completion<T>>.fromOpaque(userData!).takeRetainedValue()
+                   if let result = result, result.pointee.error_code != 0 {
+                       completion.continuation.resume(throwing: FfiError(result.pointee))
+                   } else {
+                       completion.continuation.resume(returning: value())
+                   }
+               }
+           }
+
+           /// Handle to a native `App`, freed with `app_free` when released.
+           public final class App {
+               public let handle: OpaquePointer
+
+               public init(handle: OpaquePointer) {
+                   self.handle = handle
+               }
+
+               deinit {
+                   CBackend.app_free(handle)
+               }
+           }
+
+           /// Stores the data under the name.
+           public func store(app: App, name: String, data: Data) async throws -> UInt64 {
+               return try await withCheckedThrowingContinuation { (continuation: CheckedContinuation<UInt64, Error>) in
+                   let userData = Unmanaged.passRetained(Completion(continuation)).toOpaque()
+                   data.withUnsafeBytes { data in
+                       CBackend.store(app.handle, name, data.bindMemory(to: UInt8.self).baseAddress, UInt(data.count), userData, { userData, result, id in
+                           Completion<UInt64>.resume(userData, result) { id }
+                       })
+                   }
+               }
+           }
+
+           public func fetch(app: App) async throws -> (String, Data, [UInt64], Point) {
+               return try await withCheckedThrowingContinuation { (continuation: CheckedContinuation<(String, Data, [UInt64], Point), Error>) in
+                   let userData = Unmanaged.passRetained(Completion(continuation)).toOpaque()
+                   CBackend.fetch(app.handle, userData, { userData, result, name, data, dataLen, ids, idsLen, point in
+                       Completion<(String, Data, [UInt64], Point)>.resume(userData, result) { (name.map { String(cString: $0) } ?? "", data.map { Data(bytes: $0, count: Int(dataLen)) } ?? Data(), Array(UnsafeBufferPointer(start: ids, count: Int(idsLen))), point!.pointee) }
+                   })
+               }
+           }
+
+           public func appClone(app: App) async throws -> App {
+               return try await withCheckedThrowingContinuation { (continuation: CheckedContinuation<App, Error>) in
+                   let userData = Unmanaged.passRetained(Completion(continuation)).toOpaque()
+                   CBackend.app_clone(app.handle, userData, { userData, result, app in
+                       Completion<App>.resume(userData, result) { App(handle: app!) }
+                   })
+               }
+           }
+
+           public func flush(app: App) async throws {
+               return try await withCheckedThrowingContinuation { (continuation: CheckedContinuation<Void, Error>) in
+                   let userData = Unmanaged.passRetained(Completion(continuation)).toOpaque()
+                   CBackend.flush(app.handle, userData, { userData, result in
+                       Completion<Void>.resume(userData, result) { () }
+                   })
+               }
+           }
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_units() {
+    let mut lang = LangSwift::default();
+    lang.add_provided_unit("ffi_utils", "common");
+
+    let mut outputs = Outputs::default();
+    let ffi_result: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct FfiResult { error_code: i32, description: *const c_char }"
+    ));
+    let get: syn::ItemFn = unwrap!(syn::parse_str(
+        "#[no_mangle] pub extern \"C\" fn get(result: *const FfiResult) -> u64 {}"
+    ));
+
+    unwrap!(lang.parse_struct(&ffi_result, &["ffi_utils".to_string()], &mut outputs));
+    unwrap!(lang.parse_fn(&get, &["ffi".to_string()], &mut outputs));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    let header = fetch(&outputs, "Sources/CBackend/backend.h");
+    assert!(header.contains("#include \"../../../common/Sources/CCommon/common.h\"\n"));
+    assert!(!header.contains("typedef struct FfiResult {"));
+
+    let actual = fetch(&outputs, "Package.swift");
+    let expected = indoc!(
+        r#"// swift-tools-version:5.5
+           import PackageDescription
+
+           let package = Package(
+               name: "Backend",
+               products: [
+                   .library(name: "Backend", targets: ["Backend"]),
+               ],
+               dependencies: [
+                   .package(path: "../common"),
+               ],
+               targets: [
+                   .systemLibrary(name: "CBackend"),
+                   .target(name: "Backend", dependencies: ["CBackend", .product(name: "Common", package: "common")]),
+               ]
+           )
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "Sources/Backend/Backend.swift");
+    let expected = indoc!(
+        r#"import CBackend
+           import CCommon
+           import Foundation
+
+           public func get(result: UnsafePointer<FfiResult>?) -> UInt64 {
+               return CBackend.get(result)
+           }
+           "#
+    );
+    assert_multiline_eq!(actual, expected);
+}
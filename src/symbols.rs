@@ -0,0 +1,530 @@
+//! Symbol table of the types declared in the parsed crate.
+//!
+//! Target languages refer to types by their declared names only, so type paths written with
+//! module qualifiers (`crate::ffi::Foo`, `super::Bar`) or through `use` aliases
+//! (`use foo::Bar as Baz;`) are rewritten to the names of the declarations they refer to.
+//!
+//! Types of dependency crates keep the crate name (`ffi_utils::FfiResult`), so that they can be
+//! told apart from the crate's own types until the used ones are selected (see `unqualify_file`).
+//!
+//! Type aliases of generic structs (`pub type U8Array = FfiArray<u8>;`) are replaced with
+//! concrete structs, as generic structs can't be declared in the target languages.
+
+use crate::parse;
+use std::collections::{HashMap, HashSet};
+use unwrap::unwrap;
+
+/// Limits how many `use` declarations are followed when resolving a path, which also guards
+/// against import cycles.
+const MAX_DEPTH: usize = 8;
+
+/// Crates whose qualified paths are understood by the target languages themselves.
+const KNOWN_CRATES: &[&str] = &["libc", "std", "core"];
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    /// Full paths (e.g. `["ffi", "Foo"]`) of the types declared in the crate.
+    types: HashSet<Vec<String>>,
+    /// Names imported into each module, mapped to the paths they were imported from.
+    imports: HashMap<Vec<String>, HashMap<String, Vec<String>>>,
+    /// Modules glob-imported (`use foo::*;`) into each module.
+    globs: HashMap<Vec<String>, Vec<Vec<String>>>,
+    /// Generic structs declared in the crate, by full path.
+    generics: HashMap<Vec<String>, syn::ItemStruct>,
+    /// Paths of the types that type aliases refer to, along with the modules of the aliases.
+    aliases: Vec<(Vec<String>, Vec<String>)>,
+    /// Names of the dependency crates.
+    crates: HashSet<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Collects the type declarations and imports of a file which contains the crate module
+    /// `module` (empty for the crate root).
+    pub fn add_file(&mut self, module: &[String], file: &syn::File) {
+        self.add_items(&[], module, &file.items);
+    }
+
+    /// Collects the declarations of a file which contains the module `module` of the dependency
+    /// crate `krate`, making them available under the `krate::` path.
+    pub fn add_dependency_file(&mut self, krate: &str, module: &[String], file: &syn::File) {
+        let root = [krate.to_string()];
+        self.crates.insert(krate.to_string());
+        self.add_items(&root, &[&root, module].concat(), &file.items);
+    }
+
+    /// `root` is the path that `crate::` refers to in the imports.
+    fn add_items(&mut self, root: &[String], module: &[String], items: &[syn::Item]) {
+        for item in items {
+            let ident = match *item {
+                syn::Item::Struct(ref item) => {
+                    if item.generics.type_params().next().is_some() {
+                        self.generics
+                            .insert(child(module, &item.ident), item.clone());
+                    }
+                    &item.ident
+                }
+                syn::Item::Enum(ref item) => &item.ident,
+                syn::Item::Union(ref item) => &item.ident,
+                syn::Item::Type(ref item) => {
+                    if let Some(path) = alias_path(item) {
+                        self.aliases.push((module.to_vec(), path));
+                    }
+                    &item.ident
+                }
+                syn::Item::Use(ref item) => {
+                    self.add_use_tree(root, module, Vec::new(), &item.tree);
+                    continue;
+                }
+                syn::Item::Mod(syn::ItemMod {
+                    ref ident,
+                    content: Some((_, ref items)),
+                    ..
+                }) => {
+                    self.add_items(root, &child(module, ident), items);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            self.types.insert(child(module, ident));
+        }
+    }
+
+    fn add_use_tree(
+        &mut self,
+        root: &[String],
+        module: &[String],
+        mut prefix: Vec<String>,
+        tree: &syn::UseTree,
+    ) {
+        let (name, ident) = match *tree {
+            syn::UseTree::Path(ref path) => {
+                if prefix.is_empty() && path.ident == "crate" && !root.is_empty() {
+                    prefix.extend_from_slice(root);
+                } else {
+                    prefix.push(path.ident.to_string());
+                }
+                return self.add_use_tree(root, module, prefix, &path.tree);
+            }
+            syn::UseTree::Group(ref group) => {
+                for tree in &group.items {
+                    self.add_use_tree(root, module, prefix.clone(), tree);
+                }
+                return;
+            }
+            syn::UseTree::Glob(..) => {
+                self.globs.entry(module.to_vec()).or_default().push(prefix);
+                return;
+            }
+            syn::UseTree::Name(ref name) => (&name.ident, &name.ident),
+            syn::UseTree::Rename(ref rename) => (&rename.rename, &rename.ident),
+        };
+
+        // `use foo::{self}` imports `foo` itself.
+        let name = if *name == "self" {
+            match prefix.last() {
+                Some(last) => last.clone(),
+                None => return,
+            }
+        } else {
+            name.to_string()
+        };
+        if *ident != "self" {
+            prefix.push(ident.to_string());
+        }
+
+        self.imports
+            .entry(module.to_vec())
+            .or_default()
+            .insert(name, prefix);
+    }
+
+    /// Resolves `path`, used in the crate module `module`, to the full path of the type it
+    /// refers to. Returns `None` if the type isn't declared in the crate.
+    pub fn resolve(&self, module: &[String], path: &[String]) -> Option<Vec<String>> {
+        self.resolve_at_depth(module, path, 0)
+    }
+
+    fn resolve_at_depth(
+        &self,
+        module: &[String],
+        path: &[String],
+        depth: usize,
+    ) -> Option<Vec<String>> {
+        if depth > MAX_DEPTH || path.is_empty() {
+            return None;
+        }
+
+        match path[0].as_str() {
+            "crate" => self.find(&path[1..], depth),
+            "self" => self.find(&[module, &path[1..]].concat(), depth),
+            "super" => {
+                let mut base = module.to_vec();
+                let mut rest = path;
+                while rest.first().map(String::as_str) == Some("super") {
+                    base.pop()?;
+                    rest = &rest[1..];
+                }
+                self.find(&[&base, rest].concat(), depth)
+            }
+            first => {
+                if let Some(import) = self.import(module, first) {
+                    let path = [import, &path[1..]].concat();
+                    return self.resolve_at_depth(module, &path, depth + 1);
+                }
+
+                // Items of the module itself, then paths relative to the crate root (as in
+                // `use` declarations of the 2015 edition).
+                self.find(&[module, path].concat(), depth)
+                    .or_else(|| self.find(path, depth))
+            }
+        }
+    }
+
+    /// Finds the declaration of the type at the full path `path`, following re-exports.
+    fn find(&self, path: &[String], depth: usize) -> Option<Vec<String>> {
+        if self.types.contains(path) {
+            return Some(path.to_vec());
+        }
+
+        let (name, module) = path.split_last()?;
+        if let Some(import) = self.import(module, name) {
+            return self.resolve_at_depth(module, import, depth + 1);
+        }
+
+        self.globs.get(module).and_then(|globs| {
+            globs
+                .iter()
+                .find_map(|glob| self.resolve_at_depth(module, &child(glob, name), depth + 1))
+        })
+    }
+
+    /// Returns the path a name was imported from, ignoring imports of the name itself
+    /// (`use foo;`) which can't be resolved any further.
+    fn import(&self, module: &[String], name: &str) -> Option<&[String]> {
+        self.imports
+            .get(module)
+            .and_then(|imports| imports.get(name))
+            .map(Vec::as_slice)
+            .filter(|import| *import != [name])
+    }
+
+    /// Rewrites the type paths in a file which contains the crate module `module` to the
+    /// declared names of the types they refer to.
+    pub fn resolve_file(&self, module: &[String], file: &mut syn::File) {
+        self.resolve_items(module, &mut file.items);
+    }
+
+    fn resolve_items(&self, module: &[String], items: &mut Vec<syn::Item>) {
+        // Generic structs are replaced by the aliases instantiating them, while the remaining
+        // ones are left for the target language to reject.
+        items.retain(|item| match *item {
+            syn::Item::Struct(ref item) => !self.is_instantiated(&child(module, &item.ident)),
+            _ => true,
+        });
+
+        for item in items.iter_mut() {
+            let concrete = match *item {
+                syn::Item::Type(ref alias) => self.instantiate(module, alias),
+                _ => None,
+            };
+            if let Some(concrete) = concrete {
+                *item = syn::Item::Struct(concrete);
+                continue;
+            }
+
+            if let syn::Item::Mod(syn::ItemMod {
+                ref ident,
+                content: Some((_, ref mut items)),
+                ..
+            }) = *item
+            {
+                self.resolve_items(&child(module, ident), items);
+                continue;
+            }
+            for ty in item_types(item) {
+                self.resolve_type(module, ty);
+            }
+        }
+    }
+
+    /// Returns whether the generic struct at the full path `path` is instantiated by an alias.
+    fn is_instantiated(&self, path: &[String]) -> bool {
+        self.generics.contains_key(path)
+            && self
+                .aliases
+                .iter()
+                .any(|(module, alias)| self.resolve(module, alias).as_deref() == Some(path))
+    }
+
+    /// Turns `pub type Alias = Generic<A, B>;`, declared in `module`, into a struct named
+    /// `Alias` with resolved field types. Returns `None` if the alias doesn't refer to a generic
+    /// struct or doesn't provide a concrete type for each of its type parameters.
+    fn instantiate(&self, module: &[String], alias: &syn::ItemType) -> Option<syn::ItemStruct> {
+        let path = self.resolve(module, &alias_path(alias)?)?;
+        let mut generic = self.generics.get(&path)?.clone();
+
+        // The fields of the generic struct refer to the types of its own module.
+        let (_, generic_module) = path.split_last()?;
+        for field in generic.fields.iter_mut() {
+            self.resolve_type(generic_module, &mut field.ty);
+        }
+        let mut alias = alias.clone();
+        self.resolve_type(module, &mut alias.ty);
+
+        parse::instantiate(&alias, &generic)
+    }
+
+    fn resolve_type(&self, module: &[String], ty: &mut syn::Type) {
+        visit_paths(ty, &mut |path| self.resolve_path(module, path));
+    }
+
+    /// Replaces `path` with the declared name of the type it refers to, prefixed with the crate
+    /// name for the types of dependency crates. Types from other crates are referred to by their
+    /// names too, except for the crates in `KNOWN_CRATES`.
+    fn resolve_path(&self, module: &[String], path: &mut syn::Path) {
+        let segments: Vec<_> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+
+        let name = if let Some(resolved) = self.resolve(module, &segments) {
+            if self.crates.contains(&resolved[0]) {
+                let mut segment = unwrap!(path.segments.pop()).into_value();
+                segment.ident = syn::Ident::new(unwrap!(resolved.last()), segment.ident.span());
+                let krate = syn::Ident::new(&resolved[0], segment.ident.span());
+                path.leading_colon = None;
+                path.segments = vec![syn::PathSegment::from(krate), segment]
+                    .into_iter()
+                    .collect();
+                return;
+            }
+            resolved.last().cloned()
+        } else if let Some(import) = self.import(module, &segments[0]) {
+            // Types imported from other crates, e.g. `use libc::c_char as Char;`.
+            let imported = [import, &segments[1..]].concat();
+            if imported.last() == segments.last() {
+                None
+            } else if KNOWN_CRATES.contains(&imported[0].as_str()) {
+                let mut resolved: syn::Path = unwrap!(syn::parse_str(&imported.join("::")));
+                let last = unwrap!(path.segments.pop()).into_value();
+                unwrap!(resolved.segments.last_mut()).into_value().arguments = last.arguments;
+                *path = resolved;
+                return;
+            } else {
+                imported.last().cloned()
+            }
+        } else if segments.len() > 1 && !KNOWN_CRATES.contains(&segments[0].as_str()) {
+            segments.last().cloned()
+        } else {
+            None
+        };
+
+        if let Some(name) = name {
+            let mut segment = unwrap!(path.segments.pop()).into_value();
+            segment.ident = syn::Ident::new(&name, segment.ident.span());
+            path.leading_colon = None;
+            path.segments = Some(segment).into_iter().collect();
+        }
+    }
+}
+
+/// Drops the crate names from the paths of the types of dependency crates in a resolved file,
+/// once the types used from the dependencies have been selected.
+pub fn unqualify_file(file: &mut syn::File) {
+    unqualify_items(&mut file.items);
+}
+
+fn unqualify_items(items: &mut [syn::Item]) {
+    for item in items {
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, ref mut items)),
+            ..
+        }) = *item
+        {
+            unqualify_items(items);
+            continue;
+        }
+        for ty in item_types(item) {
+            visit_paths(ty, &mut |path| {
+                // Paths of the known crates are the only other ones left with several segments
+                if path.segments.len() > 1
+                    && !KNOWN_CRATES.contains(&path.segments[0].ident.to_string().as_str())
+                {
+                    let segment = unwrap!(path.segments.pop()).into_value();
+                    path.segments = Some(segment).into_iter().collect();
+                }
+            });
+        }
+    }
+}
+
+/// Returns the types an item refers to in its declaration, except for modules.
+fn item_types(item: &mut syn::Item) -> Vec<&mut syn::Type> {
+    match *item {
+        syn::Item::Struct(ref mut item) => {
+            item.fields.iter_mut().map(|field| &mut field.ty).collect()
+        }
+        syn::Item::Union(ref mut item) => item
+            .fields
+            .named
+            .iter_mut()
+            .map(|field| &mut field.ty)
+            .collect(),
+        syn::Item::Enum(ref mut item) => item
+            .variants
+            .iter_mut()
+            .flat_map(|variant| variant.fields.iter_mut().map(|field| &mut field.ty))
+            .collect(),
+        syn::Item::Type(ref mut item) => vec![&mut *item.ty],
+        syn::Item::Const(ref mut item) => vec![&mut *item.ty],
+        syn::Item::Static(ref mut item) => vec![&mut *item.ty],
+        syn::Item::Fn(ref mut item) => {
+            let decl = &mut *item.decl;
+            let mut types: Vec<_> = decl
+                .inputs
+                .iter_mut()
+                .filter_map(|input| match *input {
+                    syn::FnArg::Captured(ref mut arg) => Some(&mut arg.ty),
+                    _ => None,
+                })
+                .collect();
+            if let syn::ReturnType::Type(_, ref mut output) = decl.output {
+                types.push(&mut **output);
+            }
+            types
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Calls `f` with the paths within `ty`, the paths of type arguments first.
+fn visit_paths(ty: &mut syn::Type, f: &mut dyn FnMut(&mut syn::Path)) {
+    match *ty {
+        syn::Type::Path(ref mut path) if path.qself.is_none() => {
+            for segment in path.path.segments.iter_mut() {
+                if let syn::PathArguments::AngleBracketed(ref mut args) = segment.arguments {
+                    for arg in args.args.iter_mut() {
+                        if let syn::GenericArgument::Type(ref mut ty) = *arg {
+                            visit_paths(ty, f);
+                        }
+                    }
+                }
+            }
+            f(&mut path.path);
+        }
+        syn::Type::Ptr(ref mut ptr) => visit_paths(&mut ptr.elem, f),
+        syn::Type::Reference(ref mut reference) => visit_paths(&mut reference.elem, f),
+        syn::Type::Array(ref mut array) => visit_paths(&mut array.elem, f),
+        syn::Type::Slice(ref mut slice) => visit_paths(&mut slice.elem, f),
+        syn::Type::Paren(ref mut paren) => visit_paths(&mut paren.elem, f),
+        syn::Type::Tuple(ref mut tuple) => {
+            for elem in tuple.elems.iter_mut() {
+                visit_paths(elem, f);
+            }
+        }
+        syn::Type::BareFn(ref mut bare_fn) => {
+            for input in bare_fn.inputs.iter_mut() {
+                visit_paths(&mut input.ty, f);
+            }
+            if let syn::ReturnType::Type(_, ref mut output) = bare_fn.output {
+                visit_paths(output, f);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Returns the path of the type that an alias refers to, if it's a plain path.
+fn alias_path(alias: &syn::ItemType) -> Option<Vec<String>> {
+    match *alias.ty {
+        syn::Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) => Some(
+            path.segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn child(module: &[String], ident: impl ToString) -> Vec<String> {
+    let mut path = module.to_vec();
+    path.push(ident.to_string());
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Vec<String> {
+        path.split("::").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let mut symbols = SymbolTable::new();
+        symbols.add_file(
+            &path("ffi"),
+            &unwrap!(syn::parse_str(
+                "pub use self::inner::Handle as PublicHandle;
+                 use super::other::*;
+                 pub mod inner {
+                     pub struct Handle;
+                     pub type Alias = super::PublicHandle;
+                 }"
+            )),
+        );
+        symbols.add_file(
+            &path("other"),
+            &unwrap!(syn::parse_str("pub enum Kind { A, B }")),
+        );
+
+        let ffi = path("ffi");
+        let inner = path("ffi::inner");
+
+        assert_eq!(
+            symbols.resolve(&ffi, &path("PublicHandle")),
+            Some(path("ffi::inner::Handle"))
+        );
+        assert_eq!(
+            symbols.resolve(&inner, &path("super::PublicHandle")),
+            Some(path("ffi::inner::Handle"))
+        );
+        assert_eq!(
+            symbols.resolve(&inner, &path("crate::ffi::inner::Alias")),
+            Some(path("ffi::inner::Alias"))
+        );
+        assert_eq!(
+            symbols.resolve(&ffi, &path("Kind")),
+            Some(path("other::Kind"))
+        );
+        assert_eq!(symbols.resolve(&ffi, &path("ffi_utils::FfiResult")), None);
+        assert_eq!(symbols.resolve(&ffi, &path("u8")), None);
+
+        symbols.add_dependency_file(
+            "ffi_utils",
+            &[],
+            &unwrap!(syn::parse_str("pub use crate::repr_c::FfiResult;")),
+        );
+        symbols.add_dependency_file(
+            "ffi_utils",
+            &path("repr_c"),
+            &unwrap!(syn::parse_str("pub struct FfiResult;")),
+        );
+
+        assert_eq!(
+            symbols.resolve(&ffi, &path("ffi_utils::FfiResult")),
+            Some(path("ffi_utils::repr_c::FfiResult"))
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use crate::common::{Lang, Outputs};
+use crate::symbols::SymbolTable;
 use crate::{parse, Error};
 use colored::*;
 use diff;
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
 use syn;
 use unwrap::unwrap;
 
@@ -53,7 +56,10 @@ pub fn try_compile(
     mut lang: impl Lang,
     rust_src: String,
 ) -> Result<HashMap<String, String>, Vec<Error>> {
-    let ast: syn::File = unwrap!(syn::parse_str(&rust_src));
+    let mut ast: syn::File = unwrap!(syn::parse_str(&rust_src));
+    let mut symbols = SymbolTable::new();
+    symbols.add_file(&[], &ast);
+    symbols.resolve_file(&[], &mut ast);
     let mut outputs = Outputs::default();
     parse::parse_file(&mut lang, &ast, &[Default::default()], &mut outputs)?;
     lang.finalise_output(&mut outputs)?;
@@ -61,6 +67,18 @@ pub fn try_compile(
     Ok(outputs)
 }
 
+/// Write the source files of a crate into a temporary directory and return the path to its root
+/// source file (the first one).
+pub fn write_crate(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join("safe_bindgen_tests").join(name);
+    for (path, contents) in files {
+        let path = dir.join(path);
+        unwrap!(fs::create_dir_all(unwrap!(path.parent())));
+        unwrap!(fs::write(path, contents));
+    }
+    dir.join(files[0].0)
+}
+
 pub fn format_diff(left: &str, right: &str) -> String {
     let mut output = String::new();
 
@@ -0,0 +1,746 @@
+//! Utilities for emiting fragments of the target language code.
+use super::Context;
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use inflector::Inflector;
+use std::fmt::Write;
+use unwrap::unwrap;
+
+/// JavaScript reserved words which can be used as identifiers in Rust, along with the names of
+/// the module-level bindings and of the local variables of the wrapper functions.
+const KEYWORDS: &[&str] = &[
+    "arguments",
+    "case",
+    "catch",
+    "class",
+    "debugger",
+    "default",
+    "delete",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "implements",
+    "import",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "switch",
+    "this",
+    "throw",
+    "typeof",
+    "var",
+    "void",
+    "with",
+    "yield",
+    "callback",
+    "error",
+    "koffi",
+    "lib",
+    "native",
+    "reject",
+    "resolve",
+    "types",
+];
+
+/// The name the wrapper of the function is exported as.
+pub fn function_name(name: &str) -> String {
+    escape_keyword(&name.to_camel_case(), KEYWORDS)
+}
+
+/// Emits the loading of the native library.
+pub fn emit_load(writer: &mut IndentedWriter, lib_name: &str) {
+    emitln!(writer, "\nfunction libraryName() {{");
+    emitln!(writer, "  switch (process.platform) {{");
+    emitln!(writer, "    case 'win32':");
+    emitln!(writer, "      return '{}.dll';", lib_name);
+    emitln!(writer, "    case 'darwin':");
+    emitln!(writer, "      return 'lib{}.dylib';", lib_name);
+    emitln!(writer, "    default:");
+    emitln!(writer, "      return 'lib{}.so';", lib_name);
+    emitln!(writer, "  }}");
+    emitln!(writer, "}}\n");
+    emitln!(
+        writer,
+        "const lib = koffi.load(process.env.{}_LIBRARY_PATH || libraryName());",
+        lib_name.to_screaming_snake_case()
+    );
+}
+
+pub fn emit_const(writer: &mut IndentedWriter, name: &str, item: &Const) {
+    emitln!(writer, "const {} = {};", name, const_value(&item.value));
+}
+
+pub fn emit_enum(writer: &mut IndentedWriter, name: &str, item: &Enum) {
+    emitln!(writer, "\nconst {} = Object.freeze({{", name);
+    writer.indent();
+    for (variant, value) in variant_values(item) {
+        emitln!(writer, "{}: {},", variant.name, value);
+    }
+    writer.unindent();
+    emitln!(writer, "}});");
+}
+
+pub fn emit_struct(writer: &mut IndentedWriter, context: &Context, name: &str, item: &Struct) {
+    let constructor = if item.union {
+        "union"
+    } else if item.pack.is_some() {
+        "pack"
+    } else {
+        "struct"
+    };
+
+    for field in &item.fields {
+        if let Type::Function(ref fun) = field.ty {
+            emit_protos(writer, context, &proto_name(name, &field.name, 0), fun);
+        }
+    }
+
+    emitln!(
+        writer,
+        "\nconst {0} = koffi.{1}('{0}', {{",
+        name,
+        constructor
+    );
+    writer.indent();
+    for field in &item.fields {
+        emitln!(
+            writer,
+            "{}: {},",
+            field.name,
+            koffi_type(context, &field.ty, &proto_name(name, &field.name, 0))
+        );
+    }
+    writer.unindent();
+    emitln!(writer, "}});");
+}
+
+/// Emits the prototypes of the callbacks and the declarations of the native functions.
+pub fn emit_native_functions(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    functions: &[Snippet<Function>],
+) {
+    let owners: Vec<_> = functions
+        .iter()
+        .map(|snippet| snippet.name.to_pascal_case())
+        .collect();
+
+    for (snippet, owner) in functions.iter().zip(&owners) {
+        for (index, (name, ty)) in snippet.item.inputs.iter().enumerate() {
+            if let Type::Function(ref fun) = *ty {
+                emit_protos(writer, context, &proto_name(owner, name, index), fun);
+            }
+        }
+    }
+
+    emitln!(writer, "\nconst native = {{");
+    writer.indent();
+    for (snippet, owner) in functions.iter().zip(&owners) {
+        emitln!(
+            writer,
+            "{}: lib.func('{}', {}, [{}]),",
+            function_name(&snippet.name),
+            snippet.name,
+            koffi_type(context, &snippet.item.output, &format!("{}Output", owner)),
+            native_params(context, owner, &snippet.item).join(", ")
+        );
+    }
+    writer.unindent();
+    emitln!(writer, "}};");
+}
+
+/// Emits the prototype of the callback, after those of the callbacks it takes.
+fn emit_protos(writer: &mut IndentedWriter, context: &Context, name: &str, fun: &Function) {
+    for (index, (param, ty)) in fun.inputs.iter().enumerate() {
+        if let Type::Function(ref fun) = *ty {
+            emit_protos(writer, context, &proto_name(name, param, index), fun);
+        }
+    }
+
+    emitln!(
+        writer,
+        "\nconst {0} = koffi.proto('{0}', {1}, [{2}]);",
+        name,
+        koffi_type(context, &fun.output, &format!("{}Output", name)),
+        native_params(context, name, fun).join(", ")
+    );
+}
+
+/// The koffi types of the parameters, with dynamic arrays passed as pointer and length.
+fn native_params(context: &Context, owner: &str, fun: &Function) -> Vec<String> {
+    let mut params = Vec::with_capacity(fun.inputs.len());
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        let proto = proto_name(owner, name, index);
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                let pointer = Type::Pointer(elem.clone());
+                params.push(koffi_type(context, &pointer, &proto));
+                params.push("'uintptr_t'".to_string());
+            }
+            ref ty => params.push(koffi_type(context, ty, &proto)),
+        }
+    }
+    params
+}
+
+/// Emits the error class and the helpers of the promise-returning wrappers used by `code`.
+pub fn emit_utilities(writer: &mut IndentedWriter, code: &str) {
+    emitln!(
+        writer,
+        r#"
+class FfiError extends Error {{
+  constructor(code, description) {{
+    super(description);
+    this.name = 'FfiError';
+    this.code = code;
+  }}
+}}
+
+function resultError(result) {{
+  if (result === null) {{
+    return null;
+  }}
+  const {{ error_code: code, description }} = koffi.decode(result, FfiResult);
+  return code === 0 ? null : new FfiError(code, description ?? '');
+}}"#
+    );
+
+    if code.contains("decodeBytes(") {
+        emitln!(
+            writer,
+            r#"
+function decodeBytes(pointer, length) {{
+  if (pointer === null || length === 0) {{
+    return new Uint8Array(0);
+  }}
+  return koffi.decode(pointer, koffi.array('uint8_t', length, 'Typed'));
+}}"#
+        );
+    }
+
+    if code.contains("decodeArray(") {
+        emitln!(
+            writer,
+            r#"
+function decodeArray(pointer, type, length) {{
+  if (pointer === null || length === 0) {{
+    return [];
+  }}
+  return koffi.decode(pointer, koffi.array(type, length, 'Array'));
+}}"#
+        );
+    }
+}
+
+pub fn emit_function(writer: &mut IndentedWriter, context: &Context, name: &str, fun: &Function) {
+    let callback = async_callback(fun, |name| context.is_declared(name));
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        if callback.is_some() {
+            if is_user_data(name, ty) {
+                args.push("null".to_string());
+                continue;
+            }
+            if extract_callback(ty).is_some() {
+                args.push("callback".to_string());
+                continue;
+            }
+        }
+
+        let param = param_name(&name.to_camel_case(), index, KEYWORDS);
+        if ty.is_dynamic_array() {
+            args.push(param.clone());
+            args.push(format!("{}.length", param));
+        } else {
+            args.push(param.clone());
+        }
+        params.push(param);
+    }
+
+    let call = format!("native.{}({})", function_name(name), args.join(", "));
+
+    emitln!(
+        writer,
+        "\nfunction {}({}) {{",
+        function_name(name),
+        params.join(", ")
+    );
+    writer.indent();
+
+    match callback {
+        Some(callback) => {
+            let (index, (param, _)) = unwrap!(fun
+                .inputs
+                .iter()
+                .enumerate()
+                .find(|(_, (_, ty))| extract_callback(ty).is_some()));
+            let proto = proto_name(&name.to_pascal_case(), param, index);
+            let closure_params: Vec<_> = flatten_params(callback)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+
+            emitln!(writer, "return new Promise((resolve, reject) => {{");
+            writer.indent();
+            emitln!(
+                writer,
+                "const callback = koffi.register(({}) => {{",
+                closure_params.join(", ")
+            );
+            writer.indent();
+            emitln!(writer, "koffi.unregister(callback);");
+            emitln!(
+                writer,
+                "const error = resultError({});",
+                param_name(&callback.inputs[1].0.to_camel_case(), 1, KEYWORDS)
+            );
+            emitln!(writer, "if (error !== null) {{");
+            emitln!(writer, "  reject(error);");
+            emitln!(writer, "}} else {{");
+            emitln!(writer, "  resolve({});", callback_value(context, callback));
+            emitln!(writer, "}}");
+            writer.unindent();
+            emitln!(writer, "}}, koffi.pointer({}));", proto);
+            emitln!(writer, "{};", call);
+            writer.unindent();
+            emitln!(writer, "}});");
+        }
+        None => match fun.output {
+            Type::Unit => emitln!(writer, "{};", call),
+            _ => emitln!(writer, "return {};", call),
+        },
+    }
+
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+/// The value the promise is resolved with, converted from the callback parameters.
+fn callback_value(context: &Context, callback: &Function) -> String {
+    let mut values = Vec::new();
+
+    for (index, (name, ty)) in callback.inputs.iter().enumerate().skip(2) {
+        let param = param_name(&name.to_camel_case(), index, KEYWORDS);
+        let value = match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                let len = len_name(name, index);
+                match **elem {
+                    Type::U8 => format!("decodeBytes({}, {})", param, len),
+                    ref elem => format!(
+                        "decodeArray({}, {}, {})",
+                        param,
+                        koffi_type(context, elem, ""),
+                        len
+                    ),
+                }
+            }
+            Type::String => format!("{} ?? ''", param),
+            Type::Pointer(ref ty) => match **ty {
+                Type::User(ref name) if context.is_struct(name) => {
+                    format!("{0} === null ? null : koffi.decode({0}, {1})", param, name)
+                }
+                _ => param,
+            },
+            _ => param,
+        };
+        values.push(value);
+    }
+
+    match values.len() {
+        0 => String::new(),
+        1 => values.remove(0),
+        _ => format!("[{}]", values.join(", ")),
+    }
+}
+
+pub fn emit_docs(writer: &mut IndentedWriter, docs: &str) {
+    let lines: Vec<_> = docs
+        .split("///")
+        .skip(1)
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect();
+
+    match lines.len() {
+        0 => (),
+        1 => emitln!(writer, "/** {} */", lines[0]),
+        _ => {
+            emitln!(writer, "/**");
+            for line in lines {
+                if line.is_empty() {
+                    emitln!(writer, " *");
+                } else {
+                    emitln!(writer, " * {}", line);
+                }
+            }
+            emitln!(writer, " */");
+        }
+    }
+}
+
+pub fn emit_const_declaration(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Const) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "export declare const {}: {};",
+        name,
+        const_type(&item.value)
+    );
+}
+
+pub fn emit_enum_declaration(writer: &mut IndentedWriter, name: &str, docs: &str, item: &Enum) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "export declare const {}: {{", name);
+    writer.indent();
+    for (variant, value) in variant_values(item) {
+        emit_docs(writer, &variant.docs);
+        emitln!(writer, "readonly {}: {};", variant.name, value);
+    }
+    writer.unindent();
+    emitln!(writer, "}};");
+    emitln!(
+        writer,
+        "export type {0} = (typeof {0})[keyof typeof {0}];",
+        name
+    );
+}
+
+pub fn emit_struct_declaration(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    item: &Struct,
+) {
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(writer, "export interface {} {{", name);
+    writer.indent();
+    for field in &item.fields {
+        emit_docs(writer, &field.docs);
+        emitln!(writer, "{}: {};", field.name, ts_type(context, &field.ty));
+    }
+    writer.unindent();
+    emitln!(writer, "}}");
+}
+
+pub fn emit_error_declaration(writer: &mut IndentedWriter) {
+    emitln!(writer, "\nexport declare class FfiError extends Error {{");
+    emitln!(writer, "  readonly code: number;");
+    emitln!(writer, "}}");
+}
+
+pub fn emit_function_declaration(
+    writer: &mut IndentedWriter,
+    context: &Context,
+    name: &str,
+    docs: &str,
+    fun: &Function,
+) {
+    let callback = async_callback(fun, |name| context.is_declared(name));
+
+    let params: Vec<_> = fun
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, ty))| {
+            callback.is_none() || !(is_user_data(name, ty) || extract_callback(ty).is_some())
+        })
+        .map(|(index, (name, ty))| {
+            format!(
+                "{}: {}",
+                param_name(&name.to_camel_case(), index, KEYWORDS),
+                param_type(context, ty)
+            )
+        })
+        .collect();
+    let output = match callback {
+        Some(callback) => format!("Promise<{}>", callback_type(context, callback)),
+        None => ts_type(context, &fun.output),
+    };
+
+    emitln!(writer, "");
+    emit_docs(writer, docs);
+    emitln!(
+        writer,
+        "export declare function {}({}): {};",
+        function_name(name),
+        params.join(", "),
+        output
+    );
+}
+
+/// The type the promise is resolved with, with labeled elements if there are several values.
+fn callback_type(context: &Context, callback: &Function) -> String {
+    let mut values = Vec::new();
+
+    for (index, (name, ty)) in callback.inputs.iter().enumerate().skip(2) {
+        let ty = match *ty {
+            Type::String => "string".to_string(),
+            Type::Pointer(ref ty) => match **ty {
+                Type::User(ref name) if context.is_struct(name) => format!("{} | null", name),
+                _ => "Pointer | null".to_string(),
+            },
+            ref ty => param_type(context, ty),
+        };
+        values.push((param_name(&name.to_camel_case(), index, KEYWORDS), ty));
+    }
+
+    match values.len() {
+        0 => "void".to_string(),
+        1 => values.remove(0).1,
+        _ => {
+            let values: Vec<_> = values
+                .into_iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
+
+/// The parameters of the function, with dynamic arrays passed as pointer and length.
+fn flatten_params(fun: &Function) -> Vec<(String, Type)> {
+    let mut params = Vec::with_capacity(fun.inputs.len());
+    for (index, (name, ty)) in fun.inputs.iter().enumerate() {
+        match *ty {
+            Type::Array(ref elem, ArraySize::Dynamic) => {
+                params.push((
+                    param_name(&name.to_camel_case(), index, KEYWORDS),
+                    Type::Pointer(elem.clone()),
+                ));
+                params.push((len_name(name, index), Type::USize));
+            }
+            ref ty => params.push((
+                param_name(&name.to_camel_case(), index, KEYWORDS),
+                ty.clone(),
+            )),
+        }
+    }
+    params
+}
+
+/// The C type koffi parses for the Rust type, unless it has to be constructed.
+fn c_type(context: &Context, ty: &Type) -> Option<String> {
+    let name = match *ty {
+        Type::Unit => "void",
+        Type::Bool => "bool",
+        Type::Char => "uint32_t",
+        Type::CChar => "char",
+        Type::F32 => "float",
+        Type::F64 => "double",
+        Type::I8 => "int8_t",
+        Type::I16 => "int16_t",
+        Type::I32 => "int32_t",
+        Type::I64 => "int64_t",
+        Type::ISize => "intptr_t",
+        Type::U8 => "uint8_t",
+        Type::U16 => "uint16_t",
+        Type::U32 => "uint32_t",
+        Type::U64 => "uint64_t",
+        Type::USize => "uintptr_t",
+        Type::String => "const char *",
+        Type::Pointer(ref ty) => {
+            return c_type(context, ty).map(|name| {
+                if name.ends_with('*') {
+                    format!("{}*", name)
+                } else {
+                    format!("{} *", name)
+                }
+            });
+        }
+        Type::Array(..) | Type::Function(..) => return None,
+        Type::User(ref name) => match context.enums.get(name) {
+            Some(Some(ty)) => return c_type(context, ty),
+            // The size of C enums is that of an `int`.
+            Some(None) => "int",
+            None => name,
+        },
+    };
+    Some(name.to_string())
+}
+
+/// The koffi type of the Rust type, where functions refer to the prototype `proto`.
+fn koffi_type(context: &Context, ty: &Type, proto: &str) -> String {
+    if let Some(name) = c_type(context, ty) {
+        return format!("'{}'", name);
+    }
+
+    match *ty {
+        Type::Pointer(ref ty) | Type::Array(ref ty, ArraySize::Dynamic) => {
+            format!("koffi.pointer({})", koffi_type(context, ty, proto))
+        }
+        Type::Array(ref ty, ref size) => {
+            let size = match *size {
+                ArraySize::Lit(size) => size.to_string(),
+                ArraySize::Const(ref name) => name.clone(),
+                ArraySize::Dynamic => unreachable!(),
+            };
+            let hint = if let Type::U8 = **ty {
+                "Typed"
+            } else {
+                "Array"
+            };
+            format!(
+                "koffi.array({}, {}, '{}')",
+                koffi_type(context, ty, proto),
+                size,
+                hint
+            )
+        }
+        Type::Function(..) => format!("koffi.pointer({})", proto),
+        _ => unreachable!("the type has a C name"),
+    }
+}
+
+/// The TypeScript type of the values of the Rust type.
+fn ts_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Unit => "void".to_string(),
+        Type::Bool => "boolean".to_string(),
+        // koffi converts the 64-bit integers which don't fit a number to bigints.
+        Type::I64 | Type::U64 => "number | bigint".to_string(),
+        Type::Char
+        | Type::CChar
+        | Type::F32
+        | Type::F64
+        | Type::I8
+        | Type::I16
+        | Type::I32
+        | Type::ISize
+        | Type::U8
+        | Type::U16
+        | Type::U32
+        | Type::USize => "number".to_string(),
+        Type::String => "string | null".to_string(),
+        Type::Array(ref elem, _) => match **elem {
+            Type::U8 => "Uint8Array".to_string(),
+            ref elem => {
+                let elem = ts_type(context, elem);
+                if elem.contains(' ') {
+                    format!("({})[]", elem)
+                } else {
+                    format!("{}[]", elem)
+                }
+            }
+        },
+        Type::Pointer(..) | Type::Function(..) => "Pointer | null".to_string(),
+        Type::User(ref name) if context.is_declared(name) => name.clone(),
+        Type::User(..) => "Pointer".to_string(),
+    }
+}
+
+/// The TypeScript type of the arguments of the Rust type, which koffi converts from objects
+/// and functions too.
+fn param_type(context: &Context, ty: &Type) -> String {
+    match *ty {
+        Type::Pointer(ref ty) => match **ty {
+            Type::User(ref name) if context.is_struct(name) => {
+                format!("{} | Pointer | null", name)
+            }
+            _ => "Pointer | null".to_string(),
+        },
+        Type::Function(ref fun) => {
+            let params: Vec<_> = flatten_params(fun)
+                .into_iter()
+                .map(|(name, ty)| format!("{}: {}", name, ts_type(context, &ty)))
+                .collect();
+            format!(
+                "({}) => {}",
+                params.join(", "),
+                ts_type(context, &fun.output)
+            )
+        }
+        ref ty => ts_type(context, ty),
+    }
+}
+
+/// The variants of the enum with their values. Variants without a value follow the previous
+/// one, as in C.
+fn variant_values(item: &Enum) -> Vec<(&EnumVariant, i64)> {
+    let mut next = 0;
+    item.variants
+        .iter()
+        .map(|variant| {
+            let value = variant.value.unwrap_or(next);
+            next = value + 1;
+            (variant, value)
+        })
+        .collect()
+}
+
+/// The name of the prototype of the callback passed as the parameter of `owner`.
+fn proto_name(owner: &str, param: &str, index: usize) -> String {
+    if param.is_empty() {
+        format!("{}Arg{}", owner, index)
+    } else {
+        format!("{}{}", owner, param.to_pascal_case())
+    }
+}
+
+/// The JavaScript literal of the constant.
+fn const_value(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(value) => value.to_string(),
+        ConstValue::Char(value) => string_literal(&value.to_string()),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(ref value) => value
+            .trim_end_matches("f32")
+            .trim_end_matches("f64")
+            .to_string(),
+        ConstValue::String(ref value) => string_literal(value),
+        ConstValue::Array(ref elements) => {
+            let elements: Vec<_> = elements.iter().map(const_value).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+fn const_type(value: &ConstValue) -> String {
+    match *value {
+        ConstValue::Bool(..) => "boolean".to_string(),
+        ConstValue::Char(..) | ConstValue::String(..) => "string".to_string(),
+        ConstValue::Int(..) | ConstValue::Float(..) => "number".to_string(),
+        ConstValue::Array(ref elements) => match elements.first() {
+            Some(element) => format!("{}[]", const_type(element)),
+            None => "never[]".to_string(),
+        },
+        ConstValue::Struct(..) => unreachable!("struct constants are skipped"),
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("'");
+    for ch in value.chars() {
+        match ch {
+            '\\' => literal.push_str("\\\\"),
+            '\'' => literal.push_str("\\'"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('\'');
+    literal
+}
+
+fn len_name(name: &str, index: usize) -> String {
+    if name.is_empty() {
+        format!("arg{}Len", index)
+    } else {
+        format!("{}_len", name).to_camel_case()
+    }
+}
@@ -0,0 +1,740 @@
+//! Functions for converting Rust definitions into TypeScript bindings for Node.js and Electron.
+//!
+//! The bindings are a CommonJS module declaring the native types and functions with
+//! [koffi](https://koffi.dev), along with a `.d.ts` file typing its exports. The structs are
+//! converted to and from plain objects keyed by the names of their C fields.
+//!
+//! The native library is loaded from the path in the `<LIB>_LIBRARY_PATH` environment variable
+//! if set, or else looked up by its platform-specific file name.
+
+mod emit;
+#[cfg(test)]
+mod tests;
+
+use self::emit::*;
+use crate::common::{self, FilterMode, Lang, Outputs};
+use crate::intermediate::*;
+use crate::output::IndentedWriter;
+use crate::{Error, Level};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use unwrap::unwrap;
+
+const INDENT_WIDTH: usize = 2;
+
+pub struct LangTypeScript {
+    filter: HashSet<String>,
+    filter_mode: FilterMode,
+    lib_name: String,
+    consts: Vec<Snippet<Const>>,
+    enums: Vec<Snippet<Enum>>,
+    structs: Vec<Snippet<Struct>>,
+    functions: Vec<Snippet<Function>>,
+    aliases: HashMap<String, Type>,
+    /// Modules of the bindings of dependency crates, by crate name.
+    provided_packages: HashMap<String, String>,
+    /// Types declared in the bindings of dependency crates, with their modules.
+    provided_types: BTreeMap<String, String>,
+}
+
+pub struct Context {
+    /// Underlying integer types of the enums, if given by `#[repr(u8)]` etc.
+    enums: HashMap<String, Option<Type>>,
+    /// Names of the structs and unions.
+    structs: HashSet<String>,
+}
+
+impl Context {
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn is_struct(&self, name: &str) -> bool {
+        self.structs.contains(name)
+    }
+
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.is_enum(name) || self.is_struct(name)
+    }
+}
+
+impl LangTypeScript {
+    pub fn new() -> Self {
+        LangTypeScript {
+            filter: Default::default(),
+            filter_mode: FilterMode::Blacklist,
+            lib_name: "backend".to_string(),
+            consts: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+            functions: Vec::new(),
+            aliases: Default::default(),
+            provided_packages: Default::default(),
+            provided_types: Default::default(),
+        }
+    }
+
+    /// Set the name of the native library. This is also the name of the generated module.
+    pub fn set_lib_name<T: Into<String>>(&mut self, name: T) {
+        self.lib_name = name.into();
+    }
+
+    /// Declare that the types of the dependency crate `package` are generated into the module
+    /// `path` (e.g. `../ffi_utils/ffi_utils`) by its own bindings. They aren't generated again,
+    /// but required from it instead.
+    pub fn add_provided_package<P, M>(&mut self, package: P, path: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        let _ = self.provided_packages.insert(package.into(), path.into());
+    }
+
+    /// Clears the current filter and sets the filter mode.
+    pub fn reset_filter(&mut self, filter_mode: FilterMode) {
+        self.filter.clear();
+        self.filter_mode = filter_mode;
+    }
+
+    /// Add the identifier to the filter.
+    /// If the filter mode is `Blacklist` (the default), the identifiers in the
+    /// filter are ignored.
+    /// If it is `Whitelist`, the identifiers not in the filter are ignored.
+    pub fn filter<T: Into<String>>(&mut self, ident: T) {
+        let _ = self.filter.insert(ident.into());
+    }
+
+    fn is_ignored(&self, ident: &str) -> bool {
+        match self.filter_mode {
+            FilterMode::Blacklist => self.filter.contains(ident),
+            FilterMode::Whitelist => !self.filter.contains(ident),
+        }
+    }
+
+    /// Record the type `name` as provided if it's declared in a provided package.
+    fn add_if_provided(&mut self, name: &str, module: &[String]) {
+        if let Some(path) = module
+            .first()
+            .and_then(|package| self.provided_packages.get(package))
+        {
+            let _ = self.provided_types.insert(name.to_string(), path.clone());
+        }
+    }
+
+    fn resolve_aliases(&mut self) {
+        for snippet in &mut self.consts {
+            resolve_alias(&self.aliases, &mut snippet.item.ty);
+        }
+
+        for snippet in &mut self.structs {
+            for field in &mut snippet.item.fields {
+                resolve_alias(&self.aliases, &mut field.ty);
+            }
+        }
+
+        for snippet in &mut self.functions {
+            resolve_alias(&self.aliases, &mut snippet.item.output);
+
+            for &mut (_, ref mut ty) in &mut snippet.item.inputs {
+                resolve_alias(&self.aliases, ty)
+            }
+        }
+    }
+
+    /// Names of the types used without being declared, which can only be used behind pointers.
+    fn opaque_types(&self, context: &Context) -> BTreeSet<String> {
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|snippet| snippet.item.fields.iter().map(|field| &field.ty));
+        let inputs = self.functions.iter().flat_map(|snippet| {
+            let inputs = snippet.item.inputs.iter().map(|(_, ty)| ty);
+            inputs.chain(Some(&snippet.item.output))
+        });
+
+        let mut names = BTreeSet::new();
+        for ty in fields.chain(inputs) {
+            visit_type(ty, &mut |ty| {
+                if let Type::User(ref name) = *ty {
+                    if !context.is_declared(name) && !self.provided_types.contains_key(name) {
+                        let _ = names.insert(name.clone());
+                    }
+                }
+            });
+        }
+        names
+    }
+
+    /// Provided types by the modules declaring them.
+    fn provided_modules(&self) -> BTreeMap<&String, Vec<&String>> {
+        let mut modules: BTreeMap<&String, Vec<&String>> = BTreeMap::new();
+        for (name, path) in &self.provided_types {
+            modules.entry(path).or_default().push(name);
+        }
+        modules
+    }
+
+    fn emit_module(&self, context: &Context) -> String {
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+        let opaque_types = self.opaque_types(context);
+        let futures = self.functions.iter().any(|snippet| {
+            async_callback(&snippet.item, |name| context.is_declared(name)).is_some()
+        });
+
+        emitln!(writer, "'use strict';\n");
+        emitln!(writer, "const koffi = require('koffi');");
+
+        // Only the structs are required, as the enums are passed as integers.
+        for (path, names) in self.provided_modules() {
+            let names: Vec<_> = names
+                .into_iter()
+                .filter(|name| context.is_struct(name))
+                .map(|name| name.as_str())
+                .collect();
+            if !names.is_empty() {
+                emitln!(
+                    writer,
+                    "const {{ types: {{ {} }} }} = require('{}');",
+                    names.join(", "),
+                    path
+                );
+            }
+        }
+
+        if !self.functions.is_empty() {
+            emit_load(&mut writer, &self.lib_name);
+        }
+
+        if !self.consts.is_empty() {
+            emitln!(writer, "");
+            for snippet in &self.consts {
+                emit_const(&mut writer, &snippet.name, &snippet.item);
+            }
+        }
+
+        for snippet in &self.enums {
+            emit_enum(&mut writer, &snippet.name, &snippet.item);
+        }
+
+        if !opaque_types.is_empty() {
+            emitln!(writer, "");
+            for name in &opaque_types {
+                emitln!(writer, "const {0} = koffi.opaque('{0}');", name);
+            }
+        }
+
+        for snippet in &self.structs {
+            emit_struct(&mut writer, context, &snippet.name, &snippet.item);
+        }
+
+        if !self.functions.is_empty() {
+            emit_native_functions(&mut writer, context, &self.functions);
+        }
+
+        let mut wrappers = IndentedWriter::new(INDENT_WIDTH);
+        for snippet in &self.functions {
+            emit_function(&mut wrappers, context, &snippet.name, &snippet.item);
+        }
+        let wrappers = wrappers.into_inner();
+
+        if futures {
+            emit_utilities(&mut writer, &wrappers);
+        }
+        unwrap!(writer.write_str(&wrappers));
+
+        // Exports
+        emitln!(writer, "\nmodule.exports = {{");
+        writer.indent();
+        for snippet in &self.consts {
+            emitln!(writer, "{},", snippet.name);
+        }
+        for snippet in &self.enums {
+            emitln!(writer, "{},", snippet.name);
+        }
+        if futures {
+            emitln!(writer, "FfiError,");
+        }
+        for snippet in &self.functions {
+            emitln!(writer, "{},", function_name(&snippet.name));
+        }
+        let types: Vec<_> = opaque_types
+            .iter()
+            .chain(self.structs.iter().map(|snippet| &snippet.name))
+            .map(String::as_str)
+            .collect();
+        if !types.is_empty() {
+            emitln!(writer, "types: {{ {} }},", types.join(", "));
+        }
+        writer.unindent();
+        emitln!(writer, "}};");
+
+        writer.into_inner()
+    }
+
+    fn emit_declarations(&self, context: &Context) -> String {
+        let mut body = IndentedWriter::new(INDENT_WIDTH);
+        let opaque_types = self.opaque_types(context);
+        let futures = self.functions.iter().any(|snippet| {
+            async_callback(&snippet.item, |name| context.is_declared(name)).is_some()
+        });
+
+        for snippet in &self.consts {
+            emit_const_declaration(&mut body, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for snippet in &self.enums {
+            emit_enum_declaration(&mut body, &snippet.name, &snippet.docs, &snippet.item);
+        }
+
+        for snippet in &self.structs {
+            emit_struct_declaration(
+                &mut body,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        if futures {
+            emit_error_declaration(&mut body);
+        }
+
+        for snippet in &self.functions {
+            emit_function_declaration(
+                &mut body,
+                context,
+                &snippet.name,
+                &snippet.docs,
+                &snippet.item,
+            );
+        }
+
+        let types: Vec<_> = opaque_types
+            .iter()
+            .chain(self.structs.iter().map(|snippet| &snippet.name))
+            .collect();
+        if !types.is_empty() {
+            emitln!(
+                body,
+                "\n/** The koffi types of the structs and opaque types. */"
+            );
+            emitln!(body, "export declare const types: {{");
+            body.indent();
+            for name in types {
+                emitln!(body, "readonly {}: IKoffiCType;", name);
+            }
+            body.unindent();
+            emitln!(body, "}};");
+        }
+
+        let body = body.into_inner();
+        let mut writer = IndentedWriter::new(INDENT_WIDTH);
+
+        if body.contains("IKoffiCType") {
+            emitln!(writer, "import type {{ IKoffiCType }} from 'koffi';");
+        }
+        for (path, names) in self.provided_modules() {
+            let names: Vec<_> = names.iter().map(|name| name.as_str()).collect();
+            emitln!(
+                writer,
+                "import type {{ {} }} from '{}';",
+                names.join(", "),
+                path
+            );
+        }
+        if body.contains("Pointer") {
+            emitln!(
+                writer,
+                "\n/** Pointer to native memory, as handled by koffi. */"
+            );
+            emitln!(writer, "export type Pointer = object;");
+        }
+
+        let mut declarations = writer.into_inner();
+        // The body starts with a blank line.
+        if declarations.is_empty() {
+            declarations.push_str(body.trim_start_matches('\n'));
+        } else {
+            declarations.push_str(&body);
+        }
+        declarations
+    }
+}
+
+impl Default for LangTypeScript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lang for LangTypeScript {
+    fn parse_ty(
+        &mut self,
+        item: &syn::ItemType,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            println!(
+                "parameterized type aliases not supported ({}). Skipping.",
+                name,
+            );
+            return Ok(());
+        }
+
+        let ty = transform_type(&item.ty).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle the type `{}`", name),
+        })?;
+
+        self.aliases.insert(name, ty);
+
+        Ok(())
+    }
+
+    fn parse_const(
+        &mut self,
+        item: &syn::ItemConst,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+        let item = transform_const(&item.ty, &item.expr).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle constant {}", name),
+        })?;
+
+        if let ConstValue::Struct(..) = item.value {
+            println!(
+                "constants of struct types not supported ({}). Skipping.",
+                name
+            );
+            return Ok(());
+        }
+
+        self.consts.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_enum(
+        &mut self,
+        item: &syn::ItemEnum,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let repr = common::parse_repr(&item.attrs);
+        let docs = common::parse_attr(&item.attrs, |_| true, retrieve_docstring).1;
+
+        // If it's neither #[repr(C)] nor #[repr(u8)] etc. ignore it.
+        if !repr.c && repr.int.is_none() {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("enums"));
+        }
+
+        if common::has_data_variants(item) {
+            println!("data-carrying enums not supported ({}). Skipping.", name);
+            return Ok(());
+        }
+
+        let vars: Vec<_> = item.to_owned().variants.into_iter().collect();
+        let mut item = transform_enum(vars.as_slice()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle enum {}", name),
+        })?;
+        item.ty = repr
+            .int
+            .and_then(|int| syn::parse_str(&int).ok())
+            .and_then(|ty| transform_type(&ty));
+
+        self.enums.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_struct(
+        &mut self,
+        item: &syn::ItemStruct,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (_, docs) = common::parse_attr(&item.attrs[..], |_| true, retrieve_docstring);
+        let repr = common::parse_repr(&item.attrs);
+
+        // If it's not #[repr(C)] or #[repr(transparent)] ignore it.
+        if !repr.c && !repr.transparent {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("structs"));
+        }
+
+        // Transparent structs are passed as the wrapped type.
+        if repr.transparent {
+            if let Some(field) = common::transparent_field(&item.fields) {
+                let ty = transform_type(&field.ty).ok_or_else(|| Error {
+                    level: Level::Error,
+                    span: None, //NONE FOR NOW
+                    message: format!("bindgen cannot handle struct {}", name),
+                })?;
+                self.aliases.insert(name, ty);
+            }
+            return Ok(());
+        }
+
+        if repr.align.is_some() {
+            println!(
+                "alignment modifiers are not supported ({}). Ignoring.",
+                name
+            );
+        }
+
+        // koffi only packs structs without any padding.
+        let pack = match repr.packed {
+            Some(1) | None => repr.packed,
+            Some(_) => {
+                println!(
+                    "packing other than 1 is not supported ({}). Ignoring.",
+                    name
+                );
+                None
+            }
+        };
+
+        // The fields are kept as declared, since koffi converts the structs to objects keyed
+        // by their C names.
+        let fields = common::named_fields(&item.fields);
+        let fields = native_fields(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle struct {}", name),
+        })?;
+        let item = Struct {
+            fields,
+            pack,
+            union: false,
+        };
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_union(
+        &mut self,
+        item: &syn::ItemUnion,
+        module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        self.add_if_provided(&name, module);
+
+        let (repr_c, docs) =
+            common::parse_attr(&item.attrs[..], common::check_repr_c, retrieve_docstring);
+
+        // If it's not #[repr(C)] ignore it.
+        if !repr_c {
+            return Ok(());
+        }
+
+        if !item.generics.params.is_empty() {
+            return Err(Error::unsupported_generics_error("unions"));
+        }
+
+        let fields: Vec<_> = item.fields.named.iter().cloned().collect();
+        let fields = native_fields(&fields).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle union {}", name),
+        })?;
+        let item = Struct {
+            fields,
+            pack: None,
+            union: true,
+        };
+        self.structs.push(Snippet { docs, name, item });
+
+        Ok(())
+    }
+
+    fn parse_static(
+        &mut self,
+        item: &syn::ItemStatic,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+
+        // koffi can't read exported variables.
+        let (no_mangle, _) = common::parse_attr(&item.attrs, common::check_no_mangle, |_| None);
+        if no_mangle {
+            println!("statics not supported ({}). Skipping.", name);
+        }
+
+        Ok(())
+    }
+
+    fn parse_fn(
+        &mut self,
+        item: &syn::ItemFn,
+        _module: &[String],
+        _outputs: &mut Outputs,
+    ) -> Result<(), Error> {
+        let name = item.ident.to_string();
+        if self.is_ignored(&name) {
+            return Ok(());
+        }
+        let (no_mangle, docs) =
+            common::parse_attr(&item.attrs, common::check_no_mangle, retrieve_docstring);
+
+        // Ignore function without #[no_mangle].
+        if !no_mangle {
+            return Ok(());
+        }
+        if item.abi.is_none() || !common::is_extern(unwrap!(item.abi.clone())) {
+            return Ok(());
+        }
+
+        let function = transform_function(*item.decl.clone()).ok_or_else(|| Error {
+            level: Level::Error,
+            span: None, //NONE FOR NOW
+            message: format!("bindgen cannot handle function {}", name),
+        })?;
+
+        self.functions.push(Snippet {
+            docs,
+            name,
+            item: function,
+        });
+
+        Ok(())
+    }
+
+    fn finalise_output(&mut self, outputs: &mut Outputs) -> Result<(), Error> {
+        self.resolve_aliases();
+
+        let context = Context {
+            enums: self
+                .enums
+                .iter()
+                .map(|snippet| (snippet.name.clone(), snippet.item.ty.clone()))
+                .collect(),
+            structs: self
+                .structs
+                .iter()
+                .map(|snippet| snippet.name.clone())
+                .collect(),
+        };
+
+        // Callbacks receiving user data can only be registered for the functions returning
+        // promises, which unregister them once called.
+        self.functions.retain(|snippet| {
+            let supported = async_callback(&snippet.item, |name| context.is_declared(name))
+                .is_some()
+                || !snippet
+                    .item
+                    .inputs
+                    .iter()
+                    .any(|(name, ty)| is_user_data(name, ty));
+            if !supported {
+                println!(
+                    "callbacks with user data are only supported by functions taking a \
+                     single `FfiResult` callback ({}). Skipping.",
+                    snippet.name
+                );
+            }
+            supported
+        });
+
+        // Types generated by the bindings of dependencies are required instead.
+        let provided_types = &self.provided_types;
+        self.enums
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+        self.structs
+            .retain(|snippet| !provided_types.contains_key(&snippet.name));
+
+        if !self.consts.is_empty()
+            || !self.enums.is_empty()
+            || !self.structs.is_empty()
+            || !self.functions.is_empty()
+        {
+            let module = self.emit_module(&context);
+            let declarations = self.emit_declarations(&context);
+            outputs.insert(format!("{}.js", self.lib_name), module);
+            outputs.insert(format!("{}.d.ts", self.lib_name), declarations);
+        }
+
+        self.consts.clear();
+        self.enums.clear();
+        self.structs.clear();
+        self.functions.clear();
+        self.aliases.clear();
+        self.provided_types.clear();
+
+        Ok(())
+    }
+
+    fn unit_reference(&self) -> Option<String> {
+        // The units of a workspace are generated into sibling directories.
+        Some(format!("../{0}/{0}", self.lib_name))
+    }
+
+    fn add_provided_unit(&mut self, package: &str, reference: &str) {
+        self.add_provided_package(package, reference);
+    }
+}
+
+/// The fields of a struct or union, without merging the pointers and lengths of the dynamic
+/// arrays.
+fn native_fields(fields: &[syn::Field]) -> Option<Vec<StructField>> {
+    fields
+        .iter()
+        .map(|field| {
+            let (_, docs) = common::parse_attr(&field.attrs[..], |_| true, retrieve_docstring);
+            let name = field.ident.as_ref()?.to_string();
+            let ty = transform_type(&field.ty)?;
+
+            Some(StructField {
+                docs,
+                name,
+                ty,
+                has_cap: false,
+            })
+        })
+        .collect()
+}
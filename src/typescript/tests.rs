@@ -0,0 +1,621 @@
+use super::*;
+use crate::test_utils::fetch;
+use indoc::indoc;
+
+#[test]
+fn types() {
+    let outputs = compile!(LangTypeScript::default(), {
+        pub const CHANNELS: usize = 2;
+        pub const CODEC: &'static str = "Ogg 'Vorbis'";
+
+        /// Playback state.
+        #[repr(i16)]
+        pub enum State {
+            Stopped = -1,
+            /// Waiting for data.
+            Buffering,
+            Playing,
+        }
+
+        /// An audio track.
+        #[repr(C)]
+        pub struct Track {
+            /// Length in samples.
+            length: i64,
+            title: *const c_char,
+            gains: [f32; CHANNELS],
+            state: State,
+            looped: bool,
+            player: *mut Player,
+            on_end: Option<extern "C" fn(position: u32, state: State)>,
+        }
+
+        #[repr(C)]
+        pub union Sample {
+            pcm: i16,
+            float: f32,
+        }
+
+        #[repr(C, packed)]
+        pub struct Chunk {
+            id: u8,
+            size: u64,
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.js");
+    let expected = indoc!(
+        r#"'use strict';
+
+           const koffi = require('koffi');
+
+           const CHANNELS = 2;
+           const CODEC = 'Ogg \'Vorbis\'';
+
+           const State = Object.freeze({
+             Stopped: -1,
+             Buffering: 0,
+             Playing: 1,
+           });
+
+           const Player = koffi.opaque('Player');
+
+           const TrackOnEnd = koffi.proto('TrackOnEnd', 'void', ['uint32_t', 'int16_t']);
+
+           const Track = koffi.struct('Track', {
+             length: 'int64_t',
+             title: 'const char *',
+             gains: koffi.array('float', CHANNELS, 'Array'),
+             state: 'int16_t',
+             looped: 'bool',
+             player: 'Player *',
+             on_end: koffi.pointer(TrackOnEnd),
+           });
+
+           const Sample = koffi.union('Sample', {
+             pcm: 'int16_t',
+             float: 'float',
+           });
+
+           const Chunk = koffi.pack('Chunk', {
+             id: 'uint8_t',
+             size: 'uint64_t',
+           });
+
+           module.exports = {
+             CHANNELS,
+             CODEC,
+             State,
+             types: { Player, Track, Sample, Chunk },
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.d.ts");
+    let expected = indoc!(
+        r#"import type { IKoffiCType } from 'koffi';
+
+           /** Pointer to native memory, as handled by koffi. */
+           export type Pointer = object;
+
+           export declare const CHANNELS: number;
+
+           export declare const CODEC: string;
+
+           /** Playback state. */
+           export declare const State: {
+             readonly Stopped: -1;
+             /** Waiting for data. */
+             readonly Buffering: 0;
+             readonly Playing: 1;
+           };
+           export type State = (typeof State)[keyof typeof State];
+
+           /** An audio track. */
+           export interface Track {
+             /** Length in samples. */
+             length: number | bigint;
+             title: string | null;
+             gains: number[];
+             state: State;
+             looped: boolean;
+             player: Pointer | null;
+             on_end: Pointer | null;
+           }
+
+           export interface Sample {
+             pcm: number;
+             float: number;
+           }
+
+           export interface Chunk {
+             id: number;
+             size: number | bigint;
+           }
+
+           /** The koffi types of the structs and opaque types. */
+           export declare const types: {
+             readonly Player: IKoffiCType;
+             readonly Track: IKoffiCType;
+             readonly Sample: IKoffiCType;
+             readonly Chunk: IKoffiCType;
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn functions() {
+    let outputs = compile!(LangTypeScript::default(), {
+        #[repr(C)]
+        pub enum Mode {
+            Read,
+            Write,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        #[no_mangle]
+        pub extern "C" fn record_id(record: *const Record, app: *const App) -> u64 {}
+
+        /// Writes the data
+        /// and the numbers.
+        #[no_mangle]
+        pub extern "C" fn write(
+            name: *const c_char,
+            data: *const u8,
+            data_len: usize,
+            numbers: *const i32,
+            numbers_len: usize,
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn mode_name(mode: Mode) -> *const c_char {}
+
+        #[no_mangle]
+        pub extern "C" fn visit(app: *const App, visitor: extern "C" fn(id: u64) -> bool) {}
+
+        #[no_mangle]
+        pub extern "C" fn subscribe(
+            app: *const App,
+            user_data: *mut c_void,
+            on_change: extern "C" fn(user_data: *mut c_void, id: u64),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.js");
+    let expected = indoc!(
+        r#"'use strict';
+
+           const koffi = require('koffi');
+
+           function libraryName() {
+             switch (process.platform) {
+               case 'win32':
+                 return 'backend.dll';
+               case 'darwin':
+                 return 'libbackend.dylib';
+               default:
+                 return 'libbackend.so';
+             }
+           }
+
+           const lib = koffi.load(process.env.BACKEND_LIBRARY_PATH || libraryName());
+
+           const Mode = Object.freeze({
+             Read: 0,
+             Write: 1,
+           });
+
+           const App = koffi.opaque('App');
+
+           const Record = koffi.struct('Record', {
+             id: 'uint64_t',
+           });
+
+           const VisitVisitor = koffi.proto('VisitVisitor', 'bool', ['uint64_t']);
+
+           const native = {
+             recordId: lib.func('record_id', 'uint64_t', ['Record *', 'App *']),
+             write: lib.func('write', 'void', ['const char *', 'uint8_t *', 'uintptr_t', 'int32_t *', 'uintptr_t']),
+             modeName: lib.func('mode_name', 'const char *', ['int']),
+             visit: lib.func('visit', 'void', ['App *', koffi.pointer(VisitVisitor)]),
+           };
+
+           function recordId(record, app) {
+             return native.recordId(record, app);
+           }
+
+           function write(name, data, numbers) {
+             native.write(name, data, data.length, numbers, numbers.length);
+           }
+
+           function modeName(mode) {
+             return native.modeName(mode);
+           }
+
+           function visit(app, visitor) {
+             native.visit(app, visitor);
+           }
+
+           module.exports = {
+             Mode,
+             recordId,
+             write,
+             modeName,
+             visit,
+             types: { App, Record },
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.d.ts");
+    let expected = indoc!(
+        r#"import type { IKoffiCType } from 'koffi';
+
+           /** Pointer to native memory, as handled by koffi. */
+           export type Pointer = object;
+
+           export declare const Mode: {
+             readonly Read: 0;
+             readonly Write: 1;
+           };
+           export type Mode = (typeof Mode)[keyof typeof Mode];
+
+           export interface Record {
+             id: number | bigint;
+           }
+
+           export declare function recordId(record: Record | Pointer | null, app: Pointer | null): number | bigint;
+
+           /**
+            * Writes the data
+            * and the numbers.
+            */
+           export declare function write(name: string | null, data: Uint8Array, numbers: number[]): void;
+
+           export declare function modeName(mode: Mode): string | null;
+
+           export declare function visit(app: Pointer | null, visitor: (id: number | bigint) => boolean): void;
+
+           /** The koffi types of the structs and opaque types. */
+           export declare const types: {
+             readonly App: IKoffiCType;
+             readonly Record: IKoffiCType;
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn async_callbacks() {
+    let outputs = compile!(LangTypeScript::default(), {
+        #[repr(C)]
+        pub struct FfiResult {
+            error_code: i32,
+            description: *const c_char,
+        }
+
+        #[repr(C)]
+        pub struct Record {
+            id: u64,
+        }
+
+        /// Fetches the entry.
+        #[no_mangle]
+        pub extern "C" fn fetch(
+            app: *const App,
+            key: *const c_char,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                name: *const c_char,
+                data: *const u8,
+                data_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn records(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(
+                user_data: *mut c_void,
+                result: *const FfiResult,
+                records: *const Record,
+                records_len: usize,
+            ),
+        ) {
+        }
+
+        #[no_mangle]
+        pub extern "C" fn flush(
+            app: *const App,
+            user_data: *mut c_void,
+            o_cb: extern "C" fn(*mut c_void, *const FfiResult),
+        ) {
+        }
+    });
+
+    let actual = fetch(&outputs, "backend.js");
+    let expected = indoc!(
+        r#"'use strict';
+
+           const koffi = require('koffi');
+
+           function libraryName() {
+             switch (process.platform) {
+               case 'win32':
+                 return 'backend.dll';
+               case 'darwin':
+                 return 'libbackend.dylib';
+               default:
+                 return 'libbackend.so';
+             }
+           }
+
+           const lib = koffi.load(process.env.BACKEND_LIBRARY_PATH || libraryName());
+
+           const App = koffi.opaque('App');
+
+           const FfiResult = koffi.struct('FfiResult', {
+             error_code: 'int32_t',
+             description: 'const char *',
+           });
+
+           const Record = koffi.struct('Record', {
+             id: 'uint64_t',
+           });
+
+           const FetchOCb = koffi.proto('FetchOCb', 'void', ['void *', 'FfiResult *', 'const char *', 'uint8_t *', 'uintptr_t']);
+
+           const RecordsOCb = koffi.proto('RecordsOCb', 'void', ['void *', 'FfiResult *', 'Record *', 'uintptr_t']);
+
+           const FlushOCb = koffi.proto('FlushOCb', 'void', ['void *', 'FfiResult *']);
+
+           const native = {
+             fetch: lib.func('fetch', 'void', ['App *', 'const char *', 'void *', koffi.pointer(FetchOCb)]),
+             records: lib.func('records', 'void', ['App *', 'void *', koffi.pointer(RecordsOCb)]),
+             flush: lib.func('flush', 'void', ['App *', 'void *', koffi.pointer(FlushOCb)]),
+           };
+
+           class FfiError extends Error {
+             constructor(code, description) {
+               super(description);
+               this.name = 'FfiError';
+               this.code = code;
+             }
+           }
+
+           function resultError(result) {
+             if (result === null) {
+               return null;
+             }
+             const { error_code: code, description } = koffi.decode(result, FfiResult);
+             return code === 0 ? null : new FfiError(code, description ?? '');
+           }
+
+           function decodeBytes(pointer, length) {
+             if (pointer === null || length === 0) {
+               return new Uint8Array(0);
+             }
+             return koffi.decode(pointer, koffi.array('uint8_t', length, 'Typed'));
+           }
+
+           function decodeArray(pointer, type, length) {
+             if (pointer === null || length === 0) {
+               return [];
+             }
+             return koffi.decode(pointer, koffi.array(type, length, 'Array'));
+           }
+
+           function fetch(app, key) {
+             return new Promise((resolve, reject) => {
+               const callback = koffi.register((userData, result, name, data, dataLen) => {
+                 koffi.unregister(callback);
+                 const error = resultError(result);
+                 if (error !== null) {
+                   reject(error);
+                 } else {
+                   resolve([name ?? '', decodeBytes(data, dataLen)]);
+                 }
+               }, koffi.pointer(FetchOCb));
+               native.fetch(app, key, null, callback);
+             });
+           }
+
+           function records(app) {
+             return new Promise((resolve, reject) => {
+               const callback = koffi.register((userData, result, records, recordsLen) => {
+                 koffi.unregister(callback);
+                 const error = resultError(result);
+                 if (error !== null) {
+                   reject(error);
+                 } else {
+                   resolve(decodeArray(records, 'Record', recordsLen));
+                 }
+               }, koffi.pointer(RecordsOCb));
+               native.records(app, null, callback);
+             });
+           }
+
+           function flush(app) {
+             return new Promise((resolve, reject) => {
+               const callback = koffi.register((arg0, arg1) => {
+                 koffi.unregister(callback);
+                 const error = resultError(arg1);
+                 if (error !== null) {
+                   reject(error);
+                 } else {
+                   resolve();
+                 }
+               }, koffi.pointer(FlushOCb));
+               native.flush(app, null, callback);
+             });
+           }
+
+           module.exports = {
+             FfiError,
+             fetch,
+             records,
+             flush,
+             types: { App, FfiResult, Record },
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.d.ts");
+    let expected = indoc!(
+        r#"import type { IKoffiCType } from 'koffi';
+
+           /** Pointer to native memory, as handled by koffi. */
+           export type Pointer = object;
+
+           export interface FfiResult {
+             error_code: number;
+             description: string | null;
+           }
+
+           export interface Record {
+             id: number | bigint;
+           }
+
+           export declare class FfiError extends Error {
+             readonly code: number;
+           }
+
+           /** Fetches the entry. */
+           export declare function fetch(app: Pointer | null, key: string | null): Promise<[name: string, data: Uint8Array]>;
+
+           export declare function records(app: Pointer | null): Promise<Record[]>;
+
+           export declare function flush(app: Pointer | null): Promise<void>;
+
+           /** The koffi types of the structs and opaque types. */
+           export declare const types: {
+             readonly App: IKoffiCType;
+             readonly FfiResult: IKoffiCType;
+             readonly Record: IKoffiCType;
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}
+
+#[test]
+fn provided_types() {
+    let mut lang = LangTypeScript::default();
+    lang.add_provided_package("inventory", "@shop/inventory");
+
+    let mut outputs = Outputs::default();
+    let sku: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct Sku { code: [u8; 12] }"
+    ));
+    let warehouse: syn::ItemEnum = unwrap!(syn::parse_str(
+        "#[repr(u8)] pub enum Warehouse { North, South }"
+    ));
+    let line: syn::ItemStruct = unwrap!(syn::parse_str(
+        "#[repr(C)] pub struct OrderLine { sku: Sku, quantity: u32, source: Warehouse }"
+    ));
+    let reserve: syn::ItemFn = unwrap!(syn::parse_str(
+        "#[no_mangle] pub extern \"C\" fn reserve(\
+             line: *const OrderLine, \
+             on_shortage: extern \"C\" fn(sku: *const Sku, missing: u32)\
+         ) -> bool {}"
+    ));
+
+    unwrap!(lang.parse_struct(&sku, &["inventory".to_string()], &mut outputs));
+    unwrap!(lang.parse_enum(&warehouse, &["inventory".to_string()], &mut outputs));
+    unwrap!(lang.parse_struct(&line, &["orders".to_string()], &mut outputs));
+    unwrap!(lang.parse_fn(&reserve, &["orders".to_string()], &mut outputs));
+    unwrap!(lang.finalise_output(&mut outputs));
+
+    // Only the provided structs are required, but the declarations import the enums too.
+    let actual = fetch(&outputs, "backend.js");
+    let expected = indoc!(
+        r#"'use strict';
+
+           const koffi = require('koffi');
+           const { types: { Sku } } = require('@shop/inventory');
+
+           function libraryName() {
+             switch (process.platform) {
+               case 'win32':
+                 return 'backend.dll';
+               case 'darwin':
+                 return 'libbackend.dylib';
+               default:
+                 return 'libbackend.so';
+             }
+           }
+
+           const lib = koffi.load(process.env.BACKEND_LIBRARY_PATH || libraryName());
+
+           const OrderLine = koffi.struct('OrderLine', {
+             sku: 'Sku',
+             quantity: 'uint32_t',
+             source: 'uint8_t',
+           });
+
+           const ReserveOnShortage = koffi.proto('ReserveOnShortage', 'void', ['Sku *', 'uint32_t']);
+
+           const native = {
+             reserve: lib.func('reserve', 'bool', ['OrderLine *', koffi.pointer(ReserveOnShortage)]),
+           };
+
+           function reserve(line, onShortage) {
+             return native.reserve(line, onShortage);
+           }
+
+           module.exports = {
+             reserve,
+             types: { OrderLine },
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+
+    let actual = fetch(&outputs, "backend.d.ts");
+    let expected = indoc!(
+        r#"import type { IKoffiCType } from 'koffi';
+           import type { Sku, Warehouse } from '@shop/inventory';
+
+           /** Pointer to native memory, as handled by koffi. */
+           export type Pointer = object;
+
+           export interface OrderLine {
+             sku: Sku;
+             quantity: number;
+             source: Warehouse;
+           }
+
+           export declare function reserve(line: OrderLine | Pointer | null, onShortage: (sku: Pointer | null, missing: number) => void): boolean;
+
+           /** The koffi types of the structs and opaque types. */
+           export declare const types: {
+             readonly OrderLine: IKoffiCType;
+           };
+           "#
+    );
+
+    assert_multiline_eq!(actual, expected);
+}